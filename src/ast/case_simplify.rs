@@ -13,6 +13,8 @@ pub struct CaseSimplify {
 pub struct CaseSimplifyPass {
     symbol_table: SymbolTable,
     id: Id,
+    // a match ran out of the clauses, for the values it does not cover
+    inexhaustive: bool,
 }
 
 #[derive(Debug)]
@@ -34,7 +36,11 @@ impl CaseSimplify {
 
 impl CaseSimplifyPass {
     fn new(symbol_table: SymbolTable, id: Id) -> Self {
-        Self { symbol_table, id }
+        Self {
+            symbol_table,
+            id,
+            inexhaustive: false,
+        }
     }
     fn symbol_table(&self) -> &SymbolTable {
         &self.symbol_table
@@ -45,31 +51,47 @@ impl CaseSimplifyPass {
 
     fn gensym(&mut self, name: &str) -> Symbol {
         let id = self.id.next();
-        Symbol(format!("#{}", name), id)
+        Symbol(format!("#{}", name).into(), id)
     }
 
     fn wildcard_to_variable(&mut self, ast: TypedCore) -> TypedCore {
         WildcardToVariable::new(self.id.clone()).transform_ast(ast)
     }
 
-    fn rename_pattern(&mut self, pattern: &mut TypedPattern) {
+    // the alternatives of or-patterns bind the same names,
+    // so the names already in `renamed` are renamed alike
+    fn rename_pattern(&mut self, pattern: &mut TypedPattern, renamed: &mut HashMap<Symbol, u64>) {
         use PatternKind::*;
         match &mut pattern.inner {
             Constructor { arg, .. } => {
                 if let Some(arg) = arg {
-                    self.rename_pattern(arg)
+                    self.rename_pattern(arg, renamed)
                 }
             }
             Tuple { tuple, .. } => {
                 for pat in tuple {
-                    self.rename_pattern(pat)
+                    self.rename_pattern(pat, renamed)
+                }
+            }
+            Variable { name, .. } => self.rename_variable(name, renamed),
+            As { name, pattern } => {
+                self.rename_variable(name, renamed);
+                self.rename_pattern(pattern, renamed)
+            }
+            Or { alternatives } => {
+                for pat in alternatives {
+                    self.rename_pattern(pat, renamed)
                 }
             }
-            Variable { name, .. } => name.1 = self.id.next(),
             _ => (),
         }
     }
 
+    fn rename_variable(&mut self, name: &mut Symbol, renamed: &mut HashMap<Symbol, u64>) {
+        let id = &mut self.id;
+        name.1 = *renamed.entry(name.clone()).or_insert_with(|| id.next());
+    }
+
     fn match_compile(
         &mut self,
         cond: Stack<(Type, Symbol)>,
@@ -77,6 +99,7 @@ impl CaseSimplifyPass {
         clauses: Vec<(Stack<TypedPattern>, TypedCoreExpr)>,
     ) -> TypedCoreExpr {
         // assuming clauses.any(|(patterns, _)| patterns.len() == cond.len())
+        let clauses = self.expand_patterns(&cond, clauses);
         if clauses.len() == 0 {
             self.match_compile_empty(cond, ty, clauses)
         } else if clauses[0].0.iter().all(|p| p.is_variable()) {
@@ -87,18 +110,103 @@ impl CaseSimplifyPass {
             self.match_compile_constant(cond, ty, clauses)
         } else if clauses[0].0.iter().any(|p| p.is_char()) {
             self.match_compile_char(cond, ty, clauses)
+        } else if clauses[0].0.iter().any(|p| p.is_str()) {
+            self.match_compile_str(cond, ty, clauses)
         } else {
             self.match_compile_mixture(cond, ty, clauses)
         }
     }
 
+    // `x as p` binds `x` to the value, then matches it against `p`.
+    // a clause with `p1 | p2` is split to the clauses for `p1` and `p2`, duplicating the arm.
+    fn expand_patterns(
+        &mut self,
+        cond: &Stack<(Type, Symbol)>,
+        mut clauses: Vec<(Stack<TypedPattern>, TypedCoreExpr)>,
+    ) -> Vec<(Stack<TypedPattern>, TypedCoreExpr)> {
+        let mut ret = Vec::new();
+        // used as a stack to keep the order of the clauses
+        clauses.reverse();
+        while let Some((mut patterns, arm)) = clauses.pop() {
+            if let Some(pos) = patterns.iter().position(|pat| pat.is_or()) {
+                let alternatives = match patterns.remove(pos).inner {
+                    PatternKind::Or { alternatives } => alternatives,
+                    _ => unreachable!(),
+                };
+                for alternative in alternatives.into_iter().rev() {
+                    let mut patterns = patterns.clone();
+                    patterns.insert(pos, alternative);
+                    clauses.push((patterns, arm.clone()));
+                }
+                continue;
+            }
+
+            let mut binds = Vec::new();
+            let mut stripped = Vec::new();
+            for (mut pattern, (cty, c)) in patterns.into_iter().zip(cond.iter()) {
+                loop {
+                    match pattern.inner {
+                        PatternKind::As {
+                            name,
+                            pattern: inner,
+                        } => {
+                            binds.push(Declaration::Val {
+                                rec: false,
+                                pattern: Pattern {
+                                    ty: pattern.ty.clone(),
+                                    span: Span::default(),
+                                    inner: PatternKind::Variable { name },
+                                },
+                                expr: Expr {
+                                    ty: cty.clone(),
+                                    span: Span::default(),
+                                    inner: ExprKind::Symbol { name: c.clone() },
+                                },
+                            });
+                            pattern = *inner;
+                        }
+                        inner => {
+                            pattern.inner = inner;
+                            break;
+                        }
+                    }
+                }
+                stripped.push(pattern);
+            }
+            let arm = if binds.is_empty() {
+                arm
+            } else {
+                Expr {
+                    ty: arm.ty(),
+                    span: Span::default(),
+                    inner: ExprKind::Binds {
+                        binds,
+                        ret: arm.boxed(),
+                    },
+                }
+            };
+            if stripped.iter().any(|pat| pat.is_or()) {
+                clauses.push((stripped, arm));
+            } else {
+                ret.push((stripped, arm));
+            }
+        }
+        ret
+    }
+
     fn match_compile_empty(
         &mut self,
         _: Stack<(Type, Symbol)>,
-        _: Type,
+        ty: Type,
         _: Vec<(Stack<TypedPattern>, TypedCoreExpr)>,
     ) -> TypedCoreExpr {
-        panic!("non-exhausitive pattern");
+        // no exceptions to raise `Match`. the placeholder is dropped with the error in `trans`
+        self.inexhaustive = true;
+        Expr {
+            ty,
+            span: Span::default(),
+            inner: ExprKind::Tuple { tuple: vec![] },
+        }
     }
 
     fn match_compile_variable(
@@ -113,11 +221,13 @@ impl CaseSimplifyPass {
             .zip(cond.iter().cloned())
             .fold(expr, |acc, (pattern, (cty, name))| Expr {
                 ty: ty.clone(),
+                span: Span::default(),
                 inner: ExprKind::Binds {
                     binds: vec![Declaration::Val {
                         rec: false,
                         expr: Expr {
                             ty: cty,
+                            span: Span::default(),
                             inner: ExprKind::Symbol { name },
                         },
                         // believing pattern is variable
@@ -139,6 +249,7 @@ impl CaseSimplifyPass {
         let (cty, c) = cond.swap_remove(pos);
         let param_tys: Vec<Type> = match cty.clone() {
             Type::Tuple(tuple) => tuple,
+            Type::Unit => vec![],
             _ => unreachable!(),
         };
         let clauses = clauses
@@ -152,21 +263,25 @@ impl CaseSimplifyPass {
                             .zip(param_tys.clone())
                             .map(|(name, ty)| Pattern {
                                 ty,
+                                span: Span::default(),
                                 inner: PatternKind::Variable { name },
                             })
                             .take(param_tys.len())
                             .collect();
                         arm = Expr {
                             ty: arm.ty(),
+                            span: Span::default(),
                             inner: ExprKind::Binds {
                                 binds: vec![Declaration::Val {
                                     rec: false,
                                     pattern: Pattern {
                                         ty: removed_pattern.ty,
+                                        span: Span::default(),
                                         inner: var,
                                     },
                                     expr: Expr {
                                         ty: cty.clone(),
+                                        span: Span::default(),
                                         inner: ExprKind::Symbol { name: c.clone() },
                                     },
                                 }],
@@ -188,21 +303,25 @@ impl CaseSimplifyPass {
         cond.extend(param_tys.clone().into_iter().zip(tmp_vars.clone()).rev());
         Expr {
             ty: ty.clone(),
+            span: Span::default(),
             inner: ExprKind::Case {
                 cond: Expr {
                     ty: cty.clone(),
+                    span: Span::default(),
                     inner: ExprKind::Symbol { name: c },
                 }
                 .boxed(),
                 clauses: vec![(
                     Pattern {
                         ty: cty,
+                        span: Span::default(),
                         inner: PatternKind::Tuple {
                             tuple: tmp_vars
                                 .into_iter()
                                 .zip(param_tys)
                                 .map(|(name, ty)| Pattern {
                                     ty,
+                                    span: Span::default(),
                                     inner: PatternKind::Variable { name },
                                 })
                                 .collect(),
@@ -236,6 +355,7 @@ impl CaseSimplifyPass {
                 Pattern {
                     ty,
                     inner: PatternKind::Constant { value },
+                    ..
                 } => Some((*value, ty.clone())),
                 _ => None,
             })
@@ -251,6 +371,7 @@ impl CaseSimplifyPass {
                 (
                     Pattern {
                         ty: ty.clone(),
+                        span: Span::default(),
                         inner: PatternKind::Constant { value: *value },
                     },
                     self.match_compile(cond.clone(), ret_ty.clone(), clauses),
@@ -264,17 +385,18 @@ impl CaseSimplifyPass {
         clauses.push((
             Pattern {
                 ty: cty.clone(),
-                inner: PatternKind::Variable {
-                    name: self.gensym("_"),
-                },
+                span: Span::default(),
+                inner: PatternKind::Wildcard {},
             },
             default,
         ));
         Expr {
             ty: ret_ty,
+            span: Span::default(),
             inner: ExprKind::Case {
                 cond: Expr {
                     ty: cty,
+                    span: Span::default(),
                     inner: ExprKind::Symbol { name: c },
                 }
                 .boxed(),
@@ -305,6 +427,7 @@ impl CaseSimplifyPass {
                 Pattern {
                     ty,
                     inner: PatternKind::Char { value },
+                    ..
                 } => Some((*value, ty.clone())),
                 _ => None,
             })
@@ -320,6 +443,7 @@ impl CaseSimplifyPass {
                 (
                     Pattern {
                         ty: ty.clone(),
+                        span: Span::default(),
                         inner: PatternKind::Char { value: *value },
                     },
                     self.match_compile(cond.clone(), ret_ty.clone(), clauses),
@@ -333,17 +457,88 @@ impl CaseSimplifyPass {
         clauses.push((
             Pattern {
                 ty: cty.clone(),
-                inner: PatternKind::Variable {
-                    name: self.gensym("_"),
-                },
+                span: Span::default(),
+                inner: PatternKind::Wildcard {},
+            },
+            default,
+        ));
+        Expr {
+            ty: ret_ty,
+            span: Span::default(),
+            inner: ExprKind::Case {
+                cond: Expr {
+                    ty: cty,
+                    span: Span::default(),
+                    inner: ExprKind::Symbol { name: c },
+                }
+                .boxed(),
+                clauses: clauses,
+            },
+        }
+    }
+
+    fn match_compile_str(
+        &mut self,
+        mut cond: Stack<(Type, Symbol)>,
+        ret_ty: Type,
+        clauses: Vec<(Stack<TypedPattern>, TypedCoreExpr)>,
+    ) -> TypedCoreExpr {
+        let pos = self.find_str(&clauses);
+
+        let (cty, c) = cond.swap_remove(pos);
+        let clause_with_heads = clauses
+            .into_iter()
+            .map(|mut clause| {
+                let head = clause.0.swap_remove(pos);
+                (head, clause)
+            })
+            .collect::<Vec<_>>();
+        // the strings are tested one by one, so they are kept in the order of the clauses
+        let mut strs: Vec<(String, Type)> = Vec::new();
+        for (head, _) in &clause_with_heads {
+            if let PatternKind::Str { value } = &head.inner {
+                if strs.iter().all(|(s, _)| s != value) {
+                    strs.push((value.clone(), head.ty.clone()))
+                }
+            }
+        }
+        let mut clauses = strs
+            .into_iter()
+            .map(|(value, ty)| {
+                let clauses = self.specialized_patterns_for_str(
+                    (cty.clone(), c.clone()),
+                    &value,
+                    clause_with_heads.iter(),
+                );
+                (
+                    Pattern {
+                        ty,
+                        span: Span::default(),
+                        inner: PatternKind::Str { value },
+                    },
+                    self.match_compile(cond.clone(), ret_ty.clone(), clauses),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // no check for exhausitiveness
+        let default =
+            self.default_patterns(c.clone(), cond, ret_ty.clone(), clause_with_heads.iter());
+        clauses.push((
+            Pattern {
+                ty: cty.clone(),
+                span: Span::default(),
+                inner: PatternKind::Wildcard {},
             },
             default,
         ));
         Expr {
             ty: ret_ty,
+            span: Span::default(),
             inner: ExprKind::Case {
                 cond: Expr {
                     ty: cty,
+                    span: Span::default(),
                     inner: ExprKind::Symbol { name: c },
                 }
                 .boxed(),
@@ -379,6 +574,7 @@ impl CaseSimplifyPass {
                 Pattern {
                     ty,
                     inner: PatternKind::Constructor { name, arg },
+                    ..
                 } => Some((name.clone(), (ty.clone(), arg.clone()))),
                 _ => None,
             })
@@ -401,6 +597,7 @@ impl CaseSimplifyPass {
                         new_cond.push((argty.clone(), tmp_var.clone()));
                         Some(Box::new(Pattern {
                             ty: argty,
+                            span: Span::default(),
                             inner: PatternKind::Variable { name: tmp_var },
                         }))
                     }
@@ -409,6 +606,7 @@ impl CaseSimplifyPass {
                 (
                     Pattern {
                         ty: ty.clone(),
+                        span: Span::default(),
                         inner: PatternKind::Constructor {
                             name: name.clone(),
                             arg,
@@ -422,9 +620,11 @@ impl CaseSimplifyPass {
         if self.is_exhausitive(&type_id, constructor_names) {
             Expr {
                 ty: ret_ty,
+                span: Span::default(),
                 inner: ExprKind::Case {
                     cond: Expr {
                         ty: cty,
+                        span: Span::default(),
                         inner: ExprKind::Symbol { name: c.clone() },
                     }
                     .boxed(),
@@ -437,17 +637,18 @@ impl CaseSimplifyPass {
             clauses.push((
                 Pattern {
                     ty: cty.clone(),
-                    inner: PatternKind::Variable {
-                        name: self.gensym("_"),
-                    },
+                    span: Span::default(),
+                    inner: PatternKind::Wildcard {},
                 },
                 default,
             ));
             Expr {
                 ty: ret_ty,
+                span: Span::default(),
                 inner: ExprKind::Case {
                     cond: Expr {
                         ty: cty,
+                        span: Span::default(),
                         inner: ExprKind::Symbol { name: c },
                     }
                     .boxed(),
@@ -469,6 +670,10 @@ impl CaseSimplifyPass {
         clauses[0].0.iter().rposition(|p| p.is_char()).unwrap()
     }
 
+    fn find_str(&mut self, clauses: &[(Stack<TypedPattern>, TypedCoreExpr)]) -> usize {
+        clauses[0].0.iter().rposition(|p| p.is_str()).unwrap()
+    }
+
     fn find_constructor(&mut self, clauses: &[(Stack<TypedPattern>, TypedCoreExpr)]) -> usize {
         clauses[0]
             .0
@@ -496,6 +701,7 @@ impl CaseSimplifyPass {
                     let pattern = match arg {
                         Some(arg) => Some(Pattern {
                             ty: arg.ty(),
+                            span: Span::default(),
                             inner: PatternKind::Variable {
                                 name: self.gensym("_"),
                             },
@@ -505,15 +711,18 @@ impl CaseSimplifyPass {
                     let (pat, arm) = clause.clone();
                     let arm = Expr {
                         ty: arm.ty(),
+                        span: Span::default(),
                         inner: ExprKind::Binds {
                             binds: vec![Declaration::Val {
                                 rec: false,
                                 pattern: Pattern {
                                     ty: head.ty.clone(),
+                                    span: Span::default(),
                                     inner: v.clone(),
                                 },
                                 expr: Expr {
                                     ty: cty.clone(),
+                                    span: Span::default(),
                                     inner: ExprKind::Symbol { name: cond.clone() },
                                 },
                             }],
@@ -548,15 +757,18 @@ impl CaseSimplifyPass {
                     let (pat, arm) = clause.clone();
                     let arm = Expr {
                         ty: arm.ty(),
+                        span: Span::default(),
                         inner: ExprKind::Binds {
                             binds: vec![Declaration::Val {
                                 rec: false,
                                 pattern: Pattern {
                                     ty: head.ty.clone(),
+                                    span: Span::default(),
                                     inner: v.clone(),
                                 },
                                 expr: Expr {
                                     ty: cty.clone(),
+                                    span: Span::default(),
                                     inner: ExprKind::Symbol { name: cond.clone() },
                                 },
                             }],
@@ -585,15 +797,58 @@ impl CaseSimplifyPass {
                     let (pat, arm) = clause.clone();
                     let arm = Expr {
                         ty: arm.ty(),
+                        span: Span::default(),
                         inner: ExprKind::Binds {
                             binds: vec![Declaration::Val {
                                 rec: false,
                                 pattern: Pattern {
                                     ty: head.ty.clone(),
+                                    span: Span::default(),
                                     inner: v.clone(),
                                 },
                                 expr: Expr {
                                     ty: cty.clone(),
+                                    span: Span::default(),
+                                    inner: ExprKind::Symbol { name: cond.clone() },
+                                },
+                            }],
+                            ret: arm.boxed(),
+                        },
+                    };
+                    Some((pat, arm))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn specialized_patterns_for_str<'a, 'b>(
+        &'a mut self,
+        (cty, cond): (Type, Symbol),
+        value: &str,
+        clause_with_heads: impl Iterator<
+            Item = &'b (TypedPattern, (Stack<TypedPattern>, TypedCoreExpr)),
+        >,
+    ) -> Vec<(Stack<TypedPattern>, TypedCoreExpr)> {
+        clause_with_heads
+            .filter_map(|(head, clause)| match &head.inner {
+                PatternKind::Str { value: value1, .. } if *value1 == value => Some(clause.clone()),
+                v @ PatternKind::Variable { .. } => {
+                    let (pat, arm) = clause.clone();
+                    let arm = Expr {
+                        ty: arm.ty(),
+                        span: Span::default(),
+                        inner: ExprKind::Binds {
+                            binds: vec![Declaration::Val {
+                                rec: false,
+                                pattern: Pattern {
+                                    ty: head.ty.clone(),
+                                    span: Span::default(),
+                                    inner: v.clone(),
+                                },
+                                expr: Expr {
+                                    ty: cty.clone(),
+                                    span: Span::default(),
                                     inner: ExprKind::Symbol { name: cond.clone() },
                                 },
                             }],
@@ -622,11 +877,13 @@ impl CaseSimplifyPass {
                 PatternKind::Variable { .. } => {
                     let arm = Expr {
                         ty: arm.ty(),
+                        span: Span::default(),
                         inner: ExprKind::Binds {
                             binds: vec![Declaration::Val {
                                 rec: false,
                                 expr: Expr {
                                     ty: p.ty.clone(),
+                                    span: Span::default(),
                                     inner: ExprKind::Symbol { name: c.clone() },
                                 },
                                 pattern: p,
@@ -649,13 +906,22 @@ impl CaseSimplifyPass {
     ) -> bool {
         use Type::*;
         match ty {
-            Real | Variable(_) | Fun(_, _) => panic!("no way to pattern match against this type"),
-            Char | Int => false,
+            Real | Variable(_) | Fun(_, _) | Ref(_) => {
+                panic!("no way to pattern match against this type")
+            }
+            Char | String | Int | Word => false,
+            Unit => true,
+            Bool => {
+                descriminansts.into_iter().collect::<HashSet<_>>()
+                    == [Symbol::new("false"), Symbol::new("true")]
+                        .iter()
+                        .collect::<HashSet<_>>()
+            }
             Tuple(_) => {
                 // unlikely reachable, but writing incase it reaches.
                 true
             }
-            Datatype(name) => {
+            Datatype(name, _) => {
                 self.symbol_table()
                     .get_type(name)
                     .unwrap()
@@ -697,25 +963,29 @@ impl Transform<Type> for CaseSimplifyPass {
                     .into_iter()
                     .map(|(name, ty)| Pattern {
                         ty: ty.clone(),
+                        span: Span::default(),
                         inner: PatternKind::Variable { name: name.clone() },
                     })
                     .collect();
                 let tuple_pat = Pattern {
                     ty: ty.clone(),
+                    span: Span::default(),
                     inner: PatternKind::Tuple { tuple: tuple_pat },
                 };
                 let mut pattern = self.transform_pattern(pattern);
-                self.rename_pattern(&mut pattern);
+                self.rename_pattern(&mut pattern, &mut HashMap::new());
                 let binds = pattern.binds();
                 let tuple = binds
                     .into_iter()
                     .map(|(name, ty)| Expr {
                         ty: ty.clone(),
+                        span: Span::default(),
                         inner: ExprKind::Symbol { name: name.clone() },
                     })
                     .collect();
                 let tuple = Expr {
                     ty: ty.clone(),
+                    span: Span::default(),
                     inner: ExprKind::Tuple { tuple },
                 };
                 let cond = self.transform_expr(expr);
@@ -724,6 +994,7 @@ impl Transform<Type> for CaseSimplifyPass {
                     pattern: tuple_pat,
                     expr: Expr {
                         ty,
+                        span: Span::default(),
                         inner: self.transform_case(cond.boxed(), vec![(pattern, tuple)]),
                     },
                 }
@@ -751,6 +1022,7 @@ impl Transform<Type> for CaseSimplifyPass {
             binds: vec![Declaration::Val {
                 pattern: Pattern {
                     ty: condty.clone(),
+                    span: Span::default(),
                     inner: PatternKind::Variable {
                         name: condsym.clone(),
                     },
@@ -772,7 +1044,7 @@ impl WildcardToVariable {
 
     fn gensym(&mut self, name: &str) -> Symbol {
         let id = self.id.next();
-        Symbol(format!("#{}", name), id)
+        Symbol(format!("#{}", name).into(), id)
     }
 }
 
@@ -796,6 +1068,9 @@ impl<'a> Pass<(SymbolTable, TypedCore), TypeError<'a>> for CaseSimplify {
         let mut pass = self.generate_pass(symbol_table);
         let ast = pass.wildcard_to_variable(ast);
         let ast = pass.transform_ast(ast);
+        if pass.inexhaustive {
+            return Err(TypeError::Inexhaustive);
+        }
         let (symbol_table, _) = pass.into_inner();
         Ok((symbol_table, ast))
     }
@@ -0,0 +1,147 @@
+use crate::ast::util::Traverse;
+use crate::ast::*;
+use crate::prim::Name;
+use std::collections::HashSet;
+
+/// the names a program binds at the top level and the names it refers to, before renaming.
+/// the references include the local names and the variables in patterns,
+/// which may be constructors yet, so they over-approximate the dependencies.
+/// the functions declared by `_import` are named `"module"."fun"`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Names {
+    pub binds: HashSet<Name>,
+    pub uses: HashSet<Name>,
+}
+
+impl Names {
+    pub fn of(ast: &UntypedCore) -> Self {
+        let mut names = Names::default();
+        for decl in &ast.0 {
+            match decl {
+                Declaration::Val { pattern, .. } => names
+                    .binds
+                    .extend(pattern.binds().into_iter().map(|(n, _)| n.0)),
+                Declaration::ValRecGroup { binds } => {
+                    for (pattern, _) in binds {
+                        names
+                            .binds
+                            .extend(pattern.binds().into_iter().map(|(n, _)| n.0))
+                    }
+                }
+                Declaration::Datatype {
+                    name, constructors, ..
+                } => {
+                    names.binds.insert(name.0);
+                    names.binds.extend(constructors.iter().map(|(c, _)| c.0));
+                }
+                Declaration::Import { module, fun, .. } => {
+                    names.binds.insert(import_name(module, fun));
+                }
+                Declaration::Export { .. } | Declaration::D(_) => (),
+            }
+        }
+        names.traverse_ast(&mut ast.clone());
+        names
+    }
+
+    fn use_type(&mut self, ty: &Type) {
+        use crate::ast::Type::*;
+        match ty {
+            Variable(_) | Unit | Char | String | Int | Word | Real | Bool => (),
+            Fun(param, ret) => {
+                self.use_type(param);
+                self.use_type(ret);
+            }
+            Tuple(tys) => tys.iter().for_each(|ty| self.use_type(ty)),
+            Datatype(name, args) => {
+                self.uses.insert(name.0);
+                args.iter().for_each(|ty| self.use_type(ty));
+            }
+            Ref(elem) => self.use_type(elem),
+        }
+    }
+}
+
+fn import_name(module: &str, fun: &str) -> Name {
+    Name::new(&format!("{:?}.{:?}", module, fun))
+}
+
+impl Traverse<()> for Names {
+    fn traverse_datatype(
+        &mut self,
+        _name: &mut Symbol,
+        _params: &mut Vec<u64>,
+        constructors: &mut Vec<(Symbol, Option<Type>)>,
+    ) {
+        for (_, arg) in constructors.iter() {
+            if let Some(ty) = arg {
+                self.use_type(ty)
+            }
+        }
+    }
+
+    fn traverse_export(&mut self, _name: &mut String, value: &mut Symbol) {
+        self.uses.insert(value.0);
+    }
+
+    fn traverse_externcall(
+        &mut self,
+        module: &mut String,
+        fun: &mut String,
+        args: &mut Vec<UntypedCoreExpr>,
+        _argty: &mut Vec<Type>,
+        _retty: &mut Vec<Type>,
+    ) {
+        self.uses.insert(import_name(module, fun));
+        for arg in args {
+            self.traverse_expr(arg)
+        }
+    }
+
+    fn traverse_constructor(&mut self, arg: &mut Option<Box<UntypedCoreExpr>>, name: &mut Symbol) {
+        self.uses.insert(name.0);
+        if let Some(arg) = arg {
+            self.traverse_expr(arg)
+        }
+    }
+
+    fn traverse_sym(&mut self, name: &mut Symbol) {
+        self.uses.insert(name.0);
+    }
+
+    fn traverse_typed(&mut self, expr: &mut Box<UntypedCoreExpr>, ty: &mut Type) {
+        self.use_type(ty);
+        self.traverse_expr(expr)
+    }
+
+    fn traverse_pat_constructor(
+        &mut self,
+        name: &mut Symbol,
+        arg: &mut Option<Box<UntypedPattern>>,
+    ) {
+        self.uses.insert(name.0);
+        if let Some(arg) = arg {
+            self.traverse_pattern(arg)
+        }
+    }
+
+    fn traverse_pat_tuple(&mut self, tuple: &mut Vec<UntypedPattern>) {
+        for pattern in tuple {
+            self.traverse_pattern(pattern)
+        }
+    }
+
+    fn traverse_pat_variable(&mut self, name: &mut Symbol) {
+        self.uses.insert(name.0);
+    }
+
+    fn traverse_pat_as(&mut self, _name: &mut Symbol, pattern: &mut Box<UntypedPattern>) {
+        self.traverse_pattern(pattern)
+    }
+
+    fn traverse_pat_or(&mut self, alternatives: &mut Vec<UntypedPattern>) {
+        for pattern in alternatives {
+            self.traverse_pattern(pattern)
+        }
+    }
+}
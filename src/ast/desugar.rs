@@ -24,26 +24,63 @@ impl Desugar {
         AST(ast
             .0
             .into_iter()
-            .filter_map(|decl| self.transform_statement(decl))
+            .flat_map(|decl| self.transform_statement(decl))
             .collect())
     }
 
-    fn transform_statement(&mut self, decl: Declaration<()>) -> Option<UntypedCoreDeclaration> {
+    // an export becomes the declaration it annotates followed by `Export`
+    fn transform_statement(&mut self, decl: Declaration<()>) -> Vec<UntypedCoreDeclaration> {
         use Declaration::*;
         match decl {
-            Datatype { name, constructors } => Some(self.transform_datatype(name, constructors)),
-            Val { rec, pattern, expr } => Some(self.transform_val(rec, pattern, expr)),
-            D(DerivedDeclaration::Fun { name, clauses }) => Some(self.transform_fun(name, clauses)),
-            D(DerivedDeclaration::Infix { .. }) => None,
+            Datatype {
+                name,
+                params,
+                constructors,
+            } => vec![self.transform_datatype(name, params, constructors)],
+            Val { rec, pattern, expr } => vec![self.transform_val(rec, pattern, expr)],
+            ValRecGroup { binds } => vec![self.transform_val_rec_group(binds)],
+            Import {
+                module,
+                fun,
+                argty,
+                retty,
+            } => vec![Import {
+                module,
+                fun,
+                argty,
+                retty,
+            }],
+            Export { name, value } => vec![Export { name, value }],
+            D(DerivedDeclaration::Fun { name, clauses }) => vec![self.transform_fun(name, clauses)],
+            D(DerivedDeclaration::FunGroup { funs }) => vec![self.transform_fun_group(funs)],
+            D(DerivedDeclaration::Infix { .. }) => vec![],
+            D(DerivedDeclaration::Export { name, decl }) => {
+                let value = match &*decl {
+                    Val { pattern, .. } => match &pattern.inner {
+                        PatternKind::Variable { name } => name.clone(),
+                        _ => unreachable!("the parser only exports variables"),
+                    },
+                    D(DerivedDeclaration::Fun { name, .. }) => name.clone(),
+                    _ => unreachable!("the parser only exports `val`s and `fun`s"),
+                };
+                let mut decls = self.transform_statement(*decl);
+                decls.push(Export { name, value });
+                decls
+            }
         }
     }
 
     fn transform_datatype(
         &mut self,
         name: Symbol,
+        params: Vec<u64>,
         constructors: Vec<(Symbol, Option<Type>)>,
     ) -> UntypedCoreDeclaration {
-        Declaration::Datatype { name, constructors }
+        Declaration::Datatype {
+            name,
+            params,
+            constructors,
+        }
     }
 
     fn transform_val(
@@ -59,12 +96,56 @@ impl Desugar {
         }
     }
 
+    fn transform_val_rec_group(
+        &mut self,
+        binds: Vec<(UntypedPattern, UntypedExpr)>,
+    ) -> UntypedCoreDeclaration {
+        Declaration::ValRecGroup {
+            binds: binds
+                .into_iter()
+                .map(|(pattern, expr)| (self.transform_pattern(pattern), self.transform_expr(expr)))
+                .collect(),
+        }
+    }
+
     fn transform_fun(
         &mut self,
         name: Symbol,
         clauses: Vec<(Vec<UntypedPattern>, UntypedExpr)>,
     ) -> UntypedCoreDeclaration {
+        let (pattern, expr) = self.transform_fun_bind(name, clauses);
+        Declaration::Val {
+            rec: true,
+            pattern,
+            expr,
+        }
+    }
+
+    fn transform_fun_group(
+        &mut self,
+        funs: Vec<(Symbol, Vec<(Vec<UntypedPattern>, UntypedExpr)>)>,
+    ) -> UntypedCoreDeclaration {
+        Declaration::ValRecGroup {
+            binds: funs
+                .into_iter()
+                .map(|(name, clauses)| self.transform_fun_bind(name, clauses))
+                .collect(),
+        }
+    }
+
+    // fun f p11 p12 ... = e1 | f p21 p22 ... = e2 | ...
+    // => f, fn x1 => fn x2 => ... => case (x1, x2, ...) of (p11, p12, ...) => e1 | ...
+    fn transform_fun_bind(
+        &mut self,
+        name: Symbol,
+        clauses: Vec<(Vec<UntypedPattern>, UntypedExpr)>,
+    ) -> (UntypedPattern, UntypedCoreExpr) {
         let arity = clauses[0].0.len();
+        let span = clauses.iter().fold(Span::default(), |span, (pats, expr)| {
+            pats.iter()
+                .fold(span, |span, pat| span.merge(pat.span))
+                .merge(expr.span)
+        });
 
         let clauses = clauses
             .into_iter()
@@ -72,6 +153,9 @@ impl Desugar {
                 (
                     Pattern {
                         ty: (),
+                        span: pats
+                            .iter()
+                            .fold(Span::default(), |span, pat| span.merge(pat.span)),
                         inner: PatternKind::Tuple { tuple: pats },
                     },
                     self.transform_expr(expr),
@@ -83,15 +167,18 @@ impl Desugar {
 
         let body = Expr {
             ty: (),
+            span,
             inner: ExprKind::Case {
                 cond: Expr {
                     ty: (),
+                    span,
                     inner: ExprKind::Tuple {
                         tuple: params
                             .iter()
                             .cloned()
                             .map(|name| Expr {
                                 ty: (),
+                                span,
                                 inner: ExprKind::Symbol { name },
                             })
                             .collect(),
@@ -104,24 +191,24 @@ impl Desugar {
 
         let fun = params.into_iter().rev().fold(body, |body, param| Expr {
             ty: (),
+            span,
             inner: ExprKind::Fn {
                 param,
                 body: body.boxed(),
             },
         });
-        Declaration::Val {
-            rec: true,
-            pattern: Pattern {
-                ty: (),
-                inner: PatternKind::Variable { name: name },
-            },
-            expr: fun,
-        }
+        let pattern = Pattern {
+            ty: (),
+            span,
+            inner: PatternKind::Variable { name: name },
+        };
+        (pattern, fun)
     }
 
     fn transform_expr(&mut self, expr: UntypedExpr) -> UntypedCoreExpr {
         use crate::ast::ExprKind::*;
         let inner = match expr.inner {
+            D(DerivedExpr::Fn { clauses }) => self.transform_fn_clauses(clauses, expr.span),
             Binds { binds, ret } => self.transform_binds(binds, ret),
             BuiltinCall { fun, args } => self.transform_builtincall(fun, args),
             ExternCall {
@@ -138,9 +225,17 @@ impl Desugar {
             Constructor { arg, name } => self.transform_constructor(arg, name),
             Symbol { name } => self.transform_symbol(name),
             Literal { value } => self.transform_literal(value),
-            D(DerivedExprKind::If { cond, then, else_ }) => self.transform_if(cond, then, else_),
+            Typed { expr, ty } => self.transform_typed(expr, ty),
+            If { cond, then, else_ } => self.transform_if(cond, then, else_),
+            AndAlso { l, r } => self.transform_andalso(l, r),
+            OrElse { l, r } => self.transform_orelse(l, r),
+            Seq { exprs } => self.transform_seq(exprs),
         };
-        UntypedCoreExpr { ty: expr.ty, inner }
+        UntypedCoreExpr {
+            ty: expr.ty,
+            span: expr.span,
+            inner,
+        }
     }
     fn transform_binds(
         &mut self,
@@ -150,7 +245,7 @@ impl Desugar {
         ExprKind::Binds {
             binds: binds
                 .into_iter()
-                .filter_map(|decl| self.transform_statement(decl))
+                .flat_map(|decl| self.transform_statement(decl))
                 .collect(),
             ret: self.transform_expr(*ret).boxed(),
         }
@@ -172,7 +267,7 @@ impl Desugar {
         fun: String,
         args: Vec<UntypedExpr>,
         argty: Vec<Type>,
-        retty: Type,
+        retty: Vec<Type>,
     ) -> UntypedCoreExprKind {
         ExprKind::ExternCall {
             module,
@@ -193,6 +288,32 @@ impl Desugar {
         }
     }
 
+    // fn p1 => e1 | p2 => e2 | ...
+    // => fn x => case x of p1 => e1 | p2 => e2 | ...
+    fn transform_fn_clauses(
+        &mut self,
+        clauses: Vec<(UntypedPattern, UntypedExpr)>,
+        span: Span,
+    ) -> UntypedCoreExprKind {
+        let param = self.gensym();
+        let cond = Expr {
+            ty: (),
+            span,
+            inner: ExprKind::Symbol {
+                name: param.clone(),
+            },
+        };
+        ExprKind::Fn {
+            param,
+            body: Expr {
+                ty: (),
+                span,
+                inner: self.transform_case(cond.boxed(), clauses),
+            }
+            .boxed(),
+        }
+    }
+
     fn transform_app(
         &mut self,
         fun: Box<UntypedExpr>,
@@ -210,30 +331,32 @@ impl Desugar {
         then: Box<UntypedExpr>,
         else_: Box<UntypedExpr>,
     ) -> UntypedCoreExprKind {
-        ExprKind::Case {
+        ExprKind::If {
             cond: self.transform_expr(*cond).boxed(),
-            clauses: vec![
-                (
-                    Pattern {
-                        ty: (),
-                        inner: PatternKind::Constructor {
-                            arg: None,
-                            name: Symbol::new("true"),
-                        },
-                    },
-                    self.transform_expr(*then),
-                ),
-                (
-                    Pattern {
-                        ty: (),
-                        inner: PatternKind::Constructor {
-                            arg: None,
-                            name: Symbol::new("false"),
-                        },
-                    },
-                    self.transform_expr(*else_),
-                ),
-            ],
+            then: self.transform_expr(*then).boxed(),
+            else_: self.transform_expr(*else_).boxed(),
+        }
+    }
+
+    fn transform_andalso(
+        &mut self,
+        l: Box<UntypedExpr>,
+        r: Box<UntypedExpr>,
+    ) -> UntypedCoreExprKind {
+        ExprKind::AndAlso {
+            l: self.transform_expr(*l).boxed(),
+            r: self.transform_expr(*r).boxed(),
+        }
+    }
+
+    fn transform_orelse(
+        &mut self,
+        l: Box<UntypedExpr>,
+        r: Box<UntypedExpr>,
+    ) -> UntypedCoreExprKind {
+        ExprKind::OrElse {
+            l: self.transform_expr(*l).boxed(),
+            r: self.transform_expr(*r).boxed(),
         }
     }
 
@@ -251,6 +374,12 @@ impl Desugar {
         }
     }
 
+    fn transform_seq(&mut self, exprs: Vec<UntypedExpr>) -> UntypedCoreExprKind {
+        ExprKind::Seq {
+            exprs: exprs.into_iter().map(|e| self.transform_expr(e)).collect(),
+        }
+    }
+
     fn transform_tuple(&mut self, tuple: Vec<UntypedExpr>) -> UntypedCoreExprKind {
         ExprKind::Tuple {
             tuple: tuple.into_iter().map(|t| self.transform_expr(t)).collect(),
@@ -275,6 +404,13 @@ impl Desugar {
         ExprKind::Literal { value }
     }
 
+    fn transform_typed(&mut self, expr: Box<UntypedExpr>, ty: Type) -> UntypedCoreExprKind {
+        ExprKind::Typed {
+            expr: self.transform_expr(*expr).boxed(),
+            ty,
+        }
+    }
+
     fn transform_pattern(&mut self, pattern: UntypedPattern) -> UntypedPattern {
         pattern
     }
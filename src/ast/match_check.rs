@@ -0,0 +1,376 @@
+use super::util::Traverse;
+use crate::ast::*;
+use crate::config::Config;
+use crate::pass::Pass;
+use std::fmt;
+
+#[derive(Debug)]
+pub struct MatchCheck {
+    warnings: Vec<Warning>,
+}
+
+struct MatchCheckPass<'a> {
+    symbol_table: &'a SymbolTable,
+    warnings: &'a mut Vec<Warning>,
+}
+
+// patterns abstracted to constructors applied to sub patterns.
+// variables and wildcards are the same for the analysis.
+#[derive(Debug, Clone)]
+enum Pat {
+    Wild,
+    Con(Con, Vec<Pat>),
+    Or(Vec<Pat>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Con {
+    Tuple(usize),
+    Datatype(Symbol),
+    Int(i64),
+    Char(u32),
+    Str(String),
+}
+
+impl MatchCheck {
+    pub fn new() -> Self {
+        Self {
+            warnings: Vec::new(),
+        }
+    }
+
+    /// the warnings found so far
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+}
+
+impl Pat {
+    fn from_pattern(pattern: &TypedPattern) -> Self {
+        use PatternKind::*;
+        match &pattern.inner {
+            Constant { value } => Pat::Con(Con::Int(*value), vec![]),
+            Char { value } => Pat::Con(Con::Char(*value), vec![]),
+            Str { value } => Pat::Con(Con::Str(value.clone()), vec![]),
+            Constructor { name, arg } => Pat::Con(
+                Con::Datatype(name.clone()),
+                arg.iter().map(|arg| Pat::from_pattern(arg)).collect(),
+            ),
+            Tuple { tuple } => Pat::Con(
+                Con::Tuple(tuple.len()),
+                tuple.iter().map(Pat::from_pattern).collect(),
+            ),
+            Variable { .. } | Wildcard {} => Pat::Wild,
+            As { pattern, .. } => Pat::from_pattern(pattern),
+            Or { alternatives } => Pat::Or(alternatives.iter().map(Pat::from_pattern).collect()),
+        }
+    }
+}
+
+impl fmt::Display for Pat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pat::Wild => write!(f, "_"),
+            Pat::Con(Con::Tuple(_), args) => {
+                write!(f, "(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+            Pat::Con(Con::Datatype(name), args) => {
+                write!(f, "{}", name.0)?;
+                for arg in args {
+                    match arg {
+                        Pat::Con(Con::Datatype(_), args) if !args.is_empty() => {
+                            write!(f, " ({})", arg)?
+                        }
+                        arg => write!(f, " {}", arg)?,
+                    }
+                }
+                Ok(())
+            }
+            Pat::Con(Con::Int(value), _) => write!(f, "{}", value),
+            Pat::Con(Con::Char(value), _) => write!(f, r##"#"{}""##, value),
+            Pat::Con(Con::Str(value), _) => write!(f, r#""{}""#, value),
+            Pat::Or(alternatives) => {
+                for (i, alternative) in alternatives.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " | ")?;
+                    }
+                    write!(f, "{}", alternative)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// the rows with the or-patterns at the first column split to the rows for each alternative
+fn expand_or(rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    let mut ret = Vec::new();
+    for row in rows {
+        match &row[0] {
+            Pat::Or(alternatives) => {
+                let rows = alternatives
+                    .iter()
+                    .map(|alternative| [vec![alternative.clone()], row[1..].to_vec()].concat())
+                    .collect::<Vec<_>>();
+                ret.extend(expand_or(&rows))
+            }
+            _ => ret.push(row.clone()),
+        }
+    }
+    ret
+}
+
+// the rows whose first pattern matches `con`, with the first pattern replaced by its arguments
+fn specialize(rows: &[Vec<Pat>], con: &Con, arity: usize) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter_map(|row| {
+            let mut args = match &row[0] {
+                Pat::Con(c, args) if c == con => args.clone(),
+                Pat::Con(..) => return None,
+                Pat::Wild => vec![Pat::Wild; arity],
+                Pat::Or(_) => unreachable!("or-patterns must be expanded"),
+            };
+            args.extend(row[1..].iter().cloned());
+            Some(args)
+        })
+        .collect()
+}
+
+// the rows whose first pattern matches anything, without the first pattern
+fn default(rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter_map(|row| match &row[0] {
+            Pat::Wild => Some(row[1..].to_vec()),
+            Pat::Con(..) => None,
+            Pat::Or(_) => unreachable!("or-patterns must be expanded"),
+        })
+        .collect()
+}
+
+// the constructors that appear at the first column
+fn head_constructors(rows: &[Vec<Pat>]) -> Vec<Con> {
+    let mut heads = Vec::new();
+    for row in rows {
+        if let Pat::Con(con, _) = &row[0] {
+            if !heads.contains(con) {
+                heads.push(con.clone())
+            }
+        }
+    }
+    heads
+}
+
+impl<'a> MatchCheckPass<'a> {
+    fn new(symbol_table: &'a SymbolTable, warnings: &'a mut Vec<Warning>) -> Self {
+        Self {
+            symbol_table,
+            warnings,
+        }
+    }
+
+    fn arity(&self, con: &Con) -> usize {
+        match con {
+            Con::Tuple(n) => *n,
+            Con::Datatype(name) => match self.symbol_table.get_argtype_of_constructor(name) {
+                Some(_) => 1,
+                None => 0,
+            },
+            Con::Int(_) | Con::Char(_) | Con::Str(_) => 0,
+        }
+    }
+
+    // the constructors of the type of `heads` that are not in `heads`.
+    // `None` if the type has too many constructors to enumerate, like int.
+    fn missing_constructors(&self, heads: &[Con]) -> Option<Vec<Con>> {
+        match &heads[0] {
+            Con::Tuple(_) => Some(vec![]),
+            Con::Datatype(name) => {
+                let type_name = self
+                    .symbol_table
+                    .get_datatype_of_constructor(name)
+                    .expect("internal error: type not found for constructor");
+                let info = self
+                    .symbol_table
+                    .get_type(type_name)
+                    .expect("internal error: type not found");
+                Some(
+                    info.constructors
+                        .iter()
+                        .map(|(cname, _)| Con::Datatype(cname.clone()))
+                        .filter(|con| !heads.contains(con))
+                        .collect(),
+                )
+            }
+            Con::Int(_) | Con::Char(_) | Con::Str(_) => None,
+        }
+    }
+
+    // the value vectors of length `n` that none of `rows` matches,
+    // abstracted by patterns.
+    // this is the usefulness algorithm from
+    // "Warnings for pattern matching" by Luc Maranget,
+    // extended to collect the witnesses.
+    fn uncovered(&self, rows: &[Vec<Pat>], n: usize) -> Vec<Vec<Pat>> {
+        if n == 0 {
+            return if rows.is_empty() {
+                vec![vec![]]
+            } else {
+                vec![]
+            };
+        }
+
+        let rows = &expand_or(rows);
+        let heads = head_constructors(rows);
+        let missing = if heads.is_empty() {
+            None
+        } else {
+            self.missing_constructors(&heads)
+        };
+
+        match missing {
+            // all the constructors appear. the values are uncovered if some of their arguments are
+            Some(ref missing) if missing.is_empty() => {
+                let mut ret = Vec::new();
+                for con in heads {
+                    let arity = self.arity(&con);
+                    let rows = specialize(rows, &con, arity);
+                    for mut args in self.uncovered(&rows, arity + n - 1) {
+                        let rest = args.split_off(arity);
+                        let mut witness = vec![Pat::Con(con.clone(), args)];
+                        witness.extend(rest);
+                        ret.push(witness);
+                    }
+                }
+                ret
+            }
+            // only the rows that match anything can cover the missing constructors
+            missing => {
+                let rests = self.uncovered(&default(rows), n - 1);
+                let firsts = match missing {
+                    Some(missing) => missing
+                        .into_iter()
+                        .map(|con| {
+                            let arity = self.arity(&con);
+                            Pat::Con(con, vec![Pat::Wild; arity])
+                        })
+                        .collect(),
+                    None => vec![Pat::Wild],
+                };
+                let mut ret = Vec::new();
+                for first in firsts {
+                    for rest in &rests {
+                        let mut witness = vec![first.clone()];
+                        witness.extend(rest.iter().cloned());
+                        ret.push(witness);
+                    }
+                }
+                ret
+            }
+        }
+    }
+
+    // whether some value matched by `row` is not matched by any of `rows`
+    fn useful(&self, rows: &[Vec<Pat>], row: &[Pat]) -> bool {
+        if row.is_empty() {
+            return rows.is_empty();
+        }
+        let rows = &expand_or(rows);
+        match &row[0] {
+            Pat::Con(con, args) => {
+                let rows = specialize(rows, con, args.len());
+                self.useful(&rows, &[args.clone(), row[1..].to_vec()].concat())
+            }
+            Pat::Wild => {
+                let heads = head_constructors(rows);
+                let missing = if heads.is_empty() {
+                    None
+                } else {
+                    self.missing_constructors(&heads)
+                };
+                match missing {
+                    Some(ref missing) if missing.is_empty() => heads.iter().any(|con| {
+                        let arity = self.arity(con);
+                        let rows = specialize(rows, con, arity);
+                        self.useful(&rows, &[vec![Pat::Wild; arity], row[1..].to_vec()].concat())
+                    }),
+                    _ => self.useful(&default(rows), &row[1..]),
+                }
+            }
+            // useful if any of the alternatives is
+            Pat::Or(alternatives) => alternatives.iter().any(|alternative| {
+                self.useful(
+                    rows,
+                    &[vec![alternative.clone()], row[1..].to_vec()].concat(),
+                )
+            }),
+        }
+    }
+
+    fn check_redundant(&mut self, clauses: &[(TypedPattern, TypedCoreExpr)]) {
+        let mut rows = Vec::new();
+        let mut reachable = true;
+        for (pat, _) in clauses {
+            let row = vec![Pat::from_pattern(pat)];
+            if !reachable || !self.useful(&rows, &row) {
+                self.warnings.push(Warning::RedundantArm { span: pat.span })
+            }
+            // arms after an irrefutable pattern are never reached
+            reachable = reachable && !pat.is_irrefutable();
+            rows.push(row);
+        }
+    }
+
+    fn check_exhaustive(&mut self, span: Span, clauses: &[(TypedPattern, TypedCoreExpr)]) {
+        let rows = clauses
+            .iter()
+            .map(|(pat, _)| vec![Pat::from_pattern(pat)])
+            .collect::<Vec<_>>();
+        let missing = self
+            .uncovered(&rows, 1)
+            .into_iter()
+            .map(|witness| witness[0].to_string())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            self.warnings.push(Warning::NonExhaustive { span, missing })
+        }
+    }
+}
+
+impl<'a> Traverse<Type> for MatchCheckPass<'a> {
+    fn traverse_case(
+        &mut self,
+        cond: &mut Box<TypedCoreExpr>,
+        clauses: &mut Vec<(TypedPattern, TypedCoreExpr)>,
+    ) {
+        let span = clauses.iter().fold(cond.span, |span, (pat, arm)| {
+            span.merge(pat.span).merge(arm.span)
+        });
+        self.check_exhaustive(span, clauses);
+        self.check_redundant(clauses);
+
+        self.traverse_expr(cond);
+        for (_, arm) in clauses.iter_mut() {
+            self.traverse_expr(arm);
+        }
+    }
+}
+
+impl<'a> Pass<(SymbolTable, TypedCore), TypeError<'a>> for MatchCheck {
+    type Target = (SymbolTable, TypedCore);
+
+    fn trans<'b>(
+        &'b mut self,
+        (symbol_table, mut ast): (SymbolTable, TypedCore),
+        _: &Config,
+    ) -> Result<'a, Self::Target> {
+        MatchCheckPass::new(&symbol_table, &mut self.warnings).traverse_ast(&mut ast);
+        Ok((symbol_table, ast))
+    }
+}
@@ -1,18 +1,27 @@
 mod case_simplify;
+mod deps;
 mod desugar;
+mod match_check;
+mod monomorphize;
 mod pp;
 mod rename;
+mod type_at;
 mod typing;
 mod util;
 mod var2constructor;
 
 pub use self::case_simplify::CaseSimplify;
+pub use self::deps::Names;
 pub use self::desugar::Desugar;
+pub use self::match_check::MatchCheck;
+pub use self::monomorphize::Monomorphize;
 pub use self::rename::Rename;
 pub use self::typing::Typer;
 pub use self::var2constructor::VarToConstructor;
 use crate::ast;
+use crate::hir::HirError;
 use crate::prim::*;
+use crate::util::PP;
 use nom;
 pub use std::collections::HashMap;
 use std::error::Error;
@@ -23,20 +32,56 @@ pub type Core<Ty> = AST<Ty, Nothing, Nothing>;
 pub type UntypedCore = Core<()>;
 pub type TypedCore = Core<Type>;
 
+/// the result of the typing for the tools inspecting the types of a program,
+/// made by `webml::typecheck`
 #[derive(Debug, Clone, PartialEq)]
-pub struct AST<Ty, DE = DerivedExprKind<Ty>, DS = DerivedDeclaration<Ty>>(
+pub struct Typed {
+    /// the datatypes, and the constructors to their datatypes
+    pub symbol_table: SymbolTable,
+    pub ast: TypedCore,
+    /// the warnings of the checks of the matches, for the caller to report
+    pub warnings: Vec<Warning>,
+}
+
+impl Typed {
+    /// the variables bound at the top level with their types, in order.
+    /// the types of polymorphic ones have `Type::Variable`s
+    pub fn bindings(&self) -> Vec<(&Symbol, &Type)> {
+        self.ast
+            .0
+            .iter()
+            .flat_map(|decl| match decl {
+                Declaration::Val { pattern, .. } => pattern.binds(),
+                _ => vec![],
+            })
+            .collect()
+    }
+
+    /// the type of the variable bound at the top level as `name`, the last one if shadowed
+    pub fn type_of(&self, name: &str) -> Option<&Type> {
+        self.bindings()
+            .into_iter()
+            .filter(|(sym, _)| sym.0 == name)
+            .map(|(_, ty)| ty)
+            .last()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AST<Ty, DE = DerivedExpr<Ty>, DS = DerivedDeclaration<Ty>>(
     pub Vec<Declaration<Ty, DE, DS>>,
 );
 
-pub type UntypedDeclaration = Declaration<(), DerivedExprKind<()>, DerivedDeclaration<()>>;
+pub type UntypedDeclaration = Declaration<(), DerivedExpr<()>, DerivedDeclaration<()>>;
 pub type CoreDeclaration<Ty> = Declaration<Ty, Nothing, Nothing>;
 pub type UntypedCoreDeclaration = CoreDeclaration<()>;
 pub type TypedCoreDeclaration = CoreDeclaration<Type>;
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum Declaration<Ty, DE = DerivedExprKind<Ty>, DS = DerivedDeclaration<Ty>> {
+pub enum Declaration<Ty, DE = DerivedExpr<Ty>, DS = DerivedDeclaration<Ty>> {
     Datatype {
         name: Symbol,
+        params: Vec<u64>,
         constructors: Vec<(Symbol, Option<Type>)>,
     },
     Val {
@@ -44,6 +89,23 @@ pub enum Declaration<Ty, DE = DerivedExprKind<Ty>, DS = DerivedDeclaration<Ty>>
         pattern: Pattern<Ty>,
         expr: Expr<Ty, DE, DS>,
     },
+    /// mutually recursive bindings `val rec p1 = e1 and p2 = e2 ...`
+    ValRecGroup {
+        binds: Vec<(Pattern<Ty>, Expr<Ty, DE, DS>)>,
+    },
+    /// `_import ("module"."fun": (arg, ty) -> retty)`, the signature of an imported function
+    /// for the `_externcall`s that don't spell it out
+    Import {
+        module: String,
+        fun: String,
+        argty: Vec<Type>,
+        retty: Vec<Type>,
+    },
+    /// exports the function `value` from the wasm module as `name`
+    Export {
+        name: String,
+        value: Symbol,
+    },
     D(DS),
 }
 
@@ -53,10 +115,27 @@ pub enum DerivedDeclaration<Ty> {
         name: Symbol,
         clauses: Vec<(Vec<Pattern<Ty>>, Expr<Ty>)>,
     },
+    /// mutually recursive functions `fun f ... and g ...`
+    FunGroup {
+        funs: Vec<(Symbol, Vec<(Vec<Pattern<Ty>>, Expr<Ty>)>)>,
+    },
     Infix {
         priority: Option<u8>,
         names: Vec<Symbol>,
     },
+    /// `export "name" fun f ...`, a top level function exported as `name`
+    Export {
+        name: String,
+        decl: Box<Declaration<Ty>>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DerivedExpr<Ty> {
+    /// `fn p1 => e1 | p2 => e2 ...`, except for `fn x => e` which is `ExprKind::Fn`
+    Fn {
+        clauses: Vec<(Pattern<Ty>, Expr<Ty>)>,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -73,14 +152,15 @@ pub type TypedCoreExprKind = CoreExprKind<Type>;
 #[derive(Debug, Clone, PartialEq)]
 pub struct Annot<Ty, Inner> {
     pub ty: Ty,
+    pub span: Span,
     pub inner: Inner,
 }
 
-pub type Expr<Ty, DE = DerivedExprKind<Ty>, DS = DerivedDeclaration<Ty>> =
+pub type Expr<Ty, DE = DerivedExpr<Ty>, DS = DerivedDeclaration<Ty>> =
     Annot<Ty, ExprKind<Ty, DE, DS>>;
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum ExprKind<Ty, DE = DerivedExprKind<Ty>, DS = DerivedDeclaration<Ty>> {
+pub enum ExprKind<Ty, DE = DerivedExpr<Ty>, DS = DerivedDeclaration<Ty>> {
     Binds {
         binds: Vec<Declaration<Ty, DE, DS>>,
         ret: Box<Expr<Ty, DE, DS>>,
@@ -93,8 +173,10 @@ pub enum ExprKind<Ty, DE = DerivedExprKind<Ty>, DS = DerivedDeclaration<Ty>> {
         module: String,
         fun: String,
         args: Vec<Expr<Ty, DE, DS>>,
+        /// empty if the signature is declared by `Declaration::Import`
         argty: Vec<Type>,
-        retty: Type,
+        /// the results. more than one makes a tuple, with wasm multi-value
+        retty: Vec<Type>,
     },
     Fn {
         param: Symbol,
@@ -108,6 +190,25 @@ pub enum ExprKind<Ty, DE = DerivedExprKind<Ty>, DS = DerivedDeclaration<Ty>> {
         cond: Box<Expr<Ty, DE, DS>>,
         clauses: Vec<(Pattern<Ty>, Expr<Ty, DE, DS>)>,
     },
+    If {
+        cond: Box<Expr<Ty, DE, DS>>,
+        then: Box<Expr<Ty, DE, DS>>,
+        else_: Box<Expr<Ty, DE, DS>>,
+    },
+    /// `l andalso r`, evaluating `r` only if `l` is true
+    AndAlso {
+        l: Box<Expr<Ty, DE, DS>>,
+        r: Box<Expr<Ty, DE, DS>>,
+    },
+    /// `l orelse r`, evaluating `r` only if `l` is false
+    OrElse {
+        l: Box<Expr<Ty, DE, DS>>,
+        r: Box<Expr<Ty, DE, DS>>,
+    },
+    /// `(e1; e2; ...; en)`, evaluating to the value of `en`
+    Seq {
+        exprs: Vec<Expr<Ty, DE, DS>>,
+    },
     Tuple {
         tuple: Vec<Expr<Ty, DE, DS>>,
     },
@@ -121,16 +222,11 @@ pub enum ExprKind<Ty, DE = DerivedExprKind<Ty>, DS = DerivedDeclaration<Ty>> {
     Literal {
         value: Literal,
     },
-    D(DE),
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum DerivedExprKind<Ty> {
-    If {
-        cond: Box<Expr<Ty>>,
-        then: Box<Expr<Ty>>,
-        else_: Box<Expr<Ty>>,
+    Typed {
+        expr: Box<Expr<Ty, DE, DS>>,
+        ty: Type,
     },
+    D(DE),
 }
 
 pub type UntypedPattern = Pattern<()>;
@@ -150,6 +246,10 @@ pub enum PatternKind<Ty> {
         // same type as Literal::Char
         value: u32,
     },
+    Str {
+        // same type as Literal::String
+        value: String,
+    },
     Constructor {
         name: Symbol,
         arg: Option<Box<Pattern<Ty>>>,
@@ -161,6 +261,13 @@ pub enum PatternKind<Ty> {
         name: Symbol,
     },
     Wildcard {},
+    As {
+        name: Symbol,
+        pattern: Box<Pattern<Ty>>,
+    },
+    Or {
+        alternatives: Vec<Pattern<Ty>>,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -172,16 +279,22 @@ pub struct SymbolTable {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Type {
     Variable(u64),
+    Unit,
     Char,
+    String,
     Int,
+    Word,
     Real,
+    Bool,
     Fun(Box<Type>, Box<Type>),
     Tuple(Vec<Type>),
-    Datatype(Symbol),
+    Datatype(Symbol, Vec<Type>),
+    Ref(Box<Type>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct TypeInfo {
+    pub params: Vec<u64>,
     pub constructors: Vec<(Symbol, Option<Type>)>,
 }
 
@@ -207,13 +320,39 @@ impl<Ty> CoreDeclaration<Ty> {
     fn map_ty<Ty2>(self, f: &mut dyn FnMut(Ty) -> Ty2) -> CoreDeclaration<Ty2> {
         use Declaration::*;
         match self {
-            Datatype { name, constructors } => Datatype { name, constructors },
+            Datatype {
+                name,
+                params,
+                constructors,
+            } => Datatype {
+                name,
+                params,
+                constructors,
+            },
 
             Val { pattern, expr, rec } => Val {
                 rec,
                 pattern: pattern.map_ty(&mut *f),
                 expr: expr.map_ty(f),
             },
+            ValRecGroup { binds } => ValRecGroup {
+                binds: binds
+                    .into_iter()
+                    .map(|(pattern, expr)| (pattern.map_ty(&mut *f), expr.map_ty(&mut *f)))
+                    .collect(),
+            },
+            Import {
+                module,
+                fun,
+                argty,
+                retty,
+            } => Import {
+                module,
+                fun,
+                argty,
+                retty,
+            },
+            Export { name, value } => Export { name, value },
             D(d) => match d {},
         }
     }
@@ -223,6 +362,7 @@ impl<Ty> CoreExpr<Ty> {
     fn map_ty<Ty2>(self, f: &mut dyn FnMut(Ty) -> Ty2) -> CoreExpr<Ty2> {
         use crate::ast::ExprKind::*;
         let ty = f(self.ty);
+        let span = self.span;
         let inner = match self.inner {
             Binds { binds, ret } => Binds {
                 binds: binds.into_iter().map(|val| val.map_ty(f)).collect(),
@@ -260,6 +400,22 @@ impl<Ty> CoreExpr<Ty> {
                     .map(move |(pat, expr)| (pat.map_ty(&mut *f), expr.map_ty(f)))
                     .collect(),
             },
+            If { cond, then, else_ } => If {
+                cond: cond.map_ty(&mut *f).boxed(),
+                then: then.map_ty(&mut *f).boxed(),
+                else_: else_.map_ty(f).boxed(),
+            },
+            AndAlso { l, r } => AndAlso {
+                l: l.map_ty(&mut *f).boxed(),
+                r: r.map_ty(f).boxed(),
+            },
+            OrElse { l, r } => OrElse {
+                l: l.map_ty(&mut *f).boxed(),
+                r: r.map_ty(f).boxed(),
+            },
+            Seq { exprs } => Seq {
+                exprs: exprs.into_iter().map(|e| e.map_ty(f)).collect(),
+            },
             Tuple { tuple } => Tuple {
                 tuple: tuple.into_iter().map(|t| t.map_ty(f)).collect(),
             },
@@ -270,9 +426,35 @@ impl<Ty> CoreExpr<Ty> {
                 name,
             },
             Literal { value } => Literal { value },
+            Typed { expr, ty } => Typed {
+                expr: expr.map_ty(f).boxed(),
+                ty,
+            },
             D(d) => match d {},
         };
-        Expr { ty, inner }
+        Expr { ty, span, inner }
+    }
+
+    /// whether evaluating the expression may do computation.
+    /// the types of expansive bindings must not be generalized (value restriction)
+    pub fn is_expansive(&self) -> bool {
+        use crate::ast::ExprKind::*;
+        match &self.inner {
+            Fn { .. } | Symbol { .. } | Literal { .. } => false,
+            Tuple { tuple } => tuple.iter().any(|e| e.is_expansive()),
+            Constructor { arg, .. } => arg.iter().any(|arg| arg.is_expansive()),
+            Typed { expr, .. } => expr.is_expansive(),
+            Binds { .. }
+            | BuiltinCall { .. }
+            | ExternCall { .. }
+            | App { .. }
+            | Case { .. }
+            | If { .. }
+            | AndAlso { .. }
+            | OrElse { .. }
+            | Seq { .. } => true,
+            D(d) => match *d {},
+        }
     }
 }
 
@@ -280,9 +462,11 @@ impl<Ty> Pattern<Ty> {
     fn map_ty<Ty2>(self, f: &mut dyn FnMut(Ty) -> Ty2) -> Pattern<Ty2> {
         use PatternKind::*;
         let ty = f(self.ty);
+        let span = self.span;
         let inner = match self.inner {
             Constant { value } => Constant { value },
             Char { value } => Char { value },
+            Str { value } => Str { value },
             Constructor { name, arg } => Constructor {
                 name,
                 arg: arg.map(|pat| Box::new(pat.map_ty(f))),
@@ -292,17 +476,41 @@ impl<Ty> Pattern<Ty> {
             },
             Variable { name } => Variable { name },
             Wildcard {} => Wildcard {},
+            As { name, pattern } => As {
+                name,
+                pattern: Box::new(pattern.map_ty(f)),
+            },
+            Or { alternatives } => Or {
+                alternatives: alternatives.into_iter().map(|pat| pat.map_ty(f)).collect(),
+            },
         };
-        Pattern { ty, inner }
+        Pattern { ty, span, inner }
     }
 
     pub fn binds(&self) -> Vec<(&Symbol, &Ty)> {
         use self::PatternKind::*;
         match &self.inner {
-            Constant { .. } | Char { .. } | Wildcard { .. } => vec![],
+            Constant { .. } | Char { .. } | Str { .. } | Wildcard { .. } => vec![],
             Variable { name } => vec![(name, &self.ty)],
             Tuple { tuple, .. } => tuple.iter().flat_map(|pat| pat.binds()).collect(),
             Constructor { arg, .. } => arg.iter().flat_map(|pat| pat.binds()).collect(),
+            As { name, pattern } => {
+                let mut binds = vec![(name, &self.ty)];
+                binds.extend(pattern.binds());
+                binds
+            }
+            // the names bound in all the alternatives
+            Or { alternatives } => match alternatives.split_first() {
+                Some((first, rest)) => first
+                    .binds()
+                    .into_iter()
+                    .filter(|(name, _)| {
+                        rest.iter()
+                            .all(|pat| pat.binds().iter().any(|(n, _)| n == name))
+                    })
+                    .collect(),
+                None => vec![],
+            },
         }
     }
 
@@ -338,6 +546,14 @@ impl<Ty> Pattern<Ty> {
         }
     }
 
+    pub fn is_str(&self) -> bool {
+        use self::PatternKind::*;
+        match &self.inner {
+            Str { .. } => true,
+            _ => false,
+        }
+    }
+
     pub fn is_tuple(&self) -> bool {
         use self::PatternKind::*;
         match &self.inner {
@@ -345,6 +561,26 @@ impl<Ty> Pattern<Ty> {
             _ => false,
         }
     }
+
+    pub fn is_or(&self) -> bool {
+        use self::PatternKind::*;
+        match &self.inner {
+            Or { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// whether the pattern matches all the values of its type
+    pub fn is_irrefutable(&self) -> bool {
+        use self::PatternKind::*;
+        match &self.inner {
+            Variable { .. } | Wildcard {} => true,
+            Tuple { tuple } => tuple.iter().all(|pat| pat.is_irrefutable()),
+            Constant { .. } | Char { .. } | Str { .. } | Constructor { .. } => false,
+            As { pattern, .. } => pattern.is_irrefutable(),
+            Or { alternatives } => alternatives.iter().any(|pat| pat.is_irrefutable()),
+        }
+    }
 }
 
 impl Type {
@@ -352,7 +588,7 @@ impl Type {
         Type::Fun(Box::new(param), Box::new(ret))
     }
     pub fn unit() -> Type {
-        Type::Tuple(Vec::new())
+        Type::Unit
     }
 }
 
@@ -407,16 +643,184 @@ impl SymbolTable {
 
 #[derive(Debug)]
 pub enum TypeError<'a> {
-    MisMatch { expected: Type, actual: Type },
+    MisMatch {
+        expected: Type,
+        actual: Type,
+        span: Option<Span>,
+    },
+    InfiniteType {
+        var: Type,
+        ty: Type,
+        span: Option<Span>,
+    },
+    NotEqualityType {
+        ty: Type,
+        span: Option<Span>,
+    },
+    UnknownType {
+        name: Symbol,
+    },
+    RecursiveType {
+        name: Symbol,
+    },
+    /// a datatype declared in a `let` reaches the outside of it
+    EscapingType {
+        name: Symbol,
+        span: Option<Span>,
+    },
+    /// an `_externcall` without types to a function not declared by `_import`
+    UndeclaredImport {
+        module: String,
+        fun: String,
+        span: Option<Span>,
+    },
+    /// an exported value is not a monomorphic function between numbers, chars, bools and unit,
+    /// defined by `fn` at the top level
+    NotExportable {
+        name: Symbol,
+        ty: Type,
+    },
+    /// an `_externcall` passes a different number of arguments than the function takes
+    ImportArity {
+        module: String,
+        fun: String,
+        expected: usize,
+        actual: usize,
+        span: Option<Span>,
+    },
     CannotInfer,
-    FreeVar,
+    /// a match that does not cover every value, which cannot be compiled without exceptions
+    Inexhaustive,
+    FreeVar {
+        name: Symbol,
+        /// a name in scope spelled similarly
+        suggestion: Option<Symbol>,
+        span: Option<Span>,
+    },
     NotFunction(ast::Expr<Type>),
+    OrPatternBinds {
+        name: Symbol,
+        span: Option<Span>,
+    },
     ParseError(nom::Err<(&'a str, nom::error::ErrorKind)>),
+    InvalidPipeline(String),
+    InvalidHir(HirError),
+    /// the errors of the declarations checked with `Config::collect_type_errors`, in order
+    Multiple(Vec<TypeError<'a>>),
+}
+
+// the types printed in the SML notation, with their variables renamed to 'a, 'b, ...
+// in the order they appear instead of the internal ids
+fn show_types(tys: &[&Type]) -> Vec<String> {
+    fn rename(ty: &Type, names: &mut HashMap<u64, u64>) -> Type {
+        use self::Type::*;
+        match ty {
+            Variable(id) => {
+                let next = names.len() as u64;
+                Variable(*names.entry(*id).or_insert(next))
+            }
+            Fun(param, ret) => Fun(Box::new(rename(param, names)), Box::new(rename(ret, names))),
+            Tuple(tys) => Tuple(tys.iter().map(|ty| rename(ty, names)).collect()),
+            Datatype(name, args) => Datatype(
+                name.clone(),
+                args.iter().map(|ty| rename(ty, names)).collect(),
+            ),
+            Ref(ty) => Ref(Box::new(rename(ty, names))),
+            ty => ty.clone(),
+        }
+    }
+    let mut names = HashMap::new();
+    tys.iter()
+        .map(|ty| {
+            let mut buf = Vec::new();
+            rename(ty, &mut names).pp(&mut buf, 0).unwrap();
+            String::from_utf8(buf).unwrap()
+        })
+        .collect()
 }
 
+// the message without the span, which the callers show with the source by `TypeError::span`
 impl<'a> fmt::Display for TypeError<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt::Debug::fmt(self, f)
+        use self::TypeError::*;
+        match self {
+            MisMatch {
+                expected, actual, ..
+            } => {
+                let tys = show_types(&[expected, actual]);
+                write!(f, "expected {} but found {}", tys[0], tys[1])
+            }
+            InfiniteType { var, ty, .. } => {
+                let tys = show_types(&[var, ty]);
+                write!(f, "type variable {} occurs in {}", tys[0], tys[1])
+            }
+            NotEqualityType { ty, .. } => {
+                write!(f, "{} does not admit equality", show_types(&[ty])[0])
+            }
+            UnknownType { name } => write!(f, "unknown type constructor {}", name.0),
+            RecursiveType { name } => write!(f, "datatype {} has no finite values", name.0),
+            EscapingType { name, .. } => write!(f, "datatype {} would escape its scope", name.0),
+            NotExportable { name, ty } => write!(
+                f,
+                "{} of type {} cannot be exported",
+                name.0,
+                show_types(&[ty])[0]
+            ),
+            UndeclaredImport { module, fun, .. } => {
+                write!(
+                    f,
+                    "\"{}\".\"{}\" is called without a type but not imported",
+                    module, fun
+                )
+            }
+            ImportArity {
+                module,
+                fun,
+                expected,
+                actual,
+                ..
+            } => write!(
+                f,
+                "\"{}\".\"{}\" takes {} arguments but is given {}",
+                module, fun, expected, actual
+            ),
+            CannotInfer => write!(f, "cannot infer the type"),
+            Inexhaustive => write!(f, "cannot compile a non-exhaustive match"),
+            FreeVar {
+                name,
+                suggestion: Some(suggestion),
+                ..
+            } => write!(
+                f,
+                "unbound identifier `{}`, did you mean `{}`?",
+                name.0, suggestion.0
+            ),
+            FreeVar { name, .. } => write!(f, "unbound identifier `{}`", name.0),
+            NotFunction(expr) => {
+                let mut buf = Vec::new();
+                expr.pp(&mut buf, 0).map_err(|_| fmt::Error)?;
+                write!(f, "{} is not a function", String::from_utf8_lossy(&buf))
+            }
+            OrPatternBinds { name, .. } => write!(
+                f,
+                "{} is not bound in all the alternatives of the or-pattern",
+                name.0
+            ),
+            ParseError(nom::Err::Incomplete(_)) => write!(f, "unexpected end of input"),
+            ParseError(nom::Err::Error((input, kind)))
+            | ParseError(nom::Err::Failure((input, kind))) => {
+                let near = input.lines().next().unwrap_or("");
+                write!(f, "parse error ({:?}) near `{}`", kind, near)
+            }
+            InvalidPipeline(reason) => write!(f, "invalid pipeline: {}", reason),
+            InvalidHir(e) => write!(f, "inconsistent HIR: {}", e),
+            Multiple(errors) => {
+                inter_iter!(errors, writeln!(f)?, |e| => {
+                    write!(f, "{}", e)?;
+                });
+                Ok(())
+            }
+        }
     }
 }
 
@@ -425,10 +829,120 @@ impl<'a> Error for TypeError<'a> {
         use self::TypeError::*;
         match self {
             &MisMatch { .. } => "type mismatches against expected type",
+            &InfiniteType { .. } => "type variable occurs in its own type",
+            &NotEqualityType { .. } => "type does not admit equality",
+            &UnknownType { .. } => "unknown type constructor",
+            &RecursiveType { .. } => "datatype has no finite values",
+            &EscapingType { .. } => "datatype escapes its scope",
+            &NotExportable { .. } => "value cannot be exported",
+            &UndeclaredImport { .. } => "extern function without type is not imported",
+            &ImportArity { .. } => "extern function is given wrong number of arguments",
             &CannotInfer => "cannot infer the type",
-            &FreeVar => "free variable is found",
+            &Inexhaustive => "non-exhaustive match cannot be compiled",
+            &FreeVar { .. } => "free variable is found",
             &NotFunction(_) => "not a function",
+            &OrPatternBinds { .. } => "alternatives of or-pattern bind different variables",
             &ParseError(_) => "parse error",
+            &InvalidPipeline(_) => "invalid pipeline of passes",
+            &InvalidHir(_) => "inconsistent HIR, a bug of the compiler",
+            &Multiple(_) => "multiple errors",
+        }
+    }
+}
+
+impl<'a> TypeError<'a> {
+    /// the span of the node that caused the error, if known
+    pub fn span(&self) -> Option<Span> {
+        use self::TypeError::*;
+        match self {
+            MisMatch { span, .. }
+            | InfiniteType { span, .. }
+            | NotEqualityType { span, .. }
+            | FreeVar { span, .. }
+            | EscapingType { span, .. }
+            | UndeclaredImport { span, .. }
+            | ImportArity { span, .. }
+            | OrPatternBinds { span, .. } => *span,
+            UnknownType { .. }
+            | RecursiveType { .. }
+            | NotExportable { .. }
+            | CannotInfer
+            | Inexhaustive
+            | NotFunction(_)
+            | ParseError(_)
+            | InvalidPipeline(_)
+            | InvalidHir(_)
+            | Multiple(_) => None,
+        }
+    }
+
+    /// the errors in `Multiple`, or the error itself
+    pub fn into_errors(self) -> Vec<Self> {
+        match self {
+            TypeError::Multiple(errors) => errors,
+            e => vec![e],
+        }
+    }
+
+    /// set `span` to the error unless it already has a more specific one
+    pub fn at(mut self, at: Span) -> Self {
+        use self::TypeError::*;
+        match &mut self {
+            MisMatch { span, .. }
+            | InfiniteType { span, .. }
+            | NotEqualityType { span, .. }
+            | FreeVar { span, .. }
+            | EscapingType { span, .. }
+            | UndeclaredImport { span, .. }
+            | ImportArity { span, .. }
+            | OrPatternBinds { span, .. } => {
+                if span.is_none() && !at.is_dummy() {
+                    *span = Some(at)
+                }
+            }
+            UnknownType { .. }
+            | RecursiveType { .. }
+            | NotExportable { .. }
+            | CannotInfer
+            | Inexhaustive
+            | NotFunction(_)
+            | ParseError(_)
+            | InvalidPipeline(_)
+            | InvalidHir(_)
+            | Multiple(_) => (),
+        }
+        self
+    }
+}
+
+/// problems found in the program that do not stop the compilation
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    NonExhaustive { span: Span, missing: Vec<String> },
+    RedundantArm { span: Span },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::Warning::*;
+        match self {
+            NonExhaustive { missing, .. } => {
+                write!(
+                    f,
+                    "match is not exhaustive. missing: {}",
+                    missing.join(", ")
+                )
+            }
+            RedundantArm { .. } => write!(f, "match arm is never reached"),
+        }
+    }
+}
+
+impl Warning {
+    pub fn span(&self) -> Span {
+        use self::Warning::*;
+        match self {
+            NonExhaustive { span, .. } | RedundantArm { span } => *span,
         }
     }
 }
@@ -452,4 +966,10 @@ impl<'a> From<nom::Err<(&'a str, nom::error::ErrorKind)>> for TypeError<'a> {
     }
 }
 
+impl<'a> From<HirError> for TypeError<'a> {
+    fn from(e: HirError) -> Self {
+        TypeError::InvalidHir(e)
+    }
+}
+
 pub type Result<'a, T> = ::std::result::Result<T, TypeError<'a>>;
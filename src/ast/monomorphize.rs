@@ -0,0 +1,462 @@
+use super::util::Traverse;
+use crate::ast::*;
+use crate::config::Config;
+use crate::id::Id;
+use crate::pass::Pass;
+use std::collections::HashMap;
+
+/// specializes the polymorphic bindings to the types they are used at,
+/// as HIR only has monomorphic types.
+/// the type variables left unconstrained after that can be of any type, and are made `unit`.
+/// the parametric datatypes are also made a datatype without parameters for each of the arguments
+#[derive(Debug)]
+pub struct Monomorphize {
+    id: Id,
+}
+
+#[derive(Debug)]
+struct MonomorphizePass {
+    id: Id,
+    // the polymorphic bindings by the names they bind, as indices of `templates`
+    polys: HashMap<Symbol, usize>,
+    templates: Vec<Template>,
+    // the instances of the parametric datatypes by the name and the arguments
+    datatypes: Vec<((Symbol, Vec<Type>), Symbol)>,
+}
+
+#[derive(Debug)]
+struct Template {
+    decl: TypedCoreDeclaration,
+    // the type variables in the types of the names bound, in order
+    vars: Vec<u64>,
+    // the types of `vars` of each instance and the names it binds
+    instances: Vec<(Vec<Type>, HashMap<Symbol, Symbol>)>,
+}
+
+fn decl_binds(decl: &TypedCoreDeclaration) -> Vec<(&Symbol, &Type)> {
+    match decl {
+        Declaration::Val { pattern, .. } => pattern.binds(),
+        Declaration::ValRecGroup { binds } => binds
+            .iter()
+            .flat_map(|(pattern, _)| pattern.binds())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn type_vars(ty: &Type, vars: &mut Vec<u64>) {
+    use crate::ast::Type::*;
+    match ty {
+        Variable(v) => {
+            if !vars.contains(v) {
+                vars.push(*v)
+            }
+        }
+        Fun(param, ret) => {
+            type_vars(param, vars);
+            type_vars(ret, vars);
+        }
+        Tuple(tys) | Datatype(_, tys) => tys.iter().for_each(|ty| type_vars(ty, vars)),
+        Ref(ty) => type_vars(ty, vars),
+        Unit | Char | String | Int | Word | Real | Bool => (),
+    }
+}
+
+// the values are duplicated for each instance, so only the ones without effects are
+fn is_polymorphic(decl: &TypedCoreDeclaration) -> bool {
+    let values = match decl {
+        Declaration::Val { expr, .. } => !expr.is_expansive(),
+        Declaration::ValRecGroup { binds } => binds.iter().all(|(_, expr)| !expr.is_expansive()),
+        _ => false,
+    };
+    let mut vars = Vec::new();
+    for (_, ty) in decl_binds(decl) {
+        type_vars(ty, &mut vars);
+    }
+    values && !vars.is_empty()
+}
+
+// the types the variables in `declared` take in `actual`
+fn matching(declared: &Type, actual: &Type, subst: &mut HashMap<u64, Type>) {
+    use crate::ast::Type::*;
+    match (declared, actual) {
+        (Variable(v), ty) => {
+            subst.entry(*v).or_insert_with(|| ty.clone());
+        }
+        (Fun(p1, r1), Fun(p2, r2)) => {
+            matching(p1, p2, subst);
+            matching(r1, r2, subst);
+        }
+        (Tuple(tys1), Tuple(tys2)) | (Datatype(_, tys1), Datatype(_, tys2)) => {
+            for (ty1, ty2) in tys1.iter().zip(tys2) {
+                matching(ty1, ty2, subst)
+            }
+        }
+        (Ref(ty1), Ref(ty2)) => matching(ty1, ty2, subst),
+        _ => (),
+    }
+}
+
+fn subst_vars(ty: Type, subst: &dyn Fn(u64) -> Option<Type>) -> Type {
+    use crate::ast::Type::*;
+    match ty {
+        Variable(v) => subst(v).unwrap_or(Variable(v)),
+        Fun(param, ret) => Fun(
+            Box::new(subst_vars(*param, subst)),
+            Box::new(subst_vars(*ret, subst)),
+        ),
+        Tuple(tys) => Tuple(tys.into_iter().map(|ty| subst_vars(ty, subst)).collect()),
+        Datatype(name, tys) => Datatype(
+            name,
+            tys.into_iter().map(|ty| subst_vars(ty, subst)).collect(),
+        ),
+        Ref(ty) => Ref(Box::new(subst_vars(*ty, subst))),
+        Unit | Char | String | Int | Word | Real | Bool => ty,
+    }
+}
+
+// the names bound by `pattern`
+fn pattern_names(pattern: &mut TypedPattern, f: &mut dyn FnMut(&mut Symbol)) {
+    use crate::ast::PatternKind::*;
+    match &mut pattern.inner {
+        Constant { .. } | Char { .. } | Str { .. } | Wildcard {} => (),
+        Constructor { arg, .. } => {
+            if let Some(arg) = arg {
+                pattern_names(arg, f)
+            }
+        }
+        Tuple { tuple } => tuple.iter_mut().for_each(|pat| pattern_names(pat, f)),
+        Variable { name } => f(name),
+        As { name, pattern } => {
+            f(name);
+            pattern_names(pattern, f)
+        }
+        Or { alternatives } => alternatives
+            .iter_mut()
+            .for_each(|pat| pattern_names(pat, f)),
+    }
+}
+
+// gives fresh names to all the names bound in an instance,
+// so that the instances do not share their local names
+struct CollectBinders<'a> {
+    id: &'a mut Id,
+    names: &'a mut HashMap<Symbol, Symbol>,
+}
+
+impl<'a> CollectBinders<'a> {
+    fn bind(&mut self, name: &Symbol) {
+        let id = &mut self.id;
+        self.names
+            .entry(name.clone())
+            .or_insert_with(|| Symbol(name.0.clone(), id.next()));
+    }
+}
+
+impl<'a> Traverse<Type> for CollectBinders<'a> {
+    fn traverse_fn(&mut self, param: &mut Symbol, body: &mut Box<TypedCoreExpr>) {
+        self.bind(param);
+        self.traverse_expr(body)
+    }
+
+    fn traverse_pattern(&mut self, pattern: &mut TypedPattern) {
+        pattern_names(pattern, &mut |name| self.bind(name))
+    }
+}
+
+struct RenameBinders<'a>(&'a HashMap<Symbol, Symbol>);
+
+impl<'a> RenameBinders<'a> {
+    fn rename(&self, name: &mut Symbol) {
+        if let Some(new) = self.0.get(name) {
+            *name = new.clone()
+        }
+    }
+}
+
+impl<'a> Traverse<Type> for RenameBinders<'a> {
+    fn traverse_sym(&mut self, name: &mut Symbol) {
+        self.rename(name)
+    }
+
+    fn traverse_fn(&mut self, param: &mut Symbol, body: &mut Box<TypedCoreExpr>) {
+        self.rename(param);
+        self.traverse_expr(body)
+    }
+
+    fn traverse_pattern(&mut self, pattern: &mut TypedPattern) {
+        pattern_names(pattern, &mut |name| self.rename(name))
+    }
+}
+
+impl Monomorphize {
+    pub fn new(id: Id) -> Self {
+        Self { id }
+    }
+}
+
+impl MonomorphizePass {
+    fn new(id: Id) -> Self {
+        Self {
+            id,
+            polys: HashMap::new(),
+            templates: Vec::new(),
+            datatypes: Vec::new(),
+        }
+    }
+
+    // `ty` without the type variables and the arguments of the datatypes, except `list`s
+    fn mono_ty(&mut self, ty: Type, symbol_table: &mut SymbolTable) -> Type {
+        use crate::ast::Type::*;
+        match ty {
+            Variable(_) => Unit,
+            Fun(param, ret) => Fun(
+                Box::new(self.mono_ty(*param, symbol_table)),
+                Box::new(self.mono_ty(*ret, symbol_table)),
+            ),
+            Tuple(tys) => Tuple(
+                tys.into_iter()
+                    .map(|ty| self.mono_ty(ty, symbol_table))
+                    .collect(),
+            ),
+            Datatype(name, args) => {
+                let args = args
+                    .into_iter()
+                    .map(|ty| self.mono_ty(ty, symbol_table))
+                    .collect::<Vec<_>>();
+                // lists are typed by `HTy::List` with the element type
+                if args.is_empty() || name == Symbol::new("list") {
+                    return Datatype(name, args);
+                }
+                let key = (name, args);
+                if let Some((_, instance)) = self.datatypes.iter().find(|(k, _)| *k == key) {
+                    return Datatype(instance.clone(), Vec::new());
+                }
+                let (name, args) = key.clone();
+                let instance = Symbol(name.0.clone(), self.id.next());
+                // registered before the constructors for the recursive datatypes
+                self.datatypes.push((key, instance.clone()));
+                let info = symbol_table
+                    .get_type(&name)
+                    .expect("internal error: type not found")
+                    .clone();
+                let constructors = info
+                    .constructors
+                    .into_iter()
+                    .map(|(cname, arg)| {
+                        let arg = arg.map(|arg| {
+                            let arg = subst_vars(arg, &|v| {
+                                let i = info.params.iter().position(|param| *param == v)?;
+                                Some(args[i].clone())
+                            });
+                            self.mono_ty(arg, symbol_table)
+                        });
+                        (cname, arg)
+                    })
+                    .collect();
+                symbol_table.types.insert(
+                    instance.clone(),
+                    TypeInfo {
+                        params: Vec::new(),
+                        constructors,
+                    },
+                );
+                Datatype(instance, Vec::new())
+            }
+            Ref(ty) => Ref(Box::new(self.mono_ty(*ty, symbol_table))),
+            Unit | Char | String | Int | Word | Real | Bool => ty,
+        }
+    }
+
+    // the name of the instance of the polymorphic `name` used at `ty`, made if not yet
+    fn instance(&mut self, name: &Symbol, ty: &Type) -> Option<Symbol> {
+        let index = *self.polys.get(name)?;
+        let template = &self.templates[index];
+        let (_, declared) = decl_binds(&template.decl)
+            .into_iter()
+            .find(|(n, _)| *n == name)?;
+        let mut subst = HashMap::new();
+        matching(declared, ty, &mut subst);
+        let args = template
+            .vars
+            .iter()
+            .map(|v| subst.remove(v).unwrap_or(Type::Variable(*v)))
+            .collect::<Vec<_>>();
+        if let Some((_, names)) = template.instances.iter().find(|(a, _)| *a == args) {
+            return names.get(name).cloned();
+        }
+        let bound = decl_binds(&template.decl)
+            .into_iter()
+            .map(|(n, _)| n.clone())
+            .collect::<Vec<_>>();
+        let names = bound
+            .into_iter()
+            .map(|n| {
+                let new = Symbol(n.0.clone(), self.id.next());
+                (n, new)
+            })
+            .collect::<HashMap<_, _>>();
+        let instance = names.get(name).cloned();
+        self.templates[index].instances.push((args, names));
+        instance
+    }
+
+    // the `n`th instance of the template at `index`, with the types and the names replaced
+    fn specialize(&mut self, index: usize, n: usize) -> TypedCoreDeclaration {
+        let template = &self.templates[index];
+        let (args, mut names) = template.instances[n].clone();
+        let subst = template
+            .vars
+            .iter()
+            .cloned()
+            .zip(args)
+            .collect::<HashMap<_, _>>();
+        let mut decl = template
+            .decl
+            .clone()
+            .map_ty(&mut |ty| subst_vars(ty, &|v| subst.get(&v).cloned()));
+        CollectBinders {
+            id: &mut self.id,
+            names: &mut names,
+        }
+        .traverse_statement(&mut decl);
+        RenameBinders(&names).traverse_statement(&mut decl);
+        decl
+    }
+
+    // the instances of a binding only use the bindings before it,
+    // so the polymorphic ones are specialized backward after the uses in the rest of the scope
+    fn mono_scope(
+        &mut self,
+        decls: Vec<TypedCoreDeclaration>,
+        ret: Option<&mut TypedCoreExpr>,
+    ) -> Vec<TypedCoreDeclaration> {
+        let mut scope = Vec::new();
+        for mut decl in decls {
+            if is_polymorphic(&decl) {
+                let index = self.templates.len();
+                let mut vars = Vec::new();
+                for (name, ty) in decl_binds(&decl) {
+                    type_vars(ty, &mut vars);
+                    self.polys.insert(name.clone(), index);
+                }
+                self.templates.push(Template {
+                    decl,
+                    vars,
+                    instances: Vec::new(),
+                });
+                scope.push(Err(index));
+            } else {
+                self.mono_decl(&mut decl);
+                scope.push(Ok(decl));
+            }
+        }
+        if let Some(ret) = ret {
+            self.mono_expr(ret);
+        }
+
+        let mut instances = HashMap::new();
+        for index in scope.iter().rev().filter_map(|decl| decl.as_ref().err()) {
+            let mut made = Vec::new();
+            // an instance may request another one of its own
+            while made.len() < self.templates[*index].instances.len() {
+                let mut decl = self.specialize(*index, made.len());
+                self.mono_decl(&mut decl);
+                made.push(decl);
+            }
+            instances.insert(*index, made);
+        }
+        scope
+            .into_iter()
+            .flat_map(|decl| match decl {
+                Ok(decl) => vec![decl],
+                // unused bindings have no instances and are removed
+                Err(index) => instances.remove(&index).unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    fn mono_decl(&mut self, decl: &mut TypedCoreDeclaration) {
+        match decl {
+            Declaration::Val { expr, .. } => self.mono_expr(expr),
+            Declaration::ValRecGroup { binds } => {
+                for (_, expr) in binds.iter_mut() {
+                    self.mono_expr(expr)
+                }
+            }
+            // the exported function is made at its own type, whose variables become `unit`
+            Declaration::Export { value, .. } => {
+                let declared = self.polys.get(value).and_then(|index| {
+                    decl_binds(&self.templates[*index].decl)
+                        .into_iter()
+                        .find(|(name, _)| *name == value)
+                        .map(|(_, ty)| ty.clone())
+                });
+                if let Some(ty) = declared {
+                    if let Some(instance) = self.instance(value, &ty) {
+                        *value = instance
+                    }
+                }
+            }
+            Declaration::Datatype { .. } | Declaration::Import { .. } => (),
+            Declaration::D(_) => (),
+        }
+    }
+
+    fn mono_expr(&mut self, expr: &mut TypedCoreExpr) {
+        use crate::ast::ExprKind::*;
+        match &mut expr.inner {
+            Symbol { name } => {
+                if let Some(instance) = self.instance(name, &expr.ty) {
+                    *name = instance
+                }
+            }
+            Binds { binds, ret } => {
+                let decls = std::mem::replace(binds, Vec::new());
+                *binds = self.mono_scope(decls, Some(ret.as_mut()));
+            }
+            BuiltinCall { args, .. } | ExternCall { args, .. } | Seq { exprs: args } => {
+                args.iter_mut().for_each(|arg| self.mono_expr(arg))
+            }
+            Tuple { tuple } => tuple.iter_mut().for_each(|e| self.mono_expr(e)),
+            Fn { body, .. } | Typed { expr: body, .. } => self.mono_expr(body),
+            App { fun: l, arg: r } | AndAlso { l, r } | OrElse { l, r } => {
+                self.mono_expr(l);
+                self.mono_expr(r);
+            }
+            Case { cond, clauses } => {
+                self.mono_expr(cond);
+                for (_, arm) in clauses.iter_mut() {
+                    self.mono_expr(arm)
+                }
+            }
+            If { cond, then, else_ } => {
+                self.mono_expr(cond);
+                self.mono_expr(then);
+                self.mono_expr(else_);
+            }
+            Constructor { arg, .. } => {
+                if let Some(arg) = arg {
+                    self.mono_expr(arg)
+                }
+            }
+            Literal { .. } => (),
+            D(_) => (),
+        }
+    }
+}
+
+impl<'a> Pass<(SymbolTable, TypedCore), TypeError<'a>> for Monomorphize {
+    type Target = (SymbolTable, TypedCore);
+
+    fn trans<'b>(
+        &'b mut self,
+        (mut symbol_table, ast): (SymbolTable, TypedCore),
+        _: &Config,
+    ) -> Result<'a, Self::Target> {
+        let mut pass = MonomorphizePass::new(self.id.clone());
+        let ast = AST(pass.mono_scope(ast.0, None));
+        let ast = ast.map_ty(&mut |ty| pass.mono_ty(ty, &mut symbol_table));
+        Ok((symbol_table, ast))
+    }
+}
@@ -1,123 +1,373 @@
 use crate::ast::*;
-use crate::util::PP;
+use crate::util::{Highlight, PpConfig, PP};
+use std::collections::HashMap;
+use std::fmt;
 use std::io;
 
-impl<Ty: PP, DE: PP, DS: PP> PP for (SymbolTable, AST<Ty, DE, DS>) {
-    fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
-        self.1.pp(w, indent)
-    }
+// the output is parsed back to the same tree. the printer follows the infix declarations
+// as the parser does to decide where parentheses are needed.
+
+// `::` of the builtin list, which is `infixr 5` unlike the user defined infixes
+static CONS: &str = "::";
+const CONS_PRIORITY: u8 = 5;
+
+// the contexts of expressions, from the loosest. an expression is parenthesized
+// if it binds weaker than its context.
+// anything, including the open ended `fn`, `if` and `case`
+const PREC_EXPR: u8 = 0;
+// followed by `|` of the enclosing clauses, which an open ended expression would take
+const PREC_CLOSED: u8 = 1;
+// the operands of `orelse`
+const PREC_ORELSE: u8 = 2;
+// the operands of `andalso`
+const PREC_ANDALSO: u8 = 3;
+// the operands of the infixes of priority `n` are in `PREC_INFIX + n`
+const PREC_INFIX: u8 = 4;
+// the function of an application
+const PREC_APPLY: u8 = 14;
+// the argument of an application
+const PREC_ATOM: u8 = 15;
+
+// the contexts of patterns, from the loosest
+const PAT_FULL: u8 = 0;
+// the alternatives of `|` and the tail of `::`
+const PAT_ALT: u8 = 1;
+// the head of `::`
+const PAT_APPLY: u8 = 2;
+// the arguments of constructors and functions
+const PAT_ATOM: u8 = 3;
+
+/// the state of printing, i.e. the infixes in scope
+pub struct Printer {
+    infixes: Vec<HashMap<Symbol, u8>>,
+    config: PpConfig,
 }
 
-impl<Ty: PP, DE: PP, DS: PP> PP for AST<Ty, DE, DS> {
-    fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
-        for bind in &self.0 {
-            bind.pp(w, indent)?;
+/// printing of the derived forms, which needs the infixes in scope
+pub trait PPDerived {
+    fn pp_derived<W: io::Write>(
+        &self,
+        printer: &mut Printer,
+        w: &mut W,
+        indent: usize,
+    ) -> io::Result<()>;
+}
+
+impl Printer {
+    fn new(config: &PpConfig) -> Self {
+        let mut builtins = HashMap::new();
+        builtins.insert(Symbol::new(CONS), CONS_PRIORITY);
+        Printer {
+            infixes: vec![builtins],
+            config: *config,
+        }
+    }
+
+    // `keyword` followed by a space
+    fn keyword<W: io::Write>(&self, w: &mut W, keyword: &str) -> io::Result<()> {
+        self.config.keyword(w, keyword)?;
+        write!(w, " ")
+    }
+
+    fn with_scope<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        self.infixes.push(HashMap::new());
+        let r = f(self);
+        self.infixes.pop();
+        r
+    }
+
+    fn new_infix(&mut self, priority: Option<u8>, names: &[Symbol]) {
+        let priority = priority.unwrap_or(0);
+        let scope = self.infixes.last_mut().expect("internal error: no scope");
+        for name in names {
+            scope.insert(name.clone(), priority);
+        }
+    }
+
+    fn infix(&self, name: &Symbol) -> Option<u8> {
+        self.infixes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).cloned())
+    }
+
+    // as the parser does, all of the infixes of the priority of `::` are right associative
+    fn is_right_assoc(&self, priority: u8) -> bool {
+        self.infix(&Symbol::new(CONS)) == Some(priority)
+    }
+
+    // `(l, r)` of `l op r` if `fun arg` is an infix application
+    fn as_infix<'a, Ty, DE, DS>(
+        &self,
+        fun: &'a Expr<Ty, DE, DS>,
+        arg: &'a Expr<Ty, DE, DS>,
+    ) -> Option<(&'a Symbol, u8, &'a Expr<Ty, DE, DS>, &'a Expr<Ty, DE, DS>)> {
+        match (&fun.inner, &arg.inner) {
+            (ExprKind::Symbol { name }, ExprKind::Tuple { tuple }) if tuple.len() == 2 => {
+                let priority = self.infix(name)?;
+                Some((name, priority, &tuple[0], &tuple[1]))
+            }
+            _ => None,
+        }
+    }
+
+    fn pp_ast<Ty, DE: PPDerived, DS: PPDerived, W: io::Write>(
+        &mut self,
+        ast: &AST<Ty, DE, DS>,
+        w: &mut W,
+        indent: usize,
+    ) -> io::Result<()> {
+        for decl in &ast.0 {
+            self.pp_decl(decl, w, indent)?;
             write!(w, "\n")?;
         }
         Ok(())
     }
-}
 
-impl<Ty: PP, DE: PP, DS: PP> PP for Declaration<Ty, DE, DS> {
-    fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
+    // `: (arg, ty) -> retty`, omitted if declared by `_import`
+    fn pp_extern_type<W: io::Write>(
+        &self,
+        argty: &[Type],
+        retty: &[Type],
+        w: &mut W,
+    ) -> io::Result<()> {
+        if argty.is_empty() {
+            return Ok(());
+        }
+        write!(w, ": (")?;
+        inter_iter! {
+            argty,
+            write!(w, ", ")?,
+            |ty| => {
+                pp_ty(ty, w, &self.config, PREC_FUN)?
+            }
+        };
+        write!(w, ") -> ")?;
+        match retty {
+            [retty] => pp_ty(retty, w, &self.config, PREC_FUN),
+            rettys => {
+                write!(w, "(")?;
+                inter_iter! {
+                    rettys,
+                    write!(w, ", ")?,
+                    |ty| => {
+                        pp_ty(ty, w, &self.config, PREC_FUN)?
+                    }
+                };
+                write!(w, ")")
+            }
+        }
+    }
+
+    fn pp_decl<Ty, DE: PPDerived, DS: PPDerived, W: io::Write>(
+        &mut self,
+        decl: &Declaration<Ty, DE, DS>,
+        w: &mut W,
+        indent: usize,
+    ) -> io::Result<()> {
         use Declaration::*;
-        match self {
-            Datatype { name, constructors } => {
-                write!(w, "datatype ")?;
-                name.pp(w, indent)?;
+        let ind = Type::nspaces(indent);
+        match decl {
+            Datatype {
+                name,
+                params,
+                constructors,
+            } => {
+                write!(w, "{}", ind)?;
+                self.keyword(w, "datatype")?;
+                match params.as_slice() {
+                    [] => (),
+                    [param] => {
+                        pp_tyvar(*param, w, &self.config)?;
+                        write!(w, " ")?;
+                    }
+                    params => {
+                        write!(w, "(")?;
+                        inter_iter!(params, write!(w, ", ")?, |param| =>{
+                            pp_tyvar(*param, w, &self.config)?;
+                        });
+                        write!(w, ") ")?;
+                    }
+                }
+                pp_symbol(name, w)?;
                 write!(w, " =")?;
                 inter_iter!(constructors, write!(w, " |")?, |(name, param)| =>{
                     write!(w, " ")?;
-                    name.pp(w, indent)?;
+                    pp_symbol(name, w)?;
                     if let Some(param) = param {
-                        write!(w, " of ")?;
-                        param.pp(w, indent)?;
+                        write!(w, " ")?;
+                        self.keyword(w, "of")?;
+                        pp_ty(param, w, &self.config, PREC_FUN)?;
                     }
                 });
                 Ok(())
             }
             Val { pattern, expr, rec } => {
-                write!(w, "{}", Self::nspaces(indent))?;
-                write!(w, "val ")?;
+                write!(w, "{}", ind)?;
+                self.keyword(w, "val")?;
                 if *rec {
-                    write!(w, "rec ")?;
+                    self.keyword(w, "rec")?;
                 }
-                pattern.pp(w, indent)?;
-                // write!(w, ": ")?;
-                // self.ty.pp(w, indent)?;
+                self.pp_pattern(pattern, w, PAT_FULL)?;
                 write!(w, " = ")?;
-                expr.pp(w, indent + 4)?;
-                Ok(())
+                self.pp_expr(expr, w, indent + 4, PREC_EXPR)
             }
-            D(d) => d.pp(w, indent),
-        }
-    }
-}
-
-impl<Ty: PP> PP for DerivedDeclaration<Ty> {
-    fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
-        use DerivedDeclaration::*;
-        match self {
-            Fun { name, clauses, .. } => {
-                write!(w, "{}", Self::nspaces(indent))?;
-                write!(w, "fun ")?;
+            ValRecGroup { binds } => {
+                write!(w, "{}", ind)?;
+                self.keyword(w, "val")?;
+                self.keyword(w, "rec")?;
                 inter_iter!(
-                    clauses,
-                    { write!(w, "\n{}  | ", Self::nspaces(indent))? ; name.pp(w, indent)? },
-                    |(params, expr)| => {
-                    name.pp(w, indent)?;
-                    write!(w, " ")?;
-                    for param in params {
-                        param.pp(w, indent)?;
-                        write!(w, " ")?;
-                    }
-                    // write!(w, ": ")?;
-                    // self.ty.pp(w, indent)?;
+                    binds,
+                    {
+                        write!(w, "\n{}", ind)?;
+                        self.keyword(w, "and")?
+                    },
+                    |(pattern, expr)| => {
+                    self.pp_pattern(pattern, w, PAT_FULL)?;
                     write!(w, " = ")?;
-                    expr.pp(w, indent + 4)?;
+                    self.pp_expr(expr, w, indent + 4, PREC_EXPR)?;
                 });
                 Ok(())
             }
-            Infix { priority, names } => {
-                write!(w, "infix")?;
-                if let Some(p) = priority {
-                    write!(w, " {}", p)?;
-                }
-                for name in names {
-                    write!(w, " ")?;
-                    name.pp(w, indent)?;
-                }
-                Ok(())
+            Import {
+                module,
+                fun,
+                argty,
+                retty,
+            } => {
+                write!(w, "{}", ind)?;
+                self.keyword(w, "_import")?;
+                write!(w, "(\"{}\".\"{}\"", module, fun)?;
+                self.pp_extern_type(argty, retty, w)?;
+                write!(w, ")")
             }
+            Export { name, value } => {
+                write!(w, "{}", ind)?;
+                self.keyword(w, "export")?;
+                write!(w, "\"{}\" ", name)?;
+                pp_symbol(value, w)
+            }
+            D(d) => d.pp_derived(self, w, indent),
         }
     }
-}
 
-impl<Ty: PP, DE: PP, DS: PP> PP for Expr<Ty, DE, DS> {
-    fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
+    fn pp_fun<Ty, W: io::Write>(
+        &mut self,
+        name: &Symbol,
+        clauses: &[(Vec<Pattern<Ty>>, Expr<Ty>)],
+        w: &mut W,
+        indent: usize,
+    ) -> io::Result<()> {
+        let ind = Type::nspaces(indent);
+        for (i, (params, expr)) in clauses.iter().enumerate() {
+            if i != 0 {
+                write!(w, "\n{}  | ", ind)?;
+            }
+            if self.infix(name).is_some() {
+                self.keyword(w, "op")?;
+            }
+            pp_symbol(name, w)?;
+            for param in params {
+                write!(w, " ")?;
+                self.pp_pattern(param, w, PAT_ATOM)?;
+            }
+            write!(w, " = ")?;
+            // the following clauses are separated by `|`
+            let prec = if i + 1 == clauses.len() {
+                PREC_EXPR
+            } else {
+                PREC_CLOSED
+            };
+            self.pp_expr(expr, w, indent + 4, prec)?;
+        }
+        Ok(())
+    }
+
+    fn expr_prec<Ty, DE, DS>(&self, expr: &Expr<Ty, DE, DS>) -> u8 {
+        use crate::ast::ExprKind::*;
+        match &expr.inner {
+            Fn { .. } | If { .. } | Case { .. } => PREC_EXPR,
+            // `let` is not an atom though closed by `end`
+            Binds { .. } => PREC_CLOSED,
+            OrElse { .. } => PREC_ORELSE,
+            AndAlso { .. } => PREC_ANDALSO,
+            App { fun, arg } => match self.as_infix(fun, arg) {
+                Some((_, priority, _, _)) => PREC_INFIX + priority,
+                None => PREC_APPLY,
+            },
+            Constructor {
+                name,
+                arg: Some(arg),
+            } => match list_elems(name, arg) {
+                Some(_) => PREC_ATOM,
+                None if name.0 == CONS => PREC_INFIX + CONS_PRIORITY,
+                None => PREC_APPLY,
+            },
+            BuiltinCall { .. }
+            | ExternCall { .. }
+            | Seq { .. }
+            | Tuple { .. }
+            | Symbol { .. }
+            | Constructor { arg: None, .. }
+            | Literal { .. }
+            | Typed { .. }
+            | D(_) => PREC_ATOM,
+        }
+    }
+
+    fn pp_expr<Ty, DE: PPDerived, DS: PPDerived, W: io::Write>(
+        &mut self,
+        expr: &Expr<Ty, DE, DS>,
+        w: &mut W,
+        indent: usize,
+        prec: u8,
+    ) -> io::Result<()> {
+        let paren = self.expr_prec(expr) < prec;
+        if paren {
+            write!(w, "(")?;
+        }
+        self.pp_expr_inner(expr, w, indent)?;
+        if paren {
+            write!(w, ")")?;
+        }
+        Ok(())
+    }
+
+    fn pp_expr_inner<Ty, DE: PPDerived, DS: PPDerived, W: io::Write>(
+        &mut self,
+        expr: &Expr<Ty, DE, DS>,
+        w: &mut W,
+        indent: usize,
+    ) -> io::Result<()> {
         use crate::ast::ExprKind::*;
-        match &self.inner {
+        let ind = Type::nspaces(indent);
+        match &expr.inner {
             Binds { binds, ret } => {
-                let ind = Self::nspaces(indent);
-                let nextind = Self::nspaces(indent + 4);
-                write!(w, "let\n")?;
-                for val in binds {
-                    val.pp(w, indent + 4)?;
-                    write!(w, "\n")?;
-                }
-                write!(w, "{}in\n{}", ind, nextind)?;
-                ret.pp(w, indent + 4)?;
-                write!(w, "\n{}end", ind)?;
+                self.config.keyword(w, "let")?;
+                write!(w, "\n")?;
+                self.with_scope(|p: &mut Printer| -> io::Result<()> {
+                    for decl in binds {
+                        p.pp_decl(decl, w, indent + 4)?;
+                        write!(w, "\n")?;
+                    }
+                    write!(w, "{}", ind)?;
+                    p.config.keyword(w, "in")?;
+                    write!(w, "\n{}", Type::nspaces(indent + 4))?;
+                    p.pp_expr(ret, w, indent + 4, PREC_EXPR)
+                })?;
+                write!(w, "\n{}", ind)?;
+                self.config.keyword(w, "end")?;
             }
             BuiltinCall { fun, args } => {
-                write!(w, "_builtincall \"")?;
+                self.keyword(w, "_builtincall")?;
+                write!(w, "\"")?;
                 fun.pp(w, indent)?;
                 write!(w, "\"(")?;
                 inter_iter! {
-                    &args,
+                    args,
                     write!(w, ", ")?,
                     |arg| => {
-                        arg.pp(w, indent)?
+                        self.pp_expr(arg, w, indent, PREC_EXPR)?
                     }
                 }
                 write!(w, ")")?;
@@ -129,165 +379,644 @@ impl<Ty: PP, DE: PP, DS: PP> PP for Expr<Ty, DE, DS> {
                 argty,
                 retty,
             } => {
-                write!(w, "_externcall \"{}\".\"{}\"(", module, fun)?;
-
+                self.keyword(w, "_externcall")?;
+                write!(w, "(\"{}\".\"{}\"", module, fun)?;
+                self.pp_extern_type(argty, retty, w)?;
+                write!(w, ") (")?;
                 inter_iter! {
-                    &args,
+                    args,
                     write!(w, ", ")?,
                     |arg| => {
-                        arg.pp(w, indent)?
-                    }
-                };
-                write!(w, "): (")?;
-                inter_iter! {
-                    &argty,
-                    write!(w, ", ")?,
-                    |ty| => {
-                        ty.pp(w, indent)?
+                        self.pp_expr(arg, w, indent, PREC_EXPR)?
                     }
                 };
-                write!(w, ") -> ")?;
-                retty.pp(w, indent)?;
+                write!(w, ")")?;
             }
             Fn { body, param } => {
-                write!(w, "fn ")?;
-                param.pp(w, indent)?;
+                self.keyword(w, "fn")?;
+                pp_symbol(param, w)?;
                 write!(w, " => ")?;
-                body.pp(w, indent + 4)?;
-            }
-            App { fun, arg } => {
-                write!(w, "(")?;
-                fun.pp(w, indent)?;
-                write!(w, ") ")?;
-                arg.pp(w, indent + 4)?;
+                self.pp_expr(body, w, indent + 4, PREC_EXPR)?;
             }
+            App { fun, arg } => match self.as_infix(fun, arg) {
+                Some((op, priority, l, r)) => {
+                    let (lprec, rprec) = if self.is_right_assoc(priority) {
+                        (priority + 1, priority)
+                    } else {
+                        (priority, priority + 1)
+                    };
+                    self.pp_expr(l, w, indent, PREC_INFIX + lprec)?;
+                    write!(w, " ")?;
+                    pp_symbol(op, w)?;
+                    write!(w, " ")?;
+                    self.pp_expr(r, w, indent, PREC_INFIX + rprec)?;
+                }
+                None => {
+                    self.pp_expr(fun, w, indent, PREC_APPLY)?;
+                    write!(w, " ")?;
+                    self.pp_expr(arg, w, indent + 4, PREC_ATOM)?;
+                }
+            },
             Case { cond, clauses } => {
-                let ind = Self::nspaces(indent);
-                write!(w, "case ")?;
-                cond.pp(w, indent + 4)?;
-                write!(w, " of")?;
-                for (pat, arm) in clauses {
-                    write!(w, "\n{}", ind)?;
-                    pat.pp(w, indent + 4)?;
+                self.keyword(w, "case")?;
+                self.pp_expr(cond, w, indent + 4, PREC_EXPR)?;
+                write!(w, " ")?;
+                self.config.keyword(w, "of")?;
+                for (i, (pat, arm)) in clauses.iter().enumerate() {
+                    let bar = if i == 0 { " " } else { "|" };
+                    write!(w, "\n{} {} ", ind, bar)?;
+                    self.pp_pattern(pat, w, PAT_FULL)?;
                     write!(w, " => ")?;
-                    arm.pp(w, indent + 4)?;
+                    // the following clauses are separated by `|`
+                    let prec = if i + 1 == clauses.len() {
+                        PREC_EXPR
+                    } else {
+                        PREC_CLOSED
+                    };
+                    self.pp_expr(arm, w, indent + 4, prec)?;
+                }
+            }
+            If { cond, then, else_ } => {
+                self.keyword(w, "if")?;
+                self.pp_expr(cond, w, indent + 4, PREC_EXPR)?;
+                write!(w, "\n{}", ind)?;
+                self.keyword(w, "then")?;
+                self.pp_expr(then, w, indent + 4, PREC_EXPR)?;
+                write!(w, "\n{}", ind)?;
+                self.keyword(w, "else")?;
+                self.pp_expr(else_, w, indent + 4, PREC_EXPR)?;
+            }
+            // both are left associative
+            AndAlso { l, r } => {
+                self.pp_expr(l, w, indent, PREC_ANDALSO)?;
+                write!(w, " ")?;
+                self.keyword(w, "andalso")?;
+                self.pp_expr(r, w, indent, PREC_ANDALSO + 1)?;
+            }
+            OrElse { l, r } => {
+                self.pp_expr(l, w, indent, PREC_ORELSE)?;
+                write!(w, " ")?;
+                self.keyword(w, "orelse")?;
+                self.pp_expr(r, w, indent, PREC_ORELSE + 1)?;
+            }
+            Seq { exprs } => {
+                write!(w, "(")?;
+                inter_iter! {
+                    exprs,
+                    write!(w, "; ")?,
+                    |e| => {
+                        self.pp_expr(e, w, indent, PREC_EXPR)?
+                    }
                 }
+                write!(w, ")")?;
             }
             Tuple { tuple } => {
                 write!(w, "(")?;
                 inter_iter! {
-                    tuple.iter(),
+                    tuple,
                     write!(w, ", ")?,
                     |t| => {
-                        t.pp(w, indent)?
+                        self.pp_expr(t, w, indent, PREC_EXPR)?
                     }
                 }
                 write!(w, ")")?;
             }
             Symbol { name } => {
-                name.pp(w, indent)?;
+                pp_symbol(name, w)?;
             }
-            Constructor { name, arg } => {
-                name.pp(w, indent)?;
-                if let Some(arg) = arg {
-                    write!(w, " ")?;
-                    arg.pp(w, indent)?;
-                }
+            Constructor { name, arg: None } if name.0 == "nil" => {
+                write!(w, "[]")?;
             }
+            Constructor { name, arg: None } => {
+                pp_symbol(name, w)?;
+            }
+            Constructor {
+                name,
+                arg: Some(arg),
+            } => match list_elems(name, arg) {
+                Some(elems) => {
+                    write!(w, "[")?;
+                    inter_iter! {
+                        elems,
+                        write!(w, ", ")?,
+                        |e| => {
+                            self.pp_expr(e, w, indent, PREC_EXPR)?
+                        }
+                    }
+                    write!(w, "]")?;
+                }
+                None => match &arg.inner {
+                    Tuple { tuple } if name.0 == CONS && tuple.len() == 2 => {
+                        self.pp_expr(&tuple[0], w, indent, PREC_INFIX + CONS_PRIORITY + 1)?;
+                        write!(w, " ")?;
+                        pp_symbol(name, w)?;
+                        write!(w, " ")?;
+                        self.pp_expr(&tuple[1], w, indent, PREC_INFIX + CONS_PRIORITY)?;
+                    }
+                    _ => {
+                        pp_symbol(name, w)?;
+                        write!(w, " ")?;
+                        self.pp_expr(arg, w, indent, PREC_ATOM)?;
+                    }
+                },
+            },
             Literal { value } => {
-                value.pp(w, indent)?;
+                self.config
+                    .paint(w, Highlight::Literal, |w| pp_literal(value, w))?;
+            }
+            Typed { expr, ty } => {
+                write!(w, "(")?;
+                self.pp_expr(expr, w, indent, PREC_EXPR)?;
+                write!(w, " : ")?;
+                pp_ty(ty, w, &self.config, PREC_FUN)?;
+                write!(w, ")")?;
             }
             D(d) => {
-                d.pp(w, indent)?;
+                d.pp_derived(self, w, indent)?;
             }
         }
         Ok(())
     }
-}
 
-impl<Ty: PP> PP for DerivedExprKind<Ty> {
-    fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
-        use DerivedExprKind::*;
-        match self {
-            If {
-                cond, then, else_, ..
-            } => {
-                let ind = Self::nspaces(indent);
-                write!(w, "if ")?;
-                cond.pp(w, indent + 4)?;
-                write!(w, "\n{}then ", ind)?;
-                then.pp(w, indent + 4)?;
-                write!(w, "\n{}else ", ind)?;
-                else_.pp(w, indent + 4)?;
+    fn pattern_prec<Ty>(&self, pattern: &Pattern<Ty>) -> u8 {
+        use PatternKind::*;
+        match &pattern.inner {
+            As { .. } | Or { .. } => PAT_FULL,
+            Constructor {
+                name,
+                arg: Some(arg),
+            } => match list_pattern_elems(name, arg) {
+                Some(_) => PAT_ATOM,
+                None if name.0 == CONS => PAT_ALT,
+                None => PAT_APPLY,
+            },
+            Constant { .. }
+            | Char { .. }
+            | Str { .. }
+            | Constructor { arg: None, .. }
+            | Tuple { .. }
+            | Variable { .. }
+            | Wildcard { .. } => PAT_ATOM,
+        }
+    }
+
+    fn pp_pattern<Ty, W: io::Write>(
+        &mut self,
+        pattern: &Pattern<Ty>,
+        w: &mut W,
+        prec: u8,
+    ) -> io::Result<()> {
+        use PatternKind::*;
+        let paren = self.pattern_prec(pattern) < prec;
+        if paren {
+            write!(w, "(")?;
+        }
+        match &pattern.inner {
+            Constant { value } => self
+                .config
+                .paint(w, Highlight::Literal, |w| pp_int(*value, w))?,
+            Char { value } => self.config.paint(w, Highlight::Literal, |w| {
+                write!(w, r##"#"{}""##, escape_char(*value))
+            })?,
+            Str { value } => self.config.paint(w, Highlight::Literal, |w| {
+                pp_literal(&Literal::String(value.clone()), w)
+            })?,
+            Constructor { name, arg: None } if name.0 == "nil" => write!(w, "[]")?,
+            Constructor { name, arg: None } => pp_symbol(name, w)?,
+            Constructor {
+                name,
+                arg: Some(arg),
+            } => match list_pattern_elems(name, arg) {
+                Some(elems) => {
+                    write!(w, "[")?;
+                    inter_iter! {
+                        elems,
+                        write!(w, ", ")?,
+                        |pat| => {
+                            self.pp_pattern(pat, w, PAT_FULL)?
+                        }
+                    }
+                    write!(w, "]")?;
+                }
+                None => match &arg.inner {
+                    Tuple { tuple } if name.0 == CONS && tuple.len() == 2 => {
+                        self.pp_pattern(&tuple[0], w, PAT_APPLY)?;
+                        write!(w, " :: ")?;
+                        self.pp_pattern(&tuple[1], w, PAT_ALT)?;
+                    }
+                    _ => {
+                        pp_symbol(name, w)?;
+                        write!(w, " ")?;
+                        self.pp_pattern(arg, w, PAT_ATOM)?;
+                    }
+                },
+            },
+            Tuple { tuple } => {
+                write!(w, "(")?;
+                inter_iter! {
+                    tuple,
+                    write!(w, ", ")?,
+                    |pat| => {
+                        self.pp_pattern(pat, w, PAT_FULL)?
+                    }
+                }
+                write!(w, ")")?;
+            }
+            Variable { name } => pp_symbol(name, w)?,
+            Wildcard {} => write!(w, "_")?,
+            As { name, pattern } => {
+                pp_symbol(name, w)?;
+                write!(w, " ")?;
+                self.keyword(w, "as")?;
+                self.pp_pattern(pattern, w, PAT_FULL)?;
+            }
+            Or { alternatives } => {
+                inter_iter! {
+                    alternatives,
+                    write!(w, " | ")?,
+                    |pat| => {
+                        self.pp_pattern(pat, w, PAT_ALT)?
+                    }
+                }
             }
         }
+        if paren {
+            write!(w, ")")?;
+        }
         Ok(())
     }
 }
 
-impl PP for Nothing {
-    fn pp<W: io::Write>(&self, _: &mut W, _: usize) -> io::Result<()> {
-        match *self {}
+// the elements of `arg` if `name arg` is a list `e1 :: e2 :: ... :: nil`
+fn list_elems<'a, Ty, DE, DS>(
+    name: &Symbol,
+    arg: &'a Expr<Ty, DE, DS>,
+) -> Option<Vec<&'a Expr<Ty, DE, DS>>> {
+    let mut elems = vec![];
+    let (mut name, mut arg) = (name, arg);
+    loop {
+        match &arg.inner {
+            ExprKind::Tuple { tuple } if name.0 == CONS && tuple.len() == 2 => {
+                elems.push(&tuple[0]);
+                match &tuple[1].inner {
+                    ExprKind::Constructor { name: n, arg: None } if n.0 == "nil" => {
+                        return Some(elems)
+                    }
+                    ExprKind::Constructor {
+                        name: n,
+                        arg: Some(a),
+                    } => {
+                        name = n;
+                        arg = a;
+                    }
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        }
     }
 }
 
-impl<Ty> PP for Pattern<Ty> {
+// the same as `list_elems` for patterns
+fn list_pattern_elems<'a, Ty>(name: &Symbol, arg: &'a Pattern<Ty>) -> Option<Vec<&'a Pattern<Ty>>> {
+    let mut elems = vec![];
+    let (mut name, mut arg) = (name, arg);
+    loop {
+        match &arg.inner {
+            PatternKind::Tuple { tuple } if name.0 == CONS && tuple.len() == 2 => {
+                elems.push(&tuple[0]);
+                match &tuple[1].inner {
+                    PatternKind::Constructor { name: n, arg: None } if n.0 == "nil" => {
+                        return Some(elems)
+                    }
+                    PatternKind::Constructor {
+                        name: n,
+                        arg: Some(a),
+                    } => {
+                        name = n;
+                        arg = a;
+                    }
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        }
+    }
+}
+
+// the symbols from the source have the id 0, which is not written
+fn pp_symbol<W: io::Write>(name: &Symbol, w: &mut W) -> io::Result<()> {
+    match name.1 {
+        0 => write!(w, "{}", name.0),
+        _ => name.pp(w, 0),
+    }
+}
+
+// negative numbers are written with `~` in SML
+fn pp_int<W: io::Write>(value: i64, w: &mut W) -> io::Result<()> {
+    match value {
+        v if v < 0 => write!(w, "~{}", -(v as i128)),
+        v => write!(w, "{}", v),
+    }
+}
+
+fn pp_literal<W: io::Write>(value: &Literal, w: &mut W) -> io::Result<()> {
+    match value {
+        Literal::Int(v) => pp_int(*v, w),
+        Literal::Real(v) => {
+            // the parser reads a number without `.` as an int, as in `1e10`
+            let mut s = format!("{:?}", v.abs());
+            if !s.contains('.') {
+                let at = s.find('e').unwrap_or(s.len());
+                s.insert_str(at, ".0");
+            }
+            if v.is_sign_negative() {
+                write!(w, "~")?;
+            }
+            write!(w, "{}", s)
+        }
+        value => value.pp(w, 0),
+    }
+}
+
+impl<Ty, DE: PPDerived, DS: PPDerived> PP for (SymbolTable, AST<Ty, DE, DS>) {
     fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
-        use PatternKind::*;
-        match &self.inner {
-            Constant { value, .. } => write!(w, "{}", value),
-            Char { value } => write!(w, r##"#"{}""##, value),
-            Constructor { name, arg, .. } => {
-                name.pp(w, indent)?;
-                if let Some(arg) = arg {
-                    // TODO: handle cases when its in function args
+        self.1.pp(w, indent)
+    }
+
+    fn pp_with<W: io::Write>(&self, w: &mut W, config: &PpConfig) -> io::Result<()> {
+        self.1.pp_with(w, config)
+    }
+}
+
+impl<Ty, DE: PPDerived, DS: PPDerived> PP for AST<Ty, DE, DS> {
+    fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
+        Printer::new(&PpConfig::default()).pp_ast(self, w, indent)
+    }
+
+    fn pp_with<W: io::Write>(&self, w: &mut W, config: &PpConfig) -> io::Result<()> {
+        Printer::new(config).pp_ast(self, w, 0)
+    }
+}
+
+impl<Ty, DE: PPDerived, DS: PPDerived> PP for Declaration<Ty, DE, DS> {
+    fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
+        Printer::new(&PpConfig::default()).pp_decl(self, w, indent)
+    }
+}
+
+impl<Ty> PPDerived for DerivedDeclaration<Ty> {
+    fn pp_derived<W: io::Write>(
+        &self,
+        printer: &mut Printer,
+        w: &mut W,
+        indent: usize,
+    ) -> io::Result<()> {
+        use DerivedDeclaration::*;
+        let ind = Type::nspaces(indent);
+        match self {
+            Fun { name, clauses } => {
+                write!(w, "{}", ind)?;
+                printer.keyword(w, "fun")?;
+                printer.pp_fun(name, clauses, w, indent)
+            }
+            FunGroup { funs } => {
+                write!(w, "{}", ind)?;
+                printer.keyword(w, "fun")?;
+                inter_iter!(
+                    funs,
+                    {
+                        write!(w, "\n{}", ind)?;
+                        printer.keyword(w, "and")?
+                    },
+                    |(name, clauses)| => {
+                    printer.pp_fun(name, clauses, w, indent)?;
+                });
+                Ok(())
+            }
+            Infix { priority, names } => {
+                printer.new_infix(*priority, names);
+                write!(w, "{}", ind)?;
+                printer.config.keyword(w, "infix")?;
+                if let Some(p) = priority {
+                    write!(w, " {}", p)?;
+                }
+                for name in names {
                     write!(w, " ")?;
-                    arg.pp(w, indent)?;
+                    pp_symbol(name, w)?;
                 }
-
                 Ok(())
             }
-            Tuple { tuple, .. } => {
+            Export { name, decl } => {
+                write!(w, "{}", ind)?;
+                printer.keyword(w, "export")?;
+                write!(w, "\"{}\"\n", name)?;
+                printer.pp_decl(decl, w, indent)
+            }
+        }
+    }
+}
+
+impl<Ty> PPDerived for DerivedExpr<Ty> {
+    fn pp_derived<W: io::Write>(
+        &self,
+        printer: &mut Printer,
+        w: &mut W,
+        indent: usize,
+    ) -> io::Result<()> {
+        let ind = Type::nspaces(indent);
+        match self {
+            // always parenthesized as the derived expressions are taken as atoms
+            DerivedExpr::Fn { clauses } => {
                 write!(w, "(")?;
-                inter_iter! {
-                    tuple.iter(),
-                    write!(w, ", ")?,
-                    |pat| => {
-                        pat.pp(w, indent)?
+                printer.keyword(w, "fn")?;
+                for (i, (pat, body)) in clauses.iter().enumerate() {
+                    if i != 0 {
+                        write!(w, "\n{}  | ", ind)?;
                     }
+                    printer.pp_pattern(pat, w, PAT_FULL)?;
+                    write!(w, " => ")?;
+                    // the following clauses are separated by `|`
+                    let prec = if i + 1 == clauses.len() {
+                        PREC_EXPR
+                    } else {
+                        PREC_CLOSED
+                    };
+                    printer.pp_expr(body, w, indent + 4, prec)?;
                 }
                 write!(w, ")")
             }
-            Variable { name, .. } => name.pp(w, indent),
-            Wildcard { .. } => write!(w, "_"),
         }
     }
 }
 
-impl PP for Type {
+impl<Ty> PP for DerivedDeclaration<Ty> {
+    fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
+        self.pp_derived(&mut Printer::new(&PpConfig::default()), w, indent)
+    }
+}
+
+impl<Ty, DE: PPDerived, DS: PPDerived> PP for Expr<Ty, DE, DS> {
     fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
+        Printer::new(&PpConfig::default()).pp_expr(self, w, indent, PREC_EXPR)
+    }
+}
+
+impl PPDerived for Nothing {
+    fn pp_derived<W: io::Write>(&self, _: &mut Printer, _: &mut W, _: usize) -> io::Result<()> {
+        match *self {}
+    }
+}
+
+impl PP for Nothing {
+    fn pp<W: io::Write>(&self, _: &mut W, _: usize) -> io::Result<()> {
+        match *self {}
+    }
+}
+
+impl<Ty> PP for Pattern<Ty> {
+    fn pp<W: io::Write>(&self, w: &mut W, _indent: usize) -> io::Result<()> {
+        Printer::new(&PpConfig::default()).pp_pattern(self, w, PAT_FULL)
+    }
+}
+
+// 0 => 'a, 1 => 'b, ..., 25 => 'z, 26 => 'a1, ...
+fn tyvar_name(id: u64) -> String {
+    let c = (b'a' + (id % 26) as u8) as char;
+    match id / 26 {
+        0 => format!("'{}", c),
+        n => format!("'{}{}", c, n),
+    }
+}
+
+fn pp_tyvar<W: io::Write>(id: u64, w: &mut W, config: &PpConfig) -> io::Result<()> {
+    config.paint(w, Highlight::TyVar, |w| write!(w, "{}", tyvar_name(id)))
+}
+
+// the precedences of the type operators. `->` is right associative
+// and binds weaker than `*`, which binds weaker than the type application
+const PREC_FUN: u8 = 0;
+const PREC_TUPLE: u8 = 1;
+const PREC_APP: u8 = 2;
+
+// print `ty` in a context of `prec`, parenthesized if it binds weaker
+fn pp_ty<W: io::Write>(ty: &Type, w: &mut W, config: &PpConfig, prec: u8) -> io::Result<()> {
+    use self::Type::*;
+    let own = match ty {
+        Fun(..) => PREC_FUN,
+        Tuple(tys) if !tys.is_empty() => PREC_TUPLE,
+        _ => PREC_APP,
+    };
+    if own < prec {
+        write!(w, "(")?;
+    }
+    match ty {
+        Variable(id) => pp_tyvar(*id, w, config)?,
+        Unit => write!(w, "unit")?,
+        Char => write!(w, "char")?,
+        String => write!(w, "string")?,
+        Int => write!(w, "int")?,
+        Word => write!(w, "word")?,
+        Real => write!(w, "real")?,
+        Bool => write!(w, "bool")?,
+        Fun(t1, t2) => {
+            pp_ty(t1, w, config, PREC_TUPLE)?;
+            write!(w, " -> ")?;
+            pp_ty(t2, w, config, PREC_FUN)?;
+        }
+        Tuple(tys) => match tys.as_slice() {
+            [] => write!(w, "unit")?,
+            tys => {
+                inter_iter!(tys, write!(w, " * ")?, |ty| => {
+                    pp_ty(ty, w, config, PREC_APP)?;
+                });
+            }
+        },
+        Datatype(name, args) => {
+            match args.as_slice() {
+                [] => (),
+                [arg] => {
+                    pp_ty(arg, w, config, PREC_APP)?;
+                    write!(w, " ")?;
+                }
+                args => {
+                    write!(w, "(")?;
+                    inter_iter!(args, write!(w, ", ")?, |arg| =>{
+                        pp_ty(arg, w, config, PREC_FUN)?;
+                    });
+                    write!(w, ") ")?;
+                }
+            }
+            pp_symbol(name, w)?
+        }
+        Ref(ty) => {
+            pp_ty(ty, w, config, PREC_APP)?;
+            write!(w, " ref")?;
+        }
+    }
+    if own < prec {
+        write!(w, ")")?;
+    }
+    Ok(())
+}
+
+impl PP for Type {
+    fn pp<W: io::Write>(&self, w: &mut W, _indent: usize) -> io::Result<()> {
+        pp_ty(self, w, &PpConfig::default(), PREC_FUN)
+    }
+}
+
+// SML notation of types with minimal parentheses, e.g. `('a -> 'a) -> int option`.
+// `->` is right associative and binds weaker than the type application.
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use self::Type::*;
         match self {
-            Variable(id) => write!(w, "'{}", id)?,
-            Char => write!(w, "char")?,
-            Int => write!(w, "int")?,
-            Real => write!(w, "float")?,
-            Fun(t1, t2) => {
-                t1.pp(w, indent)?;
-                write!(w, " -> ")?;
-                t2.pp(w, indent)?;
+            Variable(id) => write!(f, "{}", tyvar_name(*id)),
+            Unit => write!(f, "unit"),
+            Char => write!(f, "char"),
+            String => write!(f, "string"),
+            Int => write!(f, "int"),
+            Word => write!(f, "word"),
+            Real => write!(f, "real"),
+            Bool => write!(f, "bool"),
+            Fun(param, ret) => {
+                match **param {
+                    Fun(..) => write!(f, "({})", param)?,
+                    _ => write!(f, "{}", param)?,
+                }
+                write!(f, " -> {}", ret)
             }
             Tuple(tys) => {
-                write!(w, "(")?;
-                for ty in tys.iter() {
-                    ty.pp(w, indent)?;
-                    write!(w, ", ")?;
+                write!(f, "(")?;
+                inter_iter!(tys, write!(f, ", ")?, |ty| => {
+                    write!(f, "{}", ty)?;
+                });
+                write!(f, ")")
+            }
+            Datatype(name, args) => {
+                match args.as_slice() {
+                    [] => (),
+                    // parenthesize tuples not to be confused with multiple arguments
+                    [arg] => match arg {
+                        Fun(..) | Tuple(..) => write!(f, "({}) ", arg)?,
+                        _ => write!(f, "{} ", arg)?,
+                    },
+                    args => {
+                        write!(f, "(")?;
+                        inter_iter!(args, write!(f, ", ")?, |arg| => {
+                            write!(f, "{}", arg)?;
+                        });
+                        write!(f, ") ")?;
+                    }
                 }
-                write!(w, ")")?;
+                write!(f, "{}", name.0)
             }
-            Datatype(name) => name.pp(w, indent)?,
+            Ref(ty) => match **ty {
+                Fun(..) | Tuple(..) => write!(f, "({}) ref", ty),
+                _ => write!(f, "{} ref", ty),
+            },
         }
-        Ok(())
     }
 }
 
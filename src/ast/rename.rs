@@ -12,6 +12,8 @@ pub struct Rename {
     variable_tables: Vec<HashMap<Symbol, u64>>,
     type_tables: Vec<HashMap<Symbol, u64>>,
     constructor_tables: Vec<HashMap<Symbol, u64>>,
+    // the variables bound by the first alternative of the or-pattern being renamed
+    alternative_table: Option<HashMap<Symbol, u64>>,
     pos: usize,
     id: Id,
 }
@@ -60,7 +62,14 @@ impl<'a> Scope<'a> {
 
     fn new_variable(&mut self, symbol: &mut Symbol) {
         let pos = self.pos - 1;
-        let new_id = self.id.next();
+        let new_id = match self
+            .alternative_table
+            .as_ref()
+            .and_then(|table| table.get(symbol))
+        {
+            Some(id) => *id,
+            None => self.id.next(),
+        };
         self.variable_tables[pos].insert(symbol.clone(), new_id);
         symbol.1 = new_id;
     }
@@ -117,7 +126,7 @@ impl<'a> Scope<'a> {
         use Type::*;
 
         match ty {
-            Variable(_) | Char | Int | Real => {
+            Variable(_) | Unit | Char | String | Int | Word | Real | Bool => {
                 // noop
                 ()
             }
@@ -130,7 +139,11 @@ impl<'a> Scope<'a> {
                     self.rename_type(t)
                 }
             }
-            Datatype(name) => {
+            Ref(ty) => self.rename_type(ty),
+            Datatype(name, args) => {
+                for arg in args {
+                    self.rename_type(arg)
+                }
                 let pos = self.pos;
                 for table in self.type_tables[0..pos].iter_mut().rev() {
                     match table.get(name) {
@@ -150,6 +163,7 @@ impl<'a, Ty: Clone> util::Traverse<Ty> for Scope<'a> {
     fn traverse_datatype<'b, 'c>(
         &'b mut self,
         name: &mut Symbol,
+        params: &mut Vec<u64>,
         constructors: &mut Vec<(Symbol, Option<Type>)>,
     ) {
         let scope = self;
@@ -162,6 +176,7 @@ impl<'a, Ty: Clone> util::Traverse<Ty> for Scope<'a> {
         }
 
         let constructor_info = TypeInfo {
+            params: params.clone(),
             constructors: constructors.clone(),
         };
         scope
@@ -185,6 +200,16 @@ impl<'a, Ty: Clone> util::Traverse<Ty> for Scope<'a> {
         }
     }
 
+    fn traverse_val_rec_group(&mut self, binds: &mut Vec<(Pattern<Ty>, CoreExpr<Ty>)>) {
+        // all the names are visible from every body
+        for (pattern, _) in binds.iter_mut() {
+            self.traverse_pattern(pattern);
+        }
+        for (_, expr) in binds.iter_mut() {
+            self.traverse_expr(expr);
+        }
+    }
+
     fn traverse_binds(
         &mut self,
         binds: &mut Vec<CoreDeclaration<Ty>>,
@@ -216,6 +241,10 @@ impl<'a, Ty: Clone> util::Traverse<Ty> for Scope<'a> {
         }
     }
 
+    fn traverse_export(&mut self, _name: &mut String, value: &mut Symbol) {
+        self.rename(value);
+    }
+
     fn traverse_sym(&mut self, name: &mut Symbol) {
         if self.is_constructor(name) {
             self.rename_constructor(name);
@@ -224,6 +253,11 @@ impl<'a, Ty: Clone> util::Traverse<Ty> for Scope<'a> {
         }
     }
 
+    fn traverse_typed(&mut self, expr: &mut Box<CoreExpr<Ty>>, ty: &mut Type) {
+        self.traverse_expr(expr);
+        self.rename_type(ty);
+    }
+
     fn traverse_pat_constructor(&mut self, name: &mut Symbol, arg: &mut Option<Box<Pattern<Ty>>>) {
         self.rename_constructor(name);
         if let Some(pat) = arg {
@@ -244,6 +278,33 @@ impl<'a, Ty: Clone> util::Traverse<Ty> for Scope<'a> {
             self.traverse_pattern(pat)
         }
     }
+
+    fn traverse_pat_as(&mut self, name: &mut Symbol, pattern: &mut Box<Pattern<Ty>>) {
+        self.new_variable(name);
+        self.traverse_pattern(&mut *pattern);
+    }
+
+    fn traverse_pat_or(&mut self, alternatives: &mut Vec<Pattern<Ty>>) {
+        let pos = self.pos - 1;
+        let before = self.variable_tables[pos].clone();
+        let mut alternatives = alternatives.iter_mut();
+        if let Some(first) = alternatives.next() {
+            self.traverse_pattern(first);
+        }
+        // the other alternatives bind the same variables as the first one
+        let mut bound = self.alternative_table.clone().unwrap_or_default();
+        bound.extend(
+            self.variable_tables[pos]
+                .iter()
+                .filter(|(name, id)| before.get(*name) != Some(*id))
+                .map(|(name, id)| (name.clone(), *id)),
+        );
+        let outer = std::mem::replace(&mut self.alternative_table, Some(bound));
+        for pat in alternatives {
+            self.traverse_pattern(pat);
+        }
+        self.alternative_table = outer;
+    }
 }
 
 static BUILTIN_FUNCTIONS: &[(&str, BIF)] = &[
@@ -259,6 +320,20 @@ static BUILTIN_FUNCTIONS: &[(&str, BIF)] = &[
     (">=", BIF::Ge),
     ("<", BIF::Lt),
     ("<=", BIF::Le),
+    ("~", BIF::Neg),
+    ("abs", BIF::Abs),
+    ("real", BIF::RealOfInt),
+    ("floor", BIF::Floor),
+    ("ceil", BIF::Ceil),
+    ("round", BIF::Round),
+    ("trunc", BIF::Trunc),
+    ("sqrt", BIF::Sqrt),
+    ("pow", BIF::Pow),
+    ("ord", BIF::Ord),
+    ("chr", BIF::Chr),
+    ("ref", BIF::Ref),
+    ("!", BIF::Deref),
+    (":=", BIF::Assign),
 ];
 
 impl Rename {
@@ -268,8 +343,11 @@ impl Rename {
             .iter()
             .map(|(s, _)| (Symbol::new(*s), 0))
             .collect();
-        let datatypes = ["bool"].iter().map(|s| (Symbol::new(*s), 0)).collect();
-        let constructors = ["false", "true"]
+        let datatypes = ["bool", "list"]
+            .iter()
+            .map(|s| (Symbol::new(*s), 0))
+            .collect();
+        let constructors = ["false", "true", "nil", "::"]
             .iter()
             .map(|s| (Symbol::new(*s), 0))
             .collect();
@@ -278,15 +356,30 @@ impl Rename {
         symbol_table.register_type(
             Symbol::new("bool"),
             TypeInfo {
+                params: vec![],
                 constructors: vec![(Symbol::new("false"), None), (Symbol::new("true"), None)],
             },
         );
+        // datatype 'a list = nil | :: of 'a * 'a list
+        let elem = Type::Variable(0);
+        let list = Type::Datatype(Symbol::new("list"), vec![elem.clone()]);
+        symbol_table.register_type(
+            Symbol::new("list"),
+            TypeInfo {
+                params: vec![0],
+                constructors: vec![
+                    (Symbol::new("nil"), None),
+                    (Symbol::new("::"), Some(Type::Tuple(vec![elem, list]))),
+                ],
+            },
+        );
 
         Rename {
             symbol_table: Some(symbol_table),
             variable_tables: vec![functions],
             type_tables: vec![datatypes],
             constructor_tables: vec![constructors],
+            alternative_table: None,
             pos: 0,
             id,
         }
@@ -307,7 +400,7 @@ impl Rename {
 
 // bif -> fn x => _builtincall "bif"(x)
 struct WrapBIF {
-    bif_table: HashMap<String, BIF>,
+    bif_table: HashMap<Name, BIF>,
     id: Id,
 }
 impl WrapBIF {
@@ -315,13 +408,13 @@ impl WrapBIF {
         Self {
             bif_table: BUILTIN_FUNCTIONS
                 .iter()
-                .map(|(s, bif)| (s.to_string(), *bif))
+                .map(|(s, bif)| (Name::new(s), *bif))
                 .collect(),
             id,
         }
     }
 
-    fn gensym(&mut self, name: impl Into<String>) -> Symbol {
+    fn gensym(&mut self, name: impl Into<Name>) -> Symbol {
         let id = self.id.next();
         Symbol(name.into(), id)
     }
@@ -333,7 +426,29 @@ impl Transform<()> for WrapBIF {
             if let Some(bif) = self.bif_table.get(&name.0).cloned() {
                 use BIF::*;
                 return match bif {
-                    Add | Sub | Mul | Div | Divf | Mod | Eq | Neq | Gt | Ge | Lt | Le => {
+                    Neg | Abs | RealOfInt | Floor | Ceil | Round | Trunc | Sqrt | Ord | Chr
+                    | Notb | Ref | Deref => {
+                        let x = self.gensym("x");
+                        // fn x => _builtincall "op"(x)
+                        ExprKind::Fn {
+                            param: x.clone(),
+                            body: Expr {
+                                ty: (),
+                                span: Span::default(),
+                                inner: ExprKind::BuiltinCall {
+                                    fun: bif,
+                                    args: vec![Expr {
+                                        ty: (),
+                                        span: Span::default(),
+                                        inner: ExprKind::Symbol { name: x },
+                                    }],
+                                },
+                            }
+                            .boxed(),
+                        }
+                    }
+                    Add | Sub | Mul | Div | Divf | Mod | Modf | Shl | Shr | Andb | Orb | Xorb
+                    | Pow | Eq | Neq | Gt | Ge | Lt | Le | Assign => {
                         let tuple = self.gensym("tuple");
                         let l = self.gensym("x");
                         let r = self.gensym("y");
@@ -342,25 +457,30 @@ impl Transform<()> for WrapBIF {
                             param: tuple.clone(),
                             body: Expr {
                                 ty: (),
+                                span: Span::default(),
                                 inner: ExprKind::Case {
                                     cond: Expr {
                                         ty: (),
+                                        span: Span::default(),
                                         inner: ExprKind::Symbol { name: tuple },
                                     }
                                     .boxed(),
                                     clauses: vec![(
                                         Pattern {
                                             ty: (),
+                                            span: Span::default(),
                                             inner: PatternKind::Tuple {
                                                 tuple: vec![
                                                     Pattern {
                                                         ty: (),
+                                                        span: Span::default(),
                                                         inner: PatternKind::Variable {
                                                             name: l.clone(),
                                                         },
                                                     },
                                                     Pattern {
                                                         ty: (),
+                                                        span: Span::default(),
                                                         inner: PatternKind::Variable {
                                                             name: r.clone(),
                                                         },
@@ -370,15 +490,18 @@ impl Transform<()> for WrapBIF {
                                         },
                                         Expr {
                                             ty: (),
+                                            span: Span::default(),
                                             inner: ExprKind::BuiltinCall {
                                                 fun: bif,
                                                 args: vec![
                                                     Expr {
                                                         ty: (),
+                                                        span: Span::default(),
                                                         inner: ExprKind::Symbol { name: l },
                                                     },
                                                     Expr {
                                                         ty: (),
+                                                        span: Span::default(),
                                                         inner: ExprKind::Symbol { name: r },
                                                     },
                                                 ],
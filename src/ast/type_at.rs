@@ -0,0 +1,136 @@
+use crate::ast::*;
+
+impl Typed {
+    /// the type of the smallest expression or pattern in `input` covering the byte `offset`,
+    /// where `input` is the source `self` is typechecked from.
+    /// `None` on whitespaces and outside the expressions, e.g. on the keywords of declarations
+    pub fn type_at(&self, input: &str, offset: usize) -> Option<&Type> {
+        match input.get(offset..).and_then(|rest| rest.chars().next()) {
+            Some(c) if !c.is_whitespace() => (),
+            _ => return None,
+        }
+        let mut finder = TypeAt {
+            offset,
+            found: None,
+        };
+        for decl in &self.ast.0 {
+            finder.decl(decl)
+        }
+        finder.found.map(|(_, ty)| ty)
+    }
+}
+
+// the smallest node whose span covers `offset`, with its type
+struct TypeAt<'a> {
+    offset: usize,
+    found: Option<(Span, &'a Type)>,
+}
+
+impl<'a> TypeAt<'a> {
+    fn visit(&mut self, span: Span, ty: &'a Type) {
+        // the nodes made by the compiler do not have spans
+        if span.is_dummy() || offset_out_of(span, self.offset) {
+            return;
+        }
+        // the inner one of the nodes with the same span, e.g. a desugared `fun`
+        match self.found {
+            Some((found, _)) if found.end - found.start < span.end - span.start => (),
+            _ => self.found = Some((span, ty)),
+        }
+    }
+
+    fn decl(&mut self, decl: &'a TypedCoreDeclaration) {
+        match decl {
+            Declaration::Val { pattern, expr, .. } => {
+                self.pattern(pattern);
+                self.expr(expr)
+            }
+            Declaration::ValRecGroup { binds } => {
+                for (pattern, expr) in binds {
+                    self.pattern(pattern);
+                    self.expr(expr)
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn expr(&mut self, expr: &'a TypedCoreExpr) {
+        use crate::ast::ExprKind::*;
+        self.visit(expr.span, &expr.ty);
+        match &expr.inner {
+            Binds { binds, ret } => {
+                for decl in binds {
+                    self.decl(decl)
+                }
+                self.expr(ret)
+            }
+            BuiltinCall { args, .. } | ExternCall { args, .. } => {
+                for arg in args {
+                    self.expr(arg)
+                }
+            }
+            Fn { body, .. } => self.expr(body),
+            App { fun, arg } => {
+                self.expr(fun);
+                self.expr(arg)
+            }
+            Case { cond, clauses } => {
+                self.expr(cond);
+                for (pattern, expr) in clauses {
+                    self.pattern(pattern);
+                    self.expr(expr)
+                }
+            }
+            If { cond, then, else_ } => {
+                self.expr(cond);
+                self.expr(then);
+                self.expr(else_)
+            }
+            AndAlso { l, r } | OrElse { l, r } => {
+                self.expr(l);
+                self.expr(r)
+            }
+            Seq { exprs } | Tuple { tuple: exprs } => {
+                for expr in exprs {
+                    self.expr(expr)
+                }
+            }
+            Constructor { arg, .. } => {
+                if let Some(arg) = arg {
+                    self.expr(arg)
+                }
+            }
+            Typed { expr, .. } => self.expr(expr),
+            Symbol { .. } | Literal { .. } | D(_) => (),
+        }
+    }
+
+    fn pattern(&mut self, pattern: &'a TypedPattern) {
+        use crate::ast::PatternKind::*;
+        self.visit(pattern.span, &pattern.ty);
+        match &pattern.inner {
+            Constructor { arg, .. } => {
+                if let Some(arg) = arg {
+                    self.pattern(arg)
+                }
+            }
+            Tuple { tuple } => {
+                for pattern in tuple {
+                    self.pattern(pattern)
+                }
+            }
+            As { pattern, .. } => self.pattern(pattern),
+            Or { alternatives } => {
+                for pattern in alternatives {
+                    self.pattern(pattern)
+                }
+            }
+            Constant { .. } | Char { .. } | Str { .. } | Variable { .. } | Wildcard { .. } => (),
+        }
+    }
+}
+
+fn offset_out_of(span: Span, offset: usize) -> bool {
+    offset < span.start || span.end <= offset
+}
@@ -1,18 +1,32 @@
+use crate::ast::util::Traverse;
 use crate::ast::*;
-use crate::config::Config;
+use crate::config::{Config, DefaultNum};
 use crate::id::Id;
 use crate::prim::*;
-use crate::unification_pool::{NodeId, UnificationPool};
-use std::collections::HashMap;
+use crate::unification_pool::{Checkpoint, NodeId, UnificationPool};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug)]
 pub struct Typer;
 
 #[derive(Debug)]
 struct TyEnv {
-    env: HashMap<Symbol, NodeId>,
+    env: HashMap<Symbol, TyScheme>,
+    /// the datatypes in scope
     symbol_table: SymbolTable,
+    /// the datatypes declared in `let`s, moved to `symbol_table` while their `let` is inferred
+    locals: SymbolTable,
     pool: TypePool,
+    /// keep checking the later declarations after one fails,
+    /// and the rest of the declaration after a subexpression fails.
+    /// see `Config::collect_type_errors`
+    collect_errors: bool,
+    /// the mismatches of the subexpressions skipped in the current declaration
+    recovered: Vec<TypeError<'static>>,
+    /// the signatures declared by `_import`, wherever they are
+    imports: HashMap<(String, String), (Vec<Type>, Vec<Type>)>,
+    /// the types of the operands of `=` and `<>`, checked when all the program is inferred
+    equalities: Vec<(NodeId, Span)>,
 }
 
 #[derive(Debug)]
@@ -20,84 +34,460 @@ struct TypePool {
     cache: HashMap<Typing, NodeId>,
     pool: UnificationPool<Typing>,
     id: Id,
+    default_num: DefaultNum,
+}
+
+/// `forall vars. body`
+#[derive(Debug, Clone)]
+struct TyScheme {
+    vars: Vec<u64>,
+    body: NodeId,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 enum Typing {
     Variable(u64),
+    Unit,
     Char,
+    String,
     Int,
+    Word,
     Real,
+    Bool,
     Fun(NodeId, NodeId),
     Tuple(Vec<NodeId>),
-    Datatype(Symbol),
+    Datatype(Symbol, Vec<NodeId>),
+    Ref(NodeId),
     OverloadedNum,
+    // int or word, which division and shifts take
+    OverloadedInt,
     OverloadedNumText,
+    // types that admit equality
+    OverloadedEq,
+}
+
+// the `bool` datatype, which comparisons return, is the builtin type
+fn datatype(name: Symbol, args: Vec<Type>) -> Type {
+    if is_bool(&name, &args) {
+        Type::Bool
+    } else {
+        Type::Datatype(name, args)
+    }
+}
+
+fn is_bool<T>(name: &Symbol, args: &[T]) -> bool {
+    args.is_empty() && *name == Symbol::new("bool")
 }
 
-fn resolve(pool: &UnificationPool<Typing>, id: NodeId) -> Type {
-    conv_ty(pool, pool.value_of(id).clone())
+fn resolve(pool: &UnificationPool<Typing>, default_num: DefaultNum, id: NodeId) -> Type {
+    conv_ty(pool, default_num, pool.value_of(id).clone())
 }
 
-fn conv_ty(pool: &UnificationPool<Typing>, ty: Typing) -> Type {
+// the overloaded types that are not resolved by the program are defaulted by `default_num`
+fn conv_ty(pool: &UnificationPool<Typing>, default_num: DefaultNum, ty: Typing) -> Type {
     use Typing::*;
     match ty {
         Variable(id) => Type::Variable(id),
+        Unit => Type::Unit,
         Char => Type::Char,
+        String => Type::String,
         Int => Type::Int,
+        Word => Type::Word,
         Real => Type::Real,
+        Bool => Type::Bool,
         Fun(param, body) => Type::Fun(
-            Box::new(resolve(pool, param)),
-            Box::new(resolve(pool, body)),
+            Box::new(resolve(pool, default_num, param)),
+            Box::new(resolve(pool, default_num, body)),
+        ),
+        Tuple(tys) => Type::Tuple(
+            tys.into_iter()
+                .map(|ty| resolve(pool, default_num, ty))
+                .collect(),
+        ),
+        Datatype(type_id, args) => datatype(
+            type_id,
+            args.into_iter()
+                .map(|arg| resolve(pool, default_num, arg))
+                .collect(),
         ),
-        Tuple(tys) => Type::Tuple(tys.into_iter().map(|ty| resolve(pool, ty)).collect()),
-        Datatype(type_id) => Type::Datatype(type_id),
-        OverloadedNum => Type::Int,
-        OverloadedNumText => Type::Int,
+        Ref(ty) => Type::Ref(Box::new(resolve(pool, default_num, ty))),
+        OverloadedNum | OverloadedNumText => match default_num {
+            DefaultNum::Int => Type::Int,
+            DefaultNum::Word => Type::Word,
+            DefaultNum::Real => Type::Real,
+        },
+        OverloadedInt => match default_num {
+            DefaultNum::Word => Type::Word,
+            DefaultNum::Int | DefaultNum::Real => Type::Int,
+        },
+        OverloadedEq => Type::Int,
+    }
+}
+
+// same as `resolve` but memoized by the representatives of the classes
+// so that the types shared among nodes are converted only once
+fn resolve_cached(
+    pool: &UnificationPool<Typing>,
+    default_num: DefaultNum,
+    cache: &mut HashMap<NodeId, Type>,
+    id: NodeId,
+) -> Type {
+    use Typing::*;
+    let id = pool.value_id(id);
+    if let Some(ty) = cache.get(&id) {
+        return ty.clone();
+    }
+    let mut resolve = |id| resolve_cached(pool, default_num, cache, id);
+    let ty = match pool.value_of(id).clone() {
+        Fun(param, body) => Type::Fun(Box::new(resolve(param)), Box::new(resolve(body))),
+        Tuple(tys) => Type::Tuple(tys.into_iter().map(resolve).collect()),
+        Datatype(name, args) => datatype(name, args.into_iter().map(resolve).collect()),
+        Ref(ty) => Type::Ref(Box::new(resolve(ty))),
+        ty => conv_ty(pool, default_num, ty),
+    };
+    cache.insert(id, ty.clone());
+    ty
+}
+
+fn free_vars(pool: &UnificationPool<Typing>, id: NodeId, vars: &mut Vec<u64>) {
+    use Typing::*;
+    match pool.value_of(id) {
+        Variable(v) => {
+            if !vars.contains(v) {
+                vars.push(*v)
+            }
+        }
+        Fun(param, body) => {
+            free_vars(pool, *param, vars);
+            free_vars(pool, *body, vars);
+        }
+        Tuple(tys) | Datatype(_, tys) => {
+            for ty in tys {
+                free_vars(pool, *ty, vars)
+            }
+        }
+        Ref(ty) => free_vars(pool, *ty, vars),
+        _ => (),
+    }
+}
+
+fn mentions(pool: &UnificationPool<Typing>, id: NodeId, name: &Symbol) -> bool {
+    use Typing::*;
+    match pool.value_of(id) {
+        Fun(param, body) => mentions(pool, *param, name) || mentions(pool, *body, name),
+        Datatype(n, _) if n == name => true,
+        Tuple(tys) | Datatype(_, tys) => tys.iter().any(|ty| mentions(pool, *ty, name)),
+        Ref(ty) => mentions(pool, *ty, name),
+        _ => false,
+    }
+}
+
+// exported values must be functions at the top level, not closures,
+// and typed by the types wasm functions take and return
+fn check_exports<'r>(ast: &TypedCore) -> Result<'r, ()> {
+    fn is_scalar(ty: &Type) -> bool {
+        match ty {
+            Type::Unit | Type::Char | Type::Int | Type::Word | Type::Real | Type::Bool => true,
+            _ => false,
+        }
+    }
+    let mut vals = HashMap::new();
+    for decl in &ast.0 {
+        match decl {
+            Declaration::Val { pattern, expr, .. } => {
+                if let PatternKind::Variable { name } = &pattern.inner {
+                    let is_fn = match expr.inner {
+                        ExprKind::Fn { .. } => true,
+                        _ => false,
+                    };
+                    vals.insert(name, (is_fn, &pattern.ty));
+                }
+            }
+            Declaration::Export { value, .. } => match vals.get(value) {
+                Some((true, Type::Fun(param, ret))) if is_scalar(param) && is_scalar(ret) => (),
+                Some((_, ty)) => {
+                    return Err(TypeError::NotExportable {
+                        name: value.clone(),
+                        ty: (*ty).clone(),
+                    })
+                }
+                None => unreachable!("only `val`s and `fun`s are exported"),
+            },
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+// copies the signatures declared by `_import` to the `_externcall`s that omit them
+struct FillImports<'a>(&'a HashMap<(String, String), (Vec<Type>, Vec<Type>)>);
+
+impl<'a> Traverse<NodeId> for FillImports<'a> {
+    fn traverse_externcall(
+        &mut self,
+        module: &mut String,
+        fun: &mut String,
+        args: &mut Vec<CoreExpr<NodeId>>,
+        argty: &mut Vec<Type>,
+        retty: &mut Vec<Type>,
+    ) {
+        if argty.is_empty() {
+            if let Some((declared_argty, declared_retty)) =
+                self.0.get(&(module.clone(), fun.clone()))
+            {
+                *argty = declared_argty.clone();
+                *retty = declared_retty.clone();
+            }
+        }
+        for arg in args {
+            self.traverse_expr(arg)
+        }
+    }
+}
+
+// the datatypes declared in `let`s
+struct LocalDatatypes(Vec<Symbol>);
+
+impl Traverse<NodeId> for LocalDatatypes {
+    fn traverse_binds(
+        &mut self,
+        binds: &mut Vec<CoreDeclaration<NodeId>>,
+        ret: &mut Box<CoreExpr<NodeId>>,
+    ) {
+        for decl in binds.iter_mut() {
+            if let Declaration::Datatype { name, .. } = decl {
+                self.0.push(name.clone())
+            }
+            self.traverse_statement(decl)
+        }
+        self.traverse_expr(ret)
+    }
+}
+
+// rename the type variables to 0, 1, 2, ... in order of their first appearance
+// so that they are printed as 'a, 'b, 'c, ... in error messages
+fn normalize_vars(t1: &mut Type, t2: &mut Type) {
+    fn rename(ty: &mut Type, map: &mut HashMap<u64, u64>) {
+        use Type::*;
+        match ty {
+            Variable(id) => {
+                let next = map.len() as u64;
+                *id = *map.entry(*id).or_insert(next);
+            }
+            Fun(param, body) => {
+                rename(param, map);
+                rename(body, map);
+            }
+            Tuple(tys) | Datatype(_, tys) => {
+                for ty in tys {
+                    rename(ty, map)
+                }
+            }
+            Ref(ty) => rename(ty, map),
+            Unit | Char | String | Int | Word | Real | Bool => (),
+        }
+    }
+    let mut map = HashMap::new();
+    rename(t1, &mut map);
+    rename(t2, &mut map);
+}
+
+fn mismatch<'r>(
+    pool: &UnificationPool<Typing>,
+    default_num: DefaultNum,
+    t1: Typing,
+    t2: Typing,
+) -> TypeError<'r> {
+    let mut expected = conv_ty(pool, default_num, t1);
+    let mut actual = conv_ty(pool, default_num, t2);
+    normalize_vars(&mut expected, &mut actual);
+    TypeError::MisMatch {
+        expected,
+        actual,
+        span: None,
+    }
+}
+
+// resolve `ty` where the nodes under unification are replaced with `var`
+fn conv_ty_cyclic(
+    pool: &UnificationPool<Typing>,
+    default_num: DefaultNum,
+    ty: Typing,
+    var: &Type,
+) -> Type {
+    use Typing::*;
+    let resolve = |id| match pool.try_value_of(id) {
+        Some(ty) => conv_ty_cyclic(pool, default_num, ty.clone(), var),
+        None => var.clone(),
+    };
+    match ty {
+        Fun(param, body) => Type::Fun(Box::new(resolve(param)), Box::new(resolve(body))),
+        Tuple(tys) => Type::Tuple(tys.into_iter().map(resolve).collect()),
+        Datatype(name, args) => datatype(name, args.into_iter().map(resolve).collect()),
+        Ref(ty) => Type::Ref(Box::new(resolve(ty))),
+        ty => conv_ty(pool, default_num, ty),
+    }
+}
+
+// the Levenshtein distance between the names
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    // the distances from the prefix of `a` to the prefixes of `b`
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + if ca == *cb { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+// check whether `ty` refers to the nodes under unification
+fn occurs(pool: &UnificationPool<Typing>, ty: &Typing) -> bool {
+    use Typing::*;
+    let occurs_in = |id| match pool.try_value_of(id) {
+        Some(ty) => occurs(pool, ty),
+        None => true,
+    };
+    match ty {
+        Fun(param, body) => occurs_in(*param) || occurs_in(*body),
+        Tuple(tys) | Datatype(_, tys) => tys.iter().any(|ty| occurs_in(*ty)),
+        Ref(ty) => occurs_in(*ty),
+        _ => false,
     }
 }
 
 fn try_unify<'b, 'r>(
     pool: &'b mut UnificationPool<Typing>,
+    default_num: DefaultNum,
     t1: Typing,
     t2: Typing,
 ) -> Result<'r, Typing> {
     use Typing::*;
+    let unify =
+        move |pool: &mut UnificationPool<Typing>, t1, t2| try_unify(pool, default_num, t1, t2);
     match (t1, t2) {
         (t1, t2) if t1 == t2 => Ok(t1),
+        // `()` is the only value of unit
+        (Unit, Tuple(ref tys)) | (Tuple(ref tys), Unit) if tys.is_empty() => Ok(Unit),
         (Int, OverloadedNum) | (OverloadedNum, Int) => Ok(Int),
         (Int, OverloadedNumText) | (OverloadedNumText, Int) => Ok(Int),
+        (Int, OverloadedInt) | (OverloadedInt, Int) => Ok(Int),
+        // words are unsigned, and never coerced from or to ints
+        (Word, OverloadedNum) | (OverloadedNum, Word) => Ok(Word),
+        (Word, OverloadedNumText) | (OverloadedNumText, Word) => Ok(Word),
+        (Word, OverloadedInt) | (OverloadedInt, Word) => Ok(Word),
         (Char, OverloadedNumText) | (OverloadedNumText, Char) => Ok(Char),
+        (String, OverloadedNumText) | (OverloadedNumText, String) => Ok(String),
         (Real, OverloadedNum) | (OverloadedNum, Real) => Ok(Real),
+        // reals are compared with the IEEE instructions of the backend,
+        // so a NaN is unordered: every comparison but `<>` is false
         (Real, OverloadedNumText) | (OverloadedNumText, Real) => Ok(Real),
+        (Bool, Datatype(ref name, ref args)) | (Datatype(ref name, ref args), Bool)
+            if is_bool(name, args) =>
+        {
+            Ok(Bool)
+        }
         (OverloadedNumText, OverloadedNum) | (OverloadedNum, OverloadedNumText) => {
             Ok(OverloadedNumText)
         }
-        (Variable(_), ty) | (ty, Variable(_)) => Ok(ty),
+        (OverloadedInt, OverloadedNum)
+        | (OverloadedNum, OverloadedInt)
+        | (OverloadedInt, OverloadedNumText)
+        | (OverloadedNumText, OverloadedInt) => Ok(OverloadedInt),
+        (Variable(id), ty) | (ty, Variable(id)) => {
+            if occurs(pool, &ty) {
+                let mut var = Type::Variable(id);
+                let mut ty = conv_ty_cyclic(pool, default_num, ty, &var);
+                normalize_vars(&mut var, &mut ty);
+                Err(TypeError::InfiniteType {
+                    var,
+                    ty,
+                    span: None,
+                })
+            } else {
+                Ok(ty)
+            }
+        }
+        (OverloadedEq, Fun(param, body)) | (Fun(param, body), OverloadedEq) => {
+            let mut ty = conv_ty(pool, default_num, Fun(param, body));
+            normalize_vars(&mut ty, &mut Type::Unit);
+            Err(TypeError::NotEqualityType { ty, span: None })
+        }
+        // tuples and datatypes admit equality if their components do
+        (OverloadedEq, Tuple(tys)) | (Tuple(tys), OverloadedEq) => {
+            let tys = tys
+                .into_iter()
+                .map(|ty| {
+                    let eq = pool.node_new(OverloadedEq);
+                    pool.try_unify_with(ty, eq, unify)
+                })
+                .collect::<Result<'_, Vec<_>>>()?;
+            Ok(Tuple(tys))
+        }
+        (OverloadedEq, Datatype(name, args)) | (Datatype(name, args), OverloadedEq) => {
+            let args = args
+                .into_iter()
+                .map(|ty| {
+                    let eq = pool.node_new(OverloadedEq);
+                    pool.try_unify_with(ty, eq, unify)
+                })
+                .collect::<Result<'_, Vec<_>>>()?;
+            Ok(Datatype(name, args))
+        }
+        (OverloadedEq, ty) | (ty, OverloadedEq) => Ok(ty),
         (Fun(p1, b1), Fun(p2, b2)) => {
-            let p = pool.try_unify_with(p1, p2, try_unify)?;
-            let b = pool.try_unify_with(b1, b2, try_unify)?;
+            let p = pool.try_unify_with(p1, p2, unify)?;
+            let b = pool.try_unify_with(b1, b2, unify)?;
             Ok(Fun(p, b))
         }
+        (Ref(t1), Ref(t2)) => {
+            let ty = pool.try_unify_with(t1, t2, unify)?;
+            Ok(Ref(ty))
+        }
         (Tuple(tu1), Tuple(tu2)) => {
             if tu1.len() != tu2.len() {
-                Err(TypeError::MisMatch {
-                    expected: conv_ty(pool, Tuple(tu1)),
-                    actual: conv_ty(pool, Tuple(tu2)),
-                })
+                Err(mismatch(pool, default_num, Tuple(tu1), Tuple(tu2)))
             } else {
                 let tu = tu1
                     .into_iter()
                     .zip(tu2)
-                    .map(|(t1, t2)| pool.try_unify_with(t1, t2, try_unify))
+                    .map(|(t1, t2)| pool.try_unify_with(t1, t2, unify))
                     .collect::<Result<'_, Vec<_>>>()?;
                 Ok(Tuple(tu))
             }
         }
-        (t1, t2) => Err(TypeError::MisMatch {
-            expected: conv_ty(pool, t1),
-            actual: conv_ty(pool, t2),
-        }),
+        (Datatype(n1, a1), Datatype(n2, a2)) => {
+            if n1 != n2 || a1.len() != a2.len() {
+                Err(mismatch(
+                    pool,
+                    default_num,
+                    Datatype(n1, a1),
+                    Datatype(n2, a2),
+                ))
+            } else {
+                let args = a1
+                    .into_iter()
+                    .zip(a2)
+                    .map(|(t1, t2)| pool.try_unify_with(t1, t2, unify))
+                    .collect::<Result<'_, Vec<_>>>()?;
+                Ok(Datatype(n1, args))
+            }
+        }
+        (t1, t2) => Err(mismatch(pool, default_num, t1, t2)),
+    }
+}
+
+impl TyScheme {
+    fn mono(body: NodeId) -> Self {
+        TyScheme {
+            vars: Vec::new(),
+            body,
+        }
     }
 }
 
@@ -106,31 +496,59 @@ impl Typer {
         Typer
     }
 
-    fn generate_pass(&mut self, symbol_table: SymbolTable) -> TyEnv {
-        TyEnv::new(symbol_table)
+    fn generate_pass(&mut self, symbol_table: SymbolTable, default_num: DefaultNum) -> TyEnv {
+        TyEnv::new(symbol_table, default_num)
     }
 }
 
 impl TypePool {
-    fn new() -> Self {
+    fn new(default_num: DefaultNum) -> Self {
         let mut ret = Self {
             cache: HashMap::new(),
             pool: UnificationPool::new(),
             id: Id::new(),
+            default_num,
         };
         ret.init();
         ret
     }
 
     fn init(&mut self) {
+        self.node_new(Typing::Unit);
         self.node_new(Typing::Char);
+        self.node_new(Typing::String);
         self.node_new(Typing::Int);
+        self.node_new(Typing::Word);
         self.node_new(Typing::Real);
+        self.node_new(Typing::Bool);
+    }
+
+    /// start recording the changes, to undo the inference of an expression that fails.
+    /// the shared types are kept aside as the nodes they point to may be removed
+    fn snapshot(&mut self) -> (Checkpoint, HashMap<Typing, NodeId>) {
+        (self.pool.snapshot(), self.cache.clone())
+    }
+
+    fn rollback(&mut self, (checkpoint, cache): (Checkpoint, HashMap<Typing, NodeId>)) {
+        self.pool.rollback(checkpoint);
+        self.cache = cache;
+    }
+
+    fn commit(&mut self, (checkpoint, _): (Checkpoint, HashMap<Typing, NodeId>)) {
+        self.pool.commit(checkpoint);
     }
 
     fn feed_symbol_table(&mut self, symbol_table: &SymbolTable) {
-        for typename in symbol_table.types.keys() {
-            self.node_new(Typing::Datatype(typename.clone()));
+        let mut typenames = symbol_table
+            .types
+            .iter()
+            .filter(|(_, info)| info.params.is_empty())
+            .map(|(typename, _)| typename)
+            .collect::<Vec<_>>();
+        // the order of the hash maps is not stable, and so would be the numbering of the nodes
+        typenames.sort_by_key(|typename| (typename.0.as_str(), typename.1));
+        for typename in typenames {
+            self.node_new(Typing::Datatype(typename.clone(), vec![]));
         }
     }
 
@@ -142,19 +560,28 @@ impl TypePool {
         self.pool.node_new(ty)
     }
 
+    fn ty_unit(&mut self) -> NodeId {
+        *self.cache.get(&Typing::Unit).unwrap()
+    }
+
     fn ty_int(&mut self) -> NodeId {
         *self.cache.get(&Typing::Int).unwrap()
     }
 
+    fn ty_word(&mut self) -> NodeId {
+        *self.cache.get(&Typing::Word).unwrap()
+    }
+
     fn ty_char(&mut self) -> NodeId {
         *self.cache.get(&Typing::Char).unwrap()
     }
 
+    fn ty_string(&mut self) -> NodeId {
+        *self.cache.get(&Typing::String).unwrap()
+    }
+
     fn ty_bool(&mut self) -> NodeId {
-        *self
-            .cache
-            .get(&Typing::Datatype(Symbol::new("bool")))
-            .unwrap()
+        *self.cache.get(&Typing::Bool).unwrap()
     }
 
     fn ty_real(&mut self) -> NodeId {
@@ -165,14 +592,31 @@ impl TypePool {
         self.node_new(Typing::OverloadedNum)
     }
 
+    fn ty_overloaded_int(&mut self) -> NodeId {
+        self.node_new(Typing::OverloadedInt)
+    }
+
     fn ty_overloaded_num_text(&mut self) -> NodeId {
         self.node_new(Typing::OverloadedNumText)
     }
 
+    fn ty_overloaded_eq(&mut self) -> NodeId {
+        self.node_new(Typing::OverloadedEq)
+    }
+
     fn node_new(&mut self, t: Typing) -> NodeId {
         let node_id = self.pool.node_new(t.clone());
         match t {
-            t @ Typing::Char | t @ Typing::Int | t @ Typing::Real | t @ Typing::Datatype(_) => {
+            // only monomorphic types are shared
+            Typing::Datatype(_, ref args) if !args.is_empty() => (),
+            t @ Typing::Unit
+            | t @ Typing::Char
+            | t @ Typing::String
+            | t @ Typing::Int
+            | t @ Typing::Word
+            | t @ Typing::Real
+            | t @ Typing::Bool
+            | t @ Typing::Datatype(..) => {
                 self.cache.insert(t, node_id);
             }
             _ => (), // no cache
@@ -180,6 +624,37 @@ impl TypePool {
         node_id
     }
 
+    fn instantiate(&mut self, id: NodeId, subst: &HashMap<u64, NodeId>) -> NodeId {
+        use Typing::*;
+        match self.pool.value_of(id).clone() {
+            Variable(v) => subst.get(&v).cloned().unwrap_or(id),
+            Fun(param, body) => {
+                let param = self.instantiate(param, subst);
+                let body = self.instantiate(body, subst);
+                self.ty(Fun(param, body))
+            }
+            Tuple(tys) => {
+                let tys = tys
+                    .into_iter()
+                    .map(|ty| self.instantiate(ty, subst))
+                    .collect();
+                self.ty(Tuple(tys))
+            }
+            Datatype(name, args) => {
+                let args = args
+                    .into_iter()
+                    .map(|ty| self.instantiate(ty, subst))
+                    .collect();
+                self.ty(Datatype(name, args))
+            }
+            Ref(ty) => {
+                let ty = self.instantiate(ty, subst);
+                self.ty(Ref(ty))
+            }
+            _ => id,
+        }
+    }
+
     fn try_unify_with<'r>(
         &mut self,
         id1: NodeId,
@@ -197,17 +672,29 @@ impl TypePool {
 }
 
 impl TypePool {
+    fn dump_classes(&self) {
+        for (ty, ids) in self.pool.classes() {
+            println!("{:?}: {:?}", ids, ty);
+        }
+    }
+
     fn typed_ast(&self, ast: Core<NodeId>) -> TypedCore {
-        ast.map_ty(&mut |ty| resolve(&self.pool, ty))
+        let mut cache = HashMap::new();
+        ast.map_ty(&mut |ty| resolve_cached(&self.pool, self.default_num, &mut cache, ty))
     }
 }
 
 impl TyEnv {
-    pub fn new(symbol_table: SymbolTable) -> Self {
+    pub fn new(symbol_table: SymbolTable, default_num: DefaultNum) -> Self {
         let mut ret = TyEnv {
             env: HashMap::new(),
             symbol_table: symbol_table,
-            pool: TypePool::new(),
+            locals: SymbolTable::new(),
+            pool: TypePool::new(default_num),
+            collect_errors: false,
+            recovered: Vec::new(),
+            equalities: Vec::new(),
+            imports: HashMap::new(),
         };
         ret.init();
 
@@ -216,26 +703,83 @@ impl TyEnv {
 
     fn init(&mut self) {
         self.pool.feed_symbol_table(&self.symbol_table);
-        let cnames = self
+        let mut cnames = self
             .symbol_table
             .constructors
             .keys()
             .cloned()
             .collect::<Vec<_>>();
+        // in a stable order, as `feed_symbol_table`
+        cnames.sort_by_key(|cname| (cname.0.as_str(), cname.1));
+        for cname in cnames {
+            self.insert_constructor(cname)
+        }
+    }
+
+    fn insert_constructor(&mut self, cname: Symbol) {
+        let type_name = self
+            .symbol_table
+            .get_datatype_of_constructor(&cname)
+            .expect("internal error: typing")
+            .clone();
+        let params = self
+            .symbol_table
+            .get_type(&type_name)
+            .expect("internal error: typing")
+            .params
+            .clone();
+        let args = params.iter().map(|param| Type::Variable(*param)).collect();
+        let typing = self.convert(Type::Datatype(type_name, args));
+        let body = self.pool.ty(typing);
+        self.insert_scheme(cname, TyScheme { vars: params, body });
+    }
+
+    // moves the datatype `name` between `symbol_table` and `locals`
+    fn move_datatype(from: &mut SymbolTable, to: &mut SymbolTable, name: &Symbol) {
+        if let Some(info) = from.types.remove(name) {
+            for (cname, _) in &info.constructors {
+                from.constructors.remove(cname);
+            }
+            to.register_type(name.clone(), info);
+        }
+    }
+
+    // brings the datatype declared in a `let` and its constructors in scope
+    fn open_local(&mut self, name: &Symbol) {
+        let cnames = match self.locals.get_type(name) {
+            Some(info) => info
+                .constructors
+                .iter()
+                .map(|(cname, _)| cname.clone())
+                .collect::<Vec<_>>(),
+            None => return,
+        };
+        Self::move_datatype(&mut self.locals, &mut self.symbol_table, name);
         for cname in cnames {
-            let ty = self
-                .symbol_table
-                .get_datatype_of_constructor(&cname)
-                .expect("internal error: typing");
-            let ty = Type::Datatype(ty.clone());
-            let typing = self.convert(ty);
-            let node_id = self.pool.ty(typing);
-            self.insert(cname, node_id);
+            self.insert_constructor(cname)
         }
     }
 
+    // the constructors go out of scope with the environment of the `let`
+    fn close_local(&mut self, name: &Symbol) {
+        Self::move_datatype(&mut self.symbol_table, &mut self.locals, name);
+    }
+
     pub fn infer<'a, 'b>(&'a mut self, ast: &mut ast::Core<NodeId>) -> Result<'b, ()> {
+        // the datatypes in `let`s are out of scope until their `let`s
+        let mut locals = LocalDatatypes(Vec::new());
+        locals.traverse_ast(ast);
+        for name in &locals.0 {
+            if let Some(info) = self.symbol_table.get_type(name) {
+                for (cname, _) in &info.constructors {
+                    self.env.remove(cname);
+                }
+            }
+            self.close_local(name);
+        }
         self.infer_ast(ast)?;
+        // spell out the imported signatures at the call sites for the later passes
+        FillImports(&self.imports).traverse_ast(ast);
         Ok(())
     }
 
@@ -243,24 +787,208 @@ impl TyEnv {
         &self.symbol_table
     }
 
-    pub fn into_symbol_table(self) -> SymbolTable {
+    pub fn into_symbol_table(mut self) -> SymbolTable {
+        for (name, info) in self.locals.types {
+            self.symbol_table.register_type(name, info)
+        }
         self.symbol_table
     }
 
-    fn get(&self, name: &Symbol) -> Option<NodeId> {
+    fn get(&self, name: &Symbol) -> Option<TyScheme> {
         self.env.get(name).cloned()
     }
 
-    fn insert(&mut self, k: Symbol, v: NodeId) -> Option<NodeId> {
+    fn insert(&mut self, k: Symbol, v: NodeId) -> Option<TyScheme> {
+        self.insert_scheme(k, TyScheme::mono(v))
+    }
+
+    fn insert_scheme(&mut self, k: Symbol, v: TyScheme) -> Option<TyScheme> {
         self.env.insert(k, v)
     }
 
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let env = self.env.clone();
+        let ret = f(self);
+        self.env = env;
+        ret
+    }
+
+    // whether the datatype `name` appears in `ty` or in the types of the names in scope
+    fn escapes(&self, name: &Symbol, ty: NodeId) -> bool {
+        mentions(&self.pool.pool, ty, name)
+            || self
+                .env
+                .values()
+                .any(|scheme| mentions(&self.pool.pool, scheme.body, name))
+    }
+
+    fn env_free_vars(&self) -> Vec<u64> {
+        let mut vars = Vec::new();
+        for scheme in self.env.values() {
+            let mut fvs = Vec::new();
+            free_vars(&self.pool.pool, scheme.body, &mut fvs);
+            vars.extend(fvs.into_iter().filter(|v| !scheme.vars.contains(v)));
+        }
+        vars
+    }
+
+    fn generalize(&self, ty: NodeId) -> TyScheme {
+        let env_vars = self.env_free_vars();
+        let mut vars = Vec::new();
+        free_vars(&self.pool.pool, ty, &mut vars);
+        vars.retain(|v| !env_vars.contains(v));
+        TyScheme { vars, body: ty }
+    }
+
+    fn instantiate(&mut self, scheme: &TyScheme) -> NodeId {
+        if scheme.vars.is_empty() {
+            return scheme.body;
+        }
+        let subst = scheme
+            .vars
+            .iter()
+            .map(|v| (*v, self.pool.tyvar()))
+            .collect();
+        self.pool.instantiate(scheme.body, &subst)
+    }
+
+    // instantiate the type of the constructor `cname` and its argument type
+    // sharing the fresh type variables for the datatype parameters
+    fn instantiate_constructor(&mut self, cname: &Symbol) -> Option<(NodeId, Option<NodeId>)> {
+        let type_name = self
+            .symbol_table()
+            .get_datatype_of_constructor(cname)?
+            .clone();
+        let info = self.symbol_table().get_type(&type_name)?.clone();
+        let subst: HashMap<u64, NodeId> = info
+            .params
+            .iter()
+            .map(|param| (*param, self.pool.tyvar()))
+            .collect();
+        let args = info.params.iter().map(|param| subst[param]).collect();
+        let ty = self.pool.ty(Typing::Datatype(type_name, args));
+        let arg_ty = info
+            .constructors
+            .into_iter()
+            .find(|(name, _)| name == cname)
+            .and_then(|(_, arg)| arg)
+            .map(|arg| {
+                let typing = self.convert(arg);
+                let id = self.pool.ty(typing);
+                self.pool.instantiate(id, &subst)
+            });
+        Some((ty, arg_ty))
+    }
+
+    // check that all the type constructors in `ty` are declared
+    fn check_type<'r>(&self, ty: &Type) -> Result<'r, ()> {
+        use Type::*;
+        match ty {
+            Variable(_) | Unit | Char | String | Int | Word | Real | Bool => Ok(()),
+            Fun(param, body) => {
+                self.check_type(param)?;
+                self.check_type(body)
+            }
+            Tuple(tys) => tys.iter().try_for_each(|ty| self.check_type(ty)),
+            Datatype(name, args) => {
+                if self.symbol_table().get_type(name).is_none() {
+                    return Err(TypeError::UnknownType { name: name.clone() });
+                }
+                args.iter().try_for_each(|ty| self.check_type(ty))
+            }
+            Ref(ty) => self.check_type(ty),
+        }
+    }
+
+    // whether the values of `ty` can be compared by `=`.
+    // a datatype can if the arguments of all its constructors can,
+    // where the datatypes being checked are assumed to
+    fn admits_equality(&self, ty: &Type, visiting: &mut HashSet<Symbol>) -> bool {
+        use Type::*;
+        match ty {
+            Fun(..) => false,
+            Variable(_) | Unit | Char | String | Int | Word | Real | Bool | Ref(_) => true,
+            Tuple(tys) => tys.iter().all(|ty| self.admits_equality(ty, visiting)),
+            Datatype(name, args) => {
+                if !args.iter().all(|ty| self.admits_equality(ty, visiting)) {
+                    return false;
+                }
+                if !visiting.insert(name.clone()) {
+                    return true;
+                }
+                // the datatypes of the `let`s are out of scope when the equalities are checked
+                let info = match self
+                    .symbol_table()
+                    .get_type(name)
+                    .or_else(|| self.locals.get_type(name))
+                {
+                    Some(info) => info,
+                    None => return true,
+                };
+                info.constructors.iter().all(|(_, arg)| match arg {
+                    None => true,
+                    Some(arg) => self.admits_equality(arg, visiting),
+                })
+            }
+        }
+    }
+
+    // the operands of `=` and `<>` whose types are datatypes taking functions.
+    // the types are known only after the uses of the operands are inferred
+    fn check_equalities<'r>(&mut self) -> Vec<TypeError<'r>> {
+        let default_num = self.pool.default_num;
+        let equalities = std::mem::replace(&mut self.equalities, Vec::new());
+        equalities
+            .into_iter()
+            .filter_map(|(ty, span)| {
+                let mut ty = resolve(&self.pool.pool, default_num, ty);
+                if self.admits_equality(&ty, &mut HashSet::new()) {
+                    return None;
+                }
+                normalize_vars(&mut ty, &mut Type::Unit);
+                Some(TypeError::NotEqualityType { ty, span: None }.at(span))
+            })
+            .collect()
+    }
+
+    // whether `ty` has a finite value.
+    // datatypes are boxed so recursion through them is fine
+    // as long as some constructor doesn't go back to the datatype under construction.
+    fn inhabited(&self, ty: &Type, visiting: &mut HashSet<Symbol>) -> bool {
+        use Type::*;
+        match ty {
+            Variable(_) | Unit | Char | String | Int | Word | Real | Bool | Fun(..) => true,
+            Tuple(tys) => tys.iter().all(|ty| self.inhabited(ty, visiting)),
+            Ref(ty) => self.inhabited(ty, visiting),
+            Datatype(name, _) => {
+                if visiting.contains(name) {
+                    return false;
+                }
+                let info = match self.symbol_table().get_type(name) {
+                    Some(info) => info,
+                    None => return true,
+                };
+                visiting.insert(name.clone());
+                let ret = info.constructors.iter().any(|(_, arg)| match arg {
+                    None => true,
+                    Some(arg) => self.inhabited(arg, visiting),
+                });
+                visiting.remove(name);
+                ret
+            }
+        }
+    }
+
     fn convert(&mut self, ty: Type) -> Typing {
         match ty {
             Type::Variable(v) => Typing::Variable(v),
+            Type::Unit => Typing::Unit,
             Type::Char => Typing::Char,
+            Type::String => Typing::String,
             Type::Int => Typing::Int,
+            Type::Word => Typing::Word,
             Type::Real => Typing::Real,
+            Type::Bool => Typing::Bool,
             Type::Fun(arg, ret) => {
                 let arg_typing = self.convert(*arg);
                 let ret_typing = self.convert(*ret);
@@ -275,23 +1003,91 @@ impl TyEnv {
                     })
                     .collect(),
             ),
-            Type::Datatype(name) => Typing::Datatype(name),
+            Type::Datatype(name, args) => Typing::Datatype(
+                name,
+                args.into_iter()
+                    .map(|ty| {
+                        let typing = self.convert(ty);
+                        self.pool.ty(typing)
+                    })
+                    .collect(),
+            ),
+            Type::Ref(ty) => {
+                let typing = self.convert(*ty);
+                Typing::Ref(self.pool.ty(typing))
+            }
         }
     }
 }
 
 impl TyEnv {
     fn infer_ast<'b, 'r>(&'b mut self, ast: &Core<NodeId>) -> Result<'r, ()> {
+        let mut errors = Vec::new();
         for decl in ast.0.iter() {
-            self.infer_statement(&decl)?;
+            let result = self.infer_statement(&decl);
+            let failed = result.is_err() || !self.recovered.is_empty();
+            for e in self.recovered.drain(..) {
+                errors.push(e);
+            }
+            match result {
+                Ok(()) => (),
+                Err(e) if self.collect_errors => errors.push(e),
+                Err(e) => return Err(e),
+            }
+            if failed {
+                self.poison(decl);
+            }
+        }
+        let mut equalities = self.check_equalities();
+        if !self.collect_errors && !equalities.is_empty() {
+            return Err(equalities.remove(0));
+        }
+        errors.extend(equalities);
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(TypeError::Multiple(errors)),
+        }
+    }
+
+    // give the names bound by the declaration that failed fresh polymorphic types,
+    // so that their uses in the later declarations are checked without repeating the error
+    fn poison(&mut self, decl: &CoreDeclaration<NodeId>) {
+        use Declaration::*;
+        let names = match decl {
+            Datatype { .. } | Import { .. } | Export { .. } => Vec::new(),
+            Val { pattern, .. } => pattern.binds(),
+            ValRecGroup { binds } => binds
+                .iter()
+                .flat_map(|(pattern, _)| pattern.binds())
+                .collect(),
+            D(d) => match *d {},
+        };
+        for (name, _) in names {
+            let var = self.pool.tyvar();
+            let scheme = self.generalize(var);
+            self.insert_scheme(name.clone(), scheme);
         }
-        Ok(())
     }
 
     fn infer_statement<'b, 'r>(&'b mut self, decl: &CoreDeclaration<NodeId>) -> Result<'r, ()> {
         use Declaration::*;
         match decl {
-            Datatype { .. } => Ok(()),
+            Datatype {
+                name, constructors, ..
+            } => {
+                self.open_local(name);
+                for (_, argty) in constructors {
+                    if let Some(argty) = argty {
+                        self.check_type(argty)?;
+                    }
+                }
+                let datatype = Type::Datatype(name.clone(), vec![]);
+                if !self.inhabited(&datatype, &mut HashSet::new()) {
+                    return Err(TypeError::RecursiveType { name: name.clone() });
+                }
+                Ok(())
+            }
             Val { rec, pattern, expr } => {
                 let names = pattern.binds();
                 if *rec {
@@ -299,35 +1095,145 @@ impl TyEnv {
                         self.insert(name.clone(), ty.clone());
                     }
                 }
-                self.infer_expr(expr)?;
-                self.infer_pat(pattern)?;
-                self.unify(expr.ty(), pattern.ty())?;
-                if !rec {
-                    for &(name, ty) in &names {
-                        self.insert(name.clone(), ty.clone());
-                    }
+                self.infer_bind(pattern, expr)?;
+                self.bind_names(&names, !expr.is_expansive());
+                Ok(())
+            }
+            ValRecGroup { binds } => {
+                let names = binds
+                    .iter()
+                    .flat_map(|(pattern, _)| pattern.binds())
+                    .collect::<Vec<_>>();
+                for &(name, ty) in &names {
+                    self.insert(name.clone(), ty.clone());
+                }
+                for (pattern, expr) in binds {
+                    self.infer_bind(pattern, expr)?;
                 }
+                // the group is generalized together after all the bodies are inferred
+                let generalize = binds.iter().all(|(_, expr)| !expr.is_expansive());
+                self.bind_names(&names, generalize);
                 Ok(())
             }
+            Import {
+                module,
+                fun,
+                argty,
+                retty,
+            } => {
+                for ty in argty.iter().chain(retty) {
+                    self.check_type(ty)?;
+                }
+                self.imports.insert(
+                    (module.clone(), fun.clone()),
+                    (argty.clone(), retty.clone()),
+                );
+                Ok(())
+            }
+            // the types of the exports are checked by `check_exports` after they are resolved
+            Export { value, .. } => match self.get(value) {
+                Some(_) => Ok(()),
+                None => Err(self.free_var(value)),
+            },
             D(d) => match *d {},
         }
     }
 
+    fn infer_bind<'b, 'r>(
+        &'b mut self,
+        pattern: &Pattern<NodeId>,
+        expr: &CoreExpr<NodeId>,
+    ) -> Result<'r, ()> {
+        self.infer_expr(expr)?;
+        self.infer_pat(pattern)?;
+        self.unify(expr.ty(), pattern.ty())
+            .map_err(|e| e.at(pattern.span.merge(expr.span)))
+    }
+
+    fn bind_names(&mut self, names: &[(&Symbol, &NodeId)], generalize: bool) {
+        // names are visible in the env at this point. remove them not to
+        // prevent their own type variables from being generalized
+        for &(name, _) in names {
+            self.env.remove(name);
+        }
+        for &(name, ty) in names {
+            let scheme = if generalize {
+                self.generalize(*ty)
+            } else {
+                TyScheme::mono(*ty)
+            };
+            self.insert_scheme(name.clone(), scheme);
+        }
+    }
+
     fn infer_expr<'b, 'r>(&'b mut self, expr: &CoreExpr<NodeId>) -> Result<'r, ()> {
+        // errors from the sub expressions already have their spans
+        if !self.collect_errors {
+            return self.infer_expr_kind(expr).map_err(|e| e.at(expr.span));
+        }
+        let snapshot = self.pool.snapshot();
+        match self.infer_expr_kind(expr).map_err(|e| e.at(expr.span)) {
+            // record the mismatch and go on with the enclosing expression.
+            // the declaration fails with the recorded errors, so no code is generated for it
+            Err(TypeError::MisMatch {
+                expected,
+                actual,
+                span,
+            }) => {
+                // forget what the failed expression has unified, so that its type is as free as
+                // before and the enclosing expression does not mismatch with it again
+                self.pool.rollback(snapshot);
+                self.recovered.push(TypeError::MisMatch {
+                    expected,
+                    actual,
+                    span,
+                });
+                Ok(())
+            }
+            result => {
+                self.pool.commit(snapshot);
+                result
+            }
+        }
+    }
+
+    fn infer_expr_kind<'b, 'r>(&'b mut self, expr: &CoreExpr<NodeId>) -> Result<'r, ()> {
         use crate::ast::ExprKind::*;
         let int = self.pool.ty_int();
         let real = self.pool.ty_real();
         let bool = self.pool.ty_bool();
         let overloaded_num = self.pool.ty_overloaded_num();
+        let overloaded_int = self.pool.ty_overloaded_int();
         let overloaded_num_text = self.pool.ty_overloaded_num_text();
+        let overloaded_eq = self.pool.ty_overloaded_eq();
         let ty = &expr.ty;
         match &expr.inner {
             Binds { binds, ret } => {
+                // the names bound here are only visible in `ret` and the following binds
+                let result = self.scoped(|this| {
+                    for decl in binds {
+                        this.infer_statement(decl)?;
+                    }
+                    this.unify(ret.ty(), *ty)?;
+                    this.infer_expr(ret)
+                });
+                // so are the datatypes declared here and their constructors
                 for decl in binds {
-                    self.infer_statement(decl)?;
+                    if let Declaration::Datatype { name, .. } = decl {
+                        self.close_local(name);
+                    }
+                }
+                result?;
+                for decl in binds {
+                    if let Declaration::Datatype { name, .. } = decl {
+                        if self.escapes(name, *ty) {
+                            return Err(TypeError::EscapingType {
+                                name: name.clone(),
+                                span: None,
+                            });
+                        }
+                    }
                 }
-                self.unify(ret.ty(), *ty)?;
-                self.infer_expr(ret)?;
                 Ok(())
             }
             BuiltinCall { fun, args } => {
@@ -345,7 +1251,20 @@ impl TyEnv {
                         self.unify(*ty, l.ty())?;
                         Ok(())
                     }
-                    Eq | Neq | Gt | Ge | Lt | Le => {
+                    Eq | Neq => {
+                        assert!(args.len() == 2);
+                        let l = &args[0];
+                        let r = &args[1];
+
+                        self.infer_expr(l)?;
+                        self.infer_expr(r)?;
+                        self.unify(l.ty(), r.ty())?;
+                        self.unify(l.ty(), overloaded_eq)?;
+                        self.unify(*ty, bool)?;
+                        self.equalities.push((l.ty(), expr.span));
+                        Ok(())
+                    }
+                    Gt | Ge | Lt | Le => {
                         assert!(args.len() == 2);
                         let l = &args[0];
                         let r = &args[1];
@@ -357,19 +1276,40 @@ impl TyEnv {
                         self.unify(*ty, bool)?;
                         Ok(())
                     }
-                    Div | Mod => {
+                    Div | Mod | Shl | Shr => {
+                        assert!(args.len() == 2);
+                        let l = &args[0];
+                        let r = &args[1];
+
+                        self.infer_expr(l)?;
+                        self.infer_expr(r)?;
+                        self.unify(l.ty(), r.ty())?;
+                        self.unify(l.ty(), overloaded_int)?;
+                        self.unify(*ty, l.ty())?;
+                        Ok(())
+                    }
+                    Andb | Orb | Xorb => {
                         assert!(args.len() == 2);
                         let l = &args[0];
                         let r = &args[1];
 
+                        self.infer_expr(l)?;
+                        self.infer_expr(r)?;
                         self.unify(l.ty(), int)?;
                         self.unify(r.ty(), int)?;
                         self.unify(*ty, int)?;
-                        self.infer_expr(l)?;
-                        self.infer_expr(r)?;
                         Ok(())
                     }
-                    Divf => {
+                    Notb => {
+                        assert!(args.len() == 1);
+                        let arg = &args[0];
+
+                        self.infer_expr(arg)?;
+                        self.unify(arg.ty(), int)?;
+                        self.unify(*ty, int)?;
+                        Ok(())
+                    }
+                    Divf | Modf | Pow => {
                         assert!(args.len() == 2);
                         let l = &args[0];
                         let r = &args[1];
@@ -381,24 +1321,153 @@ impl TyEnv {
                         self.infer_expr(r)?;
                         Ok(())
                     }
+                    Neg | Abs => {
+                        assert!(args.len() == 1);
+                        let arg = &args[0];
+
+                        self.infer_expr(arg)?;
+                        self.unify(arg.ty(), overloaded_num)?;
+                        self.unify(*ty, arg.ty())?;
+                        Ok(())
+                    }
+                    RealOfInt => {
+                        assert!(args.len() == 1);
+                        let arg = &args[0];
+
+                        self.infer_expr(arg)?;
+                        self.unify(arg.ty(), int)?;
+                        self.unify(*ty, real)?;
+                        Ok(())
+                    }
+                    Floor | Ceil | Round | Trunc => {
+                        assert!(args.len() == 1);
+                        let arg = &args[0];
+
+                        self.infer_expr(arg)?;
+                        self.unify(arg.ty(), real)?;
+                        self.unify(*ty, int)?;
+                        Ok(())
+                    }
+                    Ord => {
+                        assert!(args.len() == 1);
+                        let arg = &args[0];
+
+                        self.infer_expr(arg)?;
+                        let char = self.pool.ty_char();
+                        self.unify(arg.ty(), char)?;
+                        self.unify(*ty, int)?;
+                        Ok(())
+                    }
+                    Chr => {
+                        assert!(args.len() == 1);
+                        let arg = &args[0];
+
+                        self.infer_expr(arg)?;
+                        self.unify(arg.ty(), int)?;
+                        let char = self.pool.ty_char();
+                        self.unify(*ty, char)?;
+                        Ok(())
+                    }
+                    Sqrt => {
+                        assert!(args.len() == 1);
+                        let arg = &args[0];
+
+                        self.infer_expr(arg)?;
+                        self.unify(arg.ty(), real)?;
+                        self.unify(*ty, real)?;
+                        Ok(())
+                    }
+                    // ref : 'a -> 'a ref
+                    Ref => {
+                        assert!(args.len() == 1);
+                        let arg = &args[0];
+
+                        self.infer_expr(arg)?;
+                        let cell = self.pool.ty(Typing::Ref(arg.ty()));
+                        self.unify(*ty, cell)?;
+                        Ok(())
+                    }
+                    // ! : 'a ref -> 'a
+                    Deref => {
+                        assert!(args.len() == 1);
+                        let arg = &args[0];
+
+                        self.infer_expr(arg)?;
+                        let cell = self.pool.ty(Typing::Ref(*ty));
+                        self.unify(arg.ty(), cell)?;
+                        Ok(())
+                    }
+                    // := : 'a ref * 'a -> unit
+                    Assign => {
+                        assert!(args.len() == 2);
+                        let l = &args[0];
+                        let r = &args[1];
+
+                        self.infer_expr(l)?;
+                        self.infer_expr(r)?;
+                        let cell = self.pool.ty(Typing::Ref(r.ty()));
+                        self.unify(l.ty(), cell)?;
+                        let unit = self.pool.ty_unit();
+                        self.unify(*ty, unit)?;
+                        Ok(())
+                    }
                 }
             }
             ExternCall {
-                args, argty, retty, ..
+                module,
+                fun,
+                args,
+                argty,
+                retty,
             } => {
-                for (arg, argty) in args.into_iter().zip(argty) {
+                // the call is checked against both the types spelled out and the import
+                let mut signatures = Vec::new();
+                if !argty.is_empty() {
+                    signatures.push((argty.clone(), retty.clone()));
+                }
+                if let Some(signature) = self.imports.get(&(module.clone(), fun.clone())) {
+                    signatures.push(signature.clone());
+                }
+                if signatures.is_empty() {
+                    return Err(TypeError::UndeclaredImport {
+                        module: module.clone(),
+                        fun: fun.clone(),
+                        span: None,
+                    });
+                }
+                for arg in args {
                     self.infer_expr(arg)?;
-                    let argty = self.convert(argty.clone());
-                    self.give(arg.ty(), argty)?;
                 }
-                let retty = self.convert(retty.clone());
-                self.give(*ty, retty)?;
+                for (argty, retty) in signatures {
+                    if argty.len() != args.len() {
+                        return Err(TypeError::ImportArity {
+                            module: module.clone(),
+                            fun: fun.clone(),
+                            expected: argty.len(),
+                            actual: args.len(),
+                            span: None,
+                        });
+                    }
+                    for (arg, argty) in args.iter().zip(argty) {
+                        let argty = self.convert(argty);
+                        self.give(arg.ty(), argty)?;
+                    }
+                    let retty = match retty.as_slice() {
+                        [retty] => retty.clone(),
+                        // the results of a multi-value function
+                        rettys => Type::Tuple(rettys.to_vec()),
+                    };
+                    let retty = self.convert(retty);
+                    self.give(*ty, retty)?;
+                }
                 Ok(())
             }
             Fn { param, body } => {
                 let param_ty = self.pool.tyvar();
-                self.insert(param.clone(), param_ty);
-                self.infer_expr(body)?;
+                self.scoped(|this| {
+                    this.insert(param.clone(), param_ty);
+                    this.infer_expr(body)
+                })?;
                 self.give(*ty, Typing::Fun(param_ty, body.ty()))?;
                 Ok(())
             }
@@ -411,13 +1480,44 @@ impl TyEnv {
             Case { cond, clauses } => {
                 self.infer_expr(cond)?;
                 for (pat, branch) in clauses {
-                    self.infer_pat(pat)?;
-                    self.unify(pat.ty(), cond.ty())?;
-                    self.infer_expr(branch)?;
-                    self.unify(branch.ty(), *ty)?;
+                    self.scoped(|this| {
+                        this.infer_pat(pat)?;
+                        this.unify(pat.ty(), cond.ty())?;
+                        this.infer_expr(branch)?;
+                        this.unify(branch.ty(), *ty)
+                    })?;
                 }
                 Ok(())
             }
+            If { cond, then, else_ } => {
+                self.infer_expr(cond)?;
+                self.unify(cond.ty(), bool).map_err(|e| e.at(cond.span))?;
+                self.infer_expr(then)?;
+                self.infer_expr(else_)?;
+                self.unify(then.ty(), else_.ty())
+                    .map_err(|e| e.at(else_.span))?;
+                self.unify(*ty, then.ty())?;
+                Ok(())
+            }
+            AndAlso { l, r } | OrElse { l, r } => {
+                for e in &[l, r] {
+                    self.infer_expr(e)?;
+                    self.unify(e.ty(), bool).map_err(|err| err.at(e.span))?;
+                }
+                self.unify(*ty, bool)?;
+                Ok(())
+            }
+            Seq { exprs } => {
+                let (last, init) = exprs.split_last().expect("empty sequence");
+                let unit = self.pool.ty_unit();
+                for e in init {
+                    self.infer_expr(e)?;
+                    self.unify(e.ty(), unit).map_err(|err| err.at(e.span))?;
+                }
+                self.infer_expr(last)?;
+                self.unify(*ty, last.ty())?;
+                Ok(())
+            }
             Tuple { tuple } => {
                 self.infer_tuple(tuple, *ty)?;
                 Ok(())
@@ -434,6 +1534,15 @@ impl TyEnv {
                 self.infer_literal(value, *ty)?;
                 Ok(())
             }
+            Typed { expr, ty: annot } => {
+                self.infer_expr(expr)?;
+                self.check_type(annot)?;
+                let annot = self.convert(annot.clone());
+                let annot = self.pool.ty(annot);
+                self.unify(expr.ty(), annot)?;
+                self.unify(*ty, annot)?;
+                Ok(())
+            }
             D(d) => match *d {},
         }
     }
@@ -444,26 +1553,48 @@ impl TyEnv {
         arg: &Option<Box<CoreExpr<NodeId>>>,
         given: NodeId,
     ) -> Result<'r, ()> {
-        match self.get(&sym) {
-            Some(ty) => {
+        match self.instantiate_constructor(sym) {
+            Some((ty, arg_ty)) => {
                 self.unify(ty, given)?;
-                let arg_ty = self.symbol_table().get_argtype_of_constructor(sym);
-                if let (Some(arg), Some(arg_ty)) = (arg.clone(), arg_ty.cloned()) {
-                    self.infer_expr(&arg)?;
-                    let arg_typing = self.convert(arg_ty);
-                    let arg_ty_id = self.pool.ty(arg_typing);
-                    self.unify(arg.ty(), arg_ty_id)?;
+                if let (Some(arg), Some(arg_ty)) = (arg, arg_ty) {
+                    self.infer_expr(arg)?;
+                    self.unify(arg.ty(), arg_ty)?;
                 }
                 Ok(())
             }
-            None => Err(TypeError::FreeVar),
+            None => Err(self.free_var(sym)),
         }
     }
 
     fn infer_symbol<'b, 'r>(&'b mut self, sym: &Symbol, given: NodeId) -> Result<'r, ()> {
         match self.get(&sym) {
-            Some(t) => self.unify(t, given),
-            None => Err(TypeError::FreeVar),
+            Some(scheme) => {
+                let t = self.instantiate(&scheme);
+                self.unify(t, given)
+            }
+            None => Err(self.free_var(sym)),
+        }
+    }
+
+    // the error for the unbound `sym`, suggesting the closest name in scope
+    fn free_var<'r>(&self, sym: &Symbol) -> TypeError<'r> {
+        let name = sym.0.as_str();
+        // allow a typo per 3 characters, at most 2
+        let max_distance = (name.chars().count() / 3).max(1).min(2);
+        let mut candidates = self
+            .env
+            .keys()
+            .map(|candidate| (edit_distance(name, candidate.0.as_str()), candidate))
+            .filter(|(distance, _)| 0 < *distance && *distance <= max_distance)
+            .collect::<Vec<_>>();
+        // the order of the hash maps is not stable
+        candidates.sort_by_key(|(distance, candidate)| (*distance, candidate.0.as_str()));
+        TypeError::FreeVar {
+            name: sym.clone(),
+            suggestion: candidates
+                .first()
+                .map(|(_, candidate)| (*candidate).clone()),
+            span: None,
         }
     }
 
@@ -471,8 +1602,10 @@ impl TyEnv {
         use crate::prim::Literal::*;
         let ty = match lit {
             Int(_) => self.pool.ty_int(),
+            Word(_) => self.pool.ty_word(),
             Real(_) => self.pool.ty_real(),
             Char(_) => self.pool.ty_char(),
+            String(_) => self.pool.ty_string(),
         };
         self.unify(given, ty)?;
         Ok(())
@@ -491,6 +1624,10 @@ impl TyEnv {
     }
 
     fn infer_pat<'b, 'r>(&'b mut self, pat: &Pattern<NodeId>) -> Result<'r, ()> {
+        self.infer_pat_kind(pat).map_err(|e| e.at(pat.span))
+    }
+
+    fn infer_pat_kind<'b, 'r>(&'b mut self, pat: &Pattern<NodeId>) -> Result<'r, ()> {
         use self::PatternKind::*;
         let ty = &pat.ty();
         match &pat.inner {
@@ -500,39 +1637,72 @@ impl TyEnv {
             Char { value } => {
                 self.infer_char(value, *ty)?;
             }
+            Str { .. } => {
+                let string = self.pool.ty_string();
+                self.unify(*ty, string)?;
+            }
             Constructor { arg, name } => {
-                let type_name = self
-                    .symbol_table()
-                    .get_datatype_of_constructor(name)
-                    .expect("internal error: typing")
-                    .clone();
-                self.give(*ty, Typing::Datatype(type_name.clone()))?;
+                let (datatype, arg_ty) = match self.instantiate_constructor(name) {
+                    Some(instance) => instance,
+                    None => return Err(self.free_var(name)),
+                };
+                self.unify(*ty, datatype)?;
                 if let Some(arg) = arg {
                     self.infer_pat(arg)?;
-                    let arg_ty = self
-                        .symbol_table()
-                        .get_type(&type_name)
-                        .expect("internal error: typing")
-                        .constructors
-                        .iter()
-                        .find(|(cname, _)| cname == name)
-                        .map(|(_, arg)| arg.clone())
-                        .expect("internal error: typing")
-                        .expect("internal error: typing");
-                    let arg_typing = self.convert(arg_ty);
-                    let arg_ty_id = self.pool.ty(arg_typing);
-                    self.unify(arg.ty(), arg_ty_id)?;
+                    let arg_ty = arg_ty.expect("internal error: typing");
+                    self.unify(arg.ty(), arg_ty)?;
                 }
             }
             Tuple { tuple } => {
                 for t in tuple {
                     self.infer_pat(t)?;
                 }
-                let tuple_ty = self
-                    .pool
-                    .ty(Typing::Tuple(tuple.iter().map(|pat| pat.ty()).collect()));
+                let tuple_ty = if tuple.is_empty() {
+                    self.pool.ty_unit()
+                } else {
+                    self.pool
+                        .ty(Typing::Tuple(tuple.iter().map(|pat| pat.ty()).collect()))
+                };
                 self.unify(*ty, tuple_ty)?;
             }
+            As { pattern, .. } => {
+                self.infer_pat(pattern)?;
+                self.unify(*ty, pattern.ty())?;
+            }
+            Or { alternatives } => {
+                for alternative in alternatives {
+                    self.infer_pat(alternative)?;
+                    self.unify(*ty, alternative.ty())?;
+                }
+                // the alternatives must bind the same names with the same types
+                if let Some((first, rest)) = alternatives.split_first() {
+                    let first_binds = first.binds();
+                    let binds = first_binds.iter().cloned().collect::<HashMap<_, _>>();
+                    for alternative in rest {
+                        let alt_binds = alternative.binds();
+                        // in the order of the pattern, to report the same name every time
+                        for (name, _) in &first_binds {
+                            if alt_binds.iter().all(|(n, _)| n != name) {
+                                return Err(TypeError::OrPatternBinds {
+                                    name: (*name).clone(),
+                                    span: Some(alternative.span),
+                                });
+                            }
+                        }
+                        for (name, ty) in &alt_binds {
+                            match binds.get(name) {
+                                Some(bound) => self.unify(**bound, **ty)?,
+                                None => {
+                                    return Err(TypeError::OrPatternBinds {
+                                        name: (*name).clone(),
+                                        span: Some(alternative.span),
+                                    })
+                                }
+                            }
+                        }
+                    }
+                }
+            }
             Wildcard { .. } | Variable { .. } => (),
         };
         for (name, ty) in pat.binds() {
@@ -547,6 +1717,10 @@ impl TyEnv {
         given: NodeId,
     ) -> Result<'r, ()> {
         use std::iter;
+        if tuple.is_empty() {
+            let unit = self.pool.ty_unit();
+            return self.unify(unit, given);
+        }
         let tys = iter::repeat_with(|| self.pool.tyvar())
             .take(tuple.len())
             .collect::<Vec<_>>();
@@ -561,7 +1735,12 @@ impl TyEnv {
     }
 
     fn unify<'b, 'r>(&'b mut self, id1: NodeId, id2: NodeId) -> Result<'r, ()> {
-        self.pool.try_unify_with(id1, id2, try_unify).map(|_| ())
+        let default_num = self.pool.default_num;
+        self.pool
+            .try_unify_with(id1, id2, |pool, t1, t2| {
+                try_unify(pool, default_num, t1, t2)
+            })
+            .map(|_| ())
     }
 
     fn give<'b, 'r>(&'b mut self, id1: NodeId, ty: Typing) -> Result<'r, ()> {
@@ -571,18 +1750,26 @@ impl TyEnv {
 }
 
 use crate::pass::Pass;
+/// The overloaded types left unresolved after the inference,
+/// like the type of `x` in `fn x => x + x`,
+/// are defaulted to `config.default_num`.
 impl<'a> Pass<(SymbolTable, UntypedCore), TypeError<'a>> for Typer {
     type Target = (SymbolTable, TypedCore);
 
     fn trans<'b>(
         &'b mut self,
         (symbol_table, ast): (SymbolTable, UntypedCore),
-        _: &Config,
+        config: &Config,
     ) -> Result<'a, Self::Target> {
-        let mut pass = self.generate_pass(symbol_table);
+        let mut pass = self.generate_pass(symbol_table, config.default_num);
+        pass.collect_errors = config.collect_type_errors;
         let mut typing_ast = pass.pool.typing_ast(ast);
         pass.infer(&mut typing_ast)?;
+        if config.dump_unification {
+            pass.pool.dump_classes();
+        }
         let typed_ast = pass.pool.typed_ast(typing_ast);
+        check_exports(&typed_ast)?;
 
         let symbol_table = pass.into_symbol_table();
         Ok((symbol_table, typed_ast))
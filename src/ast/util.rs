@@ -10,8 +10,20 @@ pub trait Traverse<Ty> {
     fn traverse_statement(&mut self, decl: &mut CoreDeclaration<Ty>) {
         use Declaration::*;
         match decl {
-            Datatype { name, constructors } => self.traverse_datatype(name, constructors),
+            Datatype {
+                name,
+                params,
+                constructors,
+            } => self.traverse_datatype(name, params, constructors),
             Val { rec, pattern, expr } => self.traverse_val(rec, pattern, expr),
+            ValRecGroup { binds } => self.traverse_val_rec_group(binds),
+            Import {
+                module,
+                fun,
+                argty,
+                retty,
+            } => self.traverse_import(module, fun, argty, retty),
+            Export { name, value } => self.traverse_export(name, value),
             D(_) => (),
         }
     }
@@ -19,6 +31,7 @@ pub trait Traverse<Ty> {
     fn traverse_datatype(
         &mut self,
         _name: &mut Symbol,
+        _params: &mut Vec<u64>,
         _constructors: &mut Vec<(Symbol, Option<Type>)>,
     ) {
     }
@@ -33,6 +46,24 @@ pub trait Traverse<Ty> {
         self.traverse_pattern(pattern)
     }
 
+    fn traverse_val_rec_group(&mut self, binds: &mut Vec<(Pattern<Ty>, CoreExpr<Ty>)>) {
+        for (pattern, expr) in binds.iter_mut() {
+            self.traverse_expr(expr);
+            self.traverse_pattern(pattern)
+        }
+    }
+
+    fn traverse_import(
+        &mut self,
+        _module: &mut String,
+        _fun: &mut String,
+        _argty: &mut Vec<Type>,
+        _retty: &mut Vec<Type>,
+    ) {
+    }
+
+    fn traverse_export(&mut self, _name: &mut String, _value: &mut Symbol) {}
+
     fn traverse_expr(&mut self, expr: &mut CoreExpr<Ty>) {
         use crate::ast::ExprKind::*;
         match &mut expr.inner {
@@ -48,10 +79,15 @@ pub trait Traverse<Ty> {
             Fn { param, body } => self.traverse_fn(param, body),
             App { fun, arg } => self.traverse_app(fun, arg),
             Case { cond, clauses } => self.traverse_case(cond, clauses),
+            If { cond, then, else_ } => self.traverse_if(cond, then, else_),
+            AndAlso { l, r } => self.traverse_andalso(l, r),
+            OrElse { l, r } => self.traverse_orelse(l, r),
+            Seq { exprs } => self.traverse_seq(exprs),
             Tuple { tuple } => self.traverse_tuple(tuple),
             Constructor { arg, name } => self.traverse_constructor(arg, name),
             Symbol { name } => self.traverse_sym(name),
             Literal { value } => self.traverse_lit(value),
+            Typed { expr, ty } => self.traverse_typed(expr, ty),
             D(_) => (),
         }
     }
@@ -78,7 +114,7 @@ pub trait Traverse<Ty> {
         _fun: &mut String,
         args: &mut Vec<CoreExpr<Ty>>,
         _argty: &mut Vec<Type>,
-        _retty: &mut Type,
+        _retty: &mut Vec<Type>,
     ) {
         for arg in args {
             self.traverse_expr(arg)
@@ -106,6 +142,33 @@ pub trait Traverse<Ty> {
         }
     }
 
+    fn traverse_if(
+        &mut self,
+        cond: &mut Box<CoreExpr<Ty>>,
+        then: &mut Box<CoreExpr<Ty>>,
+        else_: &mut Box<CoreExpr<Ty>>,
+    ) {
+        self.traverse_expr(cond);
+        self.traverse_expr(then);
+        self.traverse_expr(else_);
+    }
+
+    fn traverse_andalso(&mut self, l: &mut Box<CoreExpr<Ty>>, r: &mut Box<CoreExpr<Ty>>) {
+        self.traverse_expr(l);
+        self.traverse_expr(r);
+    }
+
+    fn traverse_orelse(&mut self, l: &mut Box<CoreExpr<Ty>>, r: &mut Box<CoreExpr<Ty>>) {
+        self.traverse_expr(l);
+        self.traverse_expr(r);
+    }
+
+    fn traverse_seq(&mut self, exprs: &mut Vec<CoreExpr<Ty>>) {
+        for e in exprs.iter_mut() {
+            self.traverse_expr(e)
+        }
+    }
+
     fn traverse_tuple(&mut self, tuple: &mut Vec<CoreExpr<Ty>>) {
         for t in tuple.iter_mut() {
             self.traverse_expr(t)
@@ -121,20 +184,28 @@ pub trait Traverse<Ty> {
 
     fn traverse_lit(&mut self, _value: &mut Literal) {}
 
+    fn traverse_typed(&mut self, expr: &mut Box<CoreExpr<Ty>>, _ty: &mut Type) {
+        self.traverse_expr(expr)
+    }
+
     fn traverse_pattern(&mut self, pattern: &mut Pattern<Ty>) {
         use PatternKind::*;
         match &mut pattern.inner {
             Constant { value } => self.traverse_pat_constant(value),
             Char { value } => self.traverse_pat_char(value),
+            Str { value } => self.traverse_pat_str(value),
             Constructor { name, arg } => self.traverse_pat_constructor(name, arg),
             Tuple { tuple } => self.traverse_pat_tuple(tuple),
             Variable { name } => self.traverse_pat_variable(name),
             Wildcard {} => self.traverse_pat_wildcard(),
+            As { name, pattern } => self.traverse_pat_as(name, pattern),
+            Or { alternatives } => self.traverse_pat_or(alternatives),
         }
     }
 
     fn traverse_pat_constant(&mut self, _value: &mut i64) {}
     fn traverse_pat_char(&mut self, _value: &mut u32) {}
+    fn traverse_pat_str(&mut self, _value: &mut String) {}
     fn traverse_pat_constructor(
         &mut self,
         _name: &mut Symbol,
@@ -144,6 +215,8 @@ pub trait Traverse<Ty> {
     fn traverse_pat_tuple(&mut self, _tuple: &mut Vec<Pattern<Ty>>) {}
     fn traverse_pat_variable(&mut self, _value: &mut Symbol) {}
     fn traverse_pat_wildcard(&mut self) {}
+    fn traverse_pat_as(&mut self, _name: &mut Symbol, _pattern: &mut Box<Pattern<Ty>>) {}
+    fn traverse_pat_or(&mut self, _alternatives: &mut Vec<Pattern<Ty>>) {}
 }
 
 pub trait Transform<Ty> {
@@ -158,8 +231,20 @@ pub trait Transform<Ty> {
     fn transform_statement(&mut self, decl: CoreDeclaration<Ty>) -> CoreDeclaration<Ty> {
         use Declaration::*;
         match decl {
-            Datatype { name, constructors } => self.transform_datatype(name, constructors),
+            Datatype {
+                name,
+                params,
+                constructors,
+            } => self.transform_datatype(name, params, constructors),
             Val { rec, pattern, expr } => self.transform_val(rec, pattern, expr),
+            ValRecGroup { binds } => self.transform_val_rec_group(binds),
+            Import {
+                module,
+                fun,
+                argty,
+                retty,
+            } => self.transform_import(module, fun, argty, retty),
+            Export { name, value } => self.transform_export(name, value),
             D(d) => match d {},
         }
     }
@@ -167,9 +252,14 @@ pub trait Transform<Ty> {
     fn transform_datatype(
         &mut self,
         name: Symbol,
+        params: Vec<u64>,
         constructors: Vec<(Symbol, Option<Type>)>,
     ) -> CoreDeclaration<Ty> {
-        Declaration::Datatype { name, constructors }
+        Declaration::Datatype {
+            name,
+            params,
+            constructors,
+        }
     }
 
     fn transform_val(
@@ -185,6 +275,37 @@ pub trait Transform<Ty> {
         }
     }
 
+    fn transform_val_rec_group(
+        &mut self,
+        binds: Vec<(Pattern<Ty>, CoreExpr<Ty>)>,
+    ) -> CoreDeclaration<Ty> {
+        Declaration::ValRecGroup {
+            binds: binds
+                .into_iter()
+                .map(|(pattern, expr)| (self.transform_pattern(pattern), self.transform_expr(expr)))
+                .collect(),
+        }
+    }
+
+    fn transform_import(
+        &mut self,
+        module: String,
+        fun: String,
+        argty: Vec<Type>,
+        retty: Vec<Type>,
+    ) -> CoreDeclaration<Ty> {
+        Declaration::Import {
+            module,
+            fun,
+            argty,
+            retty,
+        }
+    }
+
+    fn transform_export(&mut self, name: String, value: Symbol) -> CoreDeclaration<Ty> {
+        Declaration::Export { name, value }
+    }
+
     fn transform_expr(&mut self, mut expr: CoreExpr<Ty>) -> CoreExpr<Ty> {
         use crate::ast::ExprKind::*;
         expr.inner = match expr.inner {
@@ -200,10 +321,15 @@ pub trait Transform<Ty> {
             Fn { param, body } => self.transform_fn(param, body),
             App { fun, arg } => self.transform_app(fun, arg),
             Case { cond, clauses } => self.transform_case(cond, clauses),
+            If { cond, then, else_ } => self.transform_if(cond, then, else_),
+            AndAlso { l, r } => self.transform_andalso(l, r),
+            OrElse { l, r } => self.transform_orelse(l, r),
+            Seq { exprs } => self.transform_seq(exprs),
             Tuple { tuple } => self.transform_tuple(tuple),
             Constructor { arg, name } => self.transform_constructor(arg, name),
             Symbol { name } => self.transform_symbol(name),
             Literal { value } => self.transform_literal(value),
+            Typed { expr, ty } => self.transform_typed(expr, ty),
             D(d) => match d {},
         };
         expr
@@ -238,7 +364,7 @@ pub trait Transform<Ty> {
         fun: String,
         args: Vec<CoreExpr<Ty>>,
         argty: Vec<Type>,
-        retty: Type,
+        retty: Vec<Type>,
     ) -> CoreExprKind<Ty> {
         ExprKind::ExternCall {
             module,
@@ -284,6 +410,43 @@ pub trait Transform<Ty> {
         }
     }
 
+    fn transform_if(
+        &mut self,
+        cond: Box<CoreExpr<Ty>>,
+        then: Box<CoreExpr<Ty>>,
+        else_: Box<CoreExpr<Ty>>,
+    ) -> CoreExprKind<Ty> {
+        ExprKind::If {
+            cond: self.transform_expr(*cond).boxed(),
+            then: self.transform_expr(*then).boxed(),
+            else_: self.transform_expr(*else_).boxed(),
+        }
+    }
+
+    fn transform_andalso(
+        &mut self,
+        l: Box<CoreExpr<Ty>>,
+        r: Box<CoreExpr<Ty>>,
+    ) -> CoreExprKind<Ty> {
+        ExprKind::AndAlso {
+            l: self.transform_expr(*l).boxed(),
+            r: self.transform_expr(*r).boxed(),
+        }
+    }
+
+    fn transform_orelse(&mut self, l: Box<CoreExpr<Ty>>, r: Box<CoreExpr<Ty>>) -> CoreExprKind<Ty> {
+        ExprKind::OrElse {
+            l: self.transform_expr(*l).boxed(),
+            r: self.transform_expr(*r).boxed(),
+        }
+    }
+
+    fn transform_seq(&mut self, exprs: Vec<CoreExpr<Ty>>) -> CoreExprKind<Ty> {
+        ExprKind::Seq {
+            exprs: exprs.into_iter().map(|e| self.transform_expr(e)).collect(),
+        }
+    }
+
     fn transform_tuple(&mut self, tuple: Vec<CoreExpr<Ty>>) -> CoreExprKind<Ty> {
         ExprKind::Tuple {
             tuple: tuple.into_iter().map(|t| self.transform_expr(t)).collect(),
@@ -308,15 +471,25 @@ pub trait Transform<Ty> {
         ExprKind::Literal { value }
     }
 
+    fn transform_typed(&mut self, expr: Box<CoreExpr<Ty>>, ty: Type) -> CoreExprKind<Ty> {
+        ExprKind::Typed {
+            expr: self.transform_expr(*expr).boxed(),
+            ty,
+        }
+    }
+
     fn transform_pattern(&mut self, mut pattern: Pattern<Ty>) -> Pattern<Ty> {
         use PatternKind::*;
         pattern.inner = match pattern.inner {
             Constant { value } => self.transform_pat_constant(value),
             Char { value } => self.transform_pat_char(value),
+            Str { value } => self.transform_pat_str(value),
             Constructor { arg, name } => self.transform_pat_constructor(arg, name),
             Tuple { tuple } => self.transform_pat_tuple(tuple),
             Variable { name } => self.transform_pat_variable(name),
             Wildcard {} => self.transform_pat_wildcard(),
+            As { name, pattern } => self.transform_pat_as(name, pattern),
+            Or { alternatives } => self.transform_pat_or(alternatives),
         };
         pattern
     }
@@ -329,6 +502,10 @@ pub trait Transform<Ty> {
         PatternKind::Char { value }
     }
 
+    fn transform_pat_str(&mut self, value: String) -> PatternKind<Ty> {
+        PatternKind::Str { value }
+    }
+
     fn transform_pat_constructor(
         &mut self,
         arg: Option<Box<Pattern<Ty>>>,
@@ -356,4 +533,20 @@ pub trait Transform<Ty> {
     fn transform_pat_wildcard(&mut self) -> PatternKind<Ty> {
         PatternKind::Wildcard {}
     }
+
+    fn transform_pat_as(&mut self, name: Symbol, pattern: Box<Pattern<Ty>>) -> PatternKind<Ty> {
+        PatternKind::As {
+            name,
+            pattern: Box::new(self.transform_pattern(*pattern)),
+        }
+    }
+
+    fn transform_pat_or(&mut self, alternatives: Vec<Pattern<Ty>>) -> PatternKind<Ty> {
+        PatternKind::Or {
+            alternatives: alternatives
+                .into_iter()
+                .map(|pat| self.transform_pattern(pat))
+                .collect(),
+        }
+    }
 }
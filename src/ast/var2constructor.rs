@@ -61,10 +61,12 @@ impl Transform<()> for VarToConstructorPass {
                     param: sym.clone(),
                     body: Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Constructor {
                             arg: Some(
                                 Expr {
                                     ty: (),
+                                    span: Span::default(),
                                     inner: ExprKind::Symbol { name: sym },
                                 }
                                 .boxed(),
@@ -82,6 +84,28 @@ impl Transform<()> for VarToConstructorPass {
         }
     }
 
+    fn transform_app(
+        &mut self,
+        fun: Box<UntypedCoreExpr>,
+        arg: Box<UntypedCoreExpr>,
+    ) -> UntypedCoreExprKind {
+        match fun.inner {
+            // apply constructors directly rather than through the wrapper function
+            ExprKind::Symbol { ref name }
+                if self.is_constructor(name) && self.arg_type(name).is_some() =>
+            {
+                ExprKind::Constructor {
+                    arg: Some(self.transform_expr(*arg).boxed()),
+                    name: name.clone(),
+                }
+            }
+            _ => ExprKind::App {
+                fun: self.transform_expr(*fun).boxed(),
+                arg: self.transform_expr(*arg).boxed(),
+            },
+        }
+    }
+
     fn transform_pat_variable(&mut self, name: Symbol) -> UntypedPatternKind {
         if self.is_constructor(&name) {
             PatternKind::Constructor { arg: None, name }
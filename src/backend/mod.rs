@@ -1,3 +1,3 @@
 pub mod wasm;
-pub use self::wasm::LIR2WASM;
+pub use self::wasm::{Wasm, LIR2WASM};
 mod pp;
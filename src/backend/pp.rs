@@ -1,3 +1,4 @@
+use super::Wasm;
 use crate::util::PP;
 use std::io;
 use wasm::Module;
@@ -8,3 +9,10 @@ impl PP for Module {
         Ok(())
     }
 }
+
+impl PP for Wasm {
+    fn pp<W: io::Write>(&self, w: &mut W, _: usize) -> io::Result<()> {
+        writeln!(w, "{:#?}", self)?;
+        Ok(())
+    }
+}
@@ -30,6 +30,103 @@ fn lty_to_valuetype_opt(t: &lir::LTy) -> Option<ValueType> {
     }
 }
 
+// the encoding of the value type in the binary format
+fn lty_to_code_opt(t: &lir::LTy) -> Option<u8> {
+    use crate::lir::LTy::*;
+    match *t {
+        Unit => None,
+        I32 | U32 | FPtr | Ptr => Some(0x7f),
+        I64 | U64 => Some(0x7e),
+        F32 => Some(0x7d),
+        F64 => Some(0x7c),
+    }
+}
+
+fn read_uleb(code: &[u8], pos: &mut usize) -> u32 {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = code[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+fn write_uleb(buf: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+/// the module generated, and the types of the imported functions returning several results.
+/// `FuncType` of the assembler has at most one result,
+/// so those types are added without results and are given them by `dump`
+#[derive(Debug)]
+pub struct Wasm {
+    pub module: Module,
+    // the type indices and the encoded function types
+    multi_value: Vec<(u32, Vec<u8>)>,
+}
+
+impl Wasm {
+    pub fn dump(&self, buf: &mut Vec<u8>) {
+        let mut code = Vec::new();
+        self.module.dump(&mut code);
+        if self.multi_value.is_empty() {
+            buf.extend(code);
+            return;
+        }
+        // the magic number and the version
+        buf.extend_from_slice(&code[..8]);
+        let mut pos = 8;
+        while pos < code.len() {
+            let id = code[pos];
+            pos += 1;
+            let size = read_uleb(&code, &mut pos) as usize;
+            let mut body = code[pos..pos + size].to_vec();
+            pos += size;
+            // the type section
+            if id == 1 {
+                body = self.patch_types(&body);
+            }
+            buf.push(id);
+            write_uleb(buf, body.len() as u32);
+            buf.extend(body);
+        }
+    }
+
+    fn patch_types(&self, body: &[u8]) -> Vec<u8> {
+        let mut pos = 0;
+        let count = read_uleb(body, &mut pos);
+        let mut ret = Vec::new();
+        write_uleb(&mut ret, count);
+        for index in 0..count {
+            let start = pos;
+            // the form, the params and the results
+            pos += 1;
+            let nparams = read_uleb(body, &mut pos) as usize;
+            pos += nparams;
+            let nresults = read_uleb(body, &mut pos) as usize;
+            pos += nresults;
+            match self.multi_value.iter().find(|(i, _)| *i == index) {
+                Some((_, ty)) => ret.extend_from_slice(ty),
+                None => ret.extend_from_slice(&body[start..pos]),
+            }
+        }
+        ret
+    }
+}
+
 fn lty_to_valuetype(t: &lir::LTy) -> ValueType {
     lty_to_valuetype_opt(t).unwrap_or(ValueType::I32)
 }
@@ -61,31 +158,62 @@ impl LIR2WASM {
         Self
     }
 
-    fn generate_pass(&mut self, extern_types: lir::ExternTypes) -> LIR2WASMPass {
+    fn generate_pass(
+        &mut self,
+        extern_types: lir::ExternTypes,
+    ) -> (LIR2WASMPass, Vec<(u32, Vec<u8>)>) {
         let mut md = ModuleBuilder::new();
         let mut extern_functions = HashMap::new();
         let mut function_type_table = HashMap::new();
-        for ((module, name), (paramtys, retty)) in extern_types {
-            let ftype = FuncType {
-                params: paramtys
-                    .into_iter()
-                    .map(|ty| lty_to_valuetype(&ty))
-                    .collect(),
-                ret: lty_to_valuetype_opt(&retty),
-            };
-            let tyind = if !function_type_table.contains_key(&ftype) {
-                let tyi = md.add_type(ftype.clone());
-                function_type_table.insert(ftype, tyi.clone());
-                tyi
+        let mut multi_value = Vec::new();
+        // the types are the first ones added to the module
+        let mut ntypes = 0;
+        for ((module, name), (paramtys, rettys)) in extern_types {
+            let params = paramtys
+                .iter()
+                .map(|ty| lty_to_valuetype(ty))
+                .collect::<Vec<_>>();
+            let codes = rettys
+                .iter()
+                .filter_map(lty_to_code_opt)
+                .collect::<Vec<_>>();
+            let tyind = if codes.len() > 1 {
+                // a type of its own, whose results are written by `Wasm::dump`
+                let mut ty = vec![0x60];
+                write_uleb(&mut ty, paramtys.len() as u32);
+                ty.extend(
+                    paramtys
+                        .iter()
+                        .map(|ty| lty_to_code_opt(ty).unwrap_or(0x7f)),
+                );
+                write_uleb(&mut ty, codes.len() as u32);
+                ty.extend(codes);
+                multi_value.push((ntypes, ty));
+                ntypes += 1;
+                md.add_type(FuncType { params, ret: None })
             } else {
-                function_type_table[&ftype].clone()
+                let ftype = FuncType {
+                    params,
+                    ret: rettys.iter().filter_map(lty_to_valuetype_opt).next(),
+                };
+                if !function_type_table.contains_key(&ftype) {
+                    let tyi = md.add_type(ftype.clone());
+                    ntypes += 1;
+                    function_type_table.insert(ftype, tyi.clone());
+                    tyi
+                } else {
+                    function_type_table[&ftype].clone()
+                }
             };
 
             let funind = md.import(module.clone(), name.clone(), tyind);
             let fun = md.function_index_of(funind).unwrap();
             extern_functions.insert((module, name), fun);
         }
-        LIR2WASMPass::new(md, extern_functions, function_type_table)
+        (
+            LIR2WASMPass::new(md, extern_functions, function_type_table),
+            multi_value,
+        )
     }
 }
 
@@ -209,9 +337,11 @@ impl LIR2WASMPass {
         use crate::lir::Value::*;
         let ftype = fun_type(&f);
         let lir::Function {
+            name,
             nparams,
             regs,
             body,
+            export,
             ..
         } = f;
         let mut tys = regs
@@ -315,6 +445,48 @@ impl LIR2WASMPass {
                                         .i32_rem_s()
                                         .set_local(reg!(reg1))
                                 }
+                                ShlI32(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i32_shl()
+                                        .set_local(reg!(reg1))
+                                }
+                                ShrI32(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i32_shr_s()
+                                        .set_local(reg!(reg1))
+                                }
+                                ShrU32(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i32_shr_u()
+                                        .set_local(reg!(reg1))
+                                }
+                                AndI32(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i32_and()
+                                        .set_local(reg!(reg1))
+                                }
+                                OrI32(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i32_or()
+                                        .set_local(reg!(reg1))
+                                }
+                                XorI32(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i32_xor()
+                                        .set_local(reg!(reg1))
+                                }
                                 ModU32(reg1, reg2, reg3) => {
                                     cb = cb
                                         .get_local(reg!(reg2))
@@ -674,6 +846,34 @@ impl LIR2WASMPass {
                                         .f64_div()
                                         .set_local(reg!(reg1))
                                 }
+                                AbsF64(reg1, reg2) => {
+                                    cb = cb.get_local(reg!(reg2)).f64_abs().set_local(reg!(reg1))
+                                }
+                                FloorF64(reg1, reg2) => {
+                                    cb = cb.get_local(reg!(reg2)).f64_floor().set_local(reg!(reg1))
+                                }
+                                CeilF64(reg1, reg2) => {
+                                    cb = cb.get_local(reg!(reg2)).f64_ceil().set_local(reg!(reg1))
+                                }
+                                NearestF64(reg1, reg2) => {
+                                    cb =
+                                        cb.get_local(reg!(reg2)).f64_nearest().set_local(reg!(reg1))
+                                }
+                                SqrtF64(reg1, reg2) => {
+                                    cb = cb.get_local(reg!(reg2)).f64_sqrt().set_local(reg!(reg1))
+                                }
+                                ConvertI32F64(reg1, reg2) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .f64_convert_s_i32()
+                                        .set_local(reg!(reg1))
+                                }
+                                TruncF64I32(reg1, reg2) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .i32_trunc_s_f64()
+                                        .set_local(reg!(reg1))
+                                }
                                 EqF64(reg1, reg2, reg3) => {
                                     cb = cb
                                         .get_local(reg!(reg2))
@@ -802,15 +1002,17 @@ impl LIR2WASMPass {
                                         cb = cb.set_local(reg!(reg));
                                     }
                                 }
-                                ExternCall(reg, module, fun, args) => {
+                                ExternCall(regs, module, fun, args) => {
                                     for arg in args.iter() {
                                         cb = cb.get_local(reg!(arg))
                                     }
                                     let fun = self.extern_functions[&(module.clone(), fun.clone())];
                                     cb = cb.call(fun);
-                                    let ret = lty_to_valuetype_opt(&reg.0);
-                                    if let Some(_) = ret {
-                                        cb = cb.set_local(reg!(reg));
+                                    // the last result is on the top of the stack
+                                    for reg in regs.iter().rev() {
+                                        if let Some(_) = lty_to_valuetype_opt(&reg.0) {
+                                            cb = cb.set_local(reg!(reg));
+                                        }
                                     }
                                 }
                                 Jump(label) => {
@@ -836,6 +1038,10 @@ impl LIR2WASMPass {
         let (_, body) = fb.build();
         // use calculated type index,
         NewFunction::new_function(&mut self.md, self.function_type_table[&ftype], body);
+        if let Some(export) = export {
+            let index = self.function_index(&name);
+            self.md.export(export, index);
+        }
     }
 
     /// allocate block and loop scopes for jump -> break transformation.
@@ -1057,14 +1263,17 @@ impl LIR2WASMPass {
 }
 
 impl<E> Pass<(lir::ExternTypes, lir::LIR), E> for LIR2WASM {
-    type Target = Module;
+    type Target = Wasm;
 
     fn trans(
         &mut self,
         (extern_types, lir): (lir::ExternTypes, lir::LIR),
         _: &Config,
     ) -> ::std::result::Result<Self::Target, E> {
-        let mut pass = self.generate_pass(extern_types);
-        Ok(pass.trans_lir(lir))
+        let (mut pass, multi_value) = self.generate_pass(extern_types);
+        Ok(Wasm {
+            module: pass.trans_lir(lir),
+            multi_value,
+        })
     }
 }
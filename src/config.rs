@@ -1,6 +1,231 @@
+use crate::util::PpConfig;
 use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug)]
 pub struct Config {
     pub pretty_print_ir: HashSet<String>,
+    pub pp: PpConfig,
+    pub default_num: DefaultNum,
+    /// print the equivalence classes of the types after the inference
+    pub dump_unification: bool,
+    /// functions smaller than this are inlined even if they use the parameter more than once
+    pub inline_threshold: usize,
+    /// record how long each pass takes, returned by `compile`
+    pub time_passes: bool,
+    /// print the HIR after these passes
+    pub dump_hir_after: Vec<PassName>,
+    /// the passes over HIR to run, in order
+    pub pipeline: Vec<PassName>,
+    /// 0, 1 or 2. the passes in `pipeline` above this level are skipped. see `PassName::opt_level`
+    pub opt_level: u8,
+    /// check the consistency of the HIR after the passes over it
+    pub verify_hir: bool,
+    /// report the type errors of all the toplevel declarations, and the mismatches
+    /// of the independent subexpressions in them, instead of stopping at the first one
+    pub collect_type_errors: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            pretty_print_ir: HashSet::new(),
+            pp: PpConfig::default(),
+            default_num: DefaultNum::default(),
+            dump_unification: false,
+            inline_threshold: 0,
+            time_passes: false,
+            dump_hir_after: Vec::new(),
+            pipeline: PassName::default_pipeline(),
+            opt_level: 2,
+            verify_hir: false,
+            collect_type_errors: false,
+        }
+    }
+}
+
+// the passes the translation to MIR relies on
+const REQUIRED_PASSES: &[PassName] = &[
+    PassName::FlatExpr,
+    PassName::FlatLet,
+    PassName::UnnestFunc,
+    PassName::ForceClosure,
+    PassName::DecisionTree,
+];
+
+// the passes that expect another one to have run before them
+const PREREQUISITES: &[(PassName, PassName)] = &[
+    (PassName::FlatLet, PassName::FlatExpr),
+    (PassName::Cse, PassName::FlatExpr),
+    (PassName::UnnestFunc, PassName::FlatExpr),
+    (PassName::ForceClosure, PassName::UnnestFunc),
+    (PassName::PruneCaptures, PassName::UnnestFunc),
+    (PassName::DecisionTree, PassName::FlatExpr),
+];
+
+// the passes that must run before another one. these rewrite the symbols by `transform_sym`,
+// which misses the environments of the closures `UnnestFunc` makes
+const SUCCESSORS: &[(PassName, PassName)] = &[
+    (PassName::BetaReduce, PassName::UnnestFunc),
+    (PassName::Simplify, PassName::UnnestFunc),
+    (PassName::CopyProp, PassName::UnnestFunc),
+];
+
+impl Config {
+    /// checks that `pipeline` runs the passes the later ones rely on, in the order they need
+    pub fn check_pipeline(&self) -> Result<(), String> {
+        if self.pipeline.contains(&PassName::Ast2Hir) {
+            return Err(format!("{} is not a pass over HIR", PassName::Ast2Hir));
+        }
+        for required in REQUIRED_PASSES {
+            if !self.pipeline.contains(required) {
+                return Err(format!("the pipeline lacks {}", required));
+            }
+        }
+        for (i, pass) in self.pipeline.iter().enumerate() {
+            for (after, before) in PREREQUISITES {
+                if pass == after && !self.pipeline[..i].contains(before) {
+                    return Err(format!("{} needs {} to run before it", after, before));
+                }
+            }
+            for (before, after) in SUCCESSORS {
+                if pass == before && self.pipeline[..i].contains(after) {
+                    return Err(format!("{} must run before {}", before, after));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// the type that numeric literals and operators default to
+/// when the program does not determine it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DefaultNum {
+    Int,
+    Word,
+    Real,
+}
+
+impl Default for DefaultNum {
+    fn default() -> Self {
+        DefaultNum::Int
+    }
+}
+
+/// the stable names of the passes over HIR, e.g. `flat_let` in `--dhir=flat_let`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PassName {
+    Ast2Hir,
+    UnboxDatatype,
+    BetaReduce,
+    Inline,
+    ConstFold,
+    StrengthReduce,
+    Simplify,
+    DeadBindElim,
+    FlatTuple,
+    MarkTail,
+    FlatExpr,
+    FlatLet,
+    MergeBinds,
+    Cse,
+    CopyProp,
+    UnnestFunc,
+    ForceClosure,
+    PruneCaptures,
+    DecisionTree,
+}
+
+impl PassName {
+    /// in the order they run
+    pub const ALL: &'static [PassName] = &[
+        PassName::Ast2Hir,
+        PassName::UnboxDatatype,
+        PassName::BetaReduce,
+        PassName::Inline,
+        PassName::ConstFold,
+        PassName::StrengthReduce,
+        PassName::Simplify,
+        PassName::DeadBindElim,
+        PassName::FlatTuple,
+        PassName::MarkTail,
+        PassName::FlatExpr,
+        PassName::FlatLet,
+        PassName::MergeBinds,
+        PassName::Cse,
+        PassName::CopyProp,
+        PassName::UnnestFunc,
+        PassName::ForceClosure,
+        PassName::PruneCaptures,
+        PassName::DecisionTree,
+    ];
+
+    /// the passes over HIR that run by default, that is all but `Ast2Hir`
+    pub fn default_pipeline() -> Vec<PassName> {
+        PassName::ALL
+            .iter()
+            .cloned()
+            .filter(|pass| *pass != PassName::Ast2Hir)
+            .collect()
+    }
+
+    /// the lowest `Config::opt_level` the pass runs at:
+    ///
+    /// - 0: the passes the translation to MIR needs, and `MarkTail` to keep the tail calls
+    /// - 1: the cleanups, e.g. constant folding and dead binding elimination
+    /// - 2: inlining, tuple flattening, datatype unboxing and common subexpression elimination
+    pub fn opt_level(self) -> u8 {
+        use PassName::*;
+        match self {
+            Ast2Hir | MarkTail | FlatExpr | FlatLet | UnnestFunc | ForceClosure | DecisionTree => 0,
+            BetaReduce | ConstFold | StrengthReduce | Simplify | DeadBindElim | MergeBinds
+            | CopyProp | PruneCaptures => 1,
+            UnboxDatatype | Inline | FlatTuple | Cse => 2,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        use PassName::*;
+        match self {
+            Ast2Hir => "ast2hir",
+            UnboxDatatype => "unbox_datatype",
+            BetaReduce => "beta_reduce",
+            Inline => "inline",
+            ConstFold => "const_fold",
+            StrengthReduce => "strength_reduce",
+            Simplify => "simplify",
+            DeadBindElim => "dead_bind_elim",
+            FlatTuple => "flat_tuple",
+            MarkTail => "mark_tail",
+            FlatExpr => "flat_expr",
+            FlatLet => "flat_let",
+            MergeBinds => "merge_binds",
+            Cse => "cse",
+            CopyProp => "copy_prop",
+            UnnestFunc => "unnest_func",
+            ForceClosure => "force_closure",
+            PruneCaptures => "prune_captures",
+            DecisionTree => "decision_tree",
+        }
+    }
+}
+
+impl FromStr for PassName {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PassName::ALL
+            .iter()
+            .cloned()
+            .find(|pass| pass.name() == s)
+            .ok_or_else(|| format!("unknown pass: {}", s))
+    }
+}
+
+impl fmt::Display for PassName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
 }
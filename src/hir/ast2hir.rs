@@ -4,6 +4,7 @@ use crate::hir::{Expr, HTy, Pattern, SymbolTable, TypeInfo, Val, HIR};
 use crate::id::Id;
 use crate::pass::Pass;
 use crate::prim::*;
+use std::collections::HashMap;
 
 pub struct AST2HIR {
     id: Id,
@@ -11,6 +12,10 @@ pub struct AST2HIR {
 
 struct AST2HIRPass {
     symbol_table: ast::SymbolTable,
+    exports: HashMap<Symbol, String>,
+    /// the functions comparing the values of the datatypes, made by `eq_fun`
+    eq_funs: Vec<(ast::Type, Symbol)>,
+    eq_vals: Vec<Val>,
     id: Id,
 }
 
@@ -24,13 +29,20 @@ impl AST2HIR {
     }
 }
 
-fn conv_symbol_table(symbol_table: ast::SymbolTable) -> SymbolTable {
+fn conv_symbol_table(
+    symbol_table: ast::SymbolTable,
+    exports: HashMap<Symbol, String>,
+) -> SymbolTable {
     SymbolTable {
         types: symbol_table
             .types
             .into_iter()
+            // lists are typed by `HTy::List` with the element type,
+            // and the other parametric datatypes by the instances `Monomorphize` made
+            .filter(|(k, v)| !is_list(k) && v.params.is_empty())
             .map(|(k, v)| (k, conv_type_info(v)))
             .collect(),
+        exports,
     }
 }
 
@@ -45,22 +57,71 @@ fn conv_type_info(type_info: ast::TypeInfo) -> TypeInfo {
     }
 }
 
+// the values compared by a single instruction
+fn is_scalar(ty: &ast::Type) -> bool {
+    use crate::ast::Type::*;
+    match ty {
+        Int | Word | Char | Real | Ref(_) => true,
+        _ => false,
+    }
+}
+
+fn is_list(name: &Symbol) -> bool {
+    *name == Symbol::new("list")
+}
+
+// the type of the argument of a constructor, with the parameters of the datatype replaced by `args`
+fn subst(ty: ast::Type, params: &[u64], args: &[ast::Type]) -> ast::Type {
+    use crate::ast::Type::*;
+    match ty {
+        Variable(v) => match params.iter().position(|param| *param == v) {
+            Some(i) => args[i].clone(),
+            None => Variable(v),
+        },
+        Fun(param, ret) => Fun(
+            Box::new(subst(*param, params, args)),
+            Box::new(subst(*ret, params, args)),
+        ),
+        Tuple(tys) => Tuple(tys.into_iter().map(|ty| subst(ty, params, args)).collect()),
+        Datatype(name, tys) => Datatype(
+            name,
+            tys.into_iter().map(|ty| subst(ty, params, args)).collect(),
+        ),
+        Ref(ty) => Ref(Box::new(subst(*ty, params, args))),
+        Unit | Char | String | Int | Word | Real | Bool => ty,
+    }
+}
+
 fn conv_ty(ty: ast::Type) -> HTy {
     use crate::ast::Type::*;
     match ty {
+        Unit => HTy::Unit,
         Char => HTy::Char,
+        String => HTy::String,
         Int => HTy::Int,
+        Word => HTy::Word,
         Real => HTy::Real,
+        Bool => HTy::Bool,
         Tuple(tys) => HTy::Tuple(tys.into_iter().map(|ty| conv_ty(ty)).collect()),
         Fun(arg, ret) => HTy::fun(conv_ty(*arg), conv_ty(*ret)),
-        Datatype(name) => HTy::Datatype(name),
-        Variable(_) => panic!("polymorphism is not supported yet"),
+        Datatype(ref name, ref args) if is_list(name) => {
+            HTy::List(Box::new(conv_ty(args[0].clone())))
+        }
+        Datatype(name, args) => HTy::Datatype(name, args.into_iter().map(conv_ty).collect()),
+        Ref(ty) => HTy::Ref(Box::new(conv_ty(*ty))),
+        Variable(_) => panic!("bug: type variables are removed by Monomorphize"),
     }
 }
 
 impl AST2HIRPass {
     fn new(symbol_table: ast::SymbolTable, id: Id) -> Self {
-        Self { symbol_table, id }
+        Self {
+            symbol_table,
+            exports: HashMap::new(),
+            eq_funs: Vec::new(),
+            eq_vals: Vec::new(),
+            id,
+        }
     }
     fn symbol_table(&self) -> &ast::SymbolTable {
         &self.symbol_table
@@ -75,6 +136,7 @@ impl AST2HIRPass {
         use crate::ast::Type::*;
         match ty {
             Tuple(tys) => tys.into_iter().map(|ty| conv_ty(ty)).collect(),
+            Unit => vec![],
             _ => panic!(),
         }
     }
@@ -89,10 +151,14 @@ impl AST2HIRPass {
 
     fn conv_statement(&mut self, decl: ast::TypedCoreDeclaration) -> Vec<Val> {
         match decl {
-            ast::Declaration::Datatype { .. } => {
+            ast::Declaration::Datatype { .. } | ast::Declaration::Import { .. } => {
                 // ignore
                 vec![]
             }
+            ast::Declaration::Export { name, value } => {
+                self.exports.insert(value, name);
+                vec![]
+            }
             ast::Declaration::Val { rec, pattern, expr } => {
                 let ty = pattern.ty.clone();
                 match pattern.inner {
@@ -127,7 +193,7 @@ impl AST2HIRPass {
                         name: self.gensym(),
                         expr: self.conv_expr(expr),
                     }],
-                    ast::PatternKind::Char { .. } => vec![Val {
+                    ast::PatternKind::Char { .. } | ast::PatternKind::Str { .. } => vec![Val {
                         ty: conv_ty(ty),
                         rec: false,
                         name: self.gensym(),
@@ -155,7 +221,9 @@ impl AST2HIRPass {
                         name: self.gensym(),
                         expr: self.conv_expr(expr),
                     }],
-                    ast::PatternKind::Tuple { .. } => {
+                    ast::PatternKind::Tuple { .. }
+                    | ast::PatternKind::As { .. }
+                    | ast::PatternKind::Or { .. } => {
                         // when (p1, p2, p3) binds var1 var2 var3, convert
                         //
                         // ```
@@ -229,6 +297,16 @@ impl AST2HIRPass {
                     }
                 }
             }
+            ast::Declaration::ValRecGroup { binds } => binds
+                .into_iter()
+                .flat_map(|(pattern, expr)| {
+                    self.conv_statement(ast::Declaration::Val {
+                        rec: true,
+                        pattern,
+                        expr,
+                    })
+                })
+                .collect(),
             ast::Declaration::D(d) => match d {},
         }
     }
@@ -245,24 +323,51 @@ impl AST2HIRPass {
                     .collect(),
                 ret: Box::new(self.conv_expr(*ret)),
             },
-            E::BuiltinCall { fun, args } => Expr::BuiltinCall {
-                ty: conv_ty(ty),
-                fun,
-                args: args.into_iter().map(|arg| self.conv_expr(arg)).collect(),
+            E::BuiltinCall { fun, mut args } => match fun {
+                BIF::Eq | BIF::Neq if !is_scalar(&args[0].ty) => {
+                    let r = args.pop().unwrap();
+                    let l = args.pop().unwrap();
+                    let operand = l.ty.clone();
+                    let (l, r) = (self.conv_expr(l), self.conv_expr(r));
+                    let eq = self.conv_eq(operand, l, r);
+                    if fun == BIF::Eq {
+                        eq
+                    } else {
+                        Expr::Case {
+                            ty: HTy::Bool,
+                            expr: Box::new(eq),
+                            arms: vec![
+                                (self.bool_pat(true), self.bool_lit(false)),
+                                (self.bool_pat(false), self.bool_lit(true)),
+                            ],
+                        }
+                    }
+                }
+                _ => Expr::BuiltinCall {
+                    ty: conv_ty(ty),
+                    fun,
+                    args: args.into_iter().map(|arg| self.conv_expr(arg)).collect(),
+                },
             },
             E::ExternCall {
                 module,
                 fun,
                 args,
                 argty: _,
-                retty: _,
-            } => Expr::ExternCall {
-                ty: conv_ty(ty),
-                module,
-                fun,
-
-                args: args.into_iter().map(|arg| self.conv_expr(arg)).collect(),
-            },
+                retty,
+            } => {
+                // the results of a multi-value function are typed as a tuple
+                let retty = match conv_ty(ty) {
+                    HTy::Tuple(tys) if retty.len() != 1 => tys,
+                    ty => vec![ty],
+                };
+                Expr::ExternCall {
+                    retty,
+                    module,
+                    fun,
+                    args: args.into_iter().map(|arg| self.conv_expr(arg)).collect(),
+                }
+            }
             E::Fn { param, body } => {
                 let (param_ty, body_ty) = match ty {
                     ast::Type::Fun(param_ty, body_ty) => (*param_ty, *body_ty),
@@ -284,6 +389,53 @@ impl AST2HIRPass {
                     .map(|(pat, expr)| (self.conv_pat(pat), self.conv_expr(expr)))
                     .collect(),
             },
+            // `if c then e1 else e2` => `case c of true => e1 | false => e2`
+            // `true` and `false` are the constructors of bool, so `Simplify` folds
+            // the `if`s on them as the other cases of known constructors
+            E::If { cond, then, else_ } => Expr::Case {
+                ty: conv_ty(ty),
+                expr: Box::new(self.conv_expr(*cond)),
+                arms: vec![
+                    (self.bool_pat(true), self.conv_expr(*then)),
+                    (self.bool_pat(false), self.conv_expr(*else_)),
+                ],
+            },
+            // `l andalso r` => `if l then r else false`, not evaluating `r` if `l` is false
+            E::AndAlso { l, r } => Expr::Case {
+                ty: HTy::Bool,
+                expr: Box::new(self.conv_expr(*l)),
+                arms: vec![
+                    (self.bool_pat(true), self.conv_expr(*r)),
+                    (self.bool_pat(false), self.bool_lit(false)),
+                ],
+            },
+            // `l orelse r` => `if l then true else r`
+            E::OrElse { l, r } => Expr::Case {
+                ty: HTy::Bool,
+                expr: Box::new(self.conv_expr(*l)),
+                arms: vec![
+                    (self.bool_pat(true), self.bool_lit(true)),
+                    (self.bool_pat(false), self.conv_expr(*r)),
+                ],
+            },
+            // `(e1; e2; e3)` => `let val _ = e1 val _ = e2 in e3 end`
+            E::Seq { mut exprs } => {
+                let last = exprs.pop().expect("empty sequence");
+                let binds = exprs
+                    .into_iter()
+                    .map(|e| Val {
+                        ty: conv_ty(e.ty.clone()),
+                        rec: false,
+                        name: self.gensym(),
+                        expr: self.conv_expr(e),
+                    })
+                    .collect();
+                Expr::Binds {
+                    ty: conv_ty(ty),
+                    binds,
+                    ret: Box::new(self.conv_expr(last)),
+                }
+            }
             E::Tuple { tuple } => Expr::Tuple {
                 tys: self.force_tuple(ty),
                 tuple: tuple.into_iter().map(|e| self.conv_expr(e)).collect(),
@@ -301,6 +453,7 @@ impl AST2HIRPass {
                 ty: conv_ty(ty),
                 value,
             },
+            E::Typed { expr, .. } => self.conv_expr(*expr),
             E::D(d) => match d {},
         }
     }
@@ -315,44 +468,228 @@ impl AST2HIRPass {
                 value,
                 ty: conv_ty(ty),
             },
+            ast::PatternKind::Str { value } => Pattern::Str {
+                value,
+                ty: conv_ty(ty),
+            },
             ast::PatternKind::Constructor { arg, name } => Pattern::Constructor {
                 ty: conv_ty(ty),
-                arg: arg.map(|pat| match *pat {
-                    ast::Pattern {
-                        ty,
-                        inner: ast::PatternKind::Variable { name },
-                    } => (conv_ty(ty), name),
-                    _ => panic!("internal error: pattern"),
-                }),
+                arg: arg.map(|pat| Box::new(self.conv_pat(*pat))),
                 descriminant: self.conv_constructor_name(&name),
             },
             ast::PatternKind::Tuple { tuple, .. } => {
-                let (tys, tuple) = tuple
+                let tuple = tuple
                     .into_iter()
-                    .map(|pat| match pat {
-                        ast::Pattern {
-                            ty,
-                            inner: ast::PatternKind::Variable { name },
-                        } => (conv_ty(ty), name),
-                        _ => panic!("internal error: pattern"),
-                    })
-                    .unzip();
+                    .map(|pat| self.conv_pat(pat))
+                    .collect::<Vec<_>>();
+                let tys = tuple.iter().map(|pat| pat.ty()).collect();
                 Pattern::Tuple { tuple, tys }
             }
             ast::PatternKind::Variable { name } => Pattern::Var {
                 name,
                 ty: conv_ty(ty),
             },
-            ast::PatternKind::Wildcard {} => Pattern::Var {
-                name: Symbol::new("_"),
+            ast::PatternKind::Wildcard {} => Pattern::Wildcard { ty: conv_ty(ty) },
+            ast::PatternKind::As { name, pattern } => Pattern::As {
+                name,
+                pattern: Box::new(self.conv_pat(*pattern)),
                 ty: conv_ty(ty),
             },
+            ast::PatternKind::Or { alternatives } => Pattern::Or(
+                alternatives
+                    .into_iter()
+                    .map(|pat| self.conv_pat(pat))
+                    .collect(),
+            ),
+        }
+    }
+
+    // `l = r` on the types not compared by an instruction, compared component by component.
+    // `l` and `r` are evaluated first, in order
+    fn conv_eq(&mut self, ty: ast::Type, l: Expr, r: Expr) -> Expr {
+        use crate::ast::Type::*;
+        let hty = conv_ty(ty.clone());
+        match ty {
+            Int | Word | Char | Real | Ref(_) => Expr::BuiltinCall {
+                ty: HTy::Bool,
+                fun: BIF::Eq,
+                args: vec![l, r],
+            },
+            String => Expr::ExternCall {
+                retty: vec![HTy::Bool],
+                module: "webml-rt".into(),
+                fun: "string_eq".into(),
+                args: vec![l, r],
+            },
+            // `(l1, l2) = (r1, r2)` => `let val l = .. val r = .. in #1 l = #1 r andalso #2 l = #2 r end`
+            Unit | Tuple(_) => {
+                let tys = match ty {
+                    Tuple(tys) => tys,
+                    _ => vec![],
+                };
+                let (lname, rname) = (self.gensym(), self.gensym());
+                let sym = |name: &Symbol| Expr::Sym {
+                    ty: hty.clone(),
+                    name: name.clone(),
+                };
+                let (lsym, rsym) = (sym(&lname), sym(&rname));
+                let mut ret = self.bool_lit(true);
+                for (index, ty) in tys.into_iter().enumerate().rev() {
+                    let proj = |tuple: &Expr| Expr::Proj {
+                        ty: conv_ty(ty.clone()),
+                        index: index as u32,
+                        tuple: Box::new(tuple.clone()),
+                    };
+                    let eq = self.conv_eq(ty.clone(), proj(&lsym), proj(&rsym));
+                    ret = Expr::Case {
+                        ty: HTy::Bool,
+                        expr: Box::new(eq),
+                        arms: vec![
+                            (self.bool_pat(true), ret),
+                            (self.bool_pat(false), self.bool_lit(false)),
+                        ],
+                    };
+                }
+                let bind = |name, expr| Val {
+                    ty: hty.clone(),
+                    rec: false,
+                    name,
+                    expr,
+                };
+                Expr::Binds {
+                    ty: HTy::Bool,
+                    binds: vec![bind(lname, l), bind(rname, r)],
+                    ret: Box::new(ret),
+                }
+            }
+            Bool | Datatype(..) => {
+                let fun = self.eq_fun(ty);
+                let arg = Expr::Tuple {
+                    tys: vec![hty.clone(), hty],
+                    tuple: vec![l, r],
+                };
+                fun.app1(HTy::Bool, arg)
+            }
+            Fun(..) | Variable(_) => {
+                panic!("internal error: compared type does not admit equality")
+            }
+        }
+    }
+
+    // the function comparing a pair of values of the datatype `ty`,
+    // made once for each type so that the recursive datatypes are compared by recursion.
+    //
+    // ```
+    // fun eq (l, r) = case l of
+    //     C1 l' => (case r of C1 r' => l' = r' | _ => false)
+    //   | C2 => (case r of C2 => true | _ => false)
+    // ```
+    fn eq_fun(&mut self, ty: ast::Type) -> Expr {
+        let hty = conv_ty(ty.clone());
+        let pair_ty = HTy::Tuple(vec![hty.clone(), hty.clone()]);
+        let fun_ty = HTy::fun(pair_ty.clone(), HTy::Bool);
+        if let Some((_, name)) = self.eq_funs.iter().find(|(t, _)| *t == ty) {
+            return Expr::Sym {
+                ty: fun_ty,
+                name: name.clone(),
+            };
+        }
+        let name = self.gensym();
+        self.eq_funs.push((ty.clone(), name.clone()));
+
+        let (type_name, args) = match ty {
+            ast::Type::Datatype(type_name, args) => (type_name, args),
+            ast::Type::Bool => (Symbol::new("bool"), vec![]),
+            ty => panic!("internal error: {:?} is not a datatype", ty),
+        };
+        let info = self
+            .symbol_table()
+            .get_type(&type_name)
+            .expect("internal error: type not found")
+            .clone();
+        let param = self.gensym();
+        let pair = Expr::Sym {
+            ty: pair_ty.clone(),
+            name: param.clone(),
+        };
+        let proj = |index| Expr::Proj {
+            ty: hty.clone(),
+            index,
+            tuple: Box::new(pair.clone()),
+        };
+        let mut arms = Vec::new();
+        for (descriminant, (_, arg)) in info.constructors.into_iter().enumerate() {
+            let descriminant = descriminant as u32;
+            let (largty, rargty, eq) = match arg {
+                Some(arg) => {
+                    let arg = subst(arg, &info.params, &args);
+                    let argty = conv_ty(arg.clone());
+                    let (l, r) = (self.gensym(), self.gensym());
+                    let var = |name: &Symbol| Pattern::Var {
+                        ty: argty.clone(),
+                        name: name.clone(),
+                    };
+                    let sym = |name: &Symbol| Expr::Sym {
+                        ty: argty.clone(),
+                        name: name.clone(),
+                    };
+                    let eq = self.conv_eq(arg, sym(&l), sym(&r));
+                    (Some(Box::new(var(&l))), Some(Box::new(var(&r))), eq)
+                }
+                None => (None, None, self.bool_lit(true)),
+            };
+            let constructor = |arg| Pattern::Constructor {
+                ty: hty.clone(),
+                arg,
+                descriminant,
+            };
+            let same = Expr::Case {
+                ty: HTy::Bool,
+                expr: Box::new(proj(1)),
+                arms: vec![
+                    (constructor(rargty), eq),
+                    (Pattern::Wildcard { ty: hty.clone() }, self.bool_lit(false)),
+                ],
+            };
+            arms.push((constructor(largty), same));
         }
+        self.eq_vals.push(Val {
+            ty: fun_ty.clone(),
+            rec: true,
+            name: name.clone(),
+            expr: Expr::Fun {
+                param: (pair_ty, param),
+                body_ty: HTy::Bool,
+                body: Box::new(Expr::Case {
+                    ty: HTy::Bool,
+                    expr: Box::new(proj(0)),
+                    arms,
+                }),
+                captures: Vec::new(),
+            },
+        });
+        Expr::Sym { ty: fun_ty, name }
     }
 
     fn conv_constructor_name(&mut self, name: &Symbol) -> u32 {
         self.symbol_table().constructor_to_id(name)
     }
+
+    fn bool_pat(&mut self, value: bool) -> Pattern {
+        Pattern::Constructor {
+            ty: HTy::Bool,
+            arg: None,
+            descriminant: self.conv_constructor_name(&Symbol::new(value.to_string())),
+        }
+    }
+
+    fn bool_lit(&mut self, value: bool) -> Expr {
+        Expr::Constructor {
+            ty: HTy::Bool,
+            arg: None,
+            descriminant: self.conv_constructor_name(&Symbol::new(value.to_string())),
+        }
+    }
 }
 
 impl<E> Pass<(ast::SymbolTable, ast::TypedCore), E> for AST2HIR {
@@ -365,7 +702,9 @@ impl<E> Pass<(ast::SymbolTable, ast::TypedCore), E> for AST2HIR {
     ) -> ::std::result::Result<Self::Target, E> {
         let mut pass = self.generate_pass(symbol_table);
         let ast = pass.conv_ast(ast);
-        let symbol_table = conv_symbol_table(pass.symbol_table);
+        // the comparisons are referred to from anywhere in the program
+        let ast = HIR(pass.eq_vals.drain(..).chain(ast.0).collect());
+        let symbol_table = conv_symbol_table(pass.symbol_table, pass.exports);
         Ok((symbol_table, ast))
     }
 }
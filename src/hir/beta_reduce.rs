@@ -0,0 +1,74 @@
+use crate::config::Config;
+use crate::hir::util::Transform;
+use crate::hir::*;
+use crate::pass::Pass;
+use crate::prim::*;
+use std::collections::HashMap;
+
+pub struct BetaReduce {
+    // the parameters replaced with the arguments
+    subst: HashMap<Symbol, Expr>,
+}
+
+impl BetaReduce {
+    pub fn new() -> Self {
+        Self {
+            subst: HashMap::new(),
+        }
+    }
+}
+
+impl Transform for BetaReduce {
+    // `(fn param => body) arg` => `let val param = arg in body end`.
+    // simple arguments are substituted instead of bound.
+    fn transform_app(&mut self, ty: HTy, fun: Box<Expr>, arg: Box<Expr>, is_tail: bool) -> Expr {
+        let arg = self.transform_expr(*arg);
+        match *fun {
+            Expr::Fun {
+                param: (param_ty, param),
+                body,
+                ..
+            } => match arg {
+                Expr::Sym { .. } | Expr::Lit { .. } => {
+                    self.subst.insert(param, arg);
+                    self.transform_expr(*body)
+                }
+                arg => Expr::Binds {
+                    ty,
+                    binds: vec![Val {
+                        ty: param_ty,
+                        rec: false,
+                        name: param,
+                        expr: arg,
+                    }],
+                    ret: Box::new(self.transform_expr(*body)),
+                },
+            },
+            fun => Expr::App {
+                ty,
+                fun: Box::new(self.transform_expr(fun)),
+                arg: Box::new(arg),
+                is_tail,
+            },
+        }
+    }
+
+    fn transform_sym(&mut self, ty: HTy, name: Symbol) -> Expr {
+        match self.subst.get(&name) {
+            Some(expr) => expr.clone(),
+            None => Expr::Sym { ty, name },
+        }
+    }
+}
+
+impl<E> Pass<(SymbolTable, HIR), E> for BetaReduce {
+    type Target = (SymbolTable, HIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, hir): (SymbolTable, HIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        Ok((symbol_table, self.transform_hir(hir)))
+    }
+}
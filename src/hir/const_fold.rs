@@ -0,0 +1,183 @@
+use crate::config::Config;
+use crate::hir::util::Transform;
+use crate::hir::*;
+use crate::pass::Pass;
+use crate::prim::*;
+
+pub struct ConstFold;
+
+impl ConstFold {
+    pub fn new() -> Self {
+        ConstFold
+    }
+}
+
+// evaluate `fun` in the same way as the generated code does.
+// ints and words are 32 bit wide in the backend.
+fn fold(fun: BIF, l: &Literal, r: &Literal) -> Option<Literal> {
+    use crate::prim::Literal::*;
+    use crate::prim::BIF::*;
+    match (l, r) {
+        (Int(l), Int(r)) => {
+            let (l, r) = (*l as i32, *r as i32);
+            let value = match fun {
+                Add => l.wrapping_add(r),
+                Sub => l.wrapping_sub(r),
+                Mul => l.wrapping_mul(r),
+                // leave the ones that trap at runtime
+                Div => l.checked_div(r)?,
+                Mod => l.checked_rem(r)?,
+                // the shift amount is taken modulo 32 as the wasm instructions do
+                Shl => l.wrapping_shl(r as u32),
+                Shr => l.wrapping_shr(r as u32),
+                Andb => l & r,
+                Orb => l | r,
+                Xorb => l ^ r,
+                _ => return None,
+            };
+            Some(Int(value as i64))
+        }
+        (Word(l), Word(r)) => {
+            let (l, r) = (*l as u32, *r as u32);
+            let value = match fun {
+                Add => l.wrapping_add(r),
+                Sub => l.wrapping_sub(r),
+                Mul => l.wrapping_mul(r),
+                Div => l.checked_div(r)?,
+                Mod => l.checked_rem(r)?,
+                Shl => l.wrapping_shl(r),
+                // logical shift, as the operand is unsigned
+                Shr => l.wrapping_shr(r),
+                _ => return None,
+            };
+            Some(Word(value as u64))
+        }
+        (Real(l), Real(r)) => {
+            let value = match fun {
+                Add => l + r,
+                Sub => l - r,
+                Mul => l * r,
+                Divf => l / r,
+                // the remainder rounds the quotient to nearest, and is NaN for a zero divisor
+                Modf => ieee_remainder(*l, *r),
+                Pow => exact_pow(*l, *r)?,
+                // comparisons are not folded, which leaves NaN to the backend
+                _ => return None,
+            };
+            Some(Real(value))
+        }
+        _ => None,
+    }
+}
+
+fn fold_unary(fun: BIF, v: &Literal) -> Option<Literal> {
+    use crate::prim::Literal::*;
+    use crate::prim::BIF::*;
+    match (fun, v) {
+        (Neg, Int(v)) => Some(Int((*v as i32).wrapping_neg() as i64)),
+        (Neg, Word(v)) => Some(Word((*v as u32).wrapping_neg() as u64)),
+        (Neg, Real(v)) => Some(Real(-v)),
+        // `abs` of the minimum int overflows, which is left to the generated code
+        (Abs, Int(v)) => (*v as i32).checked_abs().map(|v| Int(v as i64)),
+        (Abs, Word(v)) => Some(Word(*v as u32 as u64)),
+        (Abs, Real(v)) => Some(Real(v.abs())),
+        (Notb, Int(v)) => Some(Int(!(*v as i32) as i64)),
+        (RealOfInt, Int(v)) => Some(Real(*v as i32 as f64)),
+        (Floor, Real(v)) => real_to_int(v.floor()),
+        (Ceil, Real(v)) => real_to_int(v.ceil()),
+        (Round, Real(v)) => real_to_int(round_half_even(*v)),
+        (Trunc, Real(v)) => real_to_int(v.trunc()),
+        // sqrt is correctly rounded both here and in wasm
+        (Sqrt, Real(v)) => Some(Real(v.sqrt())),
+        (Ord, Char(c)) => Some(Int(*c as i64)),
+        // out of range code points are left to trap at runtime
+        (Chr, Int(v)) if 0 <= *v && *v <= 255 => Some(Char(*v as u32)),
+        _ => None,
+    }
+}
+
+// `pow` is computed by the host at runtime, whose rounding may differ from ours.
+// only fold integral powers whose every step is exact
+fn exact_pow(base: f64, exp: f64) -> Option<f64> {
+    const EXACT: f64 = 9007199254740992.0; // 2^53
+    if base.trunc() != base || exp.trunc() != exp || exp < 0.0 || exp > 64.0 {
+        return None;
+    }
+    let mut value = 1.0;
+    for _ in 0..(exp as u32) {
+        value *= base;
+        if value.abs() > EXACT {
+            return None;
+        }
+    }
+    Some(value)
+}
+
+// IEEE 754 `remainder`: `x - n * y` where `n` is `x / y` rounded to nearest, ties to even.
+// built on the exact `%` so that `n` need not be representable, as the host does
+fn ieee_remainder(x: f64, y: f64) -> f64 {
+    let ay = y.abs();
+    // the remainder by `2 * y` tells the parity of the quotient
+    let mut r = (x % (2.0 * ay)).abs();
+    let odd = r >= ay;
+    if odd {
+        r -= ay;
+    }
+    let half = ay - r;
+    if r > half || (r == half && odd) {
+        r -= ay;
+    }
+    if x.is_sign_negative() {
+        -r
+    } else {
+        r
+    }
+}
+
+// the backend traps on NaN and on values out of the int range, which are left to it
+fn real_to_int(v: f64) -> Option<Literal> {
+    if (i32::min_value() as f64) <= v && v <= (i32::max_value() as f64) {
+        Some(Literal::Int(v as i32 as i64))
+    } else {
+        None
+    }
+}
+
+// `round` rounds ties to even as `f64.nearest` does
+fn round_half_even(v: f64) -> f64 {
+    if (v - v.trunc()).abs() == 0.5 {
+        2.0 * (v / 2.0).round()
+    } else {
+        v.round()
+    }
+}
+
+impl Transform for ConstFold {
+    fn transform_builtin_call(&mut self, ty: HTy, fun: BIF, args: Vec<Expr>) -> Expr {
+        let args = args
+            .into_iter()
+            .map(|arg| self.transform_expr(arg))
+            .collect::<Vec<_>>();
+        let folded = match args.as_slice() {
+            [Expr::Lit { value, .. }] => fold_unary(fun, value),
+            [Expr::Lit { value: l, .. }, Expr::Lit { value: r, .. }] => fold(fun, l, r),
+            _ => None,
+        };
+        match folded {
+            Some(value) => Expr::Lit { ty, value },
+            None => Expr::BuiltinCall { ty, fun, args },
+        }
+    }
+}
+
+impl<E> Pass<(SymbolTable, HIR), E> for ConstFold {
+    type Target = (SymbolTable, HIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, hir): (SymbolTable, HIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        Ok((symbol_table, self.transform_hir(hir)))
+    }
+}
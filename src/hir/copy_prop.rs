@@ -0,0 +1,60 @@
+use crate::config::Config;
+use crate::hir::util::Transform;
+use crate::hir::*;
+use crate::pass::Pass;
+use crate::prim::*;
+use std::collections::HashMap;
+
+/// removes the aliases `val x = y` replacing `x` with `y`.
+/// names are unique after renaming, so an alias is valid everywhere after its binding.
+pub struct CopyProp {
+    aliases: HashMap<Symbol, Expr>,
+}
+
+impl CopyProp {
+    pub fn new() -> Self {
+        Self {
+            aliases: HashMap::new(),
+        }
+    }
+}
+
+impl Transform for CopyProp {
+    fn transform_binds(&mut self, ty: HTy, binds: Vec<Val>, ret: Box<Expr>) -> Expr {
+        let mut vals = Vec::new();
+        for mut val in binds {
+            val.expr = self.transform_expr(val.expr);
+            match val.expr {
+                // the aliased name is already resolved, so chains collapse to the first name
+                Expr::Sym { .. } if !val.rec => {
+                    self.aliases.insert(val.name, val.expr);
+                }
+                _ => vals.push(val),
+            }
+        }
+        Expr::Binds {
+            ty,
+            binds: vals,
+            ret: Box::new(self.transform_expr(*ret)),
+        }
+    }
+
+    fn transform_sym(&mut self, ty: HTy, name: Symbol) -> Expr {
+        match self.aliases.get(&name) {
+            Some(expr) => expr.clone(),
+            None => Expr::Sym { ty, name },
+        }
+    }
+}
+
+impl<E> Pass<(SymbolTable, HIR), E> for CopyProp {
+    type Target = (SymbolTable, HIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, hir): (SymbolTable, HIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        Ok((symbol_table, self.transform_hir(hir)))
+    }
+}
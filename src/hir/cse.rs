@@ -0,0 +1,178 @@
+use crate::config::Config;
+use crate::hir::util::Transform;
+use crate::hir::*;
+use crate::pass::Pass;
+use crate::prim::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::mem;
+
+/// common subexpression elimination.
+/// expects flattened HIR, where subexpressions are bound to names.
+pub struct CSE {
+    // the values computed so far, by their hashes
+    available: HashMap<u64, Vec<(Expr, Symbol)>>,
+    // the hashes added to `available`, to restore it at the end of scopes
+    log: Vec<u64>,
+}
+
+// structural hash of the pure expressions that are worth sharing.
+// `None` for the other expressions.
+fn hash(expr: &Expr) -> Option<u64> {
+    fn hash_expr<H: Hasher>(expr: &Expr, h: &mut H) -> Option<()> {
+        use crate::hir::Expr::*;
+        mem::discriminant(expr).hash(h);
+        match expr {
+            // may trap
+            BuiltinCall { fun: BIF::Div, .. } | BuiltinCall { fun: BIF::Mod, .. } => return None,
+            // each `ref` makes a new cell and `!` depends on the assignments in between
+            BuiltinCall { fun, .. } if [BIF::Ref, BIF::Deref, BIF::Assign].contains(fun) => {
+                return None
+            }
+            BuiltinCall { fun, args, .. } => {
+                mem::discriminant(fun).hash(h);
+                for arg in args {
+                    hash_expr(arg, h)?
+                }
+            }
+            Tuple { tuple, .. } => {
+                for t in tuple {
+                    hash_expr(t, h)?
+                }
+            }
+            Proj { index, tuple, .. } => {
+                index.hash(h);
+                hash_expr(tuple, h)?
+            }
+            Sym { name, .. } => name.hash(h),
+            Lit { value, .. } => match value {
+                Literal::Int(i) => i.hash(h),
+                Literal::Word(w) => w.hash(h),
+                Literal::Real(f) => f.to_bits().hash(h),
+                Literal::Char(c) => c.hash(h),
+                Literal::String(s) => s.hash(h),
+            },
+            _ => return None,
+        }
+        Some(())
+    }
+
+    match expr {
+        Expr::Sym { .. } | Expr::Lit { .. } => None,
+        expr => {
+            let mut h = DefaultHasher::new();
+            hash_expr(expr, &mut h)?;
+            Some(h.finish())
+        }
+    }
+}
+
+impl CSE {
+    pub fn new() -> Self {
+        Self {
+            available: HashMap::new(),
+            log: Vec::new(),
+        }
+    }
+
+    fn with_scope<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let mark = self.log.len();
+        let ret = f(self);
+        for hash in self.log.split_off(mark) {
+            self.available.get_mut(&hash).unwrap().pop();
+        }
+        ret
+    }
+
+    fn lookup(&self, hash: u64, expr: &Expr) -> Option<&Symbol> {
+        self.available
+            .get(&hash)?
+            .iter()
+            .find(|(e, _)| e == expr)
+            .map(|(_, name)| name)
+    }
+
+    fn register(&mut self, hash: u64, expr: Expr, name: Symbol) {
+        self.available
+            .entry(hash)
+            .or_insert_with(Vec::new)
+            .push((expr, name));
+        self.log.push(hash);
+    }
+}
+
+impl Transform for CSE {
+    fn transform_binds(&mut self, ty: HTy, binds: Vec<Val>, ret: Box<Expr>) -> Expr {
+        self.with_scope(|this| {
+            let binds = binds
+                .into_iter()
+                .map(|mut val| {
+                    val.expr = this.transform_expr(val.expr);
+                    if let Some(hash) = hash(&val.expr) {
+                        // reuse the value computed before
+                        match this.lookup(hash, &val.expr).cloned() {
+                            Some(name) => {
+                                val.expr = Expr::Sym {
+                                    ty: val.ty.clone(),
+                                    name,
+                                }
+                            }
+                            None => this.register(hash, val.expr.clone(), val.name.clone()),
+                        }
+                    }
+                    val
+                })
+                .collect();
+            Expr::Binds {
+                ty,
+                binds,
+                ret: Box::new(this.transform_expr(*ret)),
+            }
+        })
+    }
+
+    fn transform_fun(
+        &mut self,
+        param: (HTy, Symbol),
+        body_ty: HTy,
+        body: Box<Expr>,
+        captures: Vec<(HTy, Symbol)>,
+    ) -> Expr {
+        // do not make functions capture more variables
+        let available = mem::replace(&mut self.available, HashMap::new());
+        let log = mem::replace(&mut self.log, Vec::new());
+        let body = Box::new(self.transform_expr(*body));
+        self.available = available;
+        self.log = log;
+        Expr::Fun {
+            param,
+            body_ty,
+            body,
+            captures,
+        }
+    }
+
+    fn transform_case(&mut self, ty: HTy, cond: Box<Expr>, arms: Vec<(Pattern, Expr)>) -> Expr {
+        Expr::Case {
+            ty,
+            expr: Box::new(self.transform_expr(*cond)),
+            arms: arms
+                .into_iter()
+                .map(|(pat, arm)| (pat, self.with_scope(|this| this.transform_expr(arm))))
+                .collect(),
+        }
+    }
+}
+
+impl<E> Pass<(SymbolTable, HIR), E> for CSE {
+    type Target = (SymbolTable, HIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, hir): (SymbolTable, HIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        Ok((symbol_table, self.transform_hir(hir)))
+    }
+}
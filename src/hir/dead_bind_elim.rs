@@ -0,0 +1,125 @@
+use crate::config::Config;
+use crate::hir::util::{Transform, Traverse};
+use crate::hir::*;
+use crate::pass::Pass;
+use crate::prim::*;
+use std::collections::HashSet;
+
+pub struct DeadBindElim {
+    used: HashSet<Symbol>,
+    removed: bool,
+}
+
+// collects the referenced symbols.
+// symbols are unique after renaming, so scopes need not be tracked.
+pub(crate) struct Uses<'a> {
+    pub(crate) used: &'a mut HashSet<Symbol>,
+}
+
+impl<'a> Traverse for Uses<'a> {
+    fn traverse_closure(
+        &mut self,
+        envs: &mut Vec<(HTy, Symbol)>,
+        _param_ty: &mut HTy,
+        _body_ty: &mut HTy,
+        fname: &mut Symbol,
+    ) {
+        self.used.insert(fname.clone());
+        for (_, name) in envs {
+            self.used.insert(name.clone());
+        }
+    }
+
+    fn traverse_sym(&mut self, _ty: &mut HTy, name: &mut Symbol) {
+        self.used.insert(name.clone());
+    }
+}
+
+// whether evaluating `expr` has no effects and always succeeds
+pub(crate) fn is_pure(expr: &Expr) -> bool {
+    use crate::hir::Expr::*;
+    match expr {
+        Binds { binds, ret, .. } => binds.iter().all(|val| is_pure(&val.expr)) && is_pure(ret),
+        // these trap on some operands
+        BuiltinCall { fun: BIF::Div, .. }
+        | BuiltinCall { fun: BIF::Mod, .. }
+        | BuiltinCall { fun: BIF::Chr, .. }
+        // lowered to the truncation which traps on NaN and the values out of the int range
+        | BuiltinCall { fun: BIF::Floor, .. }
+        | BuiltinCall { fun: BIF::Ceil, .. }
+        | BuiltinCall { fun: BIF::Round, .. }
+        | BuiltinCall { fun: BIF::Trunc, .. } => false,
+        BuiltinCall { fun, .. } if *fun == BIF::Assign => false,
+        BuiltinCall { args, .. } => args.iter().all(is_pure),
+        // the callee may have effects
+        ExternCall { .. } | App { .. } => false,
+        Fun { .. } | Closure { .. } | Sym { .. } | Lit { .. } => true,
+        Case { expr, arms, .. } => is_pure(expr) && arms.iter().all(|(_, arm)| is_pure(arm)),
+        Tuple { tuple, .. } => tuple.iter().all(is_pure),
+        Proj { tuple, .. } => is_pure(tuple),
+        Constructor { arg, .. } => arg.iter().all(|arg| is_pure(arg)),
+    }
+}
+
+impl DeadBindElim {
+    pub fn new() -> Self {
+        Self {
+            used: HashSet::new(),
+            removed: false,
+        }
+    }
+
+    fn elim(&mut self, mut hir: HIR) -> HIR {
+        // removing a binding may make the ones it refers to unused
+        loop {
+            self.used.clear();
+            Uses {
+                used: &mut self.used,
+            }
+            .traverse_hir(&mut hir);
+            self.removed = false;
+            hir = self.transform_hir(hir);
+            if !self.removed {
+                return hir;
+            }
+        }
+    }
+}
+
+impl Transform for DeadBindElim {
+    fn transform_binds(&mut self, ty: HTy, binds: Vec<Val>, ret: Box<Expr>) -> Expr {
+        let binds = binds
+            .into_iter()
+            .filter(|val| {
+                let dead = !self.used.contains(&val.name) && is_pure(&val.expr);
+                self.removed |= dead;
+                !dead
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|val| self.transform_val(val))
+            .collect::<Vec<_>>();
+        let ret = self.transform_expr(*ret);
+        if binds.is_empty() {
+            ret
+        } else {
+            Expr::Binds {
+                ty,
+                binds,
+                ret: Box::new(ret),
+            }
+        }
+    }
+}
+
+impl<E> Pass<(SymbolTable, HIR), E> for DeadBindElim {
+    type Target = (SymbolTable, HIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, hir): (SymbolTable, HIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        Ok((symbol_table, self.elim(hir)))
+    }
+}
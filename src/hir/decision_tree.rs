@@ -0,0 +1,377 @@
+use crate::config::Config;
+use crate::hir::util::Transform;
+use crate::hir::*;
+use crate::id::Id;
+use crate::pass::Pass;
+use std::collections::HashSet;
+
+/// compiles the arms of `case` to a switch on the keys of the patterns,
+/// falling through to at most one default arm.
+/// the arms with or-patterns are split to an arm for each alternative,
+/// and the nested patterns in the arguments of constructors are matched
+/// by the cases on the arguments inside the arms.
+/// the tuple patterns are matched element by element, trying the next arm when one fails.
+/// the strings have no keys to switch on, so they are compared with the patterns one by one.
+/// the arms never taken are removed and the keys are sorted,
+/// so that the dense cases become jump tables.
+pub struct DecisionTree {
+    id: Id,
+}
+
+// whether the argument of the constructor or the elements of the tuple, if any,
+// are just bound to names
+fn is_flat(pat: &Pattern) -> bool {
+    fn is_name(pat: &Pattern) -> bool {
+        match pat {
+            Pattern::Var { .. } | Pattern::Wildcard { .. } => true,
+            _ => false,
+        }
+    }
+    match pat {
+        Pattern::Constructor { arg: Some(arg), .. } => is_name(arg),
+        Pattern::Tuple { tuple, .. } => tuple.iter().all(is_name),
+        Pattern::Str { .. } => false,
+        Pattern::As { pattern, .. } => is_flat(pattern),
+        _ => true,
+    }
+}
+
+// `false` and `true` are the constructors 0 and 1 of bool
+fn bool_pat(value: bool) -> Pattern {
+    Pattern::Constructor {
+        descriminant: value as u32,
+        arg: None,
+        ty: HTy::Bool,
+    }
+}
+
+impl DecisionTree {
+    pub fn new(id: Id) -> Self {
+        DecisionTree { id }
+    }
+
+    fn gensym(&mut self) -> Symbol {
+        let id = self.id.next();
+        Symbol("#g".into(), id)
+    }
+
+    // `arm` with the names of the `As` layers bound to `scrutinee`
+    fn bind_aliases(ty: &HTy, names: Vec<(HTy, Symbol)>, scrutinee: &Expr, arm: Expr) -> Expr {
+        if names.is_empty() {
+            return arm;
+        }
+        Expr::Binds {
+            ty: ty.clone(),
+            binds: names
+                .into_iter()
+                .map(|(ty, name)| Val {
+                    ty,
+                    rec: false,
+                    name,
+                    expr: scrutinee.clone(),
+                })
+                .collect(),
+            ret: Box::new(arm),
+        }
+    }
+
+    // `case x of (p1, p2) => e1 | y => e2`
+    // => `case x of (v1, v2) => (case v1 of p1 => (case v2 of p2 => e1 | _ => f) | _ => f)`
+    // where `f` is `case x of y => e2`, the rest of the arms
+    fn tuple(
+        &mut self,
+        ty: HTy,
+        scrutinee: Expr,
+        tys: Vec<HTy>,
+        arms: Vec<(Pattern, Expr)>,
+    ) -> Expr {
+        let vars = tys.iter().map(|_| self.gensym()).collect::<Vec<_>>();
+        let mut fallback: Option<Expr> = None;
+        for (pat, arm) in arms.into_iter().rev() {
+            let (names, pat) = pat.strip_as();
+            let arm = Self::bind_aliases(&ty, names, &scrutinee, arm);
+            let expr = match pat {
+                Pattern::Tuple { tuple, .. } => {
+                    let mut expr = arm;
+                    for ((pat, var), elemty) in tuple.into_iter().zip(&vars).zip(&tys).rev() {
+                        let mut arms = vec![(pat, expr)];
+                        if let Some(fallback) = &fallback {
+                            let wildcard = Pattern::Wildcard { ty: elemty.clone() };
+                            arms.push((wildcard, fallback.clone()));
+                        }
+                        expr = Expr::Case {
+                            ty: ty.clone(),
+                            expr: Box::new(Expr::Sym {
+                                ty: elemty.clone(),
+                                name: var.clone(),
+                            }),
+                            arms,
+                        };
+                    }
+                    expr
+                }
+                // a name, which the arms after it are never tried
+                pat => Expr::Case {
+                    ty: ty.clone(),
+                    expr: Box::new(scrutinee.clone()),
+                    arms: vec![(pat, arm)],
+                },
+            };
+            fallback = Some(expr);
+        }
+        let body = self.transform_expr(fallback.expect("internal error: no arms"));
+        let tuple = vars
+            .into_iter()
+            .zip(&tys)
+            .map(|(name, ty)| Pattern::Var {
+                name,
+                ty: ty.clone(),
+            })
+            .collect();
+        Expr::Case {
+            ty,
+            expr: Box::new(scrutinee),
+            arms: vec![(Pattern::Tuple { tys, tuple }, body)],
+        }
+    }
+
+    // `case x of "a" => e1 | y => e2`
+    // => `case string_eq (x, "a") of true => e1 | false => (case x of y => e2)`
+    fn strings(&mut self, ty: HTy, scrutinee: Expr, arms: Vec<(Pattern, Expr)>) -> Expr {
+        let mut fallback: Option<Expr> = None;
+        for (pat, arm) in arms.into_iter().rev() {
+            let (names, pat) = pat.strip_as();
+            let arm = Self::bind_aliases(&ty, names, &scrutinee, arm);
+            let expr = match pat {
+                Pattern::Str { value, ty: strty } => {
+                    let eq = Expr::ExternCall {
+                        retty: vec![HTy::Bool],
+                        module: "webml-rt".into(),
+                        fun: "string_eq".into(),
+                        args: vec![
+                            scrutinee.clone(),
+                            Expr::Lit {
+                                ty: strty,
+                                value: Literal::String(value),
+                            },
+                        ],
+                    };
+                    let mut arms = vec![(bool_pat(true), arm)];
+                    if let Some(fallback) = fallback {
+                        arms.push((bool_pat(false), fallback));
+                    }
+                    Expr::Case {
+                        ty: ty.clone(),
+                        expr: Box::new(eq),
+                        arms,
+                    }
+                }
+                // a name, which the arms after it are never tried
+                pat => Expr::Case {
+                    ty: ty.clone(),
+                    expr: Box::new(scrutinee.clone()),
+                    arms: vec![(pat, arm)],
+                },
+            };
+            fallback = Some(expr);
+        }
+        self.transform_expr(fallback.expect("internal error: no arms"))
+    }
+
+    // `case x of C p1 => e1 | C p2 => e2 | y => e3`
+    // => `case x of C v => (case v of p1 => e1 | p2 => e2 | _ => case x of y => e3) | y => e3`
+    fn nested(&mut self, ty: HTy, scrutinee: Expr, arms: Vec<(Pattern, Expr)>) -> Expr {
+        match scrutinee.ty() {
+            HTy::Tuple(tys) => return self.tuple(ty, scrutinee, tys, arms),
+            HTy::String => return self.strings(ty, scrutinee, arms),
+            _ => (),
+        }
+        // the heads of the switch with the arms on the arguments
+        let mut groups: Vec<(Pattern, Vec<(Option<Pattern>, Expr)>)> = Vec::new();
+        let mut default = None;
+        for (pat, arm) in arms {
+            let (names, pat) = pat.strip_as();
+            let arm = Self::bind_aliases(&ty, names, &scrutinee, arm);
+            if pat.is_irrefutable() {
+                default = Some((pat, arm));
+                break;
+            }
+            let key = pat.match_key();
+            let (head, arg) = match pat {
+                Pattern::Constructor {
+                    descriminant,
+                    arg: Some(arg),
+                    ty,
+                } => (
+                    Pattern::Constructor {
+                        descriminant,
+                        arg: None,
+                        ty,
+                    },
+                    Some(*arg),
+                ),
+                pat => (pat, None),
+            };
+            match groups.iter().position(|(head, _)| head.match_key() == key) {
+                Some(i) => groups[i].1.push((arg, arm)),
+                None => {
+                    let head = match (head, &arg) {
+                        (
+                            Pattern::Constructor {
+                                descriminant, ty, ..
+                            },
+                            Some(arg),
+                        ) => Pattern::Constructor {
+                            descriminant,
+                            arg: Some(Box::new(Pattern::Var {
+                                name: self.gensym(),
+                                ty: arg.ty(),
+                            })),
+                            ty,
+                        },
+                        (head, _) => head,
+                    };
+                    groups.push((head, vec![(arg, arm)]))
+                }
+            }
+        }
+
+        let mut switch = Vec::new();
+        for (head, group) in groups {
+            let var = match &head {
+                Pattern::Constructor { arg: Some(var), .. } => match &**var {
+                    Pattern::Var { name, ty } => Some((name.clone(), ty.clone())),
+                    _ => None,
+                },
+                _ => None,
+            };
+            let arm = match var {
+                Some((name, argty)) => {
+                    let mut arms = group
+                        .into_iter()
+                        .filter_map(|(arg, arm)| arg.map(|arg| (arg, arm)))
+                        .collect::<Vec<_>>();
+                    // falls back to the default arm when no nested pattern matches
+                    if let Some((pat, arm)) = &default {
+                        let fallback = Expr::Case {
+                            ty: ty.clone(),
+                            expr: Box::new(scrutinee.clone()),
+                            arms: vec![(pat.clone(), arm.clone())],
+                        };
+                        arms.push((
+                            Pattern::Var {
+                                name: self.gensym(),
+                                ty: argty.clone(),
+                            },
+                            fallback,
+                        ))
+                    }
+                    self.transform_case(ty.clone(), Box::new(Expr::Sym { ty: argty, name }), arms)
+                }
+                // only the first arm of the same key is taken
+                None => self.transform_expr(group.into_iter().next().unwrap().1),
+            };
+            switch.push((head, arm))
+        }
+        switch.sort_by_key(|(pat, _)| pat.match_key());
+        let default = default.map(|(pat, arm)| (pat, self.transform_expr(arm)));
+        Expr::Case {
+            ty,
+            expr: Box::new(scrutinee),
+            arms: switch.into_iter().chain(default).collect(),
+        }
+    }
+}
+
+impl Transform for DecisionTree {
+    fn transform_case(&mut self, ty: HTy, expr: Box<Expr>, arms: Vec<(Pattern, Expr)>) -> Expr {
+        let expr = self.transform_expr(*expr);
+        let arms = arms
+            .into_iter()
+            .flat_map(|(pat, arm)| {
+                pat.alternatives()
+                    .into_iter()
+                    .map(move |pat| (pat, arm.clone()))
+            })
+            .collect::<Vec<_>>();
+        if !arms.iter().all(|(pat, _)| is_flat(pat)) {
+            // the nested cases refer to the value again
+            return match expr {
+                expr @ Expr::Sym { .. } => self.nested(ty, expr, arms),
+                expr => {
+                    let name = self.gensym();
+                    let scrutinee = Expr::Sym {
+                        ty: expr.ty(),
+                        name: name.clone(),
+                    };
+                    Expr::Binds {
+                        ty: ty.clone(),
+                        binds: vec![Val {
+                            ty: expr.ty(),
+                            rec: false,
+                            name,
+                            expr,
+                        }],
+                        ret: Box::new(self.nested(ty, scrutinee, arms)),
+                    }
+                }
+            };
+        }
+
+        let mut keys = HashSet::new();
+        let mut switch = Vec::new();
+        let mut default = None;
+        for (pat, arm) in arms {
+            // the arms after the irrefutable one are never taken
+            if pat.is_irrefutable() {
+                default = Some((pat, arm));
+                break;
+            }
+            // nor are the ones whose key is tested by an earlier arm
+            if keys.insert(pat.match_key()) {
+                switch.push((pat, arm));
+            }
+        }
+        switch.sort_by_key(|(pat, _)| pat.match_key());
+
+        // `case e of x => arm` => `let val x = e in arm end`
+        let bound = match &default {
+            Some((Pattern::Var { name, .. }, _)) if switch.is_empty() => Some(name.clone()),
+            Some((Pattern::Wildcard { .. }, _)) if switch.is_empty() => Some(self.gensym()),
+            _ => None,
+        };
+        match (bound, default) {
+            (Some(name), Some((_, arm))) => Expr::Binds {
+                ty,
+                binds: vec![Val {
+                    ty: expr.ty(),
+                    rec: false,
+                    name,
+                    expr,
+                }],
+                ret: Box::new(self.transform_expr(arm)),
+            },
+            (_, default) => Expr::Case {
+                ty,
+                expr: Box::new(expr),
+                arms: switch
+                    .into_iter()
+                    .chain(default)
+                    .map(|(pat, arm)| (pat, self.transform_expr(arm)))
+                    .collect(),
+            },
+        }
+    }
+}
+
+impl<E> Pass<(SymbolTable, HIR), E> for DecisionTree {
+    type Target = (SymbolTable, HIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, hir): (SymbolTable, HIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        Ok((symbol_table, self.transform_hir(hir)))
+    }
+}
@@ -0,0 +1,31 @@
+use crate::config::{Config, PassName};
+use crate::hir::{SymbolTable, HIR};
+use crate::pass::Pass;
+use crate::util::PP;
+use std::io;
+
+/// prints the HIR after the inner pass if its name is in `Config::dump_hir_after`
+pub struct DumpHir<T, W = io::Stdout>(pub T, pub PassName, pub W);
+
+impl<T> DumpHir<T> {
+    pub fn new(pass: T, name: PassName) -> Self {
+        DumpHir(pass, name, io::stdout())
+    }
+}
+
+impl<T, W, In, E> Pass<In, E> for DumpHir<T, W>
+where
+    T: Pass<In, E, Target = (SymbolTable, HIR)>,
+    W: io::Write,
+{
+    type Target = (SymbolTable, HIR);
+
+    fn trans(&mut self, i: In, config: &Config) -> Result<Self::Target, E> {
+        let o = self.0.trans(i, config)?;
+        if config.dump_hir_after.contains(&self.1) {
+            writeln!(self.2, "(* HIR after {} *)", self.1).unwrap();
+            o.pp_with(&mut self.2, &config.pp).unwrap();
+        }
+        Ok(o)
+    }
+}
@@ -113,13 +113,14 @@ impl Transform for FlatExpr {
         }
     }
 
-    fn transform_app(&mut self, ty: HTy, fun: Box<Expr>, arg: Box<Expr>) -> Expr {
+    fn transform_app(&mut self, ty: HTy, fun: Box<Expr>, arg: Box<Expr>, is_tail: bool) -> Expr {
         let (fun, funval) = self.flat_make_val(*fun);
         let (arg, argval) = self.flat_make_val(*arg);
         let (ret, retval) = self.make_val(App {
             fun,
             arg,
             ty: ty.clone(),
+            is_tail,
         });
         Binds {
             ty,
@@ -19,7 +19,7 @@ fn take_binds(mut expr: Expr) -> (Expr, Vec<Val>) {
         }
         ExternCall {
             args,
-            ty,
+            retty,
             module,
             fun,
         } => {
@@ -28,7 +28,7 @@ fn take_binds(mut expr: Expr) -> (Expr, Vec<Val>) {
                 module,
                 fun,
                 args,
-                ty,
+                retty,
             };
             (expr, bindss.into_iter().flat_map(Vec::into_iter).collect())
         }
@@ -36,13 +36,19 @@ fn take_binds(mut expr: Expr) -> (Expr, Vec<Val>) {
             mut fun,
             mut arg,
             ty,
+            is_tail,
         } => {
             let (f, mut fbinds) = take_binds(*fun);
             let (a, mut abinds) = take_binds(*arg);
             fun = Box::new(f);
             arg = Box::new(a);
             fbinds.append(&mut abinds);
-            let expr = App { fun, arg, ty };
+            let expr = App {
+                fun,
+                arg,
+                ty,
+                is_tail,
+            };
             (expr, fbinds)
         }
         Case { mut expr, arms, ty } => {
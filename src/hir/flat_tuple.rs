@@ -0,0 +1,224 @@
+use crate::config::Config;
+use crate::hir::util::{Transform, Traverse};
+use crate::hir::*;
+use crate::pass::Pass;
+use crate::prim::*;
+use std::collections::{HashMap, HashSet};
+
+/// flattens the tuples built of tuples, like `(a, (b, c))` into `(a, b, c)`,
+/// when the inner tuples are only consumed by projections.
+pub struct FlatTuple {
+    // the tuples that contain tuples, with their elements
+    candidates: HashMap<Symbol, Vec<Elem>>,
+    // the names used other than by projections
+    escaped: HashSet<Symbol>,
+    // the elements of the tuples used other than by projections
+    escaped_elems: HashSet<(Symbol, u32)>,
+    layouts: HashMap<Symbol, Layout>,
+}
+
+// an element of a tuple to flatten
+enum Elem {
+    Tuple(Vec<HTy>),
+    Other(HTy),
+}
+
+// where the elements of a tuple are placed after flattening
+struct Layout {
+    ty: HTy,
+    offsets: Vec<u32>,
+    flattened: Vec<bool>,
+}
+
+struct Analyze<'a>(&'a mut FlatTuple);
+
+impl<'a> Traverse for Analyze<'a> {
+    fn traverse_val(&mut self, val: &mut Val) {
+        if let Expr::Tuple { tys, tuple } = &val.expr {
+            let elems = tys
+                .iter()
+                .zip(tuple)
+                .map(|(ty, elem)| match elem {
+                    Expr::Tuple { tys, .. } if !tys.is_empty() => Elem::Tuple(tys.clone()),
+                    _ => Elem::Other(ty.clone()),
+                })
+                .collect::<Vec<_>>();
+            let has_tuple = elems.iter().any(|elem| match elem {
+                Elem::Tuple(_) => true,
+                Elem::Other(_) => false,
+            });
+            if !val.rec && has_tuple {
+                self.0.candidates.insert(val.name.clone(), elems);
+            }
+        }
+        self.traverse_expr(&mut val.expr)
+    }
+
+    fn traverse_proj(&mut self, _ty: &mut HTy, index: &mut u32, tuple: &mut Box<Expr>) {
+        match &mut **tuple {
+            // `#j (#i t)`
+            Expr::Proj { tuple, .. } if is_sym(&**tuple) => (),
+            // `#i t` used as a whole
+            Expr::Sym { name, .. } => {
+                self.0.escaped_elems.insert((name.clone(), *index));
+            }
+            tuple => self.traverse_expr(tuple),
+        }
+    }
+
+    fn traverse_closure(
+        &mut self,
+        envs: &mut Vec<(HTy, Symbol)>,
+        _param_ty: &mut HTy,
+        _body_ty: &mut HTy,
+        _fname: &mut Symbol,
+    ) {
+        for (_, name) in envs {
+            self.0.escaped.insert(name.clone());
+        }
+    }
+
+    fn traverse_sym(&mut self, _ty: &mut HTy, name: &mut Symbol) {
+        self.0.escaped.insert(name.clone());
+    }
+}
+
+fn is_sym(expr: &Expr) -> bool {
+    match expr {
+        Expr::Sym { .. } => true,
+        _ => false,
+    }
+}
+
+impl FlatTuple {
+    pub fn new() -> Self {
+        Self {
+            candidates: HashMap::new(),
+            escaped: HashSet::new(),
+            escaped_elems: HashSet::new(),
+            layouts: HashMap::new(),
+        }
+    }
+
+    fn layout(&self, name: &Symbol, elems: &[Elem]) -> Option<Layout> {
+        if self.escaped.contains(name) {
+            return None;
+        }
+        let mut elem_tys = Vec::new();
+        let mut offsets = Vec::new();
+        let mut flattened = Vec::new();
+        for (i, elem) in elems.iter().enumerate() {
+            offsets.push(elem_tys.len() as u32);
+            match elem {
+                Elem::Tuple(tys) if !self.escaped_elems.contains(&(name.clone(), i as u32)) => {
+                    elem_tys.extend(tys.iter().cloned());
+                    flattened.push(true);
+                }
+                Elem::Tuple(tys) => {
+                    elem_tys.push(HTy::Tuple(tys.clone()));
+                    flattened.push(false);
+                }
+                Elem::Other(ty) => {
+                    elem_tys.push(ty.clone());
+                    flattened.push(false);
+                }
+            }
+        }
+        if flattened.iter().all(|f| !f) {
+            return None;
+        }
+        Some(Layout {
+            ty: HTy::Tuple(elem_tys),
+            offsets,
+            flattened,
+        })
+    }
+
+    fn flat_hir(&mut self, mut hir: HIR) -> HIR {
+        Analyze(self).traverse_hir(&mut hir);
+        let layouts = self
+            .candidates
+            .iter()
+            .filter_map(|(name, elems)| Some((name.clone(), self.layout(name, elems)?)))
+            .collect();
+        self.layouts = layouts;
+        self.transform_hir(hir)
+    }
+}
+
+impl Transform for FlatTuple {
+    fn transform_val(&mut self, mut val: Val) -> Val {
+        val.expr = self.transform_expr(val.expr);
+        if let Some(layout) = self.layouts.get(&val.name) {
+            let elems = match val.expr {
+                Expr::Tuple { tuple, .. } => tuple,
+                _ => unreachable!("flattening a non-tuple"),
+            };
+            let mut tuple = Vec::new();
+            for (elem, flattened) in elems.into_iter().zip(&layout.flattened) {
+                match (elem, *flattened) {
+                    (Expr::Tuple { tuple: inner, .. }, true) => tuple.extend(inner),
+                    (elem, _) => tuple.push(elem),
+                }
+            }
+            let tys = match &layout.ty {
+                HTy::Tuple(tys) => tys.clone(),
+                _ => unreachable!(),
+            };
+            val.ty = layout.ty.clone();
+            val.expr = Expr::Tuple { tys, tuple };
+        }
+        val
+    }
+
+    fn transform_proj(&mut self, ty: HTy, index: u32, tuple: Box<Expr>) -> Expr {
+        let target = match &*tuple {
+            // `#j (#i t)` => `#(i' + j) t`
+            Expr::Proj {
+                index: i,
+                tuple: inner,
+                ..
+            } => match &**inner {
+                Expr::Sym { name, .. } => self
+                    .layouts
+                    .get(name)
+                    .filter(|layout| layout.flattened[*i as usize])
+                    .map(|layout| (layout.offsets[*i as usize] + index, name.clone())),
+                _ => None,
+            },
+            // `#i t` => `#i' t`
+            Expr::Sym { name, .. } => self
+                .layouts
+                .get(name)
+                .map(|layout| (layout.offsets[index as usize], name.clone())),
+            _ => None,
+        };
+        match target {
+            Some((index, name)) => Expr::Proj {
+                ty,
+                index,
+                tuple: Box::new(Expr::Sym {
+                    ty: self.layouts[&name].ty.clone(),
+                    name,
+                }),
+            },
+            None => Expr::Proj {
+                ty,
+                index,
+                tuple: Box::new(self.transform_expr(*tuple)),
+            },
+        }
+    }
+}
+
+impl<E> Pass<(SymbolTable, HIR), E> for FlatTuple {
+    type Target = (SymbolTable, HIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, hir): (SymbolTable, HIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        Ok((symbol_table, self.flat_hir(hir)))
+    }
+}
@@ -72,16 +72,21 @@ impl<'a> Traverse for Trav<'a> {
                 return;
             }
             ExternCall {
-                ty,
+                retty,
                 module,
                 fun,
                 args,
             } => {
-                self.traverse_extern_call(ty, module, fun, args);
+                self.traverse_extern_call(retty, module, fun, args);
                 return;
             }
-            App { ty, fun, arg } => {
-                self.traverse_app(ty, fun, arg);
+            App {
+                ty,
+                fun,
+                arg,
+                is_tail,
+            } => {
+                self.traverse_app(ty, fun, arg, is_tail);
                 return;
             }
             Case { ty, expr, arms } => {
@@ -142,7 +147,13 @@ impl<'a> Traverse for Trav<'a> {
         self.with_bound(false, |this| this.traverse_expr(body))
     }
 
-    fn traverse_app(&mut self, _ty: &mut HTy, fun: &mut Box<Expr>, arg: &mut Box<Expr>) {
+    fn traverse_app(
+        &mut self,
+        _ty: &mut HTy,
+        fun: &mut Box<Expr>,
+        arg: &mut Box<Expr>,
+        _is_tail: &mut bool,
+    ) {
         self.with_bound(false, |this| {
             this.traverse_expr(fun);
             this.traverse_expr(arg);
@@ -208,7 +219,13 @@ impl<'a> Traverse for Reg<'a> {
         });
     }
 
-    fn traverse_app(&mut self, _ty: &mut HTy, fun: &mut Box<Expr>, arg: &mut Box<Expr>) {
+    fn traverse_app(
+        &mut self,
+        _ty: &mut HTy,
+        fun: &mut Box<Expr>,
+        arg: &mut Box<Expr>,
+        _is_tail: &mut bool,
+    ) {
         self.with_bound_name(None, |this| {
             this.traverse_expr(fun);
             this.traverse_expr(arg);
@@ -0,0 +1,234 @@
+use crate::config::Config;
+use crate::hir::util::Transform;
+use crate::hir::*;
+use crate::id::Id;
+use crate::pass::Pass;
+use crate::prim::*;
+use std::collections::HashMap;
+
+pub struct Inline {
+    id: Id,
+    threshold: usize,
+    // non-recursive functions bound to names, with their params and bodies
+    funs: HashMap<Symbol, ((HTy, Symbol), Expr)>,
+}
+
+// copies an expression giving fresh names to the binders in it,
+// so that the copy can be placed next to the original.
+// the symbols in `map` are replaced with their values.
+struct Alpha<'a> {
+    id: &'a mut Id,
+    map: HashMap<Symbol, Expr>,
+}
+
+// the size of `expr`, counting the occurrences of `name` in `uses`
+fn size(expr: &Expr, name: &Symbol, uses: &mut usize) -> usize {
+    use crate::hir::Expr::*;
+    let children: Vec<&Expr> = match expr {
+        Binds { binds, ret, .. } => binds
+            .iter()
+            .map(|val| &val.expr)
+            .chain(Some(&**ret))
+            .collect(),
+        BuiltinCall { args, .. } | ExternCall { args, .. } => args.iter().collect(),
+        Fun { body, .. } => vec![&**body],
+        App { fun, arg, .. } => vec![&**fun, &**arg],
+        Case { expr, arms, .. } => Some(&**expr)
+            .into_iter()
+            .chain(arms.iter().map(|(_, arm)| arm))
+            .collect(),
+        Tuple { tuple, .. } => tuple.iter().collect(),
+        Proj { tuple, .. } => vec![&**tuple],
+        Constructor { arg, .. } => arg.iter().map(|arg| &**arg).collect(),
+        Closure { envs, fname, .. } => {
+            *uses += envs.iter().filter(|(_, env)| env == name).count();
+            *uses += (fname == name) as usize;
+            vec![]
+        }
+        Sym { name: sym, .. } => {
+            *uses += (sym == name) as usize;
+            vec![]
+        }
+        Lit { .. } => vec![],
+    };
+    1 + children
+        .into_iter()
+        .map(|child| size(child, name, uses))
+        .sum::<usize>()
+}
+
+impl<'a> Alpha<'a> {
+    fn fresh(&mut self, ty: &HTy, name: &Symbol) -> Symbol {
+        let new = Symbol(name.0.clone(), self.id.next());
+        self.map.insert(
+            name.clone(),
+            Expr::Sym {
+                ty: ty.clone(),
+                name: new.clone(),
+            },
+        );
+        new
+    }
+
+    fn transform_pattern(&mut self, pat: Pattern) -> Pattern {
+        // the alternatives of or-patterns bind the same names, which are renamed alike
+        let mut renamed = HashMap::new();
+        pat.map_binds(&mut |ty, name| {
+            renamed
+                .entry(name.clone())
+                .or_insert_with(|| self.fresh(ty, &name))
+                .clone()
+        })
+    }
+}
+
+impl<'a> Transform for Alpha<'a> {
+    fn transform_binds(&mut self, ty: HTy, binds: Vec<Val>, ret: Box<Expr>) -> Expr {
+        // rename all the names first for recursive binds
+        let binds = binds
+            .into_iter()
+            .map(|mut val| {
+                val.name = self.fresh(&val.ty, &val.name);
+                val
+            })
+            .collect::<Vec<_>>();
+        Expr::Binds {
+            ty,
+            binds: binds
+                .into_iter()
+                .map(|val| self.transform_val(val))
+                .collect(),
+            ret: Box::new(self.transform_expr(*ret)),
+        }
+    }
+
+    fn transform_fun(
+        &mut self,
+        (param_ty, param): (HTy, Symbol),
+        body_ty: HTy,
+        body: Box<Expr>,
+        captures: Vec<(HTy, Symbol)>,
+    ) -> Expr {
+        let param = self.fresh(&param_ty, &param);
+        Expr::Fun {
+            param: (param_ty, param),
+            body_ty,
+            captures,
+            body: Box::new(self.transform_expr(*body)),
+        }
+    }
+
+    fn transform_case(&mut self, ty: HTy, cond: Box<Expr>, arms: Vec<(Pattern, Expr)>) -> Expr {
+        Expr::Case {
+            ty,
+            expr: Box::new(self.transform_expr(*cond)),
+            arms: arms
+                .into_iter()
+                .map(|(pat, arm)| (self.transform_pattern(pat), self.transform_expr(arm)))
+                .collect(),
+        }
+    }
+
+    fn transform_sym(&mut self, ty: HTy, name: Symbol) -> Expr {
+        match self.map.get(&name) {
+            Some(expr) => expr.clone(),
+            None => Expr::Sym { ty, name },
+        }
+    }
+}
+
+impl Inline {
+    pub fn new(id: Id) -> Self {
+        Self {
+            id,
+            threshold: 0,
+            funs: HashMap::new(),
+        }
+    }
+
+    fn is_inlinable(&self, param: &Symbol, body: &Expr) -> bool {
+        let mut uses = 0;
+        let size = size(body, param, &mut uses);
+        uses <= 1 || size < self.threshold
+    }
+
+    // `(fn param => body) arg` => `let val param' = arg in body' end`
+    // where the binders in `body'` are renamed.
+    // simple arguments are substituted instead of bound.
+    fn inline(&mut self, ty: HTy, (param_ty, param): (HTy, Symbol), body: Expr, arg: Expr) -> Expr {
+        let mut alpha = Alpha {
+            id: &mut self.id,
+            map: HashMap::new(),
+        };
+        match arg {
+            Expr::Sym { .. } | Expr::Lit { .. } => {
+                alpha.map.insert(param, arg);
+                alpha.transform_expr(body)
+            }
+            arg => {
+                let name = alpha.fresh(&param_ty, &param);
+                let body = alpha.transform_expr(body);
+                Expr::Binds {
+                    ty,
+                    binds: vec![Val {
+                        ty: param_ty,
+                        rec: false,
+                        name,
+                        expr: arg,
+                    }],
+                    ret: Box::new(body),
+                }
+            }
+        }
+    }
+}
+
+impl Transform for Inline {
+    fn transform_val(&mut self, mut val: Val) -> Val {
+        val.expr = self.transform_expr(val.expr);
+        if let Expr::Fun { param, body, .. } = &val.expr {
+            // `rec` is not set for all the recursive functions
+            let mut uses = 0;
+            size(body, &val.name, &mut uses);
+            if !val.rec && uses == 0 {
+                self.funs
+                    .insert(val.name.clone(), (param.clone(), (**body).clone()));
+            }
+        }
+        val
+    }
+
+    fn transform_app(&mut self, ty: HTy, fun: Box<Expr>, arg: Box<Expr>, is_tail: bool) -> Expr {
+        let fun = self.transform_expr(*fun);
+        let arg = self.transform_expr(*arg);
+        let callee = match &fun {
+            Expr::Fun { param, body, .. } => Some((param.clone(), (**body).clone())),
+            Expr::Sym { name, .. } => self.funs.get(name).cloned(),
+            _ => None,
+        };
+        if let Some((param, body)) = callee {
+            if self.is_inlinable(&param.1, &body) {
+                return self.inline(ty, param, body, arg);
+            }
+        }
+        Expr::App {
+            ty,
+            fun: Box::new(fun),
+            arg: Box::new(arg),
+            is_tail,
+        }
+    }
+}
+
+impl<E> Pass<(SymbolTable, HIR), E> for Inline {
+    type Target = (SymbolTable, HIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, hir): (SymbolTable, HIR),
+        config: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        self.threshold = config.inline_threshold;
+        Ok((symbol_table, self.transform_hir(hir)))
+    }
+}
@@ -0,0 +1,75 @@
+use crate::config::Config;
+use crate::hir::*;
+use crate::pass::Pass;
+
+pub struct MarkTail;
+
+impl MarkTail {
+    pub fn new() -> Self {
+        MarkTail
+    }
+
+    fn mark_hir(&mut self, hir: &mut HIR) {
+        for val in hir.0.iter_mut() {
+            // top level values are not returned from any functions
+            self.mark_expr(&mut val.expr, false)
+        }
+    }
+
+    // `tail` tells whether the value of `expr` is returned from the enclosing function as is
+    fn mark_expr(&mut self, expr: &mut Expr, tail: bool) {
+        use crate::hir::Expr::*;
+        match expr {
+            Binds { binds, ret, .. } => {
+                for val in binds.iter_mut() {
+                    self.mark_expr(&mut val.expr, false)
+                }
+                self.mark_expr(ret, tail)
+            }
+            BuiltinCall { args, .. } | ExternCall { args, .. } => {
+                for arg in args.iter_mut() {
+                    self.mark_expr(arg, false)
+                }
+            }
+            Fun { body, .. } => self.mark_expr(body, true),
+            App {
+                fun, arg, is_tail, ..
+            } => {
+                *is_tail = tail;
+                self.mark_expr(fun, false);
+                self.mark_expr(arg, false);
+            }
+            Case { expr, arms, .. } => {
+                self.mark_expr(expr, false);
+                for (_, arm) in arms.iter_mut() {
+                    self.mark_expr(arm, tail)
+                }
+            }
+            Tuple { tuple, .. } => {
+                for t in tuple.iter_mut() {
+                    self.mark_expr(t, false)
+                }
+            }
+            Proj { tuple, .. } => self.mark_expr(tuple, false),
+            Constructor { arg, .. } => {
+                if let Some(arg) = arg {
+                    self.mark_expr(arg, false)
+                }
+            }
+            Closure { .. } | Sym { .. } | Lit { .. } => (),
+        }
+    }
+}
+
+impl<E> Pass<(SymbolTable, HIR), E> for MarkTail {
+    type Target = (SymbolTable, HIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, mut hir): (SymbolTable, HIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        self.mark_hir(&mut hir);
+        Ok((symbol_table, hir))
+    }
+}
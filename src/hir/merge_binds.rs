@@ -0,0 +1,112 @@
+use crate::config::Config;
+use crate::hir::util::Transform;
+use crate::hir::*;
+use crate::id::Id;
+use crate::pass::Pass;
+use crate::prim::*;
+use std::collections::{HashMap, HashSet};
+
+/// merges the nested binds `let a in let b in e end end` into `let a; b in e end`.
+/// the inner bindings that shadow the outer ones are renamed.
+pub struct MergeBinds {
+    id: Id,
+}
+
+// replaces the occurrences of the names
+struct Rename<'a> {
+    map: &'a HashMap<Symbol, Symbol>,
+}
+
+impl<'a> Transform for Rename<'a> {
+    fn transform_closure(
+        &mut self,
+        envs: Vec<(HTy, Symbol)>,
+        param_ty: HTy,
+        body_ty: HTy,
+        fname: Symbol,
+    ) -> Expr {
+        Expr::Closure {
+            envs: envs
+                .into_iter()
+                .map(|(ty, name)| (ty, self.map.get(&name).cloned().unwrap_or(name)))
+                .collect(),
+            param_ty,
+            body_ty,
+            fname,
+        }
+    }
+
+    fn transform_sym(&mut self, ty: HTy, name: Symbol) -> Expr {
+        let name = self.map.get(&name).cloned().unwrap_or(name);
+        Expr::Sym { ty, name }
+    }
+}
+
+impl MergeBinds {
+    pub fn new(id: Id) -> Self {
+        Self { id }
+    }
+
+    fn rename(&mut self, map: &HashMap<Symbol, Symbol>, expr: Expr) -> Expr {
+        if map.is_empty() {
+            return expr;
+        }
+        Rename { map }.transform_expr(expr)
+    }
+}
+
+impl Transform for MergeBinds {
+    fn transform_binds(&mut self, ty: HTy, binds: Vec<Val>, ret: Box<Expr>) -> Expr {
+        let mut binds = binds
+            .into_iter()
+            .map(|val| self.transform_val(val))
+            .collect::<Vec<_>>();
+        let mut ret = self.transform_expr(*ret);
+        let mut bound = binds
+            .iter()
+            .map(|val| val.name.clone())
+            .collect::<HashSet<_>>();
+        while let Expr::Binds {
+            binds: inner,
+            ret: inner_ret,
+            ..
+        } = ret
+        {
+            let mut map = HashMap::new();
+            for mut val in inner {
+                if bound.contains(&val.name) {
+                    let name = Symbol(val.name.0.clone(), self.id.next());
+                    // a recursive binding refers to itself
+                    if val.rec {
+                        map.insert(val.name.clone(), name.clone());
+                    }
+                    val.expr = self.rename(&map, val.expr);
+                    map.insert(val.name, name.clone());
+                    val.name = name;
+                } else {
+                    val.expr = self.rename(&map, val.expr);
+                }
+                bound.insert(val.name.clone());
+                binds.push(val);
+            }
+            ret = self.rename(&map, *inner_ret);
+        }
+        Expr::Binds {
+            ty,
+            binds,
+            ret: Box::new(ret),
+        }
+    }
+}
+
+impl<E> Pass<(SymbolTable, HIR), E> for MergeBinds {
+    type Target = (SymbolTable, HIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, hir): (SymbolTable, HIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        Ok((symbol_table, self.transform_hir(hir)))
+    }
+}
@@ -1,16 +1,50 @@
 pub mod ast2hir;
+pub mod beta_reduce;
+pub mod const_fold;
+pub mod copy_prop;
+pub mod cse;
+pub mod dead_bind_elim;
+pub mod decision_tree;
+pub mod dump;
 pub mod flat_expr;
 pub mod flat_let;
+pub mod flat_tuple;
 pub mod force_closure;
+pub mod inline;
+pub mod mark_tail;
+pub mod merge_binds;
+pub mod pipeline;
 pub mod pp;
+pub mod prune_captures;
+pub mod simplify;
+pub mod strength_reduce;
+pub mod unbox_datatype;
 pub mod unnest_func;
 pub mod util;
+pub mod verify;
 
 pub use self::ast2hir::AST2HIR;
+pub use self::beta_reduce::BetaReduce;
+pub use self::const_fold::ConstFold;
+pub use self::copy_prop::CopyProp;
+pub use self::cse::CSE;
+pub use self::dead_bind_elim::DeadBindElim;
+pub use self::decision_tree::DecisionTree;
+pub use self::dump::DumpHir;
 pub use self::flat_expr::FlatExpr;
 pub use self::flat_let::FlatLet;
+pub use self::flat_tuple::FlatTuple;
 pub use self::force_closure::ForceClosure;
+pub use self::inline::Inline;
+pub use self::mark_tail::MarkTail;
+pub use self::merge_binds::MergeBinds;
+pub use self::pipeline::Pipeline;
+pub use self::prune_captures::PruneCaptures;
+pub use self::simplify::Simplify;
+pub use self::strength_reduce::StrengthReduce;
+pub use self::unbox_datatype::UnboxDatatype;
 pub use self::unnest_func::UnnestFunc;
+pub use self::verify::{verify, HirError, Verify};
 use std::collections::HashMap;
 
 use crate::prim::*;
@@ -18,7 +52,7 @@ use crate::prim::*;
 #[derive(Debug)]
 pub struct HIR(pub Vec<Val>);
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Val {
     pub ty: HTy,
     pub rec: bool,
@@ -26,7 +60,7 @@ pub struct Val {
     pub expr: Expr,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Binds {
         ty: HTy,
@@ -39,7 +73,8 @@ pub enum Expr {
         args: Vec<Expr>,
     },
     ExternCall {
-        ty: HTy,
+        /// the results. more than one makes a tuple
+        retty: Vec<HTy>,
         module: String,
         fun: String,
         args: Vec<Expr>,
@@ -60,6 +95,8 @@ pub enum Expr {
         ty: HTy,
         fun: Box<Expr>,
         arg: Box<Expr>,
+        /// whether the result is returned from the function as is. set by `MarkTail`
+        is_tail: bool,
     },
     Case {
         ty: HTy,
@@ -101,34 +138,58 @@ pub enum Pattern {
         value: u32,
         ty: HTy,
     },
+    /// compared by the equality of strings, lowered by `DecisionTree`
+    Str {
+        value: String,
+        ty: HTy,
+    },
     Constructor {
         descriminant: u32,
-        arg: Option<(HTy, Symbol)>,
+        arg: Option<Box<Pattern>>,
         ty: HTy,
     },
     Var {
         name: Symbol,
         ty: HTy,
     },
+    Wildcard {
+        ty: HTy,
+    },
     Tuple {
         tys: Vec<HTy>,
-        tuple: Vec<Symbol>,
+        tuple: Vec<Pattern>,
     },
+    As {
+        name: Symbol,
+        pattern: Box<Pattern>,
+        ty: HTy,
+    },
+    Or(Vec<Pattern>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct SymbolTable {
     pub types: HashMap<Symbol, TypeInfo>,
+    /// the top level functions exported from the wasm module, to their exported names
+    pub exports: HashMap<Symbol, String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum HTy {
+    Unit,
     Char,
+    String,
     Int,
+    Word,
     Real,
+    Bool,
     Fun(Box<HTy>, Box<HTy>),
     Tuple(Vec<HTy>),
-    Datatype(Symbol),
+    Datatype(Symbol, Vec<HTy>),
+    /// the builtin list, represented as `datatype 'a list = nil | :: of 'a * 'a list`
+    List(Box<HTy>),
+    /// the mutable cell made by `ref`, boxed to be shared by its aliases
+    Ref(Box<HTy>),
     // Datatype(Vec<(u32, Option<HTy>)>),
 }
 
@@ -143,6 +204,7 @@ impl Expr {
             ty,
             fun: Box::new(self),
             arg: Box::new(e),
+            is_tail: false,
         }
     }
 
@@ -160,11 +222,14 @@ impl Expr {
                 ref body_ty,
                 ..
             } => HTy::fun(param_ty.clone(), body_ty.clone()),
+            &Tuple { ref tys, .. } if tys.is_empty() => HTy::Unit,
             &Tuple { ref tys, .. } => HTy::Tuple(tys.clone()),
+            &ExternCall { ref retty, .. } if retty.len() == 1 => retty[0].clone(),
+            &ExternCall { ref retty, .. } if retty.is_empty() => HTy::Unit,
+            &ExternCall { ref retty, .. } => HTy::Tuple(retty.clone()),
             &Proj { ref ty, .. }
             | &Binds { ref ty, .. }
             | &BuiltinCall { ref ty, .. }
-            | &ExternCall { ref ty, .. }
             | &App { ref ty, .. }
             | &Case { ref ty, .. }
             | &Constructor { ref ty, .. }
@@ -175,34 +240,142 @@ impl Expr {
 }
 
 impl Pattern {
-    pub fn match_key(&self) -> u32 {
+    pub fn ty(&self) -> HTy {
         use self::Pattern::*;
-        // FIXME do not panic
         match self {
-            Constant { value, .. } => *value as u32,
-            Char { value, .. } => *value,
-            Tuple { .. } => panic!("bug: non-variant expression does not have keys"),
-            Constructor { descriminant, .. } => *descriminant as u32,
-            Var { .. } => panic!("bug: default like branch does not have keys"),
+            Constant { ty, .. }
+            | Char { ty, .. }
+            | Str { ty, .. }
+            | Constructor { ty, .. }
+            | Var { ty, .. }
+            | Wildcard { ty }
+            | As { ty, .. } => ty.clone(),
+            Tuple { tys, .. } if tys.is_empty() => HTy::Unit,
+            Tuple { tys, .. } => HTy::Tuple(tys.clone()),
+            Or(alternatives) => alternatives[0].ty(),
         }
     }
 
-    pub fn binds(&self) -> Option<Symbol> {
+    /// the value the pattern tests. the integer constants are compared in full,
+    /// so that the ones that differ only in the upper bits are not confused
+    pub fn match_key(&self) -> i64 {
         use self::Pattern::*;
         // FIXME do not panic
         match self {
-            Constant { .. } | Char { .. } => None,
+            Constant { value, .. } => *value,
+            Char { value, .. } => *value as i64,
             Tuple { .. } => panic!("bug: non-variant expression does not have keys"),
-            Constructor { arg, .. } => arg.as_ref().map(|(_, name)| name.clone()),
-            Var { name, .. } => Some(name.clone()),
+            Str { .. } => panic!("bug: strings are not switched on but compared"),
+            Constructor { descriminant, .. } => *descriminant as i64,
+            Var { .. } | Wildcard { .. } => {
+                panic!("bug: default like branch does not have keys")
+            }
+            As { pattern, .. } => pattern.match_key(),
+            Or(_) => panic!("bug: or-pattern has the keys of the alternatives"),
+        }
+    }
+
+    pub fn binds(&self) -> Vec<Symbol> {
+        use self::Pattern::*;
+        match self {
+            Constant { .. } | Char { .. } | Str { .. } | Wildcard { .. } => vec![],
+            Tuple { tuple, .. } => tuple.iter().flat_map(|pat| pat.binds()).collect(),
+            Constructor { arg, .. } => arg.iter().flat_map(|pat| pat.binds()).collect(),
+            Var { name, .. } => vec![name.clone()],
+            As { name, pattern, .. } => {
+                let mut binds = vec![name.clone()];
+                binds.extend(pattern.binds());
+                binds
+            }
+            // the names bound in all the alternatives
+            Or(alternatives) => match alternatives.split_first() {
+                Some((first, rest)) => first
+                    .binds()
+                    .into_iter()
+                    .filter(|name| rest.iter().all(|pat| pat.binds().contains(name)))
+                    .collect(),
+                None => vec![],
+            },
         }
     }
 
+    /// the patterns without or-patterns, matching the same values as `self` in order
+    pub fn alternatives(self) -> Vec<Pattern> {
+        use self::Pattern::*;
+        match self {
+            Or(alternatives) => alternatives
+                .into_iter()
+                .flat_map(|pat| pat.alternatives())
+                .collect(),
+            As { name, pattern, ty } => pattern
+                .alternatives()
+                .into_iter()
+                .map(|pat| As {
+                    name: name.clone(),
+                    pattern: Box::new(pat),
+                    ty: ty.clone(),
+                })
+                .collect(),
+            pat => vec![pat],
+        }
+    }
+
+    /// the pattern with the names it binds replaced by `f`
+    pub fn map_binds(self, f: &mut dyn FnMut(&HTy, Symbol) -> Symbol) -> Pattern {
+        use self::Pattern::*;
+        match self {
+            Constructor {
+                descriminant,
+                arg,
+                ty,
+            } => Constructor {
+                descriminant,
+                arg: arg.map(|pat| Box::new(pat.map_binds(f))),
+                ty,
+            },
+            Var { name, ty } => Var {
+                name: f(&ty, name),
+                ty,
+            },
+            Tuple { tys, tuple } => Tuple {
+                tuple: tuple.into_iter().map(|pat| pat.map_binds(f)).collect(),
+                tys,
+            },
+            As { name, pattern, ty } => As {
+                name: f(&ty, name),
+                pattern: Box::new(pattern.map_binds(f)),
+                ty,
+            },
+            Or(alternatives) => Or(alternatives
+                .into_iter()
+                .map(|pat| pat.map_binds(f))
+                .collect()),
+            pat @ Constant { .. }
+            | pat @ Char { .. }
+            | pat @ Str { .. }
+            | pat @ Wildcard { .. } => pat,
+        }
+    }
+
+    /// the pattern under the `As` layers, with the names they bind to the whole value
+    pub fn strip_as(self) -> (Vec<(HTy, Symbol)>, Pattern) {
+        let mut names = Vec::new();
+        let mut pat = self;
+        while let Pattern::As { name, pattern, ty } = pat {
+            names.push((ty, name));
+            pat = *pattern;
+        }
+        (names, pat)
+    }
+
     pub fn is_irrefutable(&self) -> bool {
         use self::Pattern::*;
-        match *self {
-            Constructor { .. } | Constant { .. } | Char { .. } => false,
-            Tuple { .. } | Var { .. } => true,
+        match self {
+            Constructor { .. } | Constant { .. } | Char { .. } | Str { .. } => false,
+            Tuple { tuple, .. } => tuple.iter().all(|pat| pat.is_irrefutable()),
+            Var { .. } | Wildcard { .. } => true,
+            As { pattern, .. } => pattern.is_irrefutable(),
+            Or(alternatives) => alternatives.iter().any(|pat| pat.is_irrefutable()),
         }
     }
 }
@@ -0,0 +1,70 @@
+use crate::config::{Config, PassName};
+use crate::hir::*;
+use crate::id::Id;
+use crate::pass::{Pass, PrintablePass, TimedPass};
+
+/// runs the passes over HIR listed in `Config::pipeline` in order,
+/// skipping those above `Config::opt_level`.
+/// expects the pipeline is checked by `Config::check_pipeline`
+pub struct Pipeline {
+    id: Id,
+}
+
+type BoxedPass<E> = Box<dyn Pass<(SymbolTable, HIR), E, Target = (SymbolTable, HIR)>>;
+
+// the labels are the ones `Config::pretty_print_ir` and `Timings` use
+fn boxed<T, E>(pass: T, name: PassName, label: &'static str) -> BoxedPass<E>
+where
+    T: Pass<(SymbolTable, HIR), E, Target = (SymbolTable, HIR)> + 'static,
+{
+    Box::new(PrintablePass(
+        TimedPass(DumpHir::new(pass, name), label),
+        label,
+    ))
+}
+
+impl Pipeline {
+    pub fn new(id: Id) -> Self {
+        Pipeline { id }
+    }
+
+    fn pass<E>(&self, name: PassName) -> BoxedPass<E> {
+        let id = self.id.clone();
+        match name {
+            PassName::Ast2Hir => panic!("ast2hir is not a pass over HIR"),
+            PassName::UnboxDatatype => boxed(UnboxDatatype::new(), name, "datatype_unboxing"),
+            PassName::BetaReduce => boxed(BetaReduce::new(), name, "beta_reduction"),
+            PassName::Inline => boxed(Inline::new(id), name, "inlining"),
+            PassName::ConstFold => boxed(ConstFold::new(), name, "constant_folding"),
+            PassName::StrengthReduce => boxed(StrengthReduce::new(), name, "strength_reduction"),
+            PassName::Simplify => boxed(Simplify::new(id), name, "simplification"),
+            PassName::DeadBindElim => boxed(DeadBindElim::new(), name, "dead_bind_elimination"),
+            PassName::FlatTuple => boxed(FlatTuple::new(), name, "tuple_flattening"),
+            PassName::MarkTail => boxed(MarkTail::new(), name, "tail_call_marking"),
+            PassName::FlatExpr => boxed(FlatExpr::new(id), name, "flattening_expression"),
+            PassName::FlatLet => boxed(FlatLet::new(), name, "flattening_let"),
+            PassName::MergeBinds => boxed(MergeBinds::new(id), name, "binds_merging"),
+            PassName::Cse => boxed(CSE::new(), name, "common_subexpression_elimination"),
+            PassName::CopyProp => boxed(CopyProp::new(), name, "copy_propagation"),
+            PassName::UnnestFunc => boxed(UnnestFunc::new(id), name, "unnest_functions"),
+            PassName::ForceClosure => boxed(ForceClosure::new(), name, "closure_conversion"),
+            PassName::PruneCaptures => boxed(PruneCaptures::new(), name, "capture_pruning"),
+            PassName::DecisionTree => boxed(DecisionTree::new(id), name, "match_compilation"),
+        }
+    }
+}
+
+impl<E> Pass<(SymbolTable, HIR), E> for Pipeline {
+    type Target = (SymbolTable, HIR);
+
+    fn trans(&mut self, mut hir: (SymbolTable, HIR), config: &Config) -> Result<Self::Target, E> {
+        let enabled = config
+            .pipeline
+            .iter()
+            .filter(|name| name.opt_level() <= config.opt_level);
+        for name in enabled {
+            hir = self.pass(*name).trans(hir, config)?;
+        }
+        Ok(hir)
+    }
+}
@@ -1,212 +1,422 @@
-use std::io;
+use std::io::{self, Write};
 
 use crate::hir::*;
-use crate::util::PP;
+use crate::util::{ColorMode, Highlight, PpConfig, PP};
 
 impl PP for (SymbolTable, HIR) {
     fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
         self.1.pp(w, indent)
     }
+
+    fn pp_with<W: io::Write>(&self, w: &mut W, config: &PpConfig) -> io::Result<()> {
+        self.1.pp_with(w, config)
+    }
 }
 
 impl PP for HIR {
     fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
-        for bind in &self.0 {
-            bind.pp(w, indent)?;
-            write!(w, "\n")?;
-        }
-        Ok(())
+        pp_hir(self, w, &PpConfig::default(), indent)
+    }
+
+    fn pp_with<W: io::Write>(&self, w: &mut W, config: &PpConfig) -> io::Result<()> {
+        pp_hir(self, w, config, 0)
     }
 }
 
 impl PP for Val {
     fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
-        let rec = if self.rec { "rec " } else { "" };
-        write!(w, "{}val{} ", Self::nspaces(indent), rec)?;
-        self.name.pp(w, indent)?;
-        write!(w, ": ")?;
-        self.ty.pp(w, indent)?;
-        write!(w, " = ")?;
-        self.expr.pp(w, indent + 4)?;
-        Ok(())
+        pp_val(self, w, &PpConfig::default(), indent)
     }
 }
 
+/// pseudo SML. the internal constructs without surface syntax are in angle brackets,
+/// and the variables closures capture are in comments.
 impl PP for Expr {
     fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
-        use crate::hir::Expr::*;
-        match self {
-            Binds { binds, ret, .. } => {
-                let ind = Self::nspaces(indent);
-                let nextind = Self::nspaces(indent + 4);
-                write!(w, "let\n")?;
-                for val in binds {
-                    val.pp(w, indent + 4)?;
-                    write!(w, "\n")?;
-                }
-                write!(w, "{}in\n{}", ind, nextind)?;
-                ret.pp(w, indent + 4)?;
-                write!(w, "\n{}end", ind)?;
-            }
-            Fun {
-                body,
-                param,
-                captures,
-                ..
-            } => {
-                write!(w, "fun(")?;
-                inter_iter! {
-                    captures,
-                    write!(w, ", ")?,
-                    |(_,  cap)| => {
-                        cap.pp(w, indent)?
-                    }
-                }
-                write!(w, ") ")?;
-                param.1.pp(w, indent)?;
-                write!(w, " => ")?;
-                body.pp(w, indent + 4)?;
+        pp_expr(self, w, &PpConfig::default(), indent)
+    }
+}
+
+fn pp_hir<W: io::Write>(hir: &HIR, w: &mut W, config: &PpConfig, indent: usize) -> io::Result<()> {
+    for bind in &hir.0 {
+        pp_val(bind, w, config, indent)?;
+        write!(w, "\n")?;
+    }
+    Ok(())
+}
+
+fn pp_val<W: io::Write>(val: &Val, w: &mut W, config: &PpConfig, indent: usize) -> io::Result<()> {
+    write!(w, "{}", Val::nspaces(indent))?;
+    config.keyword(w, "val")?;
+    write!(w, " ")?;
+    if val.rec {
+        config.keyword(w, "rec")?;
+        write!(w, " ")?;
+    }
+    val.name.pp(w, indent)?;
+    write!(w, ": ")?;
+    val.ty.pp(w, indent)?;
+    write!(w, " = ")?;
+    pp_expr(&val.expr, w, config, indent)
+}
+
+// whether `expr` is printed as a single term that needs no parentheses
+// as a function or an argument of an application
+fn is_atomic(expr: &Expr) -> bool {
+    use crate::hir::Expr::*;
+    match expr {
+        Fun { .. } | App { .. } | Case { .. } | Proj { .. } => false,
+        Constructor { arg, .. } => arg.is_none(),
+        Binds { .. }
+        | BuiltinCall { .. }
+        | ExternCall { .. }
+        | Closure { .. }
+        | Tuple { .. }
+        | Sym { .. }
+        | Lit { .. } => true,
+    }
+}
+
+fn pp_atom<W: io::Write>(
+    expr: &Expr,
+    w: &mut W,
+    config: &PpConfig,
+    indent: usize,
+) -> io::Result<()> {
+    if is_atomic(expr) {
+        pp_expr(expr, w, config, indent)
+    } else {
+        write!(w, "(")?;
+        pp_expr(expr, w, config, indent)?;
+        write!(w, ")")
+    }
+}
+
+fn pp_vars<W: io::Write>(vars: &[(HTy, Symbol)], w: &mut W, indent: usize) -> io::Result<()> {
+    inter_iter! {
+        vars.iter(),
+        write!(w, ", ")?,
+        |(_, var)| => {
+            var.pp(w, indent)?
+        }
+    }
+    Ok(())
+}
+
+fn pp_flat_args<W: io::Write>(
+    args: &[Expr],
+    w: &mut W,
+    config: &PpConfig,
+    indent: usize,
+) -> io::Result<()> {
+    write!(w, "(")?;
+    inter_iter! {args.iter(), write!(w, ", ")?, |arg| => pp_expr(arg, w, config, indent)?};
+    write!(w, ")")
+}
+
+// `(a, b, c)` if it fits in the width, otherwise one element per line.
+// the line is assumed to start at `indent`
+fn pp_args<W: io::Write>(
+    args: &[Expr],
+    w: &mut W,
+    config: &PpConfig,
+    indent: usize,
+) -> io::Result<()> {
+    // measured without the escape codes, which take no columns
+    let plain = PpConfig {
+        color: ColorMode::Never,
+        ..*config
+    };
+    let mut flat = Vec::new();
+    pp_flat_args(args, &mut flat, &plain, indent)?;
+    if !flat.contains(&b'\n') && indent + flat.len() <= config.width {
+        return match config.color {
+            ColorMode::Always => pp_flat_args(args, w, config, indent),
+            ColorMode::Auto | ColorMode::Never => w.write_all(&flat),
+        };
+    }
+    let nextind = Expr::nspaces(indent + config.indent);
+    write!(w, "(")?;
+    inter_iter! {
+        args.iter(),
+        write!(w, ",")?,
+        |arg| => {
+            write!(w, "\n{}", nextind)?;
+            pp_expr(arg, w, config, indent + config.indent)?
+        }
+    }
+    write!(w, "\n{})", Expr::nspaces(indent))
+}
+
+fn pp_expr<W: io::Write>(
+    expr: &Expr,
+    w: &mut W,
+    config: &PpConfig,
+    indent: usize,
+) -> io::Result<()> {
+    use crate::hir::Expr::*;
+    let step = config.indent;
+    match expr {
+        Binds { binds, ret, .. } => {
+            let ind = Expr::nspaces(indent);
+            let nextind = Expr::nspaces(indent + step);
+            config.keyword(w, "let")?;
+            write!(w, "\n")?;
+            for val in binds {
+                pp_val(val, w, config, indent + step)?;
+                write!(w, "\n")?;
             }
-            Closure { envs, fname, .. } => {
-                write!(w, "<closure ")?;
-                fname.pp(w, indent)?;
-                write!(w, " (")?;
-                inter_iter! {
-                    envs.iter(),
-                    write!(w, ", ")?,
-                    |(_,  var)| => {
-                        var.pp(w, indent)?
-                    }
-                }
-                write!(w, ")>")?;
+            write!(w, "{}", ind)?;
+            config.keyword(w, "in")?;
+            write!(w, "\n{}", nextind)?;
+            pp_expr(ret, w, config, indent + step)?;
+            write!(w, "\n{}", ind)?;
+            config.keyword(w, "end")?;
+        }
+        Fun {
+            body,
+            param,
+            captures,
+            ..
+        } => {
+            config.keyword(w, "fn")?;
+            write!(w, " (")?;
+            param.1.pp(w, indent)?;
+            write!(w, ": ")?;
+            param.0.pp(w, indent)?;
+            write!(w, ")")?;
+            if !captures.is_empty() {
+                write!(w, " (* captures ")?;
+                pp_vars(captures, w, indent)?;
+                write!(w, " *)")?;
             }
-            App { fun, arg, .. } => {
-                write!(w, "(")?;
-                fun.pp(w, indent)?;
-                write!(w, ") ")?;
-                arg.pp(w, indent + 4)?;
+            write!(w, " => ")?;
+            pp_expr(body, w, config, indent)?;
+        }
+        Closure { envs, fname, .. } => {
+            write!(w, "<closure ")?;
+            fname.pp(w, indent)?;
+            write!(w, ">")?;
+            if !envs.is_empty() {
+                write!(w, " (* captures ")?;
+                pp_vars(envs, w, indent)?;
+                write!(w, " *)")?;
             }
-            Case { expr, arms, .. } => {
-                let ind = Self::nspaces(indent);
-                write!(w, "case ")?;
-                expr.pp(w, indent + 4)?;
-                write!(w, " of")?;
-                for (pat, arm) in arms {
+        }
+        App { fun, arg, .. } => {
+            pp_atom(fun, w, config, indent)?;
+            write!(w, " ")?;
+            pp_atom(arg, w, config, indent)?;
+        }
+        Case { expr, arms, .. } => {
+            let ind = Expr::nspaces(indent + step);
+            // the bars stand out to the left of the patterns
+            let barind = Expr::nspaces((indent + step).saturating_sub(2));
+            config.keyword(w, "case")?;
+            write!(w, " ")?;
+            pp_expr(expr, w, config, indent)?;
+            write!(w, " ")?;
+            config.keyword(w, "of")?;
+            for (i, (pat, arm)) in arms.iter().enumerate() {
+                if i == 0 {
                     write!(w, "\n{}", ind)?;
-                    pat.pp(w, indent + 4)?;
-                    write!(w, " => ")?;
-                    arm.pp(w, indent + 4)?;
+                } else {
+                    write!(w, "\n{}| ", barind)?;
                 }
+                pp_pattern(pat, w, config, indent + step)?;
+                write!(w, " => ")?;
+                pp_expr(arm, w, config, indent + step)?;
             }
-            Tuple { tuple, .. } => {
-                write!(w, "(")?;
-                inter_iter! {
-                    tuple.iter(),
-                    write!(w, ", ")?,
-                    |t| => {
-                        t.pp(w, indent)?
-                    }
-                }
-                write!(w, ")")?;
-            }
-            Proj { index, tuple, .. } => {
-                write!(w, "#{} ", index)?;
-                tuple.pp(w, indent + 4)?;
-            }
-            BuiltinCall { fun, args, .. } => {
-                fun.pp(w, indent)?;
-                write!(w, "(")?;
-                inter_iter! {args.iter(), write!(w, ", ")?, |arg| => arg.pp(w, indent)?};
-                write!(w, ")")?;
-            }
-            ExternCall {
-                module, fun, args, ..
-            } => {
-                write!(w, "\"{}\".\"{}\"(", module, fun)?;
-                inter_iter! {args.iter(), write!(w, ", ")?, |arg| => arg.pp(w, indent)?};
-                write!(w, ")")?;
-            }
-            Constructor {
-                descriminant, arg, ..
-            } => match arg {
-                None => {
-                    write!(w, "{}", descriminant)?;
-                }
-                Some(arg) => {
-                    write!(w, "{} ", descriminant)?;
-                    arg.pp(w, indent)?;
-                }
-            },
-            Sym { name, .. } => {
-                name.pp(w, indent)?;
-            }
-            Lit { value, .. } => {
-                value.pp(w, indent)?;
+        }
+        Tuple { tuple, .. } => {
+            pp_args(tuple, w, config, indent)?;
+        }
+        Proj { index, tuple, .. } => {
+            // 1-origin as in SML
+            write!(w, "#{} ", index + 1)?;
+            pp_atom(tuple, w, config, indent)?;
+        }
+        BuiltinCall { fun, args, .. } => {
+            fun.pp(w, indent)?;
+            pp_args(args, w, config, indent)?;
+        }
+        ExternCall {
+            module, fun, args, ..
+        } => {
+            write!(w, "\"{}\".\"{}\"", module, fun)?;
+            pp_args(args, w, config, indent)?;
+        }
+        Constructor {
+            descriminant, arg, ..
+        } => {
+            write!(w, "<con {}>", descriminant)?;
+            if let Some(arg) = arg {
+                write!(w, " ")?;
+                pp_atom(arg, w, config, indent)?;
             }
         }
-        Ok(())
+        Sym { name, .. } => {
+            name.pp(w, indent)?;
+        }
+        Lit { value, .. } => {
+            config.paint(w, Highlight::Literal, |w| value.pp(w, indent))?;
+        }
     }
+    Ok(())
 }
 
 impl PP for Pattern {
     fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
-        match self {
-            Pattern::Constant { value, .. } => write!(w, "{}", value),
-            Pattern::Char { value, .. } => write!(w, r##"#"{}""##, value),
-            Pattern::Constructor {
-                descriminant, arg, ..
-            } => match arg {
-                None => write!(w, "{}", descriminant),
-                Some((_, sym)) => {
-                    write!(w, "{}(", descriminant)?;
-                    sym.pp(w, indent)?;
-                    write!(w, ")")?;
-                    Ok(())
-                }
-            },
-            Pattern::Tuple { tuple, .. } => {
-                write!(w, "(")?;
-                inter_iter! {
-                    tuple.iter(),
-                    write!(w, ", ")?,
-                    |t| => {
-                        t.pp(w, indent)?
+        pp_pattern(self, w, &PpConfig::default(), indent)
+    }
+}
+
+fn pp_pattern<W: io::Write>(
+    pat: &Pattern,
+    w: &mut W,
+    config: &PpConfig,
+    indent: usize,
+) -> io::Result<()> {
+    match pat {
+        Pattern::Constant { value, .. } => {
+            config.paint(w, Highlight::Literal, |w| write!(w, "{}", value))
+        }
+        Pattern::Char { value, .. } => config.paint(w, Highlight::Literal, |w| {
+            write!(w, r##"#"{}""##, escape_char(*value))
+        }),
+        Pattern::Str { value, .. } => config.paint(w, Highlight::Literal, |w| {
+            Literal::String(value.clone()).pp(w, indent)
+        }),
+        Pattern::Constructor {
+            descriminant, arg, ..
+        } => match arg {
+            None => write!(w, "<con {}>", descriminant),
+            Some(pat) => {
+                write!(w, "<con {}> ", descriminant)?;
+                match **pat {
+                    Pattern::Constructor { arg: Some(_), .. } | Pattern::As { .. } => {
+                        write!(w, "(")?;
+                        pp_pattern(pat, w, config, indent)?;
+                        write!(w, ")")
                     }
+                    _ => pp_pattern(pat, w, config, indent),
+                }
+            }
+        },
+        Pattern::Tuple { tuple, .. } => {
+            write!(w, "(")?;
+            inter_iter! {
+                tuple.iter(),
+                write!(w, ", ")?,
+                |t| => {
+                    pp_pattern(t, w, config, indent)?
+                }
+            }
+            write!(w, ")")
+        }
+        Pattern::Var { name, .. } => name.pp(w, indent),
+        Pattern::Wildcard { .. } => write!(w, "_"),
+        Pattern::As { name, pattern, .. } => {
+            name.pp(w, indent)?;
+            write!(w, " ")?;
+            config.keyword(w, "as")?;
+            write!(w, " ")?;
+            pp_pattern(pattern, w, config, indent)
+        }
+        Pattern::Or(alternatives) => {
+            write!(w, "(")?;
+            inter_iter! {
+                alternatives.iter(),
+                write!(w, " | ")?,
+                |pat| => {
+                    pp_pattern(pat, w, config, indent)?
                 }
-                write!(w, ")")
             }
-            Pattern::Var { name, .. } => name.pp(w, indent),
+            write!(w, ")")
         }
     }
 }
 
-impl PP for HTy {
-    fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
-        use crate::hir::HTy::*;
-        match self {
-            Char => write!(w, "char")?,
-            Int => write!(w, "int")?,
-            Real => write!(w, "real")?,
-            Tuple(tys) => {
-                write!(w, "(")?;
+// the precedences of the type operators. `->` is right associative
+// and binds weaker than `*`, which binds weaker than the type application
+const PREC_FUN: u8 = 0;
+const PREC_TUPLE: u8 = 1;
+const PREC_APP: u8 = 2;
+
+// print `ty` in a context of `prec`, parenthesized if it binds weaker
+fn pp_ty<W: io::Write>(ty: &HTy, w: &mut W, prec: u8) -> io::Result<()> {
+    use crate::hir::HTy::*;
+    let own = match ty {
+        Fun(..) => PREC_FUN,
+        Tuple(tys) if !tys.is_empty() => PREC_TUPLE,
+        _ => PREC_APP,
+    };
+    if own < prec {
+        write!(w, "(")?;
+    }
+    match ty {
+        Char => write!(w, "char")?,
+        String => write!(w, "string")?,
+        Int => write!(w, "int")?,
+        Word => write!(w, "word")?,
+        Unit => write!(w, "unit")?,
+        Real => write!(w, "real")?,
+        Bool => write!(w, "bool")?,
+        Tuple(tys) => match tys.as_slice() {
+            [] => write!(w, "unit")?,
+            tys => {
                 inter_iter! {
                     tys.iter(),
                     write!(w, " * ")?,
                     |ty| => {
-                        ty.pp(w, indent)?
+                        pp_ty(ty, w, PREC_APP)?
                     }
                 }
-                write!(w, ")")?;
-            }
-            Fun(t1, t2) => {
-                t1.pp(w, indent)?;
-                write!(w, " -> ")?;
-                t2.pp(w, indent)?;
             }
-            Datatype(name) => {
-                name.pp(w, indent)?;
+        },
+        Fun(t1, t2) => {
+            pp_ty(t1, w, PREC_TUPLE)?;
+            write!(w, " -> ")?;
+            pp_ty(t2, w, PREC_FUN)?;
+        }
+        Datatype(name, args) => {
+            match args.as_slice() {
+                [] => (),
+                [arg] => {
+                    pp_ty(arg, w, PREC_APP)?;
+                    write!(w, " ")?;
+                }
+                args => {
+                    write!(w, "(")?;
+                    inter_iter! {
+                        args.iter(),
+                        write!(w, ", ")?,
+                        |arg| => {
+                            pp_ty(arg, w, PREC_FUN)?
+                        }
+                    }
+                    write!(w, ") ")?;
+                }
             }
+            name.pp(w, 0)?;
+        }
+        List(elem) => {
+            pp_ty(elem, w, PREC_APP)?;
+            write!(w, " list")?;
+        }
+        Ref(elem) => {
+            pp_ty(elem, w, PREC_APP)?;
+            write!(w, " ref")?;
         }
-        Ok(())
+    }
+    if own < prec {
+        write!(w, ")")?;
+    }
+    Ok(())
+}
+
+impl PP for HTy {
+    fn pp<W: io::Write>(&self, w: &mut W, _indent: usize) -> io::Result<()> {
+        pp_ty(self, w, PREC_FUN)
     }
 }
@@ -0,0 +1,82 @@
+use crate::config::Config;
+use crate::hir::dead_bind_elim::Uses;
+use crate::hir::util::Traverse;
+use crate::hir::*;
+use crate::pass::Pass;
+use crate::prim::*;
+use std::collections::{HashMap, HashSet};
+
+/// removes the captures that functions no longer refer to.
+/// expects all the functions are lifted to the toplevel by `UnnestFunc`.
+pub struct PruneCaptures {
+    // the captures left for the pruned functions
+    kept: HashMap<Symbol, Vec<Symbol>>,
+}
+
+// drops the environments of the closures that the functions no longer capture
+struct PruneEnvs<'a> {
+    kept: &'a HashMap<Symbol, Vec<Symbol>>,
+}
+
+impl<'a> Traverse for PruneEnvs<'a> {
+    fn traverse_closure(
+        &mut self,
+        envs: &mut Vec<(HTy, Symbol)>,
+        _param_ty: &mut HTy,
+        _body_ty: &mut HTy,
+        fname: &mut Symbol,
+    ) {
+        if let Some(kept) = self.kept.get(fname) {
+            envs.retain(|(_, name)| kept.contains(name))
+        }
+    }
+}
+
+impl PruneCaptures {
+    pub fn new() -> Self {
+        Self {
+            kept: HashMap::new(),
+        }
+    }
+
+    fn prune_hir(&mut self, hir: &mut HIR) {
+        // pruning a closure can make the variables it captured dead in the enclosing function
+        while self.prune_funs(hir) {
+            PruneEnvs { kept: &self.kept }.traverse_hir(hir);
+        }
+    }
+
+    // returns whether some captures are removed
+    fn prune_funs(&mut self, hir: &mut HIR) -> bool {
+        let mut pruned = false;
+        for val in hir.0.iter_mut() {
+            if let Expr::Fun { body, captures, .. } = &mut val.expr {
+                let mut used = HashSet::new();
+                Uses { used: &mut used }.traverse_expr(body);
+                let len = captures.len();
+                captures.retain(|(_, name)| used.contains(name));
+                if captures.len() != len {
+                    pruned = true;
+                    self.kept.insert(
+                        val.name.clone(),
+                        captures.iter().map(|(_, name)| name.clone()).collect(),
+                    );
+                }
+            }
+        }
+        pruned
+    }
+}
+
+impl<E> Pass<(SymbolTable, HIR), E> for PruneCaptures {
+    type Target = (SymbolTable, HIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, mut hir): (SymbolTable, HIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        self.prune_hir(&mut hir);
+        Ok((symbol_table, hir))
+    }
+}
@@ -0,0 +1,405 @@
+use crate::config::Config;
+use crate::hir::dead_bind_elim::is_pure;
+use crate::hir::util::Transform;
+use crate::hir::*;
+use crate::id::Id;
+use crate::pass::Pass;
+use crate::prim::*;
+use std::collections::{HashMap, HashSet};
+
+/// peephole simplifications of the expressions whose shapes are known at compile time
+pub struct Simplify {
+    id: Id,
+    // the values of the names whose shapes are known
+    known: HashMap<Symbol, Expr>,
+    // the names replaced with simple values
+    subst: HashMap<Symbol, Expr>,
+    // the names bound recursively
+    recursive: HashSet<Symbol>,
+    // the names whose bindings are being simplified
+    defining: Vec<Symbol>,
+}
+
+fn is_simple(expr: &Expr) -> bool {
+    match expr {
+        Expr::Sym { .. } | Expr::Lit { .. } => true,
+        _ => false,
+    }
+}
+
+// the constructed values whose components can be referred to without evaluating them again
+fn is_known(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit { .. } => true,
+        Expr::Constructor { arg, .. } => arg.iter().all(|arg| is_simple(arg)),
+        Expr::Tuple { tuple, .. } => tuple.iter().all(is_simple),
+        _ => false,
+    }
+}
+
+// the bindings that `pat` makes when it matches `value`, or `None` if it does not match.
+// `scrutinee` is the expression `value` is taken from.
+fn match_known(pat: &Pattern, scrutinee: &Expr, value: &Expr) -> Option<Vec<(HTy, Symbol, Expr)>> {
+    use crate::hir::Pattern::*;
+    match (pat, value) {
+        (Var { name, ty }, _) => Some(vec![(ty.clone(), name.clone(), scrutinee.clone())]),
+        (Wildcard { .. }, _) => Some(vec![]),
+        (As { name, pattern, ty }, _) => match_known(pattern, scrutinee, value).map(|mut binds| {
+            binds.insert(0, (ty.clone(), name.clone(), scrutinee.clone()));
+            binds
+        }),
+        (Or(alternatives), _) => alternatives
+            .iter()
+            .find_map(|pat| match_known(pat, scrutinee, value)),
+        (
+            Constant { value, .. },
+            Expr::Lit {
+                value: Literal::Int(lit),
+                ..
+            },
+        ) if value == lit => Some(vec![]),
+        (
+            Char { value, .. },
+            Expr::Lit {
+                value: Literal::Char(lit),
+                ..
+            },
+        ) if value == lit => Some(vec![]),
+        (
+            Str { value, .. },
+            Expr::Lit {
+                value: Literal::String(lit),
+                ..
+            },
+        ) if value == lit => Some(vec![]),
+        (
+            Constructor {
+                descriminant, arg, ..
+            },
+            Expr::Constructor {
+                descriminant: d,
+                arg: value,
+                ..
+            },
+        ) if descriminant == d => match (arg, value) {
+            (Some(arg), Some(value)) => match_known(arg, value, value),
+            _ => Some(vec![]),
+        },
+        (Tuple { tuple, .. }, Expr::Tuple { tuple: values, .. }) => tuple
+            .iter()
+            .zip(values)
+            .try_fold(Vec::new(), |mut binds, (pat, value)| {
+                binds.extend(match_known(pat, value, value)?);
+                Some(binds)
+            }),
+        _ => None,
+    }
+}
+
+// whether `match_known` can tell if `pat` matches `value`.
+// the nested patterns cannot be matched against the values that are not constructed in place.
+fn is_decidable(pat: &Pattern, value: &Expr) -> bool {
+    use crate::hir::Pattern::*;
+    match (pat, value) {
+        (Var { .. }, _) | (Wildcard { .. }, _) => true,
+        (As { pattern, .. }, _) => is_decidable(pattern, value),
+        (Or(alternatives), _) => alternatives.iter().all(|pat| is_decidable(pat, value)),
+        (Constant { .. }, Expr::Lit { .. })
+        | (Char { .. }, Expr::Lit { .. })
+        | (Str { .. }, Expr::Lit { .. }) => true,
+        (Tuple { tuple, .. }, Expr::Tuple { tuple: values, .. }) => tuple
+            .iter()
+            .zip(values)
+            .all(|(pat, value)| is_decidable(pat, value)),
+        (
+            Constructor {
+                descriminant, arg, ..
+            },
+            Expr::Constructor {
+                descriminant: d,
+                arg: value,
+                ..
+            },
+        ) => {
+            descriminant != d
+                || match (arg, value) {
+                    (Some(arg), Some(value)) => is_decidable(arg, value),
+                    _ => true,
+                }
+        }
+        _ => false,
+    }
+}
+
+impl Simplify {
+    pub fn new(id: Id) -> Self {
+        Self {
+            id,
+            known: HashMap::new(),
+            subst: HashMap::new(),
+            recursive: HashSet::new(),
+            defining: Vec::new(),
+        }
+    }
+
+    fn gensym(&mut self) -> Symbol {
+        let id = self.id.next();
+        Symbol("#g".into(), id)
+    }
+
+    // binds the components of a value constructed in place to fresh names in order,
+    // so that they are still evaluated when the pattern drops them
+    fn name_components(&mut self, expr: Expr, vals: &mut Vec<Val>) -> Expr {
+        match expr {
+            Expr::Tuple { tys, tuple } => Expr::Tuple {
+                tys,
+                tuple: tuple
+                    .into_iter()
+                    .map(|elem| self.name_components(elem, vals))
+                    .collect(),
+            },
+            Expr::Constructor {
+                ty,
+                arg,
+                descriminant,
+            } => Expr::Constructor {
+                ty,
+                arg: arg.map(|arg| Box::new(self.name_components(*arg, vals))),
+                descriminant,
+            },
+            expr if is_simple(&expr) => expr,
+            expr => {
+                let name = self.gensym();
+                let ty = expr.ty();
+                vals.push(Val {
+                    ty: ty.clone(),
+                    rec: false,
+                    name: name.clone(),
+                    expr,
+                });
+                Expr::Sym { ty, name }
+            }
+        }
+    }
+
+    fn register_recursive(&mut self, vals: &[Val]) {
+        self.recursive.extend(
+            vals.iter()
+                .filter(|val| val.rec)
+                .map(|val| val.name.clone()),
+        )
+    }
+}
+
+impl Transform for Simplify {
+    fn transform_hir(&mut self, hir: HIR) -> HIR {
+        self.register_recursive(&hir.0);
+        HIR(hir
+            .0
+            .into_iter()
+            .map(|val| self.transform_val(val))
+            .collect())
+    }
+
+    fn transform_val(&mut self, mut val: Val) -> Val {
+        self.defining.push(val.name.clone());
+        val.expr = self.transform_expr(val.expr);
+        self.defining.pop();
+        val
+    }
+
+    fn transform_binds(&mut self, ty: HTy, binds: Vec<Val>, ret: Box<Expr>) -> Expr {
+        self.register_recursive(&binds);
+        let mut vals = Vec::new();
+        for val in binds {
+            let val = self.transform_val(val);
+            if !val.rec {
+                // propagate constants
+                if let Expr::Lit { .. } = val.expr {
+                    self.subst.insert(val.name, val.expr);
+                    continue;
+                }
+                if is_known(&val.expr) {
+                    self.known.insert(val.name.clone(), val.expr.clone());
+                }
+            }
+            vals.push(val)
+        }
+        let ret = self.transform_expr(*ret);
+        if vals.is_empty() {
+            ret
+        } else {
+            Expr::Binds {
+                ty,
+                binds: vals,
+                ret: Box::new(ret),
+            }
+        }
+    }
+
+    // `fn x => f x` => `f`.
+    // the lambda captures `f` if needed, so `f` is available wherever the lambda is built.
+    // the functions being defined are kept wrapped not to make values refer to themselves.
+    fn transform_fun(
+        &mut self,
+        param: (HTy, Symbol),
+        body_ty: HTy,
+        body: Box<Expr>,
+        captures: Vec<(HTy, Symbol)>,
+    ) -> Expr {
+        let body = self.transform_expr(*body);
+        if let Expr::App { fun, arg, .. } = &body {
+            if let (Expr::Sym { name: f, .. }, Expr::Sym { name: x, .. }) = (&**fun, &**arg) {
+                if *x == param.1
+                    && *f != param.1
+                    && !self.recursive.contains(f)
+                    && !self.defining.contains(f)
+                {
+                    return (**fun).clone();
+                }
+            }
+        }
+        Expr::Fun {
+            param,
+            body_ty,
+            body: Box::new(body),
+            captures,
+        }
+    }
+
+    // `case C e of p1 => e1 | C x => e2 | ...` => `let val x = e in e2 end`
+    // and the same for literals and tuples
+    fn transform_case(&mut self, ty: HTy, cond: Box<Expr>, arms: Vec<(Pattern, Expr)>) -> Expr {
+        let cond = self.transform_expr(*cond);
+        let mut vals = Vec::new();
+        let named = match &cond {
+            Expr::Constructor { .. } | Expr::Tuple { .. } => {
+                self.name_components(cond.clone(), &mut vals)
+            }
+            _ => cond.clone(),
+        };
+        let value = match &named {
+            Expr::Sym { name, .. } => self.known.get(name).cloned(),
+            Expr::Lit { .. } | Expr::Constructor { .. } | Expr::Tuple { .. } => Some(named.clone()),
+            _ => None,
+        };
+        let matched = value.and_then(|value| {
+            // the arms are tried in order until one is known to match
+            for (i, (pat, _)) in arms.iter().enumerate() {
+                if !is_decidable(pat, &value) {
+                    return None;
+                }
+                if let Some(binds) = match_known(pat, &named, &value) {
+                    return Some((binds, i));
+                }
+            }
+            None
+        });
+        let (binds, i) = match matched {
+            Some(matched) => matched,
+            None => {
+                return Expr::Case {
+                    ty,
+                    expr: Box::new(cond),
+                    arms: arms
+                        .into_iter()
+                        .map(|(pat, arm)| (pat, self.transform_expr(arm)))
+                        .collect(),
+                }
+            }
+        };
+
+        // the components are evaluated in order before the names the pattern binds
+        for (ty, name, value) in binds {
+            if is_simple(&value) {
+                self.subst.insert(name, value);
+            } else {
+                vals.push(Val {
+                    ty,
+                    rec: false,
+                    name,
+                    expr: value,
+                });
+            }
+        }
+        let arm = self.transform_expr(arms.into_iter().nth(i).unwrap().1);
+        if vals.is_empty() {
+            arm
+        } else {
+            Expr::Binds {
+                ty,
+                binds: vals,
+                ret: Box::new(arm),
+            }
+        }
+    }
+
+    // `#i (e0, e1, ...)` => `ei`.
+    // the other elements with effects are still evaluated in order.
+    fn transform_proj(&mut self, ty: HTy, index: u32, tuple: Box<Expr>) -> Expr {
+        let tuple = self.transform_expr(*tuple);
+        let elems = match tuple {
+            Expr::Tuple { tuple, .. } => tuple,
+            tuple => {
+                return Expr::Proj {
+                    ty,
+                    index,
+                    tuple: Box::new(tuple),
+                }
+            }
+        };
+        let index = index as usize;
+        let effectful = elems
+            .iter()
+            .enumerate()
+            .any(|(i, elem)| i != index && !is_pure(elem));
+        if !effectful {
+            return elems.into_iter().nth(index).unwrap();
+        }
+
+        let mut binds = Vec::new();
+        let mut ret = None;
+        for (i, elem) in elems.into_iter().enumerate() {
+            if i != index && is_pure(&elem) {
+                continue;
+            }
+            let name = self.gensym();
+            if i == index {
+                ret = Some(Expr::Sym {
+                    ty: ty.clone(),
+                    name: name.clone(),
+                });
+            }
+            binds.push(Val {
+                ty: elem.ty(),
+                rec: false,
+                name,
+                expr: elem,
+            })
+        }
+        Expr::Binds {
+            ty,
+            binds,
+            ret: Box::new(ret.unwrap()),
+        }
+    }
+
+    fn transform_sym(&mut self, ty: HTy, name: Symbol) -> Expr {
+        match self.subst.get(&name) {
+            Some(expr) => expr.clone(),
+            None => Expr::Sym { ty, name },
+        }
+    }
+}
+
+impl<E> Pass<(SymbolTable, HIR), E> for Simplify {
+    type Target = (SymbolTable, HIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, hir): (SymbolTable, HIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        Ok((symbol_table, self.transform_hir(hir)))
+    }
+}
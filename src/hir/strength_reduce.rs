@@ -0,0 +1,107 @@
+use crate::config::Config;
+use crate::hir::util::Transform;
+use crate::hir::*;
+use crate::pass::Pass;
+use crate::prim::*;
+
+/// replaces the multiplications and divisions by powers of two with shifts
+pub struct StrengthReduce;
+
+impl StrengthReduce {
+    pub fn new() -> Self {
+        StrengthReduce
+    }
+}
+
+// `k` if `expr` is the int literal `2^k` for `k > 0`
+fn log2(expr: &Expr) -> Option<i64> {
+    match expr {
+        Expr::Lit {
+            value: Literal::Int(i),
+            ..
+        } => {
+            // ints are 32 bit wide in the backend
+            let i = *i as i32;
+            if i > 1 && i.count_ones() == 1 {
+                Some(i.trailing_zeros() as i64)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+// whether `expr` evaluates to a non negative int.
+// `div` rounds toward negative infinity only for these, so it is the same as the arithmetic shift.
+fn is_non_negative(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit {
+            value: Literal::Int(i),
+            ..
+        } => *i as i32 >= 0,
+        Expr::BuiltinCall {
+            fun: BIF::Shr,
+            args,
+            ..
+        } => is_non_negative(&args[0]),
+        _ => false,
+    }
+}
+
+fn shift(fun: BIF, ty: HTy, e: Expr, k: i64) -> Expr {
+    Expr::BuiltinCall {
+        ty,
+        fun,
+        args: vec![
+            e,
+            Expr::Lit {
+                ty: HTy::Int,
+                value: Literal::Int(k),
+            },
+        ],
+    }
+}
+
+impl Transform for StrengthReduce {
+    fn transform_builtin_call(&mut self, ty: HTy, fun: BIF, args: Vec<Expr>) -> Expr {
+        let mut args = args
+            .into_iter()
+            .map(|arg| self.transform_expr(arg))
+            .collect::<Vec<_>>();
+        if ty != HTy::Int || args.len() != 2 {
+            return Expr::BuiltinCall { ty, fun, args };
+        }
+        match fun {
+            // `e * 2^k` => `e << k`, which is the same even when it overflows
+            BIF::Mul => {
+                if let Some(k) = log2(&args[1]) {
+                    return shift(BIF::Shl, ty, args.remove(0), k);
+                }
+                if let Some(k) = log2(&args[0]) {
+                    return shift(BIF::Shl, ty, args.remove(1), k);
+                }
+            }
+            // `e div 2^k` => `e >> k` for non negative `e`
+            BIF::Div if is_non_negative(&args[0]) => {
+                if let Some(k) = log2(&args[1]) {
+                    return shift(BIF::Shr, ty, args.remove(0), k);
+                }
+            }
+            _ => (),
+        }
+        Expr::BuiltinCall { ty, fun, args }
+    }
+}
+
+impl<E> Pass<(SymbolTable, HIR), E> for StrengthReduce {
+    type Target = (SymbolTable, HIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, hir): (SymbolTable, HIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        Ok((symbol_table, self.transform_hir(hir)))
+    }
+}
@@ -0,0 +1,291 @@
+use crate::config::Config;
+use crate::hir::*;
+use crate::pass::Pass;
+use std::collections::{HashMap, HashSet};
+
+/// represents the datatypes with only one constructor taking an argument by the argument,
+/// so that making and matching their values do not allocate nor load anything.
+/// e.g. a value of `datatype box = Box of int` is just an `int`
+pub struct UnboxDatatype;
+
+impl UnboxDatatype {
+    pub fn new() -> Self {
+        UnboxDatatype
+    }
+}
+
+// the arguments of the unboxed datatypes
+struct Unbox {
+    payloads: HashMap<Symbol, HTy>,
+}
+
+// collects the names of the `candidates` that `ty` refers to,
+// also through the arguments of those candidates
+fn refers(candidates: &HashMap<Symbol, HTy>, ty: &HTy, names: &mut HashSet<Symbol>) {
+    use crate::hir::HTy::*;
+    match ty {
+        Unit | Char | String | Int | Word | Real | Bool => (),
+        Fun(param, ret) => {
+            refers(candidates, param, names);
+            refers(candidates, ret, names);
+        }
+        Tuple(tys) => tys.iter().for_each(|ty| refers(candidates, ty, names)),
+        Datatype(name, args) => {
+            args.iter().for_each(|ty| refers(candidates, ty, names));
+            if let Some(payload) = candidates.get(name) {
+                if names.insert(name.clone()) {
+                    refers(candidates, payload, names)
+                }
+            }
+        }
+        List(elem) | Ref(elem) => refers(candidates, elem, names),
+    }
+}
+
+impl Unbox {
+    fn new(symbol_table: &SymbolTable) -> Self {
+        let candidates = symbol_table
+            .types
+            .iter()
+            .filter_map(|(name, info)| match info.constructors.as_slice() {
+                [(_, Some(arg))] => Some((name.clone(), arg.clone())),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+        // `datatype t = T of t * int` cannot be replaced by its argument infinitely
+        let payloads = candidates
+            .iter()
+            .filter(|(name, payload)| {
+                let mut names = HashSet::new();
+                refers(&candidates, payload, &mut names);
+                !names.contains(*name)
+            })
+            .map(|(name, payload)| (name.clone(), payload.clone()))
+            .collect();
+        Unbox { payloads }
+    }
+
+    fn is_unboxed(&self, ty: &HTy) -> bool {
+        match ty {
+            HTy::Datatype(name, _) => self.payloads.contains_key(name),
+            _ => false,
+        }
+    }
+
+    fn ty(&self, ty: HTy) -> HTy {
+        use crate::hir::HTy::*;
+        match ty {
+            Unit | Char | String | Int | Word | Real | Bool => ty,
+            Fun(param, ret) => HTy::fun(self.ty(*param), self.ty(*ret)),
+            Tuple(tys) => Tuple(self.tys(tys)),
+            Datatype(name, args) => match self.payloads.get(&name) {
+                Some(payload) => self.ty(payload.clone()),
+                None => Datatype(name, self.tys(args)),
+            },
+            List(elem) => List(Box::new(self.ty(*elem))),
+            Ref(elem) => Ref(Box::new(self.ty(*elem))),
+        }
+    }
+
+    fn tys(&self, tys: Vec<HTy>) -> Vec<HTy> {
+        tys.into_iter().map(|ty| self.ty(ty)).collect()
+    }
+
+    fn vars(&self, vars: Vec<(HTy, Symbol)>) -> Vec<(HTy, Symbol)> {
+        vars.into_iter()
+            .map(|(ty, name)| (self.ty(ty), name))
+            .collect()
+    }
+
+    fn symbol_table(&self, mut symbol_table: SymbolTable) -> SymbolTable {
+        symbol_table.types = symbol_table
+            .types
+            .into_iter()
+            .filter(|(name, _)| !self.payloads.contains_key(name))
+            .map(|(name, info)| {
+                let constructors = info
+                    .constructors
+                    .into_iter()
+                    .map(|(descriminant, arg)| (descriminant, arg.map(|ty| self.ty(ty))))
+                    .collect();
+                (name, TypeInfo { constructors })
+            })
+            .collect();
+        symbol_table
+    }
+
+    fn val(&self, val: Val) -> Val {
+        Val {
+            ty: self.ty(val.ty),
+            rec: val.rec,
+            name: val.name,
+            expr: self.expr(val.expr),
+        }
+    }
+
+    fn expr(&self, expr: Expr) -> Expr {
+        use crate::hir::Expr::*;
+        match expr {
+            // the only constructor is the identity
+            Constructor {
+                ty, arg: Some(arg), ..
+            } if self.is_unboxed(&ty) => self.expr(*arg),
+            Binds { ty, binds, ret } => Binds {
+                ty: self.ty(ty),
+                binds: binds.into_iter().map(|val| self.val(val)).collect(),
+                ret: Box::new(self.expr(*ret)),
+            },
+            BuiltinCall { ty, fun, args } => BuiltinCall {
+                ty: self.ty(ty),
+                fun,
+                args: self.exprs(args),
+            },
+            ExternCall {
+                retty,
+                module,
+                fun,
+                args,
+            } => ExternCall {
+                retty: self.tys(retty),
+                module,
+                fun,
+                args: self.exprs(args),
+            },
+            Fun {
+                param: (param_ty, param),
+                body_ty,
+                body,
+                captures,
+            } => Fun {
+                param: (self.ty(param_ty), param),
+                body_ty: self.ty(body_ty),
+                body: Box::new(self.expr(*body)),
+                captures: self.vars(captures),
+            },
+            Closure {
+                envs,
+                param_ty,
+                body_ty,
+                fname,
+            } => Closure {
+                envs: self.vars(envs),
+                param_ty: self.ty(param_ty),
+                body_ty: self.ty(body_ty),
+                fname,
+            },
+            App {
+                ty,
+                fun,
+                arg,
+                is_tail,
+            } => App {
+                ty: self.ty(ty),
+                fun: Box::new(self.expr(*fun)),
+                arg: Box::new(self.expr(*arg)),
+                is_tail,
+            },
+            Case { ty, expr, arms } => Case {
+                ty: self.ty(ty),
+                expr: Box::new(self.expr(*expr)),
+                arms: arms
+                    .into_iter()
+                    .map(|(pat, arm)| (self.pattern(pat), self.expr(arm)))
+                    .collect(),
+            },
+            Tuple { tys, tuple } => Tuple {
+                tys: self.tys(tys),
+                tuple: self.exprs(tuple),
+            },
+            Proj { ty, index, tuple } => Proj {
+                ty: self.ty(ty),
+                index,
+                tuple: Box::new(self.expr(*tuple)),
+            },
+            Constructor {
+                ty,
+                arg,
+                descriminant,
+            } => Constructor {
+                ty: self.ty(ty),
+                arg: arg.map(|arg| Box::new(self.expr(*arg))),
+                descriminant,
+            },
+            Sym { ty, name } => Sym {
+                ty: self.ty(ty),
+                name,
+            },
+            Lit { ty, value } => Lit {
+                ty: self.ty(ty),
+                value,
+            },
+        }
+    }
+
+    fn exprs(&self, exprs: Vec<Expr>) -> Vec<Expr> {
+        exprs.into_iter().map(|expr| self.expr(expr)).collect()
+    }
+
+    fn pattern(&self, pat: Pattern) -> Pattern {
+        use crate::hir::Pattern::*;
+        match pat {
+            // matching the only constructor always succeeds
+            Constructor { ty, arg, .. } if self.is_unboxed(&ty) => match arg {
+                Some(arg) => self.pattern(*arg),
+                None => Wildcard { ty: self.ty(ty) },
+            },
+            Constructor {
+                descriminant,
+                arg,
+                ty,
+            } => Constructor {
+                descriminant,
+                arg: arg.map(|arg| Box::new(self.pattern(*arg))),
+                ty: self.ty(ty),
+            },
+            Constant { value, ty } => Constant {
+                value,
+                ty: self.ty(ty),
+            },
+            Char { value, ty } => Char {
+                value,
+                ty: self.ty(ty),
+            },
+            Str { value, ty } => Str {
+                value,
+                ty: self.ty(ty),
+            },
+            Var { name, ty } => Var {
+                name,
+                ty: self.ty(ty),
+            },
+            Wildcard { ty } => Wildcard { ty: self.ty(ty) },
+            Tuple { tys, tuple } => Tuple {
+                tys: self.tys(tys),
+                tuple: tuple.into_iter().map(|pat| self.pattern(pat)).collect(),
+            },
+            As { name, pattern, ty } => As {
+                name,
+                pattern: Box::new(self.pattern(*pattern)),
+                ty: self.ty(ty),
+            },
+            Or(alternatives) => Or(alternatives
+                .into_iter()
+                .map(|pat| self.pattern(pat))
+                .collect()),
+        }
+    }
+}
+
+impl<E> Pass<(SymbolTable, HIR), E> for UnboxDatatype {
+    type Target = (SymbolTable, HIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, hir): (SymbolTable, HIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        let unbox = Unbox::new(&symbol_table);
+        let hir = HIR(hir.0.into_iter().map(|val| unbox.val(val)).collect());
+        Ok((unbox.symbol_table(symbol_table), hir))
+    }
+}
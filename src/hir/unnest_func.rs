@@ -3,12 +3,14 @@ use crate::hir::*;
 use crate::id::Id;
 use crate::pass::Pass;
 use crate::prim::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::ops::{Deref, DerefMut, Drop};
 
 pub struct UnnestFunc {
     tables: Vec<HashSet<Symbol>>,
     tops: Vec<Val>,
+    // the recursive functions of the enclosing `let`s to their names after hoisted
+    hoisted: HashMap<Symbol, Symbol>,
     pos: usize,
     id: Id,
 }
@@ -53,7 +55,7 @@ impl<'a> Scope<'a> {
             Some(name) => format!("<{}>", name.0),
         };
         let id = self.id.next();
-        Symbol(new_name, id)
+        Symbol(new_name.into(), id)
     }
 
     fn add_scope(&mut self, symbol: Symbol) {
@@ -78,18 +80,17 @@ impl<'a> Scope<'a> {
     }
 
     fn conv_hir(&mut self, mut hir: HIR) -> HIR {
+        // the recursive functions may call the ones defined after them with `and`
+        for val in hir.0.iter().filter(|val| val.rec) {
+            self.add_scope(val.name.clone());
+        }
         let mut vals = hir
             .0
             .into_iter()
             .map(|val| {
-                if val.rec {
-                    self.add_scope(val.name.clone());
-                    self.conv_top_val(val)
-                } else {
-                    let val = self.conv_top_val(val);
-                    self.add_scope(val.name.clone());
-                    val
-                }
+                let val = self.conv_top_val(val);
+                self.add_scope(val.name.clone());
+                val
             })
             .collect();
         let mut closures = self.0.tops.drain(..).collect::<Vec<_>>();
@@ -116,6 +117,15 @@ impl<'a> Scope<'a> {
                 mut binds,
                 mut ret,
             } => {
+                // the recursive functions may call each other with `and`,
+                // so all of them are named before hoisted
+                let outer = self.hoisted.clone();
+                for bind in binds.iter() {
+                    if let (true, Fun { .. }) = (bind.rec, &bind.expr) {
+                        let fname = self.new_fname(Some(bind.name.clone()));
+                        self.hoisted.insert(bind.name.clone(), fname);
+                    }
+                }
                 binds = binds
                     .into_iter()
                     .map(|mut bind| {
@@ -129,6 +139,7 @@ impl<'a> Scope<'a> {
                         bind
                     })
                     .collect();
+                self.hoisted = outer;
                 ret = Box::new(self.conv_expr(*ret, None, false));
                 Binds { ty, binds, ret }
             }
@@ -157,8 +168,17 @@ impl<'a> Scope<'a> {
                     };
                 }
 
-                let fname = self.new_fname(bind_name.clone());
+                let hoisted = bind_name
+                    .as_ref()
+                    .and_then(|name| self.hoisted.get(name).cloned());
+                let fname = match hoisted {
+                    Some(fname) => fname,
+                    None => self.new_fname(bind_name.clone()),
+                };
                 self.rename(&mut body, &bind_name, &fname);
+                for (from, to) in self.hoisted.clone() {
+                    self.rename(&mut body, &Some(from), &to);
+                }
                 let anonfun = Fun {
                     param: (param_ty.clone(), param),
                     body_ty: body_ty.clone(),
@@ -194,7 +214,7 @@ impl<'a> Scope<'a> {
                 BuiltinCall { ty, fun, args }
             }
             ExternCall {
-                ty,
+                retty,
                 module,
                 fun,
                 args,
@@ -204,7 +224,7 @@ impl<'a> Scope<'a> {
                     .map(|arg| self.conv_expr(arg, None, false))
                     .collect();
                 ExternCall {
-                    ty,
+                    retty,
                     module,
                     fun,
                     args,
@@ -214,10 +234,16 @@ impl<'a> Scope<'a> {
                 ty,
                 mut fun,
                 mut arg,
+                is_tail,
             } => {
                 fun = Box::new(self.conv_expr(*fun, None, false));
                 arg = Box::new(self.conv_expr(*arg, None, false));
-                App { ty, fun, arg }
+                App {
+                    ty,
+                    fun,
+                    arg,
+                    is_tail,
+                }
             }
             Case {
                 ty,
@@ -312,21 +338,8 @@ impl<'a> Scope<'a> {
                 self.analyze_free_expr(frees, bound, expr);
                 let scope = self;
                 for (pat, arm) in arms.iter() {
-                    use self::Pattern::*;
-                    match pat {
-                        Constructor { arg, .. } => {
-                            if let Some((_, arg)) = arg {
-                                scope.add_scope(arg.clone())
-                            }
-                        }
-                        Constant { .. } => (),
-                        Char { .. } => (),
-                        Tuple { tuple, .. } => {
-                            for name in tuple {
-                                scope.add_scope(name.clone())
-                            }
-                        }
-                        Var { name, .. } => scope.add_scope(name.clone()),
+                    for name in pat.binds() {
+                        scope.add_scope(name)
                     }
                     scope.analyze_free_expr(frees, bound, arm);
                 }
@@ -338,13 +351,14 @@ impl<'a> Scope<'a> {
             }
             Proj { tuple, .. } => self.analyze_free_expr(frees, bound, tuple),
             Sym { name, ty } => {
-                if !(self.is_in_scope(name) || bound == name) {
+                if !(self.is_in_scope(name) || bound == name || self.hoisted.contains_key(name)) {
                     frees.push((ty.clone(), name.clone()))
                 }
             }
             Closure { envs, .. } => {
                 for (ty, name) in envs {
-                    if !(self.is_in_scope(name) || bound == name) {
+                    if !(self.is_in_scope(name) || bound == name || self.hoisted.contains_key(name))
+                    {
                         frees.push((ty.clone(), name.clone()))
                     }
                 }
@@ -414,6 +428,7 @@ impl UnnestFunc {
         UnnestFunc {
             tables: Vec::new(),
             tops: Vec::new(),
+            hoisted: HashMap::new(),
             pos: 0,
             id,
         }
@@ -29,12 +29,17 @@ pub trait Traverse {
             } => self.traverse_closure(envs, param_ty, body_ty, fname),
             BuiltinCall { ty, fun, args } => self.traverse_builtin_call(ty, fun, args),
             ExternCall {
-                ty,
+                retty,
                 module,
                 fun,
                 args,
-            } => self.traverse_extern_call(ty, module, fun, args),
-            App { ty, fun, arg } => self.traverse_app(ty, fun, arg),
+            } => self.traverse_extern_call(retty, module, fun, args),
+            App {
+                ty,
+                fun,
+                arg,
+                is_tail,
+            } => self.traverse_app(ty, fun, arg, is_tail),
             Case { ty, expr, arms } => self.traverse_case(ty, expr, arms),
             Tuple { tys, tuple } => self.traverse_tuple(tys, tuple),
             Proj { ty, index, tuple } => self.traverse_proj(ty, index, tuple),
@@ -81,7 +86,7 @@ pub trait Traverse {
 
     fn traverse_extern_call(
         &mut self,
-        _ty: &mut HTy,
+        _retty: &mut Vec<HTy>,
         _module: &mut String,
         _fun: &mut String,
         args: &mut Vec<Expr>,
@@ -91,7 +96,13 @@ pub trait Traverse {
         }
     }
 
-    fn traverse_app(&mut self, _ty: &mut HTy, fun: &mut Box<Expr>, arg: &mut Box<Expr>) {
+    fn traverse_app(
+        &mut self,
+        _ty: &mut HTy,
+        fun: &mut Box<Expr>,
+        arg: &mut Box<Expr>,
+        _is_tail: &mut bool,
+    ) {
         self.traverse_expr(fun);
         self.traverse_expr(arg);
     }
@@ -159,17 +170,22 @@ pub trait Transform {
                 body,
                 captures,
             } => self.transform_fun(param, body_ty, body, captures),
-            App { fun, arg, ty } => self.transform_app(ty, fun, arg),
+            App {
+                fun,
+                arg,
+                ty,
+                is_tail,
+            } => self.transform_app(ty, fun, arg, is_tail),
             Case { ty, expr, arms } => self.transform_case(ty, expr, arms),
             Tuple { tys, tuple } => self.transform_tuple(tys, tuple),
             Proj { ty, index, tuple } => self.transform_proj(ty, index, tuple),
             BuiltinCall { ty, fun, args } => self.transform_builtin_call(ty, fun, args),
             ExternCall {
-                ty,
+                retty,
                 module,
                 fun,
                 args,
-            } => self.transform_extern_call(ty, module, fun, args),
+            } => self.transform_extern_call(retty, module, fun, args),
             Closure {
                 envs,
                 param_ty,
@@ -240,13 +256,13 @@ pub trait Transform {
 
     fn transform_extern_call(
         &mut self,
-        ty: HTy,
+        retty: Vec<HTy>,
         module: String,
         fun: String,
         args: Vec<Expr>,
     ) -> Expr {
         Expr::ExternCall {
-            ty,
+            retty,
             module,
             fun,
             args: args
@@ -256,11 +272,12 @@ pub trait Transform {
         }
     }
 
-    fn transform_app(&mut self, ty: HTy, fun: Box<Expr>, arg: Box<Expr>) -> Expr {
+    fn transform_app(&mut self, ty: HTy, fun: Box<Expr>, arg: Box<Expr>, is_tail: bool) -> Expr {
         Expr::App {
             ty,
             fun: Box::new(self.transform_expr(*fun)),
             arg: Box::new(self.transform_expr(*arg)),
+            is_tail,
         }
     }
 
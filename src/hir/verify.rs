@@ -0,0 +1,193 @@
+use crate::config::Config;
+use crate::hir::*;
+use crate::pass::Pass;
+use std::fmt;
+
+/// an inconsistency in HIR, made by a bug of a pass
+#[derive(Debug, Clone, PartialEq)]
+pub enum HirError {
+    /// the function of `App` is not typed by a function type
+    NotFunction {
+        ty: HTy,
+    },
+    /// the argument or the result of `App` disagrees with the type of the function
+    AppMismatch {
+        fun: HTy,
+        arg: HTy,
+        ret: HTy,
+    },
+    ProjOutOfRange {
+        index: u32,
+        ty: HTy,
+    },
+    UnknownConstructor {
+        descriminant: u32,
+        ty: HTy,
+    },
+    /// the pattern of a `Case` arm does not match the type of the scrutinee
+    PatternMismatch {
+        expected: HTy,
+        actual: HTy,
+    },
+}
+
+impl fmt::Display for HirError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// checks that the types in `hir` agree with each other
+pub fn verify(symbol_table: &SymbolTable, hir: &HIR) -> Result<(), HirError> {
+    let verifier = Verifier { symbol_table };
+    hir.0.iter().try_for_each(|val| verifier.val(val))
+}
+
+struct Verifier<'a> {
+    symbol_table: &'a SymbolTable,
+}
+
+impl<'a> Verifier<'a> {
+    fn val(&self, val: &Val) -> Result<(), HirError> {
+        self.expr(&val.expr)
+    }
+
+    fn expr(&self, expr: &Expr) -> Result<(), HirError> {
+        use crate::hir::Expr::*;
+        match expr {
+            Binds { binds, ret, .. } => {
+                binds.iter().try_for_each(|val| self.val(val))?;
+                self.expr(ret)
+            }
+            BuiltinCall { args, .. } | ExternCall { args, .. } => {
+                args.iter().try_for_each(|arg| self.expr(arg))
+            }
+            Fun { body, .. } => self.expr(body),
+            App { ty, fun, arg, .. } => {
+                self.expr(fun)?;
+                self.expr(arg)?;
+                match fun.ty() {
+                    HTy::Fun(param, ret) if *param == arg.ty() && *ret == *ty => Ok(()),
+                    HTy::Fun(..) => Err(HirError::AppMismatch {
+                        fun: fun.ty(),
+                        arg: arg.ty(),
+                        ret: ty.clone(),
+                    }),
+                    ty => Err(HirError::NotFunction { ty }),
+                }
+            }
+            Case { expr, arms, .. } => {
+                self.expr(expr)?;
+                let ty = expr.ty();
+                for (pat, arm) in arms {
+                    self.pattern(&ty, pat)?;
+                    self.expr(arm)?;
+                }
+                Ok(())
+            }
+            Tuple { tuple, .. } => tuple.iter().try_for_each(|e| self.expr(e)),
+            Proj { index, tuple, .. } => {
+                self.expr(tuple)?;
+                match tuple.ty() {
+                    HTy::Tuple(ref tys) if (*index as usize) < tys.len() => Ok(()),
+                    ty => Err(HirError::ProjOutOfRange { index: *index, ty }),
+                }
+            }
+            Constructor {
+                ty,
+                arg,
+                descriminant,
+            } => {
+                if let Some(arg) = arg {
+                    self.expr(arg)?;
+                }
+                self.constructor(ty, *descriminant).map(|_| ())
+            }
+            Closure { .. } | Sym { .. } | Lit { .. } => Ok(()),
+        }
+    }
+
+    // the type of the argument of the constructor of `ty`, if it takes one
+    fn constructor(&self, ty: &HTy, descriminant: u32) -> Result<Option<HTy>, HirError> {
+        let arg = match ty {
+            HTy::Bool if descriminant < 2 => Some(None),
+            // `nil` and `::`
+            HTy::List(_) if descriminant == 0 => Some(None),
+            HTy::List(elem) if descriminant == 1 => {
+                Some(Some(HTy::Tuple(vec![(**elem).clone(), ty.clone()])))
+            }
+            HTy::Datatype(name, _) => self.symbol_table.types.get(name).and_then(|info| {
+                info.constructors
+                    .iter()
+                    .find(|(d, _)| *d == descriminant)
+                    .map(|(_, arg)| arg.clone())
+            }),
+            _ => None,
+        };
+        arg.ok_or_else(|| HirError::UnknownConstructor {
+            descriminant,
+            ty: ty.clone(),
+        })
+    }
+
+    fn pattern(&self, ty: &HTy, pat: &Pattern) -> Result<(), HirError> {
+        match pat {
+            Pattern::Or(alternatives) => {
+                return alternatives
+                    .iter()
+                    .try_for_each(|pat| self.pattern(ty, pat))
+            }
+            Pattern::As { pattern, .. } => self.pattern(ty, pattern)?,
+            Pattern::Tuple { tuple, .. } => {
+                if let HTy::Tuple(tys) = ty {
+                    for (ty, pat) in tys.iter().zip(tuple) {
+                        self.pattern(ty, pat)?
+                    }
+                }
+            }
+            Pattern::Constructor {
+                descriminant, arg, ..
+            } => {
+                if let (Some(argty), Some(arg)) = (self.constructor(ty, *descriminant)?, arg) {
+                    self.pattern(&argty, arg)?
+                }
+            }
+            _ => (),
+        }
+        if pat.ty() == *ty {
+            Ok(())
+        } else {
+            Err(HirError::PatternMismatch {
+                expected: ty.clone(),
+                actual: pat.ty(),
+            })
+        }
+    }
+}
+
+/// checks the HIR with `verify` if `Config::verify_hir` is set
+pub struct Verify;
+
+impl Verify {
+    pub fn new() -> Self {
+        Verify
+    }
+}
+
+impl<E> Pass<(SymbolTable, HIR), E> for Verify
+where
+    E: From<HirError>,
+{
+    type Target = (SymbolTable, HIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, hir): (SymbolTable, HIR),
+        config: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        if config.verify_hir {
+            verify(&symbol_table, &hir)?;
+        }
+        Ok((symbol_table, hir))
+    }
+}
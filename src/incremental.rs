@@ -0,0 +1,183 @@
+//! typing a program one top-level declaration at a time, for the editors.
+//! a declaration is re-typed only when it or the declarations it refers to change,
+//! together with the declarations it refers to, and the results of the others are kept
+
+use crate::ast::{self, Names, Typed};
+use crate::config::Config;
+use crate::id::Id;
+use crate::pass::Pass;
+use crate::prim::Name;
+use std::collections::{BTreeSet, HashSet};
+
+struct Decl {
+    source: String,
+    names: Names,
+    /// whether it only declares infix operators, which all the following declarations need
+    infix: bool,
+    /// the declarations it needs and itself typed, if up to date
+    typed: Option<Typed>,
+}
+
+/// a program given as the sources of its top-level declarations
+pub struct Session {
+    config: Config,
+    decls: Vec<Decl>,
+    retyped: Vec<usize>,
+}
+
+/// a declaration failed to parse or to typecheck
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeclError {
+    /// the index of the declaration
+    pub index: usize,
+    pub message: String,
+}
+
+impl Session {
+    pub fn new(config: Config) -> Self {
+        Session {
+            config,
+            decls: Vec::new(),
+            retyped: Vec::new(),
+        }
+    }
+
+    /// appends a declaration, returning its index
+    pub fn push(&mut self, source: impl Into<String>) -> Result<usize, DeclError> {
+        let index = self.decls.len();
+        self.decls.push(Decl {
+            source: String::new(),
+            names: Names::default(),
+            infix: false,
+            typed: None,
+        });
+        if let Err(e) = self.edit(index, source) {
+            self.decls.pop();
+            return Err(e);
+        }
+        Ok(index)
+    }
+
+    /// replaces the source of the `index`th declaration,
+    /// making it and the declarations referring to it out of date
+    pub fn edit(&mut self, index: usize, source: impl Into<String>) -> Result<(), DeclError> {
+        let source = source.into();
+        let (names, infix) = self.names(index, &source)?;
+        let old = std::mem::replace(&mut self.decls[index].names, names);
+        let decl = &mut self.decls[index];
+        decl.source = source;
+        decl.infix = infix;
+        decl.typed = None;
+        // the ones referring to the names bound before the edit as well as after it
+        let changed: HashSet<Name> = old
+            .binds
+            .union(&self.decls[index].names.binds)
+            .cloned()
+            .collect();
+        self.invalidate(index, &changed, infix);
+        Ok(())
+    }
+
+    /// types the declarations out of date. the others are not inferred again
+    pub fn typecheck(&mut self) -> Result<(), DeclError> {
+        self.retyped.clear();
+        for index in 0..self.decls.len() {
+            if self.decls[index].typed.is_some() {
+                continue;
+            }
+            let source = self
+                .dependencies(index)
+                .into_iter()
+                .chain(Some(index))
+                .map(|i| self.decls[i].source.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+            let typed = crate::typecheck(&source, &self.config).map_err(|e| DeclError {
+                index,
+                message: e.to_string(),
+            })?;
+            self.decls[index].typed = Some(typed);
+            self.retyped.push(index);
+            // the ones out of date because of this, typed later in this loop
+            let binds = self.decls[index].names.binds.clone();
+            let infix = self.decls[index].infix;
+            self.invalidate(index, &binds, infix);
+        }
+        Ok(())
+    }
+
+    /// the indices of the declarations typed by the last `typecheck`, in order
+    pub fn retyped(&self) -> &[usize] {
+        &self.retyped
+    }
+
+    /// the typed program of the `index`th declaration and the declarations it needs,
+    /// if it is up to date
+    pub fn typed(&self, index: usize) -> Option<&Typed> {
+        self.decls[index].typed.as_ref()
+    }
+
+    /// the type of the variable bound at the top level as `name`, the last one if shadowed
+    pub fn type_of(&self, name: &str) -> Option<&ast::Type> {
+        let key = Name::new(name);
+        self.decls
+            .iter()
+            .rev()
+            .find(|decl| decl.names.binds.contains(&key))
+            .and_then(|decl| decl.typed.as_ref())
+            .and_then(|typed| typed.type_of(name))
+    }
+
+    // the names the source binds and refers to, parsed with the infix declarations before it
+    fn names(&self, index: usize, source: &str) -> Result<(Names, bool), DeclError> {
+        let error = |message: String| DeclError { index, message };
+        let infixes = self.decls[..index]
+            .iter()
+            .filter(|decl| decl.infix)
+            .map(|decl| decl.source.as_str())
+            .collect::<Vec<_>>();
+        let input = infixes
+            .iter()
+            .cloned()
+            .chain(Some(source))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let parsed = crate::parse(&input).map_err(|e| error(format!("{:?}", e)))?;
+        let infix = parsed.0.len()
+            == parsed
+                .0
+                .iter()
+                .filter(|decl| match decl {
+                    ast::Declaration::D(ast::DerivedDeclaration::Infix { .. }) => true,
+                    _ => false,
+                })
+                .count();
+        let result: Result<_, ()> = ast::Desugar::new(Id::new()).trans(parsed, &self.config);
+        Ok((Names::of(&result.unwrap()), infix))
+    }
+
+    // the declarations before `index` it refers to, directly or not, and the infix ones
+    fn dependencies(&self, index: usize) -> BTreeSet<usize> {
+        let mut deps = BTreeSet::new();
+        let mut todo = vec![index];
+        while let Some(i) = todo.pop() {
+            for (j, decl) in self.decls[..i].iter().enumerate() {
+                let used = decl.infix || !decl.names.binds.is_disjoint(&self.decls[i].names.uses);
+                if used && deps.insert(j) {
+                    todo.push(j)
+                }
+            }
+        }
+        deps
+    }
+
+    // makes the declarations after `index` referring to `names` out of date,
+    // or all of them if the `index`th declares infix operators
+    fn invalidate(&mut self, index: usize, names: &HashSet<Name>, infix: bool) {
+        for decl in self.decls[index + 1..].iter_mut() {
+            if infix || !decl.names.uses.is_disjoint(names) {
+                decl.typed = None;
+            }
+        }
+    }
+}
@@ -5,44 +5,152 @@ pub mod backend;
 mod config;
 pub mod hir;
 pub mod id;
+pub mod incremental;
 pub mod lir;
 pub mod mir;
 mod parser;
 pub mod pass;
 pub mod prim;
-mod unification_pool;
+pub mod unification_pool;
 
-pub use crate::ast::TypeError;
-pub use crate::config::Config;
+pub use crate::ast::{TypeError, Warning};
+pub use crate::config::{Config, DefaultNum, PassName};
 pub use crate::parser::parse;
-pub use crate::pass::{Chain, Pass};
+pub use crate::pass::{Chain, Pass, Timings};
 
-pub fn compile_str<'a>(input: &'a str, config: &Config) -> Result<Vec<u8>, TypeError<'a>> {
+// the passes from the source to the typed AST
+fn to_typed<'a>(
+    id: &id::Id,
+) -> impl Pass<&'a str, TypeError<'a>, Target = (ast::SymbolTable, ast::TypedCore)> {
     use crate::pass::{ConvError, PrintablePass};
-    use wasm::Dump;
+
+    compile_pass![
+        parse: ConvError::new(parse),
+        desugar: ast::Desugar::new(id.clone()),
+        rename: ast::Rename::new(id.clone()),
+        var_to_constructor: ast::VarToConstructor::new(id.clone()),
+        typing: ast::Typer::new(),
+    ]
+}
+
+// typechecks `input` and checks the matches in it, adding the warnings found to `warnings`
+fn to_checked<'a>(
+    input: &'a str,
+    config: &Config,
+    id: &id::Id,
+    warnings: &mut Vec<Warning>,
+) -> Result<(ast::SymbolTable, ast::TypedCore), TypeError<'a>> {
+    use crate::pass::PrintablePass;
+
+    let typed = to_typed(id).trans(input, config)?;
+    let mut match_check = compile_pass![match_check: ast::MatchCheck::new()];
+    let checked = match_check.trans(typed, config)?;
+    // inside `PrintablePass` and `TimedPass`
+    warnings.extend_from_slice((match_check.0).0.warnings());
+    Ok(checked)
+}
+
+/// parses and typechecks `input`, for the tools inspecting the types of a program
+pub fn typecheck<'a>(input: &'a str, config: &Config) -> Result<ast::Typed, TypeError<'a>> {
+    let mut warnings = Vec::new();
+    let (symbol_table, ast) = to_checked(input, config, &id::Id::new(), &mut warnings)?;
+    Ok(ast::Typed {
+        symbol_table,
+        ast,
+        warnings,
+    })
+}
+
+// the passes from the typed AST to HIR, through the passes over HIR in `config.pipeline`
+fn to_hir<'a>(
+    id: &id::Id,
+) -> impl Pass<(ast::SymbolTable, ast::TypedCore), TypeError<'a>, Target = (hir::SymbolTable, hir::HIR)>
+{
+    use crate::hir::DumpHir;
+    use crate::pass::PrintablePass;
+
+    Chain::new(
+        compile_pass![
+            monomorphize: ast::Monomorphize::new(id.clone()),
+            case_simplify: ast::CaseSimplify::new(id.clone()),
+            ast_to_hir: DumpHir::new(hir::AST2HIR::new(id.clone()), PassName::Ast2Hir),
+        ],
+        Chain::new(
+            // the passes over HIR in `config.pipeline`
+            hir::Pipeline::new(id.clone()),
+            compile_pass![verify_hir: hir::Verify::new()],
+        ),
+    )
+}
+
+/// compiles `input` to HIR with the passes over HIR in `config.pipeline`, for the tools
+/// inspecting the program after the typing and the optimizations.
+/// the symbol table has the constructors of the datatypes in the HIR
+pub fn compile_to_hir<'a>(
+    input: &'a str,
+    config: &Config,
+) -> Result<(hir::SymbolTable, hir::HIR), TypeError<'a>> {
+    config
+        .check_pipeline()
+        .map_err(TypeError::InvalidPipeline)?;
 
     let id = id::Id::new();
+    let typed = to_checked(input, config, &id, &mut Vec::new())?;
+    to_hir(&id).trans(typed, config)
+}
+
+/// the result of `compile`, and what is recorded while compiling even if it fails
+#[derive(Debug)]
+pub struct Output<'a> {
+    pub code: Result<Vec<u8>, TypeError<'a>>,
+    /// the durations of the passes if `Config::time_passes` is set
+    pub timings: Timings,
+    /// the warnings of the checks of the matches
+    pub warnings: Vec<Warning>,
+}
+
+/// compiles `input` to a wasm module, as `compile_str` does,
+/// with the records of the compilation for the caller to report
+pub fn compile<'a>(input: &'a str, config: &Config) -> Output<'a> {
+    let mut warnings = Vec::new();
+    let (code, timings) = Timings::collect(|| to_wasm(input, config, &mut warnings));
+    Output {
+        code,
+        timings,
+        warnings,
+    }
+}
+
+pub fn compile_str<'a>(input: &'a str, config: &Config) -> Result<Vec<u8>, TypeError<'a>> {
+    to_wasm(input, config, &mut Vec::new())
+}
+
+fn to_wasm<'a>(
+    input: &'a str,
+    config: &Config,
+    warnings: &mut Vec<Warning>,
+) -> Result<Vec<u8>, TypeError<'a>> {
+    use crate::pass::PrintablePass;
+
+    let id = id::Id::new();
+
+    config
+        .check_pipeline()
+        .map_err(TypeError::InvalidPipeline)?;
+
+    let typed = to_checked(input, config, &id, warnings)?;
+    let mut passes = Chain::new(
+        to_hir(&id),
+        compile_pass![
+            hir_to_mir: mir::HIR2MIR::new(id),
+            unalias: mir::UnAlias::new(),
+            block_arrange: mir::BlockArrange::new(),
+            mir_to_lir: lir::MIR2LIR::new(),
+            backend: backend::LIR2WASM::new(),
+        ],
+    );
 
-    let mut passes = compile_pass![
-       parse: ConvError::new(parse),
-       desugar: ast::Desugar::new(id.clone()),
-       rename: ast::Rename::new(id.clone()),
-       var_to_constructor: ast::VarToConstructor::new(id.clone()),
-       typing: ast::Typer::new(),
-       case_simplify: ast::CaseSimplify::new(id.clone()),
-       ast_to_hir: hir::AST2HIR::new(id.clone()),
-       flattening_expression: hir::FlatExpr::new(id.clone()),
-       flattening_let: hir::FlatLet::new(),
-       unnest_functions: hir::UnnestFunc::new(id.clone()),
-       closure_conversion: hir::ForceClosure::new(),
-       hir_to_mir: mir::HIR2MIR::new(id),
-       unalias: mir::UnAlias::new(),
-       block_arrange: mir::BlockArrange::new(),
-       mir_to_lir: lir::MIR2LIR::new(),
-       backend: backend::LIR2WASM::new(),
-    ];
-
-    let module: wasm::Module = passes.trans(input, config)?;
+    let module: backend::Wasm = passes.trans(typed, config)?;
 
     let mut code = Vec::new();
     module.dump(&mut code);
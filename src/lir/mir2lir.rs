@@ -37,6 +37,7 @@ impl MIR2LIRPass {
             Unit => LTy::Unit,
             Char => LTy::U32,
             Int => LTy::I32,
+            Word => LTy::U32,
             Float => LTy::F64,
             Bool => LTy::I32,
             Tuple(_) => LTy::Ptr,
@@ -60,6 +61,7 @@ impl MIR2LIRPass {
             name,
             body,
             body_ty,
+            export,
         } = f;
         let nparams = body[0].params.len() as u32;
         let ret_ty = self.ebbty_to_lty(&body_ty);
@@ -97,7 +99,34 @@ impl MIR2LIRPass {
                         } => match value {
                             &Literal::Char(c) => ops.push(ConstI32(reg!(var), c as u32)),
                             &Literal::Int(i) => ops.push(ConstI32(reg!(var), i as u32)),
+                            &Literal::Word(w) => ops.push(ConstU32(reg!(var), w as u32)),
                             &Literal::Real(f) => ops.push(ConstF64(reg!(var), f as f64)),
+                            // the UTF-8 bytes are copied to the heap, packed in 8 byte words
+                            &Literal::String(ref s) => {
+                                let bytes = s.as_bytes();
+                                let words = (bytes.len() + 7) / 8;
+                                let ptr = new_reg(LTy::Ptr);
+                                ops.push(HeapAlloc(
+                                    ptr.clone(),
+                                    I((words * 8) as i32),
+                                    vec![LTy::I64; words],
+                                ));
+                                let word = new_reg(LTy::I64);
+                                for (i, chunk) in bytes.chunks(8).enumerate() {
+                                    let mut buf = [0; 8];
+                                    buf[..chunk.len()].copy_from_slice(chunk);
+                                    ops.push(ConstI64(word.clone(), u64::from_le_bytes(buf)));
+                                    ops.push(StoreI64(
+                                        Addr(ptr.clone(), i as u32 * 8),
+                                        word.clone(),
+                                    ));
+                                }
+                                let len = new_reg(LTy::I32);
+                                ops.push(ConstI32(len.clone(), bytes.len() as u32));
+                                ops.push(HeapAlloc(reg!(var), I(16), vec![LTy::Ptr, LTy::I32]));
+                                ops.push(StoreI32(Addr(reg!(var), 0), ptr));
+                                ops.push(StoreI32(Addr(reg!(var), 8), len));
+                            }
                         },
                         &m::Alias {
                             ref var,
@@ -128,6 +157,8 @@ impl MIR2LIRPass {
                         } => {
                             if ty == &mir::EbbTy::Int {
                                 ops.push(AddI32(reg!(var), reg!(l), reg!(r)));
+                            } else if ty == &mir::EbbTy::Word {
+                                ops.push(AddU32(reg!(var), reg!(l), reg!(r)));
                             } else {
                                 assert_eq!(ty, &mir::EbbTy::Float);
                                 ops.push(AddF64(reg!(var), reg!(l), reg!(r)));
@@ -141,6 +172,8 @@ impl MIR2LIRPass {
                         } => {
                             if ty == &mir::EbbTy::Int {
                                 ops.push(SubI32(reg!(var), reg!(l), reg!(r)));
+                            } else if ty == &mir::EbbTy::Word {
+                                ops.push(SubU32(reg!(var), reg!(l), reg!(r)));
                             } else {
                                 assert_eq!(ty, &mir::EbbTy::Float);
                                 ops.push(SubF64(reg!(var), reg!(l), reg!(r)));
@@ -154,6 +187,8 @@ impl MIR2LIRPass {
                         } => {
                             if ty == &mir::EbbTy::Int {
                                 ops.push(MulI32(reg!(var), reg!(l), reg!(r)));
+                            } else if ty == &mir::EbbTy::Word {
+                                ops.push(MulU32(reg!(var), reg!(l), reg!(r)));
                             } else {
                                 assert_eq!(ty, &mir::EbbTy::Float);
                                 ops.push(MulF64(reg!(var), reg!(l), reg!(r)));
@@ -161,11 +196,15 @@ impl MIR2LIRPass {
                         }
                         &m::DivInt {
                             ref var,
+                            ref ty,
                             ref l,
                             ref r,
-                            ..
                         } => {
-                            ops.push(DivI32(reg!(var), reg!(l), reg!(r)));
+                            if ty == &mir::EbbTy::Word {
+                                ops.push(DivU32(reg!(var), reg!(l), reg!(r)));
+                            } else {
+                                ops.push(DivI32(reg!(var), reg!(l), reg!(r)));
+                            }
                         }
                         &m::DivFloat {
                             ref var,
@@ -176,12 +215,128 @@ impl MIR2LIRPass {
                             ops.push(DivF64(reg!(var), reg!(l), reg!(r)));
                         }
                         &m::Mod {
+                            ref var,
+                            ref ty,
+                            ref l,
+                            ref r,
+                        } => {
+                            if ty == &mir::EbbTy::Word {
+                                ops.push(ModU32(reg!(var), reg!(l), reg!(r)));
+                            } else {
+                                ops.push(ModI32(reg!(var), reg!(l), reg!(r)));
+                            }
+                        }
+                        &m::Shl {
+                            ref var,
+                            ref l,
+                            ref r,
+                            ..
+                        } => {
+                            ops.push(ShlI32(reg!(var), reg!(l), reg!(r)));
+                        }
+                        &m::Shr {
+                            ref var,
+                            ref ty,
+                            ref l,
+                            ref r,
+                        } => {
+                            if ty == &mir::EbbTy::Word {
+                                ops.push(ShrU32(reg!(var), reg!(l), reg!(r)));
+                            } else {
+                                ops.push(ShrI32(reg!(var), reg!(l), reg!(r)));
+                            }
+                        }
+                        &m::And {
                             ref var,
                             ref l,
                             ref r,
                             ..
                         } => {
-                            ops.push(ModI32(reg!(var), reg!(l), reg!(r)));
+                            ops.push(AndI32(reg!(var), reg!(l), reg!(r)));
+                        }
+                        &m::Or {
+                            ref var,
+                            ref l,
+                            ref r,
+                            ..
+                        } => {
+                            ops.push(OrI32(reg!(var), reg!(l), reg!(r)));
+                        }
+                        &m::Xor {
+                            ref var,
+                            ref l,
+                            ref r,
+                            ..
+                        } => {
+                            ops.push(XorI32(reg!(var), reg!(l), reg!(r)));
+                        }
+                        &m::Abs {
+                            ref var,
+                            ref ty,
+                            ref arg,
+                        } => {
+                            if ty == &mir::EbbTy::Int {
+                                // arg * (2 * (arg >> 31) + 1), which wraps the minimum int to itself
+                                let sign = new_reg(LTy::I32);
+                                let constant = new_reg(LTy::I32);
+                                ops.push(ConstI32(constant.clone(), 31));
+                                ops.push(ShrI32(sign.clone(), reg!(arg), constant.clone()));
+                                ops.push(AddI32(sign.clone(), sign.clone(), sign.clone()));
+                                ops.push(ConstI32(constant.clone(), 1));
+                                ops.push(AddI32(sign.clone(), sign.clone(), constant));
+                                ops.push(MulI32(reg!(var), reg!(arg), sign));
+                            } else if ty == &mir::EbbTy::Word {
+                                // words are never negative
+                                ops.push(MoveU32(reg!(var), reg!(arg)));
+                            } else {
+                                assert_eq!(ty, &mir::EbbTy::Float);
+                                ops.push(AbsF64(reg!(var), reg!(arg)));
+                            }
+                        }
+                        &m::RealOfInt {
+                            ref var, ref arg, ..
+                        } => {
+                            ops.push(ConvertI32F64(reg!(var), reg!(arg)));
+                        }
+                        &m::Floor {
+                            ref var, ref arg, ..
+                        } => {
+                            let rounded = new_reg(LTy::F64);
+                            ops.push(FloorF64(rounded.clone(), reg!(arg)));
+                            ops.push(TruncF64I32(reg!(var), rounded));
+                        }
+                        &m::Ceil {
+                            ref var, ref arg, ..
+                        } => {
+                            let rounded = new_reg(LTy::F64);
+                            ops.push(CeilF64(rounded.clone(), reg!(arg)));
+                            ops.push(TruncF64I32(reg!(var), rounded));
+                        }
+                        &m::Round {
+                            ref var, ref arg, ..
+                        } => {
+                            let rounded = new_reg(LTy::F64);
+                            ops.push(NearestF64(rounded.clone(), reg!(arg)));
+                            ops.push(TruncF64I32(reg!(var), rounded));
+                        }
+                        &m::Trunc {
+                            ref var, ref arg, ..
+                        } => {
+                            ops.push(TruncF64I32(reg!(var), reg!(arg)));
+                        }
+                        &m::Sqrt {
+                            ref var, ref arg, ..
+                        } => {
+                            ops.push(SqrtF64(reg!(var), reg!(arg)));
+                        }
+                        &m::Chr {
+                            ref var, ref arg, ..
+                        } => {
+                            // arg / (arg <u 256), which traps by the division by zero when out of range
+                            let in_range = new_reg(LTy::U32);
+                            ops.push(ConstU32(in_range.clone(), 256));
+                            ops.push(LtU32(in_range.clone(), reg!(arg), in_range.clone()));
+                            ops.push(DivU32(reg!(var), reg!(arg), in_range));
                         }
                         &m::Eq {
                             ref var,
@@ -195,6 +350,8 @@ impl MIR2LIRPass {
                             (&LTy::U64, &LTy::U64) => ops.push(EqU64(reg!(var), reg!(l), reg!(r))),
                             (&LTy::F32, &LTy::F32) => ops.push(EqF32(reg!(var), reg!(l), reg!(r))),
                             (&LTy::F64, &LTy::F64) => ops.push(EqF64(reg!(var), reg!(l), reg!(r))),
+                            // the refs are equal if they are the same cell
+                            (&LTy::Ptr, &LTy::Ptr) => ops.push(EqI32(reg!(var), reg!(l), reg!(r))),
                             ty => panic!("unknown overloaded ty {:?} for eq", ty),
                         },
                         &m::Neq {
@@ -209,6 +366,7 @@ impl MIR2LIRPass {
                             (&LTy::U64, &LTy::U64) => ops.push(NeqU64(reg!(var), reg!(l), reg!(r))),
                             (&LTy::F32, &LTy::F32) => ops.push(NeqF32(reg!(var), reg!(l), reg!(r))),
                             (&LTy::F64, &LTy::F64) => ops.push(NeqF64(reg!(var), reg!(l), reg!(r))),
+                            (&LTy::Ptr, &LTy::Ptr) => ops.push(NeqI32(reg!(var), reg!(l), reg!(r))),
                             ty => panic!("unknown overloaded ty {:?} for neq", ty),
                         },
                         &m::Gt {
@@ -342,6 +500,33 @@ impl MIR2LIRPass {
                                 break;
                             }
                         }
+                        &m::Store {
+                            ref ty,
+                            ref index,
+                            ref tuple,
+                            ref value,
+                        } => {
+                            #[allow(clippy::never_loop)]
+                            loop {
+                                let ctor = match self.ebbty_to_lty(ty) {
+                                    LTy::F32 => StoreF32,
+                                    LTy::F64 => StoreF64,
+                                    LTy::I32 => StoreI32,
+                                    LTy::U32 => StoreU32,
+                                    LTy::I64 => StoreI64,
+                                    LTy::U64 => StoreU64,
+                                    LTy::Ptr => StoreI32,
+                                    LTy::FPtr => StoreI32,
+                                    LTy::Unit =>
+                                    // do nothing
+                                    {
+                                        break
+                                    }
+                                };
+                                ops.push(ctor(Addr(reg!(tuple), *index * 8), reg!(value)));
+                                break;
+                            }
+                        }
 
                         &m::Union {
                             ref var,
@@ -457,23 +642,21 @@ impl MIR2LIRPass {
                             }
                         }
                         &m::ExternCall {
-                            ref var,
+                            ref rets,
                             ref module,
                             ref fun,
                             ref args,
-                            ..
                         } => {
-                            let args = args.iter().map(|a| reg!(a)).collect();
+                            let args = args.iter().map(|a| reg!(a)).collect::<Vec<_>>();
+                            let rets = rets.iter().map(|(_, r)| reg!(r)).collect::<Vec<_>>();
                             self.extern_types.insert(
                                 (module.to_string(), fun.to_string()),
-                                (vec![LTy::I32], LTy::Unit),
+                                (
+                                    args.iter().map(|a| a.0.clone()).collect(),
+                                    rets.iter().map(|r| r.0.clone()).collect(),
+                                ),
                             );
-                            ops.push(ExternCall(
-                                reg!(var),
-                                module.to_string(),
-                                fun.to_string(),
-                                args,
-                            ))
+                            ops.push(ExternCall(rets, module.to_string(), fun.to_string(), args))
                         }
                         &m::Call {
                             ref var,
@@ -615,6 +798,7 @@ impl MIR2LIRPass {
             regs,
             ret_ty,
             body: blocks,
+            export,
         }
     }
 
@@ -671,6 +855,45 @@ impl MIR2LIRPass {
                     | &mir::Op::Mod {
                         ref var, ref ty, ..
                     }
+                    | &mir::Op::Shl {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Shr {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::And {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Or {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Xor {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Abs {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::RealOfInt {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Floor {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Ceil {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Round {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Trunc {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Sqrt {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Chr {
+                        ref var, ref ty, ..
+                    }
                     | &mir::Op::Eq {
                         ref var, ref ty, ..
                     }
@@ -695,14 +918,16 @@ impl MIR2LIRPass {
                     | &mir::Op::Select {
                         ref var, ref ty, ..
                     }
-                    | &mir::Op::ExternCall {
-                        ref var, ref ty, ..
-                    }
                     | &mir::Op::Call {
                         ref var, ref ty, ..
                     } => {
                         intern!(self.ebbty_to_lty(ty), var);
                     }
+                    &mir::Op::ExternCall { ref rets, .. } => {
+                        for (ty, var) in rets {
+                            intern!(self.ebbty_to_lty(ty), var);
+                        }
+                    }
                     &mir::Op::Tuple { ref var, .. } | &mir::Op::Closure { ref var, .. } => {
                         intern!(LTy::Ptr, var);
                     }
@@ -39,7 +39,7 @@ impl LTy {
     }
 }
 
-pub type ExternTypes = HashMap<(String, String), (Vec<LTy>, LTy)>;
+pub type ExternTypes = HashMap<(String, String), (Vec<LTy>, Vec<LTy>)>;
 
 #[derive(Debug, Clone)]
 pub struct Reg(pub LTy, pub u32);
@@ -73,6 +73,8 @@ pub struct Function {
     pub regs: Vec<LTy>,
     pub ret_ty: LTy,
     pub body: Vec<Block>,
+    /// the name exported from the wasm module, if any
+    pub export: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -90,6 +92,12 @@ pub enum Op {
     MulI32(Reg, Reg, Reg),
     DivI32(Reg, Reg, Reg),
     ModI32(Reg, Reg, Reg),
+    ShlI32(Reg, Reg, Reg),
+    ShrI32(Reg, Reg, Reg),
+    ShrU32(Reg, Reg, Reg),
+    AndI32(Reg, Reg, Reg),
+    OrI32(Reg, Reg, Reg),
+    XorI32(Reg, Reg, Reg),
     EqI32(Reg, Reg, Reg),
     NeqI32(Reg, Reg, Reg),
     GtI32(Reg, Reg, Reg),
@@ -171,6 +179,11 @@ pub enum Op {
     SubF64(Reg, Reg, Reg),
     MulF64(Reg, Reg, Reg),
     DivF64(Reg, Reg, Reg),
+    AbsF64(Reg, Reg),
+    FloorF64(Reg, Reg),
+    CeilF64(Reg, Reg),
+    NearestF64(Reg, Reg),
+    SqrtF64(Reg, Reg),
     EqF64(Reg, Reg, Reg),
     NeqF64(Reg, Reg, Reg),
     GtF64(Reg, Reg, Reg),
@@ -180,11 +193,14 @@ pub enum Op {
     StoreF64(Addr, Reg),
     LoadF64(Reg, Addr),
 
+    ConvertI32F64(Reg, Reg),
+    TruncF64I32(Reg, Reg),
+
     HeapAlloc(Reg, Value, Vec<LTy>),
     StackAlloc(Reg, u32, Vec<LTy>),
 
     StoreFnPtr(Addr, Symbol),
-    ExternCall(Reg, String, String, Vec<Reg>),
+    ExternCall(Vec<Reg>, String, String, Vec<Reg>),
     FunCall(Reg, Symbol, Vec<Reg>),
     ClosureCall(Reg, Reg, Vec<Reg>),
     Jump(Label),
@@ -20,6 +20,9 @@ impl PP for LIR {
 impl PP for Function {
     fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
         let indent = indent + 4;
+        if let Some(export) = &self.export {
+            write!(w, "export \"{}\" ", export)?;
+        }
         write!(w, "fun ")?;
         self.name.pp(w, 0)?;
         write!(w, ": (")?;
@@ -125,6 +128,55 @@ impl PP for Op {
                 write!(w, " <- ")?;
                 r2.pp(w, indent)?;
             }
+            AbsF64(r1, r2) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- abs ")?;
+                r2.pp(w, indent)?;
+            }
+            FloorF64(r1, r2) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- floor ")?;
+                r2.pp(w, indent)?;
+            }
+            CeilF64(r1, r2) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- ceil ")?;
+                r2.pp(w, indent)?;
+            }
+            NearestF64(r1, r2) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- nearest ")?;
+                r2.pp(w, indent)?;
+            }
+            SqrtF64(r1, r2) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- sqrt ")?;
+                r2.pp(w, indent)?;
+            }
+            ConvertI32F64(r1, r2) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- convert ")?;
+                r2.pp(w, indent)?;
+            }
+            TruncF64I32(r1, r2) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- trunc ")?;
+                r2.pp(w, indent)?;
+            }
             StoreI32(addr, v)
             | StoreU32(addr, v)
             | StoreI64(addr, v)
@@ -243,6 +295,51 @@ impl PP for Op {
                 write!(w, " mod ")?;
                 r3.pp(w, indent)?;
             }
+            ShlI32(r1, r2, r3) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- ")?;
+                r2.pp(w, indent)?;
+                write!(w, " << ")?;
+                r3.pp(w, indent)?;
+            }
+            ShrI32(r1, r2, r3) | ShrU32(r1, r2, r3) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- ")?;
+                r2.pp(w, indent)?;
+                write!(w, " >> ")?;
+                r3.pp(w, indent)?;
+            }
+            AndI32(r1, r2, r3) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- ")?;
+                r2.pp(w, indent)?;
+                write!(w, " & ")?;
+                r3.pp(w, indent)?;
+            }
+            OrI32(r1, r2, r3) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- ")?;
+                r2.pp(w, indent)?;
+                write!(w, " | ")?;
+                r3.pp(w, indent)?;
+            }
+            XorI32(r1, r2, r3) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- ")?;
+                r2.pp(w, indent)?;
+                write!(w, " ^ ")?;
+                r3.pp(w, indent)?;
+            }
             EqI32(r1, r2, r3)
             | EqU32(r1, r2, r3)
             | EqI64(r1, r2, r3)
@@ -383,10 +480,16 @@ impl PP for Op {
                 }
                 write!(w, ")")?;
             }
-            ExternCall(reg, module, name, args) => {
-                reg.pp(w, indent)?;
-                write!(w, ": ")?;
-                reg.0.pp(w, indent)?;
+            ExternCall(regs, module, name, args) => {
+                inter_iter! {
+                    regs.iter(),
+                    write!(w, ", ")?,
+                    |reg| => {
+                        reg.pp(w, indent)?;
+                        write!(w, ": ")?;
+                        reg.0.pp(w, indent)?
+                    }
+                }
                 write!(w, " <- extern call \"{}\" \"{}\"", module, name)?;
                 write!(w, "(")?;
                 inter_iter! {
@@ -1,9 +1,11 @@
 use clap::{app_from_crate, crate_authors, crate_description, crate_name, crate_version, Arg};
 use std::collections::HashSet;
 use std::fs;
-use std::io::{self, prelude::*};
+use std::io::{self, prelude::*, IsTerminal};
 use std::path::Path;
-use webml::{compile_str, Config};
+use webml::prim::Span;
+use webml::util::{ColorMode, Highlight, PpConfig};
+use webml::{compile, Config, PassName};
 
 fn read_and_append_to_string(path: impl AsRef<Path>, buf: &mut String) -> io::Result<usize> {
     let file = fs::File::open(path)?;
@@ -11,6 +13,32 @@ fn read_and_append_to_string(path: impl AsRef<Path>, buf: &mut String) -> io::Re
     input.read_to_string(buf)
 }
 
+// `s` in the color of errors
+fn error_colored(color: ColorMode, s: &str) -> String {
+    let mut buf = Vec::new();
+    color
+        .paint(&mut buf, Highlight::Error, |w| write!(w, "{}", s))
+        .unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+// print the line of `span` with the spanned part underlined
+fn underline(input: &str, span: Span, color: ColorMode) {
+    let line_start = input[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = input[span.start..]
+        .find('\n')
+        .map_or(input.len(), |i| span.start + i);
+    let lineno = input[..line_start].matches('\n').count() + 1;
+    let width = span.end.min(line_end).saturating_sub(span.start).max(1);
+    eprintln!("{:>5} | {}", lineno, &input[line_start..line_end]);
+    eprintln!(
+        "{:>5} | {}{}",
+        "",
+        " ".repeat(span.start - line_start),
+        error_colored(color, &"^".repeat(width))
+    );
+}
+
 fn main() {
     env_logger::init();
     let matches = app_from_crate!()
@@ -22,6 +50,85 @@ fn main() {
                 .takes_value(true)
                 .multiple(true),
         )
+        .arg(
+            Arg::with_name("PP_INDENT")
+                .long("pp-indent")
+                .help("the number of spaces to indent the printed IR by")
+                .value_name("N")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("PP_WIDTH")
+                .long("pp-width")
+                .help("the line width of the printed IR")
+                .value_name("N")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("COLOR")
+                .long("color")
+                .help("when to color the printed IR and the errors")
+                .value_name("WHEN")
+                .possible_values(&["auto", "always", "never"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("DUMP_HIR")
+                .long("dhir")
+                .help("print the HIR after the pass")
+                .value_name("PASS")
+                .possible_values(
+                    &PassName::ALL
+                        .iter()
+                        .map(|pass| pass.name())
+                        .collect::<Vec<_>>(),
+                )
+                .takes_value(true)
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("PIPELINE")
+                .long("passes")
+                .help("the passes over HIR to run, in order")
+                .value_name("PASS")
+                .possible_values(
+                    &PassName::default_pipeline()
+                        .iter()
+                        .map(|pass| pass.name())
+                        .collect::<Vec<_>>(),
+                )
+                .takes_value(true)
+                .multiple(true)
+                .use_delimiter(true),
+        )
+        .arg(
+            Arg::with_name("OPT_LEVEL")
+                .short("O")
+                .help("the optimization level")
+                .value_name("LEVEL")
+                .possible_values(&["0", "1", "2"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("VERIFY_HIR")
+                .long("verify-hir")
+                .help("check the consistency of the HIR after the optimizations"),
+        )
+        .arg(
+            Arg::with_name("ALL_ERRORS")
+                .long("all-errors")
+                .help("report the type errors in all the declarations, not only the first one"),
+        )
+        .arg(
+            Arg::with_name("TIME_PASSES")
+                .long("time-passes")
+                .help("print how long each pass takes"),
+        )
+        .arg(
+            Arg::with_name("DUMP_UNIFY")
+                .long("dunify")
+                .help("print the equivalence classes of types after the inference"),
+        )
         .arg(
             Arg::with_name("INPUT")
                 .help("file to compile")
@@ -39,14 +146,76 @@ fn main() {
         .map(|s| s.to_string())
         .collect::<HashSet<String>>();
 
-    let config = Config {
+    let mut pp = PpConfig::default();
+    if let Some(indent) = matches.value_of("PP_INDENT") {
+        pp.indent = indent.parse().expect("--pp-indent takes a number");
+    }
+    if let Some(width) = matches.value_of("PP_WIDTH") {
+        pp.width = width.parse().expect("--pp-width takes a number");
+    }
+    pp.color = match matches.value_of("COLOR") {
+        Some("always") => ColorMode::Always,
+        Some("never") => ColorMode::Never,
+        _ => ColorMode::Auto,
+    };
+
+    let mut config = Config {
         pretty_print_ir,
+        pp,
+        dump_unification: matches.is_present("DUMP_UNIFY"),
+        time_passes: matches.is_present("TIME_PASSES"),
+        verify_hir: matches.is_present("VERIFY_HIR"),
+        collect_type_errors: matches.is_present("ALL_ERRORS"),
+        dump_hir_after: matches
+            .values_of("DUMP_HIR")
+            .into_iter()
+            .flatten()
+            .map(|pass| pass.parse().expect("--dhir takes a pass name"))
+            .collect(),
         ..Default::default()
     };
+    if let Some(level) = matches.value_of("OPT_LEVEL") {
+        config.opt_level = level.parse().unwrap();
+    }
+    if let Some(passes) = matches.values_of("PIPELINE") {
+        config.pipeline = passes
+            .map(|pass| pass.parse().expect("--passes takes pass names"))
+            .collect();
+    }
 
     let prelude = include_str!("../ml_src/prelude.sml").to_string();
+    let prelude_len = prelude.len();
     let mut input = prelude;
     read_and_append_to_string(filename, &mut input).expect("failed to load file");
-    let code = compile_str(&input, &config).unwrap();
+    let output = compile(&input, &config);
+    if config.time_passes {
+        output.timings.print(&mut io::stderr()).unwrap();
+    }
+    let color = config.pp.color.resolve(io::stderr().is_terminal());
+    // the spans in the file are shown relative to it, not to the prelude before it
+    let show_span = |span: Span| {
+        if prelude_len <= span.start {
+            let span = Span::new(span.start - prelude_len, span.end - prelude_len);
+            underline(&input[prelude_len..], span, color)
+        } else {
+            underline(&input, span, color)
+        }
+    };
+    for warning in &output.warnings {
+        eprintln!("warning: {}", warning);
+        show_span(warning.span());
+    }
+    let code = match output.code {
+        Ok(code) => code,
+        Err(e) => {
+            for e in e.into_errors() {
+                eprintln!("{}: {}", error_colored(color, "error"), e);
+                if let Some(span) = e.span() {
+                    show_span(span)
+                }
+            }
+            std::process::exit(1)
+        }
+    };
     fs::write("out.wasm", &code).unwrap()
 }
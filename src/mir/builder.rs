@@ -9,9 +9,14 @@ impl FunctionBuilder {
             name,
             body_ty,
             body: Vec::new(),
+            export: None,
         })
     }
 
+    pub fn export(&mut self, name: String) {
+        self.0.export = Some(name);
+    }
+
     pub fn add_ebb(&mut self, ebb: EBB) {
         self.0.body.push(ebb);
     }
@@ -76,6 +81,71 @@ impl EBBBuilder {
         self
     }
 
+    pub fn shl(&mut self, var: Symbol, ty: EbbTy, l: Symbol, r: Symbol) -> &mut Self {
+        self.push(Op::Shl { var, ty, l, r });
+        self
+    }
+
+    pub fn shr(&mut self, var: Symbol, ty: EbbTy, l: Symbol, r: Symbol) -> &mut Self {
+        self.push(Op::Shr { var, ty, l, r });
+        self
+    }
+
+    pub fn and(&mut self, var: Symbol, ty: EbbTy, l: Symbol, r: Symbol) -> &mut Self {
+        self.push(Op::And { var, ty, l, r });
+        self
+    }
+
+    pub fn or(&mut self, var: Symbol, ty: EbbTy, l: Symbol, r: Symbol) -> &mut Self {
+        self.push(Op::Or { var, ty, l, r });
+        self
+    }
+
+    pub fn xor(&mut self, var: Symbol, ty: EbbTy, l: Symbol, r: Symbol) -> &mut Self {
+        self.push(Op::Xor { var, ty, l, r });
+        self
+    }
+
+    pub fn abs(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::Abs { var, ty, arg });
+        self
+    }
+
+    pub fn real_of_int(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::RealOfInt { var, ty, arg });
+        self
+    }
+
+    pub fn floor(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::Floor { var, ty, arg });
+        self
+    }
+
+    pub fn ceil(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::Ceil { var, ty, arg });
+        self
+    }
+
+    pub fn round(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::Round { var, ty, arg });
+        self
+    }
+
+    pub fn trunc(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::Trunc { var, ty, arg });
+        self
+    }
+
+    pub fn sqrt(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::Sqrt { var, ty, arg });
+        self
+    }
+
+    pub fn chr(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::Chr { var, ty, arg });
+        self
+    }
+
     pub fn eq(&mut self, var: Symbol, ty: EbbTy, l: Symbol, r: Symbol) -> &mut Self {
         self.push(Op::Eq { var, ty, l, r });
         self
@@ -126,15 +196,13 @@ impl EBBBuilder {
 
     pub fn extern_call(
         &mut self,
-        var: Symbol,
-        ty: EbbTy,
+        rets: Vec<(EbbTy, Symbol)>,
         module: String,
         fun: String,
         args: Vec<Symbol>,
     ) -> &mut Self {
         self.push(Op::ExternCall {
-            var,
-            ty,
+            rets,
             module,
             fun,
             args,
@@ -162,6 +230,16 @@ impl EBBBuilder {
         self
     }
 
+    pub fn store(&mut self, ty: EbbTy, index: u32, tuple: Symbol, value: Symbol) -> &mut Self {
+        self.push(Op::Store {
+            ty,
+            index,
+            tuple,
+            value,
+        });
+        self
+    }
+
     pub fn union(
         &mut self,
         var: Symbol,
@@ -39,28 +39,34 @@ impl HIR2MIRPass {
     }
 
     fn genlabel(&mut self, name: &str) -> Symbol {
-        let name = name.to_string();
         let label = self.label;
         self.label += 1;
-        Symbol(name, label)
+        Symbol(name.into(), label)
     }
 
     fn gensym(&mut self, name: &str) -> Symbol {
-        let name = name.to_string();
         let id = self.id.next();
-        Symbol(name, id)
+        Symbol(name.into(), id)
     }
 
     fn generate_symbol_table(&self) -> SymbolTable {
-        let table = self
+        let mut table = self
             .symbol_table
             .types
             .iter()
             .map(|(name, info)| (name.clone(), self.trans_type_info(info)))
-            .collect();
+            .collect::<HashMap<_, _>>();
+        // the lists of all the element types share the entry,
+        // which only tells that they are boxed
+        table.insert(
+            list_name(),
+            self.trans_ty_canonical(&hir::HTy::List(Box::new(hir::HTy::Unit))),
+        );
         SymbolTable { table }
     }
 
+    // a value of a datatype is boxed: the descriminant tags the argument of the constructor,
+    // and recursive datatypes refer to themselves through `EbbTy::Variable`
     fn trans_type_info(&self, info: &hir::TypeInfo) -> EbbTy {
         let union = info
             .constructors
@@ -76,8 +82,12 @@ impl HIR2MIRPass {
     fn trans_ty(&self, ty: &hir::HTy) -> EbbTy {
         use crate::hir::HTy::*;
         match ty {
+            Unit => EbbTy::Unit,
             Char => EbbTy::Char,
+            // the pointer to the bytes and the length
+            String => EbbTy::Tuple(vec![EbbTy::Int, EbbTy::Int]),
             Int => EbbTy::Int,
+            Word => EbbTy::Word,
             Real => EbbTy::Float,
             Tuple(tys) => match tys.len() {
                 0 => EbbTy::Unit,
@@ -89,11 +99,18 @@ impl HIR2MIRPass {
                 param: Box::new(self.trans_ty(&*arg)),
                 ret: Box::new(self.trans_ty(&*ret)),
             },
-            Datatype(name) => EbbTy::Variable(name.clone()),
+            Bool => EbbTy::Tuple(vec![EbbTy::Int, EbbTy::Union(bool_constructors())]),
+            Datatype(name, _) => EbbTy::Variable(name.clone()),
+            List(_) => EbbTy::Variable(list_name()),
+            // a cell is a 1-tuple whose element is overwritten by `:=`
+            Ref(elem) => EbbTy::Tuple(vec![self.trans_ty(elem)]),
         }
     }
 
     fn trans_ty_canonical(&self, ty: &hir::HTy) -> EbbTy {
+        if let hir::HTy::List(elem) = ty {
+            return EbbTy::Tuple(vec![EbbTy::Int, EbbTy::Union(self.list_constructors(elem))]);
+        }
         match self.trans_ty(ty) {
             EbbTy::Variable(name) => {
                 self.trans_type_info(self.symbol_table.types.get(&name).unwrap())
@@ -102,9 +119,17 @@ impl HIR2MIRPass {
         }
     }
 
+    // the arguments of `nil` and `::`, laid out in the same way as datatypes
+    fn list_constructors(&self, elem: &hir::HTy) -> Vec<EbbTy> {
+        vec![
+            EbbTy::Unit,
+            EbbTy::Tuple(vec![self.trans_ty(elem), EbbTy::Variable(list_name())]),
+        ]
+    }
+
     fn wrapper_name(&mut self, mut name: Symbol) -> Symbol {
         name.1 = self.id.next();
-        name.0.push_str("_closure_wrapper");
+        name.0 = format!("{}_closure_wrapper", name.0).into();
         name
     }
 
@@ -216,7 +241,11 @@ impl HIR2MIRPass {
                     // make pure function
                     eb_ = EBBBuilder::new(Symbol::new("entry"), vec![param]);
                 }
+                let export = self.symbol_table.exports.get(&name).cloned();
                 let mut fb = FunctionBuilder::new(name, self.trans_ty(&body_ty));
+                if let Some(export) = export {
+                    fb.export(export);
+                }
                 let ebb = self.trans_expr(&mut fb, eb_, body_ty, *body);
                 fb.add_ebb(ebb);
                 let function = fb.build();
@@ -231,6 +260,7 @@ impl HIR2MIRPass {
             BuiltinCall { ty, fun, args } => {
                 assert_eq!(ty, ty_);
                 use crate::prim::BIF::*;
+                let cell_ty = args.first().map(|arg| arg.ty());
                 let mut args = args
                     .into_iter()
                     .map(|arg| force_symbol(arg))
@@ -247,27 +277,116 @@ impl HIR2MIRPass {
                     Div => eb.div_int(name, self.trans_ty(&ty), pop!(), pop!()),
                     Divf => eb.div_float(name, self.trans_ty(&ty), pop!(), pop!()),
                     Mod => eb.mod_(name, self.trans_ty(&ty), pop!(), pop!()),
+                    Shl => eb.shl(name, self.trans_ty(&ty), pop!(), pop!()),
+                    Shr => eb.shr(name, self.trans_ty(&ty), pop!(), pop!()),
+                    Andb => eb.and(name, self.trans_ty(&ty), pop!(), pop!()),
+                    Orb => eb.or(name, self.trans_ty(&ty), pop!(), pop!()),
+                    Xorb => eb.xor(name, self.trans_ty(&ty), pop!(), pop!()),
+                    Notb => {
+                        // `x xor -1`
+                        let ty = self.trans_ty(&ty);
+                        let ones = self.gensym("ones");
+                        eb.lit(ones.clone(), ty.clone(), Literal::Int(-1));
+                        eb.xor(name, ty, pop!(), ones)
+                    }
                     Eq => eb.eq(name, self.trans_ty(&ty), pop!(), pop!()),
                     Neq => eb.neq(name, self.trans_ty(&ty), pop!(), pop!()),
                     Gt => eb.gt(name, self.trans_ty(&ty), pop!(), pop!()),
                     Ge => eb.ge(name, self.trans_ty(&ty), pop!(), pop!()),
                     Lt => eb.lt(name, self.trans_ty(&ty), pop!(), pop!()),
                     Le => eb.le(name, self.trans_ty(&ty), pop!(), pop!()),
+                    Neg => {
+                        // `0 - x`, where -0.0 keeps the sign of the negated zero for reals
+                        let ty = self.trans_ty(&ty);
+                        let zero = self.gensym("zero");
+                        let value = match ty {
+                            EbbTy::Float => Literal::Real(-0.0),
+                            EbbTy::Word => Literal::Word(0),
+                            _ => Literal::Int(0),
+                        };
+                        eb.lit(zero.clone(), ty.clone(), value);
+                        eb.sub(name, ty, zero, pop!())
+                    }
+                    Abs => eb.abs(name, self.trans_ty(&ty), pop!()),
+                    RealOfInt => eb.real_of_int(name, self.trans_ty(&ty), pop!()),
+                    Floor => eb.floor(name, self.trans_ty(&ty), pop!()),
+                    Ceil => eb.ceil(name, self.trans_ty(&ty), pop!()),
+                    Round => eb.round(name, self.trans_ty(&ty), pop!()),
+                    Trunc => eb.trunc(name, self.trans_ty(&ty), pop!()),
+                    Sqrt => eb.sqrt(name, self.trans_ty(&ty), pop!()),
+                    // chars are represented by their code points
+                    Ord => eb.alias(name, self.trans_ty(&ty), pop!()),
+                    Chr => eb.chr(name, self.trans_ty(&ty), pop!()),
+                    // wasm has no instruction for these. ask the host
+                    Modf => eb.extern_call(
+                        vec![(self.trans_ty(&ty), name)],
+                        "js-ffi".into(),
+                        "rem".into(),
+                        vec![pop!(), pop!()],
+                    ),
+                    Pow => eb.extern_call(
+                        vec![(self.trans_ty(&ty), name)],
+                        "js-ffi".into(),
+                        "pow".into(),
+                        vec![pop!(), pop!()],
+                    ),
+                    Ref => match self.trans_ty(&ty) {
+                        EbbTy::Tuple(tys) => eb.tuple(name, tys, vec![pop!()]),
+                        ty => unreachable!("ref makes {:?}", ty),
+                    },
+                    Deref => eb.proj(name, self.trans_ty(&ty), 0, pop!()),
+                    Assign => {
+                        let cell = pop!();
+                        let value = pop!();
+                        let value_ty = match cell_ty {
+                            Some(hir::HTy::Ref(elem)) => self.trans_ty(&elem),
+                            ty => unreachable!("assignment to {:?}", ty),
+                        };
+                        eb.store(value_ty, 0, cell, value);
+                        eb.tuple(name, vec![], vec![])
+                    }
                 };
                 eb
             }
             ExternCall {
-                ty,
+                retty,
                 module,
                 fun,
                 args,
             } => {
-                assert_eq!(ty, ty_);
-                let args = args.into_iter().map(|arg| force_symbol(arg)).collect();
-                eb.extern_call(name, self.trans_ty(&ty), module, fun, args);
+                let mut syms = Vec::new();
+                for arg in args {
+                    let ty = arg.ty();
+                    let arg = force_symbol(arg);
+                    if ty == hir::HTy::String {
+                        // a string is passed as the pointer to its bytes and its length
+                        let ptr = self.gensym("ptr");
+                        let len = self.gensym("len");
+                        eb.proj(ptr.clone(), EbbTy::Int, 0, arg.clone());
+                        eb.proj(len.clone(), EbbTy::Int, 1, arg);
+                        syms.push(ptr);
+                        syms.push(len);
+                    } else {
+                        syms.push(arg);
+                    }
+                }
+                let args = syms;
+                if let [ty] = retty.as_slice() {
+                    assert_eq!(*ty, ty_);
+                    eb.extern_call(vec![(self.trans_ty(ty), name)], module, fun, args);
+                } else {
+                    // bind each result and pack them into a tuple
+                    let rets: Vec<_> = retty
+                        .iter()
+                        .map(|ty| (self.trans_ty(ty), self.gensym("ret")))
+                        .collect();
+                    let (tys, vars) = rets.iter().cloned().unzip();
+                    eb.extern_call(rets, module, fun, args);
+                    eb.tuple(name, tys, vars);
+                }
                 eb
             }
-            App { ty, fun, arg } => {
+            App { ty, fun, arg, .. } => {
                 assert_eq!(ty, ty_);
                 let arg = force_symbol(*arg);
                 let fun = force_symbol(*fun);
@@ -292,9 +411,21 @@ impl HIR2MIRPass {
                     .into_iter()
                     .enumerate()
                     .map(|(n, (pat, expr))| {
+                        let (aliases, pat) = pat.strip_as();
+                        let binds = match &pat {
+                            hir::Pattern::Constructor { arg: Some(arg), .. } => match &**arg {
+                                hir::Pattern::Var { name, .. } => Some(name.clone()),
+                                hir::Pattern::Wildcard { .. } => None,
+                                _ => unreachable!("nested patterns must be compiled to switches"),
+                            },
+                            _ => None,
+                        };
                         (
-                            pat.match_key(),
-                            pat.binds(),
+                            // the constants are in the range of i32 as the parser checks,
+                            // so the keys are not truncated
+                            pat.match_key() as u32,
+                            aliases,
+                            binds,
                             self.genlabel(&format!("branch_arm_{}", n)),
                             expr,
                         )
@@ -302,7 +433,7 @@ impl HIR2MIRPass {
                     .collect::<Vec<_>>();
                 let labels = arms
                     .iter()
-                    .map(|&(key, _, ref label, _)| (key, label.clone(), true))
+                    .map(|&(key, _, _, ref label, _)| (key, label.clone(), true))
                     .collect::<Vec<_>>();
 
                 let descriminant = self.gensym("descriminant");
@@ -318,7 +449,8 @@ impl HIR2MIRPass {
                     hir::HTy::Tuple(tys) => {
                         MatchTy::Tuple(tys.into_iter().map(|ty| self.trans_ty(&ty)).collect())
                     }
-                    hir::HTy::Datatype(name) => MatchTy::Datatype(
+                    hir::HTy::Unit => MatchTy::Tuple(vec![]),
+                    hir::HTy::Datatype(name, _) => MatchTy::Datatype(
                         self.symbol_table.types[&name]
                             .constructors
                             .iter()
@@ -330,6 +462,8 @@ impl HIR2MIRPass {
                             })
                             .collect(),
                     ),
+                    hir::HTy::Bool => MatchTy::Datatype(bool_constructors()),
+                    hir::HTy::List(elem) => MatchTy::Datatype(self.list_constructors(&elem)),
                     hir::HTy::Int => MatchTy::Int,
                     hir::HTy::Char => MatchTy::Char,
                     ty => unreachable!("{:?}", ty),
@@ -360,8 +494,11 @@ impl HIR2MIRPass {
 
                 fb.add_ebb(ebb);
 
-                for (key, binds, label, arm) in arms {
+                for (key, aliases, binds, label, arm) in arms {
                     let mut eb = EBBBuilder::new(label, Vec::new());
+                    for (ty, name) in aliases {
+                        eb.alias(name, self.trans_ty(&ty), var.clone());
+                    }
                     match &exprty {
                         MatchTy::Datatype(tys) => {
                             let vararg = match binds {
@@ -381,25 +518,43 @@ impl HIR2MIRPass {
                 }
                 match (default, default_label) {
                     (Some((pat, arm)), Some((label, _))) => {
-                        let eb = match pat {
+                        let (aliases, pat) = pat.strip_as();
+                        let mut eb = match pat {
                             hir::Pattern::Var { name, ty } => {
                                 let eb = EBBBuilder::new(label, vec![(self.trans_ty(&ty), name)]);
                                 eb
                             }
+                            hir::Pattern::Wildcard { ty } => EBBBuilder::new(
+                                label,
+                                vec![(self.trans_ty(&ty), self.gensym("wildcard"))],
+                            ),
                             hir::Pattern::Tuple { tys, tuple } => {
                                 let ty = hir::HTy::Tuple(tys.clone());
                                 let var = self.gensym("tuple");
                                 let mut eb =
                                     EBBBuilder::new(label, vec![(self.trans_ty(&ty), var.clone())]);
-                                for (i, (t, ty)) in tuple.into_iter().zip(tys).enumerate() {
+                                for (i, (pat, ty)) in tuple.into_iter().zip(tys).enumerate() {
+                                    let t = match pat {
+                                        hir::Pattern::Var { name, .. } => name,
+                                        hir::Pattern::Wildcard { .. } => self.gensym("wildcard"),
+                                        _ => unreachable!(
+                                            "nested patterns must be compiled to switches"
+                                        ),
+                                    };
                                     eb.proj(t, self.trans_ty(&ty), i as u32, var.clone());
                                 }
                                 eb
                             }
                             hir::Pattern::Constructor { .. }
                             | hir::Pattern::Constant { .. }
-                            | hir::Pattern::Char { .. } => unreachable!(),
+                            | hir::Pattern::Char { .. }
+                            | hir::Pattern::Str { .. }
+                            | hir::Pattern::As { .. }
+                            | hir::Pattern::Or(_) => unreachable!(),
                         };
+                        for (ty, name) in aliases {
+                            eb.alias(name, self.trans_ty(&ty), var.clone());
+                        }
 
                         let (eb, var) = self.trans_expr_block(fb, eb, ty.clone(), arm);
                         let ebb = eb.jump(joinlabel.clone(), true, vec![var]);
@@ -553,6 +708,15 @@ fn force_symbol(e: hir::Expr) -> Symbol {
     }
 }
 
+// the arguments of `false` and `true`, laid out in the same way as datatypes
+fn bool_constructors() -> Vec<EbbTy> {
+    vec![EbbTy::Unit, EbbTy::Unit]
+}
+
+fn list_name() -> Symbol {
+    Symbol::new("list")
+}
+
 impl<E> Pass<(hir::SymbolTable, hir::HIR), E> for HIR2MIR {
     type Target = (SymbolTable, MIR);
 
@@ -21,6 +21,8 @@ pub struct Function {
     // pub params_ty: Vec<EbbTy>,
     pub body: Vec<EBB>,
     pub body_ty: EbbTy,
+    /// the name exported from the wasm module, if any
+    pub export: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -78,6 +80,76 @@ pub enum Op {
         l: Symbol,
         r: Symbol,
     },
+    Abs {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    RealOfInt {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    Floor {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    Ceil {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    Round {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    Trunc {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    Sqrt {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    Chr {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    Shl {
+        var: Symbol,
+        ty: EbbTy,
+        l: Symbol,
+        r: Symbol,
+    },
+    Shr {
+        var: Symbol,
+        ty: EbbTy,
+        l: Symbol,
+        r: Symbol,
+    },
+    And {
+        var: Symbol,
+        ty: EbbTy,
+        l: Symbol,
+        r: Symbol,
+    },
+    Or {
+        var: Symbol,
+        ty: EbbTy,
+        l: Symbol,
+        r: Symbol,
+    },
+    Xor {
+        var: Symbol,
+        ty: EbbTy,
+        l: Symbol,
+        r: Symbol,
+    },
     Eq {
         var: Symbol,
         ty: EbbTy,
@@ -121,9 +193,9 @@ pub enum Op {
         fun: Symbol,
         env: Vec<(EbbTy, Symbol)>,
     },
+    /// binds each result of the function to its own variable
     ExternCall {
-        var: Symbol,
-        ty: EbbTy,
+        rets: Vec<(EbbTy, Symbol)>,
         module: String,
         fun: String,
         args: Vec<Symbol>,
@@ -146,6 +218,14 @@ pub enum Op {
         index: u32,
         tuple: Symbol,
     },
+    /// overwrites an element of a tuple, used for the mutable cells
+    Store {
+        ty: EbbTy,
+        /// 0-origin
+        index: u32,
+        tuple: Symbol,
+        value: Symbol,
+    },
     Union {
         var: Symbol,
         tys: Vec<EbbTy>,
@@ -187,6 +267,7 @@ pub enum EbbTy {
     Unit,
     Char,
     Int,
+    Word,
     Float,
     Bool,
     Tuple(Vec<EbbTy>),
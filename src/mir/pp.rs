@@ -20,6 +20,9 @@ impl PP for MIR {
 impl PP for Function {
     fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
         let indent = indent + 4;
+        if let Some(export) = &self.export {
+            write!(w, "export \"{}\" ", export)?;
+        }
         write!(w, "fun ")?;
         self.name.pp(w, indent)?;
         write!(w, ": (")?;
@@ -52,6 +55,7 @@ impl PP for EbbTy {
             Bool => write!(w, "bool")?,
             Char => write!(w, "char")?,
             Int => write!(w, "int")?,
+            Word => write!(w, "word")?,
             Float => write!(w, "float")?,
             Tuple(tys) => {
                 write!(w, "(")?;
@@ -191,6 +195,85 @@ impl PP for Op {
             Mod { var, ty, l, r } => {
                 pp_binop(w, indent, &space, "mod", var, ty, l, r)?;
             }
+            Abs { var, ty, arg } => {
+                write!(w, "{}", space)?;
+                var.pp(w, indent)?;
+                write!(w, ": ")?;
+                ty.pp(w, indent)?;
+                write!(w, " := abs ")?;
+                arg.pp(w, indent)?;
+            }
+            RealOfInt { var, ty, arg } => {
+                write!(w, "{}", space)?;
+                var.pp(w, indent)?;
+                write!(w, ": ")?;
+                ty.pp(w, indent)?;
+                write!(w, " := real ")?;
+                arg.pp(w, indent)?;
+            }
+            Floor { var, ty, arg } => {
+                write!(w, "{}", space)?;
+                var.pp(w, indent)?;
+                write!(w, ": ")?;
+                ty.pp(w, indent)?;
+                write!(w, " := floor ")?;
+                arg.pp(w, indent)?;
+            }
+            Ceil { var, ty, arg } => {
+                write!(w, "{}", space)?;
+                var.pp(w, indent)?;
+                write!(w, ": ")?;
+                ty.pp(w, indent)?;
+                write!(w, " := ceil ")?;
+                arg.pp(w, indent)?;
+            }
+            Round { var, ty, arg } => {
+                write!(w, "{}", space)?;
+                var.pp(w, indent)?;
+                write!(w, ": ")?;
+                ty.pp(w, indent)?;
+                write!(w, " := round ")?;
+                arg.pp(w, indent)?;
+            }
+            Trunc { var, ty, arg } => {
+                write!(w, "{}", space)?;
+                var.pp(w, indent)?;
+                write!(w, ": ")?;
+                ty.pp(w, indent)?;
+                write!(w, " := trunc ")?;
+                arg.pp(w, indent)?;
+            }
+            Sqrt { var, ty, arg } => {
+                write!(w, "{}", space)?;
+                var.pp(w, indent)?;
+                write!(w, ": ")?;
+                ty.pp(w, indent)?;
+                write!(w, " := sqrt ")?;
+                arg.pp(w, indent)?;
+            }
+            Chr { var, ty, arg } => {
+                write!(w, "{}", space)?;
+                var.pp(w, indent)?;
+                write!(w, ": ")?;
+                ty.pp(w, indent)?;
+                write!(w, " := chr ")?;
+                arg.pp(w, indent)?;
+            }
+            Shl { var, ty, l, r } => {
+                pp_binop(w, indent, &space, "<<", var, ty, l, r)?;
+            }
+            Shr { var, ty, l, r } => {
+                pp_binop(w, indent, &space, ">>", var, ty, l, r)?;
+            }
+            And { var, ty, l, r } => {
+                pp_binop(w, indent, &space, "&", var, ty, l, r)?;
+            }
+            Or { var, ty, l, r } => {
+                pp_binop(w, indent, &space, "|", var, ty, l, r)?;
+            }
+            Xor { var, ty, l, r } => {
+                pp_binop(w, indent, &space, "^", var, ty, l, r)?;
+            }
             Eq { var, ty, l, r } => {
                 pp_binop(w, indent, &space, "=", var, ty, l, r)?;
             }
@@ -240,16 +323,21 @@ impl PP for Op {
                 write!(w, ")")?;
             }
             ExternCall {
-                var,
-                ty,
+                rets,
                 module,
                 fun,
                 args,
             } => {
                 write!(w, "{}", space)?;
-                var.pp(w, indent)?;
-                write!(w, ": ")?;
-                ty.pp(w, indent)?;
+                inter_iter! {
+                    rets.iter(),
+                    write!(w, ", ")?,
+                    |(ty, var)| => {
+                        var.pp(w, indent)?;
+                        write!(w, ": ")?;
+                        ty.pp(w, indent)?
+                    }
+                }
                 write!(w, " := \"{}\".\"{}\"", module, fun)?;
                 write!(w, "(")?;
                 inter_iter! {
@@ -312,6 +400,19 @@ impl PP for Op {
                 write!(w, " := #{} ", index)?;
                 tuple.pp(w, indent)?;
             }
+            Store {
+                ty,
+                index,
+                tuple,
+                value,
+            } => {
+                write!(w, "{}#{} ", space, index)?;
+                tuple.pp(w, indent)?;
+                write!(w, " <- ")?;
+                value.pp(w, indent)?;
+                write!(w, ": ")?;
+                ty.pp(w, indent)?;
+            }
             Union {
                 var,
                 tys,
@@ -71,6 +71,31 @@ impl UnAlias {
                     ref mut r,
                     ..
                 }
+                | &mut Shl {
+                    ref mut l,
+                    ref mut r,
+                    ..
+                }
+                | &mut Shr {
+                    ref mut l,
+                    ref mut r,
+                    ..
+                }
+                | &mut And {
+                    ref mut l,
+                    ref mut r,
+                    ..
+                }
+                | &mut Or {
+                    ref mut l,
+                    ref mut r,
+                    ..
+                }
+                | &mut Xor {
+                    ref mut l,
+                    ref mut r,
+                    ..
+                }
                 | &mut Eq {
                     ref mut l,
                     ref mut r,
@@ -109,9 +134,27 @@ impl UnAlias {
                         self.resolv_alias(v);
                     }
                 }
+                &mut Abs { ref mut arg, .. }
+                | &mut RealOfInt { ref mut arg, .. }
+                | &mut Floor { ref mut arg, .. }
+                | &mut Ceil { ref mut arg, .. }
+                | &mut Round { ref mut arg, .. }
+                | &mut Trunc { ref mut arg, .. }
+                | &mut Sqrt { ref mut arg, .. }
+                | &mut Chr { ref mut arg, .. } => {
+                    self.resolv_alias(arg);
+                }
                 &mut Proj { ref mut tuple, .. } => {
                     self.resolv_alias(tuple);
                 }
+                &mut Store {
+                    ref mut tuple,
+                    ref mut value,
+                    ..
+                } => {
+                    self.resolv_alias(tuple);
+                    self.resolv_alias(value);
+                }
                 &mut Union {
                     ref mut variant, ..
                 } => {
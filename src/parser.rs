@@ -1,10 +1,14 @@
 use crate::ast::*;
 use crate::prim::*;
 use nom::branch::alt;
-use nom::bytes::complete::tag;
-use nom::character::complete::{alphanumeric1, digit1, multispace0, multispace1};
-use nom::combinator::{all_consuming, complete, map, map_res, opt, recognize, value, verify};
-use nom::multi::{many1, separated_list, separated_nonempty_list};
+use nom::bytes::complete::{tag, take_while1};
+use nom::character::complete::{
+    alphanumeric1, digit0, digit1, hex_digit1, multispace0, multispace1, one_of,
+};
+use nom::combinator::{
+    all_consuming, complete, cut, map, map_opt, map_res, not, opt, peek, recognize, value, verify,
+};
+use nom::multi::{many0, many1, separated_list, separated_nonempty_list};
 use nom::number::complete::recognize_float;
 use nom::sequence::{preceded, terminated, tuple};
 use nom::IResult;
@@ -13,22 +17,35 @@ use std::collections::{BTreeMap, HashMap};
 
 static KEYWORDS: &[&str] = &[
     "val", "fun", "fn", "let", "in", "end", "if", "then", "else", "case", "of", "_", "datatype",
-    "op", "=>", "infix", "infixr",
+    "op", "=>", "infix", "infixr", "rec", "and", "as", "andalso", "orelse",
 ];
 
-static RESERVED: &[&str] = &["|", "=", "#"];
+static RESERVED: &[&str] = &["|", "=", "#", ":"];
+
+// `::` of the builtin list, which is `infixr 5` unlike the user defined infixes
+static CONS: &str = "::";
+const CONS_PRIORITY: u8 = 5;
 
 struct Parser {
     infixes: RefCell<Vec<BTreeMap<u8, Vec<Symbol>>>>,
+    input_len: usize,
 }
 
 impl Parser {
-    fn new() -> Self {
+    fn new(input: &str) -> Self {
+        let mut builtins = BTreeMap::new();
+        builtins.insert(CONS_PRIORITY, vec![Symbol::new(CONS)]);
         Self {
-            infixes: RefCell::new(vec![BTreeMap::new()]),
+            infixes: RefCell::new(vec![builtins]),
+            input_len: input.len(),
         }
     }
 
+    // `start` and `end` are the rest of the input before and after parsing a node
+    fn span(&self, start: &str, end: &str) -> Span {
+        Span::new(self.input_len - start.len(), self.input_len - end.len())
+    }
+
     fn with_scope<R>(&self, f: impl FnOnce() -> R) -> R {
         self.infixes.borrow_mut().push(BTreeMap::default());
         let r = f();
@@ -67,11 +84,17 @@ impl Parser {
     }
 }
 
+// a name quoted by `"`s without escapes, of extern functions and exports
+fn name_parser(i: &str) -> IResult<&str, &str> {
+    let allowed = recognize(many1(nom::character::complete::none_of("\"")));
+    preceded(tag("\""), terminated(allowed, tag("\"")))(i)
+}
+
 impl Parser {
     fn top(&self) -> impl Fn(&str) -> IResult<&str, UntypedAst> + '_ {
         move |i| {
             let (i, _) = multispace0(i)?;
-            let (i, tops) = separated_list(multispace1, self.decl())(i)?;
+            let (i, tops) = separated_list(multispace1, alt((self.decl_export(), self.decl())))(i)?;
             let (i, _) = multispace0(i)?;
             Ok((i, AST(tops)))
         }
@@ -83,6 +106,7 @@ impl Parser {
                 self.decl_val(),
                 self.decl_fun(),
                 self.decl_infix(),
+                self.decl_import(),
             ))(i)
         }
     }
@@ -91,6 +115,8 @@ impl Parser {
         move |i| {
             let (i, _) = tag("datatype")(i)?;
             let (i, _) = multispace1(i)?;
+            let (i, params) = opt(terminated(self.tyvarseq(), multispace0))(i)?;
+            let params = params.unwrap_or_else(Vec::new);
             let (i, name) = self.symbol()(i)?;
             let (i, _) = multispace0(i)?;
             let (i, _) = tag("=")(i)?;
@@ -99,7 +125,14 @@ impl Parser {
                 tuple((multispace0, tag("|"), multispace0)),
                 self.constructor_def(),
             )(i)?;
-            Ok((i, Declaration::Datatype { name, constructors }))
+            Ok((
+                i,
+                Declaration::Datatype {
+                    name,
+                    params,
+                    constructors,
+                },
+            ))
         }
     }
 
@@ -107,19 +140,32 @@ impl Parser {
         move |i| {
             let (i, _) = tag("val")(i)?;
             let (i, _) = multispace1(i)?;
+            let (i, rec) = opt(terminated(tag("rec"), multispace1))(i)?;
+            let (i, mut binds) = separated_nonempty_list(
+                tuple((multispace1, tag("and"), multispace1)),
+                self.decl_valbind(),
+            )(i)?;
+            let rec = rec.is_some();
+            if binds.len() == 1 {
+                let (pattern, expr) = binds.remove(0);
+                Ok((i, Declaration::Val { rec, pattern, expr }))
+            } else if rec {
+                Ok((i, Declaration::ValRecGroup { binds }))
+            } else {
+                // simultaneous non recursive bindings are not supported
+                Err(nom::Err::Error((i, nom::error::ErrorKind::Tag)))
+            }
+        }
+    }
+
+    fn decl_valbind(&self) -> impl Fn(&str) -> IResult<&str, (Pattern<()>, Expr<()>)> + '_ {
+        move |i| {
             let (i, pattern) = self.pattern()(i)?;
             let (i, _) = multispace0(i)?;
             let (i, _) = tag("=")(i)?;
             let (i, _) = multispace0(i)?;
             let (i, expr) = self.expr()(i)?;
-            Ok((
-                i,
-                Declaration::Val {
-                    rec: false,
-                    pattern,
-                    expr,
-                },
-            ))
+            Ok((i, (pattern, expr)))
         }
     }
 
@@ -127,6 +173,23 @@ impl Parser {
         move |i| {
             let (i, _) = tag("fun")(i)?;
             let (i, _) = multispace1(i)?;
+            let (i, mut funs) = separated_nonempty_list(
+                tuple((multispace1, tag("and"), multispace1)),
+                self.decl_funclauses(),
+            )(i)?;
+            if funs.len() == 1 {
+                let (name, clauses) = funs.remove(0);
+                Ok((i, Declaration::D(DerivedDeclaration::Fun { name, clauses })))
+            } else {
+                Ok((i, Declaration::D(DerivedDeclaration::FunGroup { funs })))
+            }
+        }
+    }
+
+    fn decl_funclauses(
+        &self,
+    ) -> impl Fn(&str) -> IResult<&str, (Symbol, Vec<(Vec<Pattern<()>>, Expr<()>)>)> + '_ {
+        move |i| {
             let (i, cs) = separated_nonempty_list(
                 tuple((multispace0, tag("|"), multispace0)),
                 map(
@@ -149,7 +212,7 @@ impl Parser {
                 }
                 clauses.push((params, expr))
             }
-            Ok((i, Declaration::D(DerivedDeclaration::Fun { name, clauses })))
+            Ok((i, (name, clauses)))
         }
     }
 
@@ -178,17 +241,66 @@ impl Parser {
         }
     }
 
+    /// `export "name" fun f ...` or `export "name" val f = ...`, only at the top level
+    fn decl_export(&self) -> impl Fn(&str) -> IResult<&str, Declaration<()>> + '_ {
+        move |i| {
+            let (i, _) = tag("export")(i)?;
+            let (i, _) = multispace1(i)?;
+            let (i, name) = map(name_parser, String::from)(i)?;
+            let (i, _) = multispace1(i)?;
+            let (i, decl) = verify(
+                alt((self.decl_val(), self.decl_fun())),
+                |decl: &Declaration<()>| match decl {
+                    Declaration::Val { pattern, .. } => match pattern.inner {
+                        PatternKind::Variable { .. } => true,
+                        _ => false,
+                    },
+                    Declaration::D(DerivedDeclaration::Fun { .. }) => true,
+                    _ => false,
+                },
+            )(i)?;
+            Ok((
+                i,
+                Declaration::D(DerivedDeclaration::Export {
+                    name,
+                    decl: Box::new(decl),
+                }),
+            ))
+        }
+    }
+
+    /// `_import ("module"."fun": (arg, ty) -> retty)`
+    fn decl_import(&self) -> impl Fn(&str) -> IResult<&str, Declaration<()>> + '_ {
+        move |i| {
+            let (i, _) = tag("_import")(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, _) = tag("(")(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, (module, fun)) = self.extern_name()(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, (argty, retty)) = self.extern_type()(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, _) = tag(")")(i)?;
+            Ok((
+                i,
+                Declaration::Import {
+                    module,
+                    fun,
+                    argty,
+                    retty,
+                },
+            ))
+        }
+    }
+
     fn decl_infix(&self) -> impl Fn(&str) -> IResult<&str, Declaration<()>> + '_ {
         move |i| {
             let (i, _) = tag("infix")(i)?;
             let (i, _) = multispace1(i)?;
-            let (i, priority) = opt(digit1)(i)?;
+            // the priorities out of the range of u8 are errors
+            let (i, priority) = opt(map_res(digit1, |s: &str| s.parse::<u8>()))(i)?;
             let (i, _) = multispace1(i)?;
             let (i, names) = separated_nonempty_list(multispace1, self.symbol_eq())(i)?;
-            let priority = priority.map(|s| {
-                s.parse()
-                    .expect("internal error: falied to parse digits as integer")
-            });
             self.new_infix_op(priority, names.clone());
             Ok((
                 i,
@@ -204,7 +316,7 @@ impl Parser {
                 self.expr_fun(),
                 self.expr_if(),
                 self.expr_case(),
-                self.expr_infix_and_app(),
+                self.expr_orelse(),
             ))(i)
         }
     }
@@ -213,11 +325,16 @@ impl Parser {
         move |i| {
             alt((
                 self.expr1_tuple(),
+                self.expr1_seq(),
                 self.expr1_unit(),
+                self.expr1_list(),
+                self.expr1_typed(),
                 self.expr1_paren(),
                 self.expr1_float(),
+                self.expr1_word(),
                 self.expr1_int(),
                 self.expr1_char(),
+                self.expr1_string(),
                 self.expr1_bool(),
                 self.expr1_sym(),
                 self.expr1_builtincall(),
@@ -229,19 +346,21 @@ impl Parser {
     fn expr_bind(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
         move |i| {
             self.with_scope(|| {
+                let start = i;
                 let (i, _) = tag("let")(i)?;
-                let (i, _) = multispace1(i)?;
-                let (i, binds) = separated_list(multispace1, self.decl())(i)?;
+                // the declarations may be empty, as in `let in e end`
+                let (i, binds) = many0(preceded(multispace1, self.decl()))(i)?;
                 let (i, _) = multispace1(i)?;
                 let (i, _) = tag("in")(i)?;
                 let (i, _) = multispace1(i)?;
-                let (i, ret) = self.expr()(i)?;
+                let (i, ret) = self.exprs_seq()(i)?;
                 let (i, _) = multispace1(i)?;
                 let (i, _) = tag("end")(i)?;
                 Ok((
                     i,
                     Expr {
                         ty: (),
+                        span: self.span(start, i),
                         inner: ExprKind::Binds {
                             binds: binds,
                             ret: ret.boxed(),
@@ -254,21 +373,40 @@ impl Parser {
 
     fn expr_fun(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
         move |i| {
+            let start = i;
             let (i, _) = tag("fn")(i)?;
             let (i, _) = multispace1(i)?;
-            let (i, param) = self.symbol()(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag("=>")(i)?;
-            let (i, _) = multispace1(i)?;
-            let (i, body) = self.expr()(i)?;
+            let (i, mut clauses) = separated_nonempty_list(
+                tuple((multispace0, tag("|"), multispace0)),
+                map(
+                    tuple((
+                        self.pattern(),
+                        multispace0,
+                        tag("=>"),
+                        multispace0,
+                        self.expr(),
+                    )),
+                    |(pat, _, _, _, expr)| (pat, expr),
+                ),
+            )(i)?;
+            // `fn x => e` is the core form, and the others are desugared later
+            let param = match (clauses.len(), &clauses[0].0.inner) {
+                (1, PatternKind::Variable { name }) => Some(name.clone()),
+                _ => None,
+            };
+            let inner = match param {
+                Some(param) => ExprKind::Fn {
+                    param,
+                    body: clauses.remove(0).1.boxed(),
+                },
+                None => ExprKind::D(DerivedExpr::Fn { clauses }),
+            };
             Ok((
                 i,
                 Expr {
                     ty: (),
-                    inner: ExprKind::Fn {
-                        param: param,
-                        body: body.boxed(),
-                    },
+                    span: self.span(start, i),
+                    inner,
                 },
             ))
         }
@@ -276,6 +414,7 @@ impl Parser {
 
     fn expr_if(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
         move |i| {
+            let start = i;
             let (i, _) = tag("if")(i)?;
             let (i, _) = multispace1(i)?;
             let (i, cond) = self.expr()(i)?;
@@ -291,11 +430,12 @@ impl Parser {
                 i,
                 Expr {
                     ty: (),
-                    inner: ExprKind::D(DerivedExprKind::If {
+                    span: self.span(start, i),
+                    inner: ExprKind::If {
                         cond: cond.boxed(),
                         then: then.boxed(),
                         else_: else_.boxed(),
-                    }),
+                    },
                 },
             ))
         }
@@ -303,6 +443,7 @@ impl Parser {
 
     fn expr_case(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
         move |i| {
+            let start = i;
             let (i, _) = tag("case")(i)?;
             let (i, _) = multispace1(i)?;
             let (i, cond) = self.expr()(i)?;
@@ -326,6 +467,7 @@ impl Parser {
                 i,
                 Expr {
                     ty: (),
+                    span: self.span(start, i),
                     inner: ExprKind::Case {
                         cond: cond.boxed(),
                         clauses: clauses,
@@ -335,6 +477,42 @@ impl Parser {
         }
     }
 
+    // `andalso` binds tighter than `orelse`, and both are looser than any infix
+    fn expr_orelse(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+        self.expr_logical("orelse", self.expr_andalso(), |l, r| ExprKind::OrElse {
+            l,
+            r,
+        })
+    }
+
+    fn expr_andalso(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+        self.expr_logical("andalso", self.expr_infix_and_app(), |l, r| {
+            ExprKind::AndAlso { l, r }
+        })
+    }
+
+    // `operand keyword operand keyword ...`, associating to the left
+    fn expr_logical<'a>(
+        &'a self,
+        keyword: &'static str,
+        operand: impl Fn(&str) -> IResult<&str, Expr<()>> + 'a,
+        make: fn(Box<Expr<()>>, Box<Expr<()>>) -> ExprKind<()>,
+    ) -> impl Fn(&str) -> IResult<&str, Expr<()>> + 'a {
+        move |i| {
+            let (i, first) = operand(i)?;
+            let (i, rest) = many0(preceded(
+                tuple((multispace1, tag(keyword), multispace1)),
+                &operand,
+            ))(i)?;
+            let e = rest.into_iter().fold(first, |l, r| Expr {
+                ty: (),
+                span: l.span.merge(r.span),
+                inner: make(l.boxed(), r.boxed()),
+            });
+            Ok((i, e))
+        }
+    }
+
     // treat all of the infix operators and applications, i.e. sequeces of expressions
     fn expr_infix_and_app(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
         move |i| {
@@ -343,7 +521,7 @@ impl Parser {
             #[derive(Debug)]
             enum Mixed {
                 E(Expr<()>),
-                Fix(u8, Symbol),
+                Fix(u8, Symbol, Span),
             }
             use Mixed::*;
             // find infixes
@@ -353,7 +531,7 @@ impl Parser {
                     ExprKind::Symbol { name } => {
                         for (f, table) in self.get_table() {
                             if table.contains(&name) {
-                                return Fix(f, name);
+                                return Fix(f, name, e.span);
                             }
                         }
                         e.inner = ExprKind::Symbol { name };
@@ -370,6 +548,7 @@ impl Parser {
                 (E(e1), E(e2)) => (
                     E(Expr {
                         ty: (),
+                        span: e1.span.merge(e2.span),
                         inner: ExprKind::App {
                             fun: e1.boxed(),
                             arg: e2.boxed(),
@@ -381,33 +560,52 @@ impl Parser {
             });
 
             // reduce infixes
-            fn reduce_infixl_n(n: u8, mixed: Vec<Mixed>) -> Vec<Mixed> {
+            fn reduce_infix_n(n: u8, right: bool, mut mixed: Vec<Mixed>) -> Vec<Mixed> {
                 use Mixed::*;
-                map_window3(mixed, |m1, m2, m3| match (m1, m2, m3) {
-                    (E(l), Fix(fixty, op), E(r)) if fixty == n => (
-                        E(Expr {
-                            ty: (),
-                            inner: ExprKind::App {
-                                fun: Expr {
-                                    ty: (),
-                                    inner: ExprKind::Symbol { name: op },
-                                }
-                                .boxed(),
-                                arg: Expr {
-                                    ty: (),
-                                    inner: ExprKind::Tuple { tuple: vec![l, r] },
-                                }
-                                .boxed(),
-                            },
-                        }),
-                        None,
-                    ),
+                // the right associative infixes are reduced from the right end
+                if right {
+                    mixed.reverse();
+                }
+                let mut mixed = map_window3(mixed, |m1, m2, m3| match (m1, m2, m3) {
+                    (E(l), Fix(fixty, op, op_span), E(r)) if fixty == n => {
+                        let (l, r) = if right { (r, l) } else { (l, r) };
+                        let span = l.span.merge(r.span);
+                        (
+                            E(Expr {
+                                ty: (),
+                                span,
+                                inner: ExprKind::App {
+                                    fun: Expr {
+                                        ty: (),
+                                        span: op_span,
+                                        inner: ExprKind::Symbol { name: op },
+                                    }
+                                    .boxed(),
+                                    arg: Expr {
+                                        ty: (),
+                                        span,
+                                        inner: ExprKind::Tuple { tuple: vec![l, r] },
+                                    }
+                                    .boxed(),
+                                },
+                            }),
+                            None,
+                        )
+                    }
                     (m1, m2, m3) => (m1, Some((m2, m3))),
-                })
+                });
+                if right {
+                    mixed.reverse();
+                }
+                mixed
             }
-            let mut rest = (1u8..=9)
-                .rev()
-                .fold(rest, |rest, n| reduce_infixl_n(n, rest));
+            let table = self.get_table();
+            let mut rest = (1u8..=9).rev().fold(rest, |rest, n| {
+                let right = table
+                    .get(&n)
+                    .map_or(false, |names| names.contains(&Symbol::new(CONS)));
+                reduce_infix_n(n, right, rest)
+            });
             assert_eq!(rest.len(), 1);
             let e = match rest.remove(0) {
                 E(e) => e,
@@ -418,42 +616,115 @@ impl Parser {
     }
     fn expr1_sym(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
         move |i| {
+            let start = i;
             // = is allowed to be used in expression exceptionally
-            map(alt((self.symbol(), map(tag("="), Symbol::new))), |name| {
+            let (i, name) = alt((self.symbol(), map(tag("="), Symbol::new)))(i)?;
+            Ok((
+                i,
                 Expr {
                     ty: (),
+                    span: self.span(start, i),
                     inner: ExprKind::Symbol { name },
-                }
-            })(i)
+                },
+            ))
         }
     }
 
     fn expr1_int(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
         move |i| {
-            map(digit1, |s: &str| Expr {
-                ty: (),
-                inner: ExprKind::Literal {
-                    value: Literal::Int(s.parse().unwrap()),
+            let start = i;
+            let (i, value) = self.int_literal()(i)?;
+            Ok((
+                i,
+                Expr {
+                    ty: (),
+                    span: self.span(start, i),
+                    inner: ExprKind::Literal {
+                        value: Literal::Int(value),
+                    },
                 },
-            })(i)
+            ))
         }
     }
 
-    fn expr1_float(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+    // `0w123` or `0wx7b`.
+    // the words are 32 bit at runtime, so the literals out of the range of u32 are errors
+    fn expr1_word(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+        move |i| {
+            let start = i;
+            let (i, value) = preceded(
+                tag("0w"),
+                alt((
+                    preceded(
+                        tag("x"),
+                        cut(map_res(hex_digit1, |s: &str| {
+                            u32::from_str_radix(s, 16).map(u64::from)
+                        })),
+                    ),
+                    // not `0 w123` when out of the range of u32
+                    preceded(
+                        peek(digit1),
+                        cut(map_res(digit1, |s: &str| s.parse::<u32>().map(u64::from))),
+                    ),
+                )),
+            )(i)?;
+            Ok((
+                i,
+                Expr {
+                    ty: (),
+                    span: self.span(start, i),
+                    inner: ExprKind::Literal {
+                        value: Literal::Word(value),
+                    },
+                },
+            ))
+        }
+    }
+
+    // `123`, `0x7b` or `0b1111011`, optionally negated by `~` as `~123`.
+    // the integers are 32 bit at runtime, so the literals out of the range of i32 are errors
+    fn int_literal(&self) -> impl Fn(&str) -> IResult<&str, i64> + '_ {
         move |i| {
-            let not_int = verify(recognize_float, |s: &&str| s.contains('.'));
+            let (i, negative) = opt(tag("~"))(i)?;
+            let sign = if negative.is_some() { "-" } else { "" };
+            let bin_digit1 = take_while1(|c: char| c == '0' || c == '1');
+            let parse = |s: &str, radix| {
+                i32::from_str_radix(&format!("{}{}", sign, s), radix).map(i64::from)
+            };
+            alt((
+                preceded(tag("0x"), cut(map_res(hex_digit1, |s| parse(s, 16)))),
+                preceded(tag("0b"), cut(map_res(bin_digit1, |s| parse(s, 2)))),
+                map_res(digit1, |s| parse(s, 10)),
+            ))(i)
+        }
+    }
 
-            map(not_int, |s: &str| Expr {
-                ty: (),
-                inner: ExprKind::Literal {
-                    value: Literal::Real(s.parse().unwrap()),
+    fn expr1_float(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+        move |i| {
+            let start = i;
+            // `-` is subtraction, not a sign
+            let not_int = verify(recognize_float, |s: &&str| {
+                s.starts_with(|c: char| c.is_ascii_digit()) && s.contains('.')
+            });
+            let (i, negative) = opt(tag("~"))(i)?;
+            let (i, s) = not_int(i)?;
+            let value: f64 = s.parse().unwrap();
+            Ok((
+                i,
+                Expr {
+                    ty: (),
+                    span: self.span(start, i),
+                    inner: ExprKind::Literal {
+                        value: Literal::Real(if negative.is_some() { -value } else { value }),
+                    },
                 },
-            })(i)
+            ))
         }
     }
 
     fn expr1_char(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
         move |i| {
+            let start = i;
             let (i, _) = tag("#")(i)?;
             let (i, s) = self.string_literal()(i)?;
             assert_eq!(s.iter().count(), 1);
@@ -462,6 +733,7 @@ impl Parser {
                 i,
                 Expr {
                     ty: (),
+                    span: self.span(start, i),
                     inner: ExprKind::Literal {
                         value: Literal::Char(c),
                     },
@@ -470,16 +742,44 @@ impl Parser {
         }
     }
 
+    fn expr1_string(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+        move |i| {
+            let start = i;
+            let (i, s) = self.string_literal()(i)?;
+            let s = s
+                .into_iter()
+                .map(|c| std::char::from_u32(c).unwrap())
+                .collect();
+            Ok((
+                i,
+                Expr {
+                    ty: (),
+                    span: self.span(start, i),
+                    inner: ExprKind::Literal {
+                        value: Literal::String(s),
+                    },
+                },
+            ))
+        }
+    }
+
     fn string_literal(&self) -> impl Fn(&str) -> IResult<&str, Vec<u32>> + '_ {
         move |i| {
             let (i, _) = tag("\"")(i)?;
             let mut s = vec![];
             let mut chars = i.chars();
             while let Some(c) = chars.next() {
-                if c == '"' {
-                    break;
+                match c {
+                    '"' => break,
+                    '\\' => {
+                        let rest = chars.as_str();
+                        let (rest, c) = escape(rest)
+                            .ok_or(nom::Err::Failure((rest, nom::error::ErrorKind::Escaped)))?;
+                        s.push(c);
+                        chars = rest.chars();
+                    }
+                    c => s.push(c as u32),
                 }
-                s.push(c as u32)
             }
             let i = chars.as_str();
             Ok((i, s))
@@ -488,28 +788,19 @@ impl Parser {
 
     fn expr1_bool(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
         move |i| {
-            alt((
-                value(
-                    Expr {
-                        ty: (),
-                        inner: ExprKind::Constructor {
-                            name: Symbol::new("true"),
-                            arg: None,
-                        },
-                    },
-                    tag("true"),
-                ),
-                value(
-                    Expr {
-                        ty: (),
-                        inner: ExprKind::Constructor {
-                            name: Symbol::new("false"),
-                            arg: None,
-                        },
+            let start = i;
+            let (i, name) = alt((tag("true"), tag("false")))(i)?;
+            Ok((
+                i,
+                Expr {
+                    ty: (),
+                    span: self.span(start, i),
+                    inner: ExprKind::Constructor {
+                        name: Symbol::new(name),
+                        arg: None,
                     },
-                    tag("false"),
-                ),
-            ))(i)
+                },
+            ))
         }
     }
 
@@ -524,8 +815,35 @@ impl Parser {
         }
     }
 
+    fn expr1_typed(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+        move |i| {
+            let start = i;
+            let (i, _) = tag("(")(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, e) = self.expr()(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, _) = tag(":")(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, ty) = self.typename()(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, _) = tag(")")(i)?;
+            Ok((
+                i,
+                Expr {
+                    ty: (),
+                    span: self.span(start, i),
+                    inner: ExprKind::Typed {
+                        expr: e.boxed(),
+                        ty,
+                    },
+                },
+            ))
+        }
+    }
+
     fn expr1_tuple(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
         move |i| {
+            let start = i;
             let (i, _) = tag("(")(i)?;
             let (i, _) = multispace0(i)?;
             let sep = tuple((multispace0, tag(","), multispace0));
@@ -540,26 +858,117 @@ impl Parser {
                 i,
                 Expr {
                     ty: (),
+                    span: self.span(start, i),
                     inner: ExprKind::Tuple { tuple: es },
                 },
             ))
         }
     }
 
+    // `(e1; e2; ...; en)`
+    fn expr1_seq(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+        move |i| {
+            let start = i;
+            let (i, _) = tag("(")(i)?;
+            let (i, _) = multispace0(i)?;
+            let sep = tuple((multispace0, tag(";"), multispace0));
+            let (i, es) = many1(map(tuple((self.expr(), sep)), |(e, _)| e))(i)?;
+            let (i, e) = self.expr()(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, _) = tag(")")(i)?;
+
+            let mut es = es;
+            es.push(e);
+            Ok((
+                i,
+                Expr {
+                    ty: (),
+                    span: self.span(start, i),
+                    inner: ExprKind::Seq { exprs: es },
+                },
+            ))
+        }
+    }
+
+    // `e1; e2; ...; en` in the body of `let`, which is a single expression if not separated
+    fn exprs_seq(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+        move |i| {
+            let sep = tuple((multispace0, tag(";"), multispace0));
+            let (i, mut es) = separated_nonempty_list(sep, self.expr())(i)?;
+            if es.len() == 1 {
+                return Ok((i, es.remove(0)));
+            }
+            let span = es[0].span.merge(es[es.len() - 1].span);
+            Ok((
+                i,
+                Expr {
+                    ty: (),
+                    span,
+                    inner: ExprKind::Seq { exprs: es },
+                },
+            ))
+        }
+    }
+
     fn expr1_unit(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
         move |i| {
-            value(
+            let start = i;
+            let (i, _) = tuple((tag("("), multispace0, tag(")")))(i)?;
+            Ok((
+                i,
                 Expr {
                     ty: (),
+                    span: self.span(start, i),
                     inner: ExprKind::Tuple { tuple: vec![] },
                 },
-                tuple((tag("("), multispace0, tag(")"))),
-            )(i)
+            ))
+        }
+    }
+
+    // `[e1, e2]` => `e1 :: e2 :: nil`
+    fn expr1_list(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+        move |i| {
+            let start = i;
+            let (i, _) = tag("[")(i)?;
+            let (i, _) = multispace0(i)?;
+            let sep = tuple((multispace0, tag(","), multispace0));
+            let (i, es) = separated_list(sep, self.expr())(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, _) = tag("]")(i)?;
+
+            let span = self.span(start, i);
+            let nil = Expr {
+                ty: (),
+                span,
+                inner: ExprKind::Constructor {
+                    name: Symbol::new("nil"),
+                    arg: None,
+                },
+            };
+            let list = es.into_iter().rev().fold(nil, |tail, head| Expr {
+                ty: (),
+                span,
+                inner: ExprKind::Constructor {
+                    name: Symbol::new(CONS),
+                    arg: Some(
+                        Expr {
+                            ty: (),
+                            span,
+                            inner: ExprKind::Tuple {
+                                tuple: vec![head, tail],
+                            },
+                        }
+                        .boxed(),
+                    ),
+                },
+            });
+            Ok((i, list))
         }
     }
 
     fn expr1_builtincall(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
         move |i| {
+            let start = i;
             let (i, _) = tag("_builtincall")(i)?;
             let (i, _) = multispace0(i)?;
             let (i, _) = tag("\"")(i)?;
@@ -570,12 +979,33 @@ impl Parser {
                 "div" => Ok(BIF::Div),
                 "divf" => Ok(BIF::Divf),
                 "mod" => Ok(BIF::Mod),
+                "modf" => Ok(BIF::Modf),
+                "shl" => Ok(BIF::Shl),
+                "shr" => Ok(BIF::Shr),
+                "andb" => Ok(BIF::Andb),
+                "orb" => Ok(BIF::Orb),
+                "xorb" => Ok(BIF::Xorb),
+                "notb" => Ok(BIF::Notb),
                 "eq" => Ok(BIF::Eq),
                 "neq" => Ok(BIF::Neq),
                 "gt" => Ok(BIF::Gt),
                 "ge" => Ok(BIF::Ge),
                 "lt" => Ok(BIF::Lt),
                 "le" => Ok(BIF::Le),
+                "neg" => Ok(BIF::Neg),
+                "abs" => Ok(BIF::Abs),
+                "real" => Ok(BIF::RealOfInt),
+                "floor" => Ok(BIF::Floor),
+                "ceil" => Ok(BIF::Ceil),
+                "round" => Ok(BIF::Round),
+                "trunc" => Ok(BIF::Trunc),
+                "sqrt" => Ok(BIF::Sqrt),
+                "pow" => Ok(BIF::Pow),
+                "ord" => Ok(BIF::Ord),
+                "chr" => Ok(BIF::Chr),
+                "ref" => Ok(BIF::Ref),
+                "deref" => Ok(BIF::Deref),
+                "assign" => Ok(BIF::Assign),
                 _ => Err(nom::Err::Error(nom::error::ErrorKind::Tag)),
             })(i)?;
             let (i, _) = tag("\"")(i)?;
@@ -590,41 +1020,26 @@ impl Parser {
                 i,
                 Expr {
                     ty: (),
+                    span: self.span(start, i),
                     inner: ExprKind::BuiltinCall { fun, args },
                 },
             ))
         }
     }
 
-    /// `_externcall ("module"."fun": (arg, ty) -> retty) (arg, s)`
+    /// `_externcall ("module"."fun": (arg, ty) -> retty) (arg, s)`,
+    /// or `_externcall ("module"."fun") (arg, s)` for a function declared by `_import`
     fn expr1_externcall(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
         move |i| {
-            fn name_parser(i: &str) -> IResult<&str, &str> {
-                let allowed = recognize(many1(nom::character::complete::none_of("\"")));
-                preceded(tag("\""), terminated(allowed, tag("\"")))(i)
-            }
+            let start = i;
             let (i, _) = tag("_externcall")(i)?;
             let (i, _) = multispace0(i)?;
             let (i, _) = tag("(")(i)?;
             let (i, _) = multispace0(i)?;
-            let (i, module) = map(name_parser, String::from)(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag(".")(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, fun) = map(name_parser, String::from)(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag(":")(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag("(")(i)?;
-            let (i, argty) = separated_nonempty_list(
-                tuple((multispace0, tag(","), multispace0)),
-                self.typename(),
-            )(i)?;
-            let (i, _) = tag(")")(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag("->")(i)?;
+            let (i, (module, fun)) = self.extern_name()(i)?;
             let (i, _) = multispace0(i)?;
-            let (i, retty) = self.typename()(i)?;
+            let (i, sig) = opt(self.extern_type())(i)?;
+            let (argty, retty) = sig.unwrap_or_else(|| (Vec::new(), Vec::new()));
             let (i, _) = multispace0(i)?;
             let (i, _) = tag(")")(i)?;
             let (i, _) = multispace0(i)?;
@@ -638,6 +1053,7 @@ impl Parser {
                 i,
                 Expr {
                     ty: (),
+                    span: self.span(start, i),
                     inner: ExprKind::ExternCall {
                         module,
                         fun,
@@ -650,6 +1066,53 @@ impl Parser {
         }
     }
 
+    /// `"module"."fun"`
+    fn extern_name(&self) -> impl Fn(&str) -> IResult<&str, (String, String)> + '_ {
+        move |i| {
+            let (i, module) = map(name_parser, String::from)(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, _) = tag(".")(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, fun) = map(name_parser, String::from)(i)?;
+            Ok((i, (module, fun)))
+        }
+    }
+
+    /// `: (arg, ty) -> retty`, or `-> (ret, ty)` for a function with multiple results
+    fn extern_type(&self) -> impl Fn(&str) -> IResult<&str, (Vec<Type>, Vec<Type>)> + '_ {
+        move |i| {
+            let (i, _) = tag(":")(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, _) = tag("(")(i)?;
+            let (i, argty) = separated_nonempty_list(
+                tuple((multispace0, tag(","), multispace0)),
+                self.typename(),
+            )(i)?;
+            let (i, _) = tag(")")(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, _) = tag("->")(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, retty) = alt((
+                |i: &str| {
+                    let (i, _) = tag("(")(i)?;
+                    let (i, _) = multispace0(i)?;
+                    let (i, first) = self.typename()(i)?;
+                    let (i, _) = tuple((multispace0, tag(","), multispace0))(i)?;
+                    let (i, mut rest) = separated_nonempty_list(
+                        tuple((multispace0, tag(","), multispace0)),
+                        self.typename(),
+                    )(i)?;
+                    let (i, _) = multispace0(i)?;
+                    let (i, _) = tag(")")(i)?;
+                    rest.insert(0, first);
+                    Ok((i, rest))
+                },
+                map(self.typename(), |retty| vec![retty]),
+            ))(i)?;
+            Ok((i, (argty, retty)))
+        }
+    }
+
     fn typename(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
         move |i| self.typename0()(i)
     }
@@ -663,7 +1126,28 @@ impl Parser {
     }
 
     fn typename2(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
-        move |i| alt((self.typename2_paren(), self.typename2_datatype()))(i)
+        move |i| {
+            let (i, ty) = self.typename3()(i)?;
+            let (i, names) = many0(preceded(multispace1, self.symbol_alphanumeric()))(i)?;
+            let ty = names
+                .into_iter()
+                .fold(ty, |ty, name| match name.0.as_str() {
+                    "ref" => Type::Ref(Box::new(ty)),
+                    _ => Type::Datatype(name, vec![ty]),
+                });
+            Ok((i, ty))
+        }
+    }
+
+    fn typename3(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
+        move |i| {
+            alt((
+                self.typename3_args(),
+                self.typename3_paren(),
+                self.typename3_tyvar(),
+                self.typename3_datatype(),
+            ))(i)
+        }
     }
 
     fn typename0_fun(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
@@ -690,7 +1174,27 @@ impl Parser {
         }
     }
 
-    fn typename2_paren(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
+    // `(ty1, ty2, ...) name`
+    fn typename3_args(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
+        move |i| {
+            let sep = tuple((multispace0, tag(","), multispace0));
+
+            let (i, _) = tag("(")(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, ty) = self.typename()(i)?;
+            let (i, tys) = many1(map(tuple((sep, self.typename())), |(_, ty)| ty))(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, _) = tag(")")(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, name) = self.symbol_alphanumeric()(i)?;
+
+            let mut args = vec![ty];
+            args.extend(tys);
+            Ok((i, Type::Datatype(name, args)))
+        }
+    }
+
+    fn typename3_paren(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
         move |i| {
             let (i, _) = tag("(")(i)?;
             let (i, _) = multispace0(i)?;
@@ -701,17 +1205,60 @@ impl Parser {
         }
     }
 
-    fn typename2_datatype(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
+    fn typename3_tyvar(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
+        move |i| map(self.tyvar(), Type::Variable)(i)
+    }
+
+    fn typename3_datatype(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
         move |i| {
             map(self.symbol(), |name| match name.0.as_str() {
-                "unit" => Type::Tuple(vec![]),
+                "unit" => Type::Unit,
                 "real" => Type::Real,
                 "int" => Type::Int,
-                _ => Type::Datatype(name),
+                "word" => Type::Word,
+                "string" => Type::String,
+                _ => Type::Datatype(name, vec![]),
             })(i)
         }
     }
 
+    // the inverse of the naming in the pretty printer: 'a => 0, ..., 'z => 25, 'a1 => 26, ...
+    fn tyvar(&self) -> impl Fn(&str) -> IResult<&str, u64> + '_ {
+        move |i| {
+            let (i, _) = tag("'")(i)?;
+            let (i, c) = one_of("abcdefghijklmnopqrstuvwxyz")(i)?;
+            // the numbers out of the range of u64 are errors
+            map_opt(digit0, |n: &str| {
+                let n = if n.is_empty() {
+                    0
+                } else {
+                    n.parse::<u64>().ok()?
+                };
+                n.checked_mul(26)?.checked_add(c as u64 - 'a' as u64)
+            })(i)
+        }
+    }
+
+    // `'a` or `('a, 'b, ...)`
+    fn tyvarseq(&self) -> impl Fn(&str) -> IResult<&str, Vec<u64>> + '_ {
+        move |i| {
+            let sep = tuple((multispace0, tag(","), multispace0));
+            alt((
+                map(self.tyvar(), |var| vec![var]),
+                map(
+                    tuple((
+                        tag("("),
+                        multispace0,
+                        separated_nonempty_list(sep, self.tyvar()),
+                        multispace0,
+                        tag(")"),
+                    )),
+                    |(_, _, vars, _, _)| vars,
+                ),
+            ))(i)
+        }
+    }
+
     fn symbol_eq(&self) -> impl Fn(&str) -> IResult<&str, Symbol> + '_ {
         move |i| alt((self.symbol_alphanumeric(), self.symbol_symbolic_eq()))(i)
     }
@@ -752,9 +1299,11 @@ impl Parser {
 
     fn symbol_symbolic(&self) -> impl Fn(&str) -> IResult<&str, Symbol> + '_ {
         move |i| {
-            let symbolic1 = recognize(many1(nom::character::complete::one_of(
-                "!%&$#+-/:<=>?@\\~'^|*",
-            )));
+            // `~` followed by a digit starts a negative literal, as in `x-~1`
+            let symbolic1 = recognize(many1(alt((
+                recognize(nom::character::complete::one_of("!%&$#+-/:<=>?@\\'^|*")),
+                terminated(tag("~"), not(digit1)),
+            ))));
 
             let (i, sym) = verify(symbolic1, |s: &str| {
                 !KEYWORDS.contains(&s) && !RESERVED.contains(&s)
@@ -764,7 +1313,36 @@ impl Parser {
     }
 
     fn pattern(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
-        move |i| alt((self.pattern_constructor(), self.pattern_atmic()))(i)
+        move |i| {
+            let start = i;
+            let sep = tuple((multispace0, tag("|"), multispace0));
+            let (i, pat) = self.pattern_alternative()(i)?;
+            let (i, rest) = many0(preceded(sep, self.pattern_alternative()))(i)?;
+            if rest.is_empty() {
+                return Ok((i, pat));
+            }
+            let mut alternatives = vec![pat];
+            alternatives.extend(rest);
+            Ok((
+                i,
+                Pattern {
+                    ty: (),
+                    span: self.span(start, i),
+                    inner: PatternKind::Or { alternatives },
+                },
+            ))
+        }
+    }
+
+    fn pattern_alternative(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
+        move |i| {
+            alt((
+                self.pattern_as(),
+                self.pattern_cons(),
+                self.pattern_constructor(),
+                self.pattern_atmic(),
+            ))(i)
+        }
     }
 
     fn pattern_atmic(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
@@ -772,11 +1350,13 @@ impl Parser {
             alt((
                 self.pattern_bool(),
                 self.pattern_char(),
+                self.pattern_str(),
                 self.pattern_int(),
                 self.pattern_tuple(),
                 self.pattern_var(),
                 self.pattern_wildcard(),
                 self.pattern_unit(),
+                self.pattern_list(),
                 self.pattern_paren(),
             ))(i)
         }
@@ -784,38 +1364,40 @@ impl Parser {
 
     fn pattern_bool(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
         move |i| {
-            alt((
-                map(tag("true"), |_| Pattern {
-                    ty: (),
-                    inner: PatternKind::Constructor {
-                        name: Symbol::new("true"),
-                        arg: None,
-                    },
-                }),
-                map(tag("false"), |_| Pattern {
+            let start = i;
+            let (i, name) = alt((tag("true"), tag("false")))(i)?;
+            Ok((
+                i,
+                Pattern {
                     ty: (),
+                    span: self.span(start, i),
                     inner: PatternKind::Constructor {
-                        name: Symbol::new("false"),
+                        name: Symbol::new(name),
                         arg: None,
                     },
-                }),
-            ))(i)
+                },
+            ))
         }
     }
 
     fn pattern_int(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
         move |i| {
-            map(digit1, |s: &str| Pattern {
-                ty: (),
-                inner: PatternKind::Constant {
-                    value: s.parse().unwrap(),
+            let start = i;
+            let (i, value) = self.int_literal()(i)?;
+            Ok((
+                i,
+                Pattern {
+                    ty: (),
+                    span: self.span(start, i),
+                    inner: PatternKind::Constant { value },
                 },
-            })(i)
+            ))
         }
     }
 
     fn pattern_char(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
         move |i| {
+            let start = i;
             let (i, _) = tag("#")(i)?;
             let (i, s) = self.string_literal()(i)?;
             assert_eq!(s.iter().count(), 1);
@@ -824,14 +1406,35 @@ impl Parser {
                 i,
                 Pattern {
                     ty: (),
+                    span: self.span(start, i),
                     inner: PatternKind::Char { value: c },
                 },
             ))
         }
     }
 
+    fn pattern_str(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
+        move |i| {
+            let start = i;
+            let (i, s) = self.string_literal()(i)?;
+            let value = s
+                .into_iter()
+                .map(|c| std::char::from_u32(c).unwrap())
+                .collect();
+            Ok((
+                i,
+                Pattern {
+                    ty: (),
+                    span: self.span(start, i),
+                    inner: PatternKind::Str { value },
+                },
+            ))
+        }
+    }
+
     fn pattern_tuple(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
         move |i| {
+            let start = i;
             let (i, _) = tag("(")(i)?;
             let (i, _) = multispace0(i)?;
             let sep = tuple((multispace0, tag(","), multispace0));
@@ -846,6 +1449,7 @@ impl Parser {
                 i,
                 Pattern {
                     ty: (),
+                    span: self.span(start, i),
                     inner: PatternKind::Tuple { tuple: es },
                 },
             ))
@@ -854,13 +1458,55 @@ impl Parser {
 
     fn pattern_unit(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
         move |i| {
-            value(
+            let start = i;
+            let (i, _) = tuple((tag("("), multispace0, tag(")")))(i)?;
+            Ok((
+                i,
                 Pattern {
                     ty: (),
+                    span: self.span(start, i),
                     inner: PatternKind::Tuple { tuple: vec![] },
                 },
-                tuple((tag("("), multispace0, tag(")"))),
-            )(i)
+            ))
+        }
+    }
+
+    // `p1 :: p2`, which is right associative
+    fn pattern_cons(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
+        move |i| {
+            let start = i;
+            let (i, head) = alt((self.pattern_constructor(), self.pattern_atmic()))(i)?;
+            let (i, _) = tuple((multispace0, tag(CONS), multispace0))(i)?;
+            let (i, tail) = self.pattern_alternative()(i)?;
+            Ok((i, cons_pattern(self.span(start, i), head, tail)))
+        }
+    }
+
+    // `[p1, p2]` => `p1 :: p2 :: nil`
+    fn pattern_list(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
+        move |i| {
+            let start = i;
+            let (i, _) = tag("[")(i)?;
+            let (i, _) = multispace0(i)?;
+            let sep = tuple((multispace0, tag(","), multispace0));
+            let (i, ps) = separated_list(sep, self.pattern())(i)?;
+            let (i, _) = multispace0(i)?;
+            let (i, _) = tag("]")(i)?;
+
+            let span = self.span(start, i);
+            let nil = Pattern {
+                ty: (),
+                span,
+                inner: PatternKind::Constructor {
+                    name: Symbol::new("nil"),
+                    arg: None,
+                },
+            };
+            let list = ps
+                .into_iter()
+                .rev()
+                .fold(nil, |tail, head| cons_pattern(span, head, tail));
+            Ok((i, list))
         }
     }
 
@@ -869,6 +1515,7 @@ impl Parser {
     //  will be converted in later phases
     fn pattern_constructor(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
         move |i| {
+            let start = i;
             let (i, name) = self.symbol()(i)?;
             let (i, _) = multispace0(i)?;
             let (i, arg) = self.pattern_atmic()(i)?;
@@ -876,6 +1523,7 @@ impl Parser {
                 i,
                 Pattern {
                     ty: (),
+                    span: self.span(start, i),
                     inner: PatternKind::Constructor {
                         name,
                         arg: Some(Box::new(arg)),
@@ -885,24 +1533,54 @@ impl Parser {
         }
     }
 
+    fn pattern_as(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
+        move |i| {
+            let start = i;
+            let (i, name) = self.symbol()(i)?;
+            let (i, _) = tuple((multispace1, tag("as"), multispace1))(i)?;
+            let (i, pattern) = self.pattern()(i)?;
+            Ok((
+                i,
+                Pattern {
+                    ty: (),
+                    span: self.span(start, i),
+                    inner: PatternKind::As {
+                        name,
+                        pattern: Box::new(pattern),
+                    },
+                },
+            ))
+        }
+    }
+
     fn pattern_var(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
         move |i| {
-            map(self.symbol(), |name| Pattern {
-                ty: (),
-                inner: PatternKind::Variable { name: name },
-            })(i)
+            let start = i;
+            // `::` is always the infix constructor
+            let (i, name) = verify(self.symbol(), |name: &Symbol| name.0 != CONS)(i)?;
+            Ok((
+                i,
+                Pattern {
+                    ty: (),
+                    span: self.span(start, i),
+                    inner: PatternKind::Variable { name: name },
+                },
+            ))
         }
     }
 
     fn pattern_wildcard(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
         move |i| {
-            value(
+            let start = i;
+            let (i, _) = tag("_")(i)?;
+            Ok((
+                i,
                 Pattern {
                     ty: (),
+                    span: self.span(start, i),
                     inner: PatternKind::Wildcard {},
                 },
-                tag("_"),
-            )(i)
+            ))
         }
     }
 
@@ -919,6 +1597,56 @@ impl Parser {
     }
 }
 
+fn cons_pattern(span: Span, head: Pattern<()>, tail: Pattern<()>) -> Pattern<()> {
+    Pattern {
+        ty: (),
+        span,
+        inner: PatternKind::Constructor {
+            name: Symbol::new(CONS),
+            arg: Some(Box::new(Pattern {
+                ty: (),
+                span,
+                inner: PatternKind::Tuple {
+                    tuple: vec![head, tail],
+                },
+            })),
+        },
+    }
+}
+
+// decodes the escape sequence after `\` in a string or char literal
+fn escape(i: &str) -> Option<(&str, u32)> {
+    let mut chars = i.chars();
+    let c = match chars.next()? {
+        'a' => 7,
+        'b' => 8,
+        't' => 9,
+        'n' => 10,
+        'v' => 11,
+        'f' => 12,
+        'r' => 13,
+        '"' => '"' as u32,
+        '\\' => '\\' as u32,
+        'u' => {
+            return escape_digits(chars.as_str(), 4, 16)
+                .filter(|&(_, c)| std::char::from_u32(c).is_some())
+        }
+        c if c.is_ascii_digit() => return escape_digits(i, 3, 10).filter(|&(_, c)| c <= 0xff),
+        _ => return None,
+    };
+    Some((chars.as_str(), c))
+}
+
+// the code point written in exactly `n` digits
+fn escape_digits(i: &str, n: usize, radix: u32) -> Option<(&str, u32)> {
+    let digits = i.get(..n)?;
+    if !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    let c = u32::from_str_radix(digits, radix).ok()?;
+    Some((&i[n..], c))
+}
+
 fn map_window2<I>(
     iter: I,
     mut f: impl FnMut(I::Item, I::Item) -> (I::Item, Option<I::Item>),
@@ -998,13 +1726,14 @@ where
 #[test]
 fn test_expr_infix_and_app() {
     let input = "true";
-    let ret = Parser::new().expr_infix_and_app()(input).unwrap();
+    let ret = Parser::new(input).expr_infix_and_app()(input).unwrap();
     assert_eq!(
         ret,
         (
             "",
             Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::Constructor {
                     arg: None,
                     name: Symbol::new("true")
@@ -1017,16 +1746,18 @@ fn test_expr_infix_and_app() {
 #[test]
 fn test_expr_infix_and_app2() {
     let input = "f arg";
-    let ret = Parser::new().expr_infix_and_app()(input).unwrap();
+    let ret = Parser::new(input).expr_infix_and_app()(input).unwrap();
     assert_eq!(
         ret,
         (
             "",
             Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::App {
                     fun: Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Symbol {
                             name: Symbol::new("f"),
                         }
@@ -1034,6 +1765,7 @@ fn test_expr_infix_and_app2() {
                     .boxed(),
                     arg: Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Symbol {
                             name: Symbol::new("arg"),
                         }
@@ -1048,7 +1780,7 @@ fn test_expr_infix_and_app2() {
 pub fn parse(
     input: &str,
 ) -> ::std::result::Result<UntypedAst, nom::Err<(&str, nom::error::ErrorKind)>> {
-    let parser = Parser::new();
+    let parser = Parser::new(input);
     let (_, iresult) = all_consuming(parser.top())(input)?;
     Ok(iresult)
 }
@@ -1,8 +1,11 @@
 use crate::config::Config;
-use crate::util::PP;
+use crate::util::{PpConfig, PP};
 use log::info;
+use std::cell::RefCell;
 use std::fmt::Debug;
+use std::io::{self, IsTerminal};
 use std::marker::PhantomData;
+use std::time::{Duration, Instant};
 
 pub trait Pass<T, E> {
     type Target;
@@ -20,6 +23,14 @@ where
     }
 }
 
+// the layout of the IRs printed to the stdout, colored if it is a terminal
+fn stdout_pp(config: &Config) -> PpConfig {
+    PpConfig {
+        color: config.pp.color.resolve(io::stdout().is_terminal()),
+        ..config.pp
+    }
+}
+
 pub struct DebugPass<T>(pub T);
 
 impl<T, In, Out, Err> Pass<In, Err> for DebugPass<T>
@@ -47,7 +58,7 @@ where
 
     fn trans(&mut self, i: In, config: &Config) -> Result<Self::Target, Err> {
         let o = self.0.trans(i, config)?;
-        o.pp(&mut ::std::io::stdout(), 0).unwrap();
+        o.pp_with(&mut io::stdout(), &stdout_pp(config)).unwrap();
         Ok(o)
     }
 }
@@ -65,13 +76,93 @@ where
         let o = self.0.trans(i, config)?;
         info!("pass: {}", self.1);
         if config.pretty_print_ir.contains(self.1) {
-            o.pp(&mut ::std::io::stdout(), 0).unwrap();
+            o.pp_with(&mut io::stdout(), &stdout_pp(config)).unwrap();
         }
 
         Ok(o)
     }
 }
 
+thread_local! {
+    // the durations recorded by the `TimedPass`es in `Timings::collect`
+    static RECORDS: RefCell<Option<Vec<(&'static str, Duration)>>> = RefCell::new(None);
+}
+
+/// the wall-clock durations of the passes in the order they ran
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Timings(Vec<(&'static str, Duration)>);
+
+impl Timings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// runs `f` and returns the durations of the `TimedPass`es run in it
+    pub fn collect<R>(f: impl FnOnce() -> R) -> (R, Timings) {
+        let outer = RECORDS.with(|records| records.replace(Some(Vec::new())));
+        let ret = f();
+        let records = RECORDS.with(|records| records.replace(outer));
+        (ret, Timings(records.unwrap_or_default()))
+    }
+
+    fn record(name: &'static str, duration: Duration) {
+        RECORDS.with(|records| {
+            if let Some(records) = records.borrow_mut().as_mut() {
+                records.push((name, duration))
+            }
+        })
+    }
+
+    pub fn entries(&self) -> &[(&'static str, Duration)] {
+        &self.0
+    }
+
+    /// print a table of the durations in milliseconds and their total
+    pub fn print<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        let entries = &self.0;
+        let width = entries
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0)
+            .max("total".len());
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        writeln!(w, "{:<width$}  {:>10}", "pass", "time (ms)", width = width)?;
+        for (name, duration) in entries.iter() {
+            writeln!(
+                w,
+                "{:<width$}  {:>10.3}",
+                name,
+                ms(*duration),
+                width = width
+            )?;
+        }
+        let total = entries.iter().map(|(_, d)| *d).sum::<Duration>();
+        writeln!(w, "{:<width$}  {:>10.3}", "total", ms(total), width = width)
+    }
+}
+
+/// records how long the inner pass takes if `Config::time_passes` is set,
+/// for `Timings::collect` around it
+pub struct TimedPass<T>(pub T, pub &'static str);
+
+impl<T, In, Err> Pass<In, Err> for TimedPass<T>
+where
+    T: Pass<In, Err>,
+{
+    type Target = T::Target;
+
+    fn trans(&mut self, i: In, config: &Config) -> Result<Self::Target, Err> {
+        if !config.time_passes {
+            return self.0.trans(i, config);
+        }
+        let start = Instant::now();
+        let o = self.0.trans(i, config);
+        Timings::record(self.1, start.elapsed());
+        o
+    }
+}
+
 pub struct Chain<F, FO, S, SO> {
     pub fst: F,
     pub snd: S,
@@ -139,9 +230,12 @@ macro_rules! compile_pass {
         compile_pass!($($labels: $passes),*)
     };
     ($label: ident : $pass: expr, $($labels: ident : $passes: expr),*) => {
-        Chain::new(PrintablePass($pass, stringify!($label)), compile_pass!($($labels: $passes),*))
+        Chain::new(
+            PrintablePass($crate::pass::TimedPass($pass, stringify!($label)), stringify!($label)),
+            compile_pass!($($labels: $passes),*),
+        )
     };
     ($label: ident : $pass: expr) => {
-        PrintablePass($pass, stringify!($label))
+        PrintablePass($crate::pass::TimedPass($pass, stringify!($label)), stringify!($label))
     };
 }
@@ -1,11 +1,87 @@
 use crate::util::PP;
+use std::collections::HashMap;
+use std::fmt;
 use std::io;
+use std::sync::{Mutex, OnceLock};
+
+/// an interned string. names are compared and hashed by their ids
+/// and the strings are kept in a global table for display
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Name(u32);
+
+struct Interner {
+    ids: HashMap<&'static str, u32>,
+    names: Vec<&'static str>,
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(|| {
+        Mutex::new(Interner {
+            ids: HashMap::new(),
+            names: Vec::new(),
+        })
+    })
+}
+
+impl Name {
+    pub fn new(s: &str) -> Self {
+        let mut interner = interner().lock().unwrap();
+        if let Some(&id) = interner.ids.get(s) {
+            return Name(id);
+        }
+        // interned strings live as long as the program
+        let s: &'static str = Box::leak(s.to_string().into_boxed_str());
+        let id = interner.names.len() as u32;
+        interner.names.push(s);
+        interner.ids.insert(s, id);
+        Name(id)
+    }
+
+    pub fn id(&self) -> u32 {
+        self.0
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        interner().lock().unwrap().names[self.0 as usize]
+    }
+}
+
+impl From<&str> for Name {
+    fn from(s: &str) -> Self {
+        Name::new(s)
+    }
+}
+
+impl From<String> for Name {
+    fn from(s: String) -> Self {
+        Name::new(&s)
+    }
+}
+
+impl<'a> PartialEq<&'a str> for Name {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl fmt::Debug for Name {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Symbol(pub String, pub u64);
+pub struct Symbol(pub Name, pub u64);
 
 impl Symbol {
-    pub fn new<S: Into<String>>(s: S) -> Self {
+    pub fn new<S: Into<Name>>(s: S) -> Self {
         Symbol(s.into(), 0)
     }
 }
@@ -17,11 +93,71 @@ impl PP for Symbol {
     }
 }
 
+/// byte offsets of a syntax node in the source, `start..end`
+#[derive(Debug, Clone, Copy, Default, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    /// the smallest span that covers both of `self` and `other`
+    pub fn merge(self, other: Span) -> Self {
+        if self.is_dummy() {
+            return other;
+        }
+        if other.is_dummy() {
+            return self;
+        }
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    /// spans of nodes that are generated by compiler and not in the source
+    pub fn is_dummy(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+// spans don't take part in comparison of syntax trees
+// so that trees parsed from differently formatted sources are equal.
+impl PartialEq for Span {
+    fn eq(&self, _: &Span) -> bool {
+        true
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
     Int(i64),
+    Word(u64),
     Real(f64),
     Char(u32),
+    String(String),
+}
+
+/// `c` as written in a literal, with the canonical escape if it is not printable
+pub fn escape_char(c: u32) -> String {
+    match c {
+        7 => r"\a".into(),
+        8 => r"\b".into(),
+        9 => r"\t".into(),
+        10 => r"\n".into(),
+        11 => r"\v".into(),
+        12 => r"\f".into(),
+        13 => r"\r".into(),
+        0x22 => r#"\""#.into(),
+        0x5c => r"\\".into(),
+        0x20..=0x7e => (c as u8 as char).to_string(),
+        0..=0xff => format!(r"\{:03}", c),
+        0x100..=0xffff => format!(r"\u{:04x}", c),
+        _ => std::char::from_u32(c)
+            .expect("internal error: invalid char")
+            .to_string(),
+    }
 }
 
 impl PP for Literal {
@@ -31,11 +167,18 @@ impl PP for Literal {
             Int(v) => {
                 write!(w, "{}", v)?;
             }
+            Word(v) => {
+                write!(w, "0w{}", v)?;
+            }
             Real(v) => {
                 write!(w, "{}", v)?;
             }
             Char(c) => {
-                write!(w, r##"#"{}""##, c)?;
+                write!(w, r##"#"{}""##, escape_char(*c))?;
+            }
+            String(s) => {
+                let s = s.chars().map(|c| escape_char(c as u32)).collect::<String>();
+                write!(w, r#""{}""#, s)?;
             }
         }
         Ok(())
@@ -50,12 +193,34 @@ pub enum BIF {
     Div,
     Divf,
     Mod,
+    Modf,
+    Shl,
+    // arithmetic on ints and logical on words
+    Shr,
+    Andb,
+    Orb,
+    Xorb,
+    Notb,
     Eq,
     Neq,
     Gt,
     Ge,
     Lt,
     Le,
+    Neg,
+    Abs,
+    RealOfInt,
+    Floor,
+    Ceil,
+    Round,
+    Trunc,
+    Sqrt,
+    Pow,
+    Ord,
+    Chr,
+    Ref,
+    Deref,
+    Assign,
 }
 
 impl PP for BIF {
@@ -80,6 +245,27 @@ impl PP for BIF {
             Mod => {
                 write!(w, "mod")?;
             }
+            Modf => {
+                write!(w, "modf")?;
+            }
+            Shl => {
+                write!(w, "shl")?;
+            }
+            Shr => {
+                write!(w, "shr")?;
+            }
+            Andb => {
+                write!(w, "andb")?;
+            }
+            Orb => {
+                write!(w, "orb")?;
+            }
+            Xorb => {
+                write!(w, "xorb")?;
+            }
+            Notb => {
+                write!(w, "notb")?;
+            }
             Eq => {
                 write!(w, "eq")?;
             }
@@ -98,6 +284,48 @@ impl PP for BIF {
             Le => {
                 write!(w, "le")?;
             }
+            Neg => {
+                write!(w, "neg")?;
+            }
+            Abs => {
+                write!(w, "abs")?;
+            }
+            RealOfInt => {
+                write!(w, "real")?;
+            }
+            Floor => {
+                write!(w, "floor")?;
+            }
+            Ceil => {
+                write!(w, "ceil")?;
+            }
+            Round => {
+                write!(w, "round")?;
+            }
+            Trunc => {
+                write!(w, "trunc")?;
+            }
+            Sqrt => {
+                write!(w, "sqrt")?;
+            }
+            Pow => {
+                write!(w, "pow")?;
+            }
+            Ord => {
+                write!(w, "ord")?;
+            }
+            Chr => {
+                write!(w, "chr")?;
+            }
+            Ref => {
+                write!(w, "ref")?;
+            }
+            Deref => {
+                write!(w, "deref")?;
+            }
+            Assign => {
+                write!(w, "assign")?;
+            }
         }
         Ok(())
     }
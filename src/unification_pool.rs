@@ -1,6 +1,13 @@
+use std::collections::HashMap;
+
 #[derive(Debug, Clone, PartialEq, Eq, Copy, Hash)]
 pub struct NodeId(usize);
 
+impl NodeId {
+    // dummy id for the nodes whose value is taken during unification
+    const TAKEN: NodeId = NodeId(std::usize::MAX);
+}
+
 #[derive(Debug, Clone)]
 enum Node<T> {
     Value(T),
@@ -11,28 +18,45 @@ impl<T> Node<T> {
     fn new(t: T) -> Self {
         Node::Value(t)
     }
+}
 
-    fn take(&mut self) -> Option<T> {
-        let node = std::mem::replace(self, Node::Refer(NodeId(std::usize::MAX)));
-        match node {
-            Node::Value(t) => Some(t),
-            Node::Refer(_) => None,
-        }
-    }
+// the state before a change to the pool
+#[derive(Debug)]
+enum Undo<T> {
+    Node(NodeId, Node<T>),
+    Rank(NodeId, u32),
+}
+
+/// a state of the pool to roll back to, taken by `snapshot`
+#[derive(Debug)]
+pub struct Checkpoint {
+    len: usize,
+    undo_len: usize,
 }
 
 #[derive(Debug)]
 pub struct UnificationPool<T> {
     pool: Vec<Node<T>>,
+    // upper bounds of the heights of the trees, valid for the roots
+    rank: Vec<u32>,
+    // the changes made while some snapshots are alive
+    undo_log: Vec<Undo<T>>,
+    snapshots: usize,
 }
 
 impl<T> UnificationPool<T> {
     pub fn new() -> Self {
-        Self { pool: vec![] }
+        Self {
+            pool: vec![],
+            rank: vec![],
+            undo_log: vec![],
+            snapshots: 0,
+        }
     }
 
     fn register(&mut self, node: Node<T>) -> NodeId {
         self.pool.push(node);
+        self.rank.push(0);
         NodeId(self.pool.len() - 1)
     }
 
@@ -48,7 +72,8 @@ impl<T> UnificationPool<T> {
         &mut self.pool[node_id.0]
     }
 
-    fn value_id(&self, mut id: NodeId) -> NodeId {
+    /// the representative of the equivalence class of `id`
+    pub fn value_id(&self, mut id: NodeId) -> NodeId {
         loop {
             match self.at(id) {
                 Node::Value(_) => return id,
@@ -66,18 +91,114 @@ impl<T> UnificationPool<T> {
         }
     }
 
+    /// same as `value_of` but returns `None` if the value is taken by `try_unify_with`.
+    pub fn try_value_of(&self, mut id: NodeId) -> Option<&T> {
+        loop {
+            match self.at(id) {
+                Node::Value(t) => return Some(t),
+                Node::Refer(new_id) if *new_id == NodeId::TAKEN => return None,
+                Node::Refer(new_id) => id = *new_id,
+            }
+        }
+    }
+
+    /// the nodes unified with `id`, including `id` itself
+    pub fn class_of(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let root = self.value_id(id);
+        (0..self.pool.len())
+            .map(NodeId)
+            .filter(move |id| self.value_id(*id) == root)
+    }
+
+    /// the equivalence classes of the nodes with their values,
+    /// in the order of the first node of each class
+    pub fn classes(&self) -> Vec<(&T, Vec<NodeId>)> {
+        let mut classes: Vec<(NodeId, Vec<NodeId>)> = Vec::new();
+        let mut index_of_root = HashMap::new();
+        for id in (0..self.pool.len()).map(NodeId) {
+            let root = self.value_id(id);
+            let index = *index_of_root.entry(root).or_insert_with(|| {
+                classes.push((root, Vec::new()));
+                classes.len() - 1
+            });
+            classes[index].1.push(id);
+        }
+        classes
+            .into_iter()
+            .map(|(root, ids)| (self.value_of(root), ids))
+            .collect()
+    }
+
+    /// start recording the changes to the pool.
+    /// the checkpoint must be passed to either `rollback` or `commit`.
+    pub fn snapshot(&mut self) -> Checkpoint {
+        self.snapshots += 1;
+        Checkpoint {
+            len: self.pool.len(),
+            undo_len: self.undo_log.len(),
+        }
+    }
+
+    /// undo all the changes made after `checkpoint` is taken
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        while self.undo_log.len() > checkpoint.undo_len {
+            match self.undo_log.pop().unwrap() {
+                Undo::Node(id, node) => *self.at_mut(id) = node,
+                Undo::Rank(id, rank) => self.rank[id.0] = rank,
+            }
+        }
+        self.pool.truncate(checkpoint.len);
+        self.rank.truncate(checkpoint.len);
+        self.end_snapshot();
+    }
+
+    /// keep the changes made after `checkpoint` is taken
+    pub fn commit(&mut self, _checkpoint: Checkpoint) {
+        self.end_snapshot();
+    }
+
+    fn end_snapshot(&mut self) {
+        self.snapshots -= 1;
+        if self.snapshots == 0 {
+            self.undo_log.clear()
+        }
+    }
+}
+
+impl<T: Clone> UnificationPool<T> {
+    fn replace(&mut self, id: NodeId, node: Node<T>) -> Node<T> {
+        let old = std::mem::replace(self.at_mut(id), node);
+        if self.snapshots > 0 {
+            self.undo_log.push(Undo::Node(id, old.clone()))
+        }
+        old
+    }
+
+    fn take(&mut self, id: NodeId) -> Option<T> {
+        match self.replace(id, Node::Refer(NodeId::TAKEN)) {
+            Node::Value(t) => Some(t),
+            Node::Refer(_) => None,
+        }
+    }
+
+    fn set_rank(&mut self, id: NodeId, rank: u32) {
+        if self.snapshots > 0 {
+            self.undo_log.push(Undo::Rank(id, self.rank[id.0]))
+        }
+        self.rank[id.0] = rank;
+    }
+
     fn reduction(&mut self, mut start: NodeId) {
         let value_id = self.value_id(start);
         loop {
-            match self.at_mut(start) {
-                Node::Value(_) => {
-                    return;
-                }
-                Node::Refer(ref mut id) => {
-                    start = *id;
-                    *id = value_id;
-                }
+            let next = match self.at(start) {
+                Node::Value(_) => return,
+                Node::Refer(id) => *id,
+            };
+            if next != value_id {
+                self.replace(start, Node::Refer(value_id));
             }
+            start = next;
         }
     }
 
@@ -92,14 +213,32 @@ impl<T> UnificationPool<T> {
         if lid == rid {
             return Ok(lid);
         }
-        let l = self.at_mut(lid).take().unwrap();
-        let r = self.at_mut(rid).take().unwrap();
-        let new = try_unify(self, l, r)?;
-        *self.at_mut(lid) = Node::Value(new);
-        *self.at_mut(rid) = Node::Refer(lid);
+        let l = self.take(lid).unwrap();
+        let r = self.take(rid).unwrap();
+        // put the values back on failure, leaving the roots taken would break the later lookups
+        let new = match try_unify(self, l.clone(), r.clone()) {
+            Ok(new) => new,
+            Err(e) => {
+                self.replace(lid, Node::Value(l));
+                self.replace(rid, Node::Value(r));
+                return Err(e);
+            }
+        };
+        // union by rank: attach the lower tree under the root of the higher one
+        let (lrank, rrank) = (self.rank[lid.0], self.rank[rid.0]);
+        let (root, child) = if lrank < rrank {
+            (rid, lid)
+        } else {
+            (lid, rid)
+        };
+        if lrank == rrank {
+            self.set_rank(root, lrank + 1);
+        }
+        self.replace(root, Node::Value(new));
+        self.replace(child, Node::Refer(root));
 
         self.reduction(id1);
         self.reduction(id2);
-        Ok(lid)
+        Ok(root)
     }
 }
@@ -1,7 +1,113 @@
 use std::io;
 
+/// the layout of the pretty printed IRs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PpConfig {
+    /// the number of spaces a nested block is indented by
+    pub indent: usize,
+    /// the line width that the printer tries to fit in
+    pub width: usize,
+    /// whether to highlight the output. `Auto` is resolved by whoever knows the output
+    /// and the printers leave it uncolored
+    pub color: ColorMode,
+}
+
+impl Default for PpConfig {
+    fn default() -> Self {
+        PpConfig {
+            indent: 2,
+            width: 80,
+            color: ColorMode::Auto,
+        }
+    }
+}
+
+impl PpConfig {
+    /// write what `f` writes colored as `hl` if the color is enabled
+    pub fn paint<W: io::Write>(
+        &self,
+        w: &mut W,
+        hl: Highlight,
+        f: impl FnOnce(&mut W) -> io::Result<()>,
+    ) -> io::Result<()> {
+        self.color.paint(w, hl, f)
+    }
+
+    pub fn keyword<W: io::Write>(&self, w: &mut W, keyword: &str) -> io::Result<()> {
+        self.paint(w, Highlight::Keyword, |w| write!(w, "{}", keyword))
+    }
+}
+
+/// when to color the printed IRs and the diagnostics with ANSI escape codes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    /// only if the output is a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+impl Default for ColorMode {
+    fn default() -> Self {
+        ColorMode::Auto
+    }
+}
+
+impl ColorMode {
+    /// `Auto` decided by whether the output is a terminal
+    pub fn resolve(self, is_tty: bool) -> Self {
+        match self {
+            ColorMode::Auto if is_tty => ColorMode::Always,
+            ColorMode::Auto => ColorMode::Never,
+            mode => mode,
+        }
+    }
+
+    /// write what `f` writes colored as `hl` only if `Always`
+    pub fn paint<W: io::Write>(
+        self,
+        w: &mut W,
+        hl: Highlight,
+        f: impl FnOnce(&mut W) -> io::Result<()>,
+    ) -> io::Result<()> {
+        match self {
+            ColorMode::Always => {
+                write!(w, "\x1b[{}m", hl.sgr())?;
+                f(w)?;
+                write!(w, "\x1b[0m")
+            }
+            ColorMode::Auto | ColorMode::Never => f(w),
+        }
+    }
+}
+
+/// the kinds of the text that are colored
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Highlight {
+    Keyword,
+    Literal,
+    TyVar,
+    Error,
+}
+
+impl Highlight {
+    // the parameters of the SGR escape code
+    fn sgr(self) -> &'static str {
+        match self {
+            Highlight::Keyword => "1;35",
+            Highlight::Literal => "32",
+            Highlight::TyVar => "36",
+            Highlight::Error => "1;31",
+        }
+    }
+}
+
 pub trait PP {
     fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()>;
+    /// print with the given layout. the IRs without a configurable layout ignore it
+    fn pp_with<W: io::Write>(&self, w: &mut W, _config: &PpConfig) -> io::Result<()> {
+        self.pp(w, 0)
+    }
     fn nspaces(n: usize) -> String {
         let mut s = String::new();
         for _ in 0..n {
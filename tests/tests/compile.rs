@@ -1,8 +1,9 @@
 use std::fs;
 use std::io::{self, prelude::*};
 use std::path::{Path, PathBuf};
+use webml::hir::HTy;
 use webml::TypeError;
-use webml::{compile_str, Config};
+use webml::{compile_str, compile_to_hir, Config, PassName};
 
 fn read_and_append_to_string(path: impl AsRef<Path>, buf: &mut String) -> io::Result<usize> {
     let file = fs::File::open(path)?;
@@ -15,11 +16,20 @@ fn with_compile_result(
     callback: impl for<'a> FnOnce(Result<Vec<u8>, TypeError<'a>>),
 ) {
     let path = path.as_ref();
+    let mut input = String::new();
+    read_and_append_to_string(&path, &mut input).expect("failed to load file");
+    println!("{}", path.to_str().unwrap());
+    with_compile_str_result(&input, callback)
+}
+
+fn with_compile_str_result(
+    src: &str,
+    callback: impl for<'a> FnOnce(Result<Vec<u8>, TypeError<'a>>),
+) {
     let mut input = include_str!("../../ml_src/prelude.sml").to_string();
     let config = Config::default();
-    read_and_append_to_string(&path, &mut input).expect("failed to load file");
+    input.push_str(src);
     let result = compile_str(&input, &config);
-    println!("{}", path.to_str().unwrap());
     callback(result)
 }
 
@@ -58,10 +68,342 @@ fn examples_compile_pass() {
 
 #[test]
 fn test_compile_pass() {
-    walk_dir("tests/compile_pass", assert_compile_pass)
+    walk_dir("tests/tests/compile_pass", assert_compile_pass)
 }
 
 #[test]
 fn test_compile_fail() {
-    walk_dir("tests/compile_fail", assert_compile_fail)
+    walk_dir("tests/tests/compile_fail", assert_compile_fail)
+}
+
+#[test]
+fn test_infinite_type_self_application() {
+    with_compile_str_result("val f = fn x => x x", |res| match res {
+        Err(TypeError::InfiniteType { .. }) => (),
+        res => panic!("expected infinite type error, got {:?}", res),
+    })
+}
+
+#[test]
+fn test_infinite_type_recursive_lambda() {
+    with_compile_str_result("val rec f = fn x => f", |res| match res {
+        Err(TypeError::InfiniteType { .. }) => (),
+        res => panic!("expected infinite type error, got {:?}", res),
+    })
+}
+
+#[test]
+fn test_invalid_pipeline() {
+    let config = Config {
+        pipeline: vec![PassName::FlatExpr],
+        ..Config::default()
+    };
+    match compile_str("val x = 1", &config) {
+        Err(TypeError::InvalidPipeline(_)) => (),
+        res => panic!("expected invalid pipeline error, got {:?}", res),
+    }
+}
+
+#[test]
+fn test_polymorphic_function() {
+    with_compile_str_result("fun id x = x val a = print (id 1)", |res| match res {
+        Ok(_) => (),
+        Err(e) => panic!("failed to compile: {}", e),
+    })
+}
+
+// the function types of a wasm module, by the type, import, function and export sections
+const I32: u8 = 0x7f;
+const F64: u8 = 0x7c;
+
+struct Functions {
+    types: Vec<(Vec<u8>, Vec<u8>)>,
+    // the module name, the field name and the type index
+    imports: Vec<(Vec<u8>, Vec<u8>, usize)>,
+    // the type indices of the functions defined in the module
+    functions: Vec<usize>,
+    // the export name and the function index
+    exports: Vec<(Vec<u8>, usize)>,
+}
+
+impl Functions {
+    fn read(wasm: &[u8]) -> Self {
+        fn leb(wasm: &[u8], pos: &mut usize) -> u32 {
+            let mut value = 0;
+            let mut shift = 0;
+            loop {
+                let byte = wasm[*pos];
+                *pos += 1;
+                value |= ((byte & 0x7f) as u32) << shift;
+                if byte & 0x80 == 0 {
+                    return value;
+                }
+                shift += 7;
+            }
+        }
+        fn bytes(wasm: &[u8], pos: &mut usize) -> Vec<u8> {
+            let len = leb(wasm, pos) as usize;
+            *pos += len;
+            wasm[*pos - len..*pos].to_vec()
+        }
+        fn limits(wasm: &[u8], pos: &mut usize) {
+            let has_max = wasm[*pos] == 1;
+            *pos += 1;
+            leb(wasm, pos);
+            if has_max {
+                leb(wasm, pos);
+            }
+        }
+
+        let mut ret = Functions {
+            types: Vec::new(),
+            imports: Vec::new(),
+            functions: Vec::new(),
+            exports: Vec::new(),
+        };
+        // skip the magic number and the version
+        let mut pos = 8;
+        while pos < wasm.len() {
+            let id = wasm[pos];
+            pos += 1;
+            let size = leb(wasm, &mut pos) as usize;
+            let end = pos + size;
+            match id {
+                1 => {
+                    for _ in 0..leb(wasm, &mut pos) {
+                        assert_eq!(wasm[pos], 0x60, "not a function type");
+                        pos += 1;
+                        let params = bytes(wasm, &mut pos);
+                        let results = bytes(wasm, &mut pos);
+                        ret.types.push((params, results));
+                    }
+                }
+                2 => {
+                    for _ in 0..leb(wasm, &mut pos) {
+                        let module = bytes(wasm, &mut pos);
+                        let field = bytes(wasm, &mut pos);
+                        let kind = wasm[pos];
+                        pos += 1;
+                        match kind {
+                            0 => {
+                                let index = leb(wasm, &mut pos) as usize;
+                                ret.imports.push((module, field, index));
+                            }
+                            1 => {
+                                pos += 1;
+                                limits(wasm, &mut pos);
+                            }
+                            2 => limits(wasm, &mut pos),
+                            _ => pos += 2,
+                        }
+                    }
+                }
+                3 => {
+                    for _ in 0..leb(wasm, &mut pos) {
+                        ret.functions.push(leb(wasm, &mut pos) as usize);
+                    }
+                }
+                7 => {
+                    for _ in 0..leb(wasm, &mut pos) {
+                        let name = bytes(wasm, &mut pos);
+                        let kind = wasm[pos];
+                        pos += 1;
+                        let index = leb(wasm, &mut pos) as usize;
+                        if kind == 0 {
+                            ret.exports.push((name, index));
+                        }
+                    }
+                }
+                _ => (),
+            }
+            pos = end;
+        }
+        ret
+    }
+
+    // the parameter and result types of the function exported as `name`
+    fn exported(&self, name: &str) -> Option<(Vec<u8>, Vec<u8>)> {
+        self.exports
+            .iter()
+            .find(|(export, _)| export.as_slice() == name.as_bytes())
+            .map(|(_, index)| self.types[self.functions[index - self.imports.len()]].clone())
+    }
+
+    // the parameter and result types of the imported function `module`.`name`
+    fn imported(&self, module: &str, name: &str) -> Option<(Vec<u8>, Vec<u8>)> {
+        self.imports
+            .iter()
+            .find(|(m, n, _)| m.as_slice() == module.as_bytes() && n.as_slice() == name.as_bytes())
+            .map(|(_, _, index)| self.types[*index].clone())
+    }
+}
+
+#[test]
+fn test_export() {
+    let input = r#"
+export "inc" fun inc x = x + 1
+export "half" val half = fn x => x / 2.0
+fun double x = x * 2
+"#;
+    with_compile_str_result(input, |res| {
+        let functions = Functions::read(&res.expect("failed to compile"));
+        assert_eq!(functions.exported("inc"), Some((vec![I32], vec![I32])));
+        assert_eq!(functions.exported("half"), Some((vec![F64], vec![F64])));
+        assert_eq!(functions.exported("double"), None);
+    })
+}
+
+#[test]
+fn test_hello_world() {
+    let input = r#"
+val () = print_string "Hello, world!\n"
+val written = fd_write (2, "bye")
+"#;
+    with_compile_str_result(input, |res| {
+        let functions = Functions::read(&res.expect("failed to compile"));
+        // the fd, the pointer to the bytes and the length
+        assert_eq!(
+            functions.imported("wasi", "fd_write"),
+            Some((vec![I32, I32, I32], vec![I32]))
+        );
+    });
+
+    // strings are passed unpacked to the runtime too
+    let input = r#"val b = case "a" of "a" => true | _ => false"#;
+    let wasm = compile_str(input, &Config::default()).expect("failed to compile");
+    assert_eq!(
+        Functions::read(&wasm).imported("webml-rt", "string_eq"),
+        Some((vec![I32, I32, I32, I32], vec![I32]))
+    );
+}
+
+#[test]
+fn test_real_externcall() {
+    let input = r#"
+_import ("m"."pow": (real, real) -> real)
+val x = _externcall ("m"."pow") (1.5, 2.0)
+val y = _externcall ("m"."floor": (real) -> int) (x)
+"#;
+    with_compile_str_result(input, |res| {
+        let functions = Functions::read(&res.expect("failed to compile"));
+        assert_eq!(
+            functions.imported("m", "pow"),
+            Some((vec![F64, F64], vec![F64]))
+        );
+        assert_eq!(
+            functions.imported("m", "floor"),
+            Some((vec![F64], vec![I32]))
+        );
+    })
+}
+
+#[test]
+fn test_unboxed_datatype() {
+    let input = r#"
+datatype box = Box of int
+datatype pair = Pair of box * box
+fun add (Pair (Box x, Box y)) = Box (x + y)
+val Box z = add (Pair (Box 1, Box 2))
+"#;
+    with_compile_str_result(input, |res| {
+        res.expect("failed to compile");
+    })
+}
+
+#[test]
+fn test_structural_equality() {
+    let input = r#"
+datatype tree = Leaf | Node of tree * int * tree
+val a = (1, "ab") = (1, "ab")
+val b = Node (Leaf, 1, Leaf) <> Node (Leaf, 2, Leaf)
+val c = [1, 2] = [1, 2] andalso (true, ()) = (true, ())
+val r = ref 1
+val d = r = r
+"#;
+    with_compile_str_result(input, |res| {
+        res.expect("failed to compile");
+    })
+}
+
+#[test]
+fn test_compile_to_hir() {
+    let input = r#"
+datatype shape = Circle of real | Square of real
+fun area (Circle r) = r * r * 3.14
+  | area (Square a) = a * a
+val x = area (Square 2.0)
+"#;
+    let config = Config {
+        opt_level: 0,
+        ..Config::default()
+    };
+    let (symbol_table, hir) = compile_to_hir(input, &config).expect("failed to compile");
+    let (shape, info) = symbol_table
+        .types
+        .iter()
+        .find(|(name, _)| name.0 == "shape")
+        .expect("no datatype shape");
+    assert_eq!(
+        info.constructors,
+        vec![(0, Some(HTy::Real)), (1, Some(HTy::Real))]
+    );
+    let ty = |name: &str| {
+        hir.0
+            .iter()
+            .find(|val| val.name.0 == name)
+            .map(|val| val.ty.clone())
+    };
+    assert_eq!(
+        ty("area"),
+        Some(HTy::fun(HTy::Datatype(shape.clone(), vec![]), HTy::Real))
+    );
+    assert_eq!(ty("x"), Some(HTy::Real));
+}
+
+#[test]
+fn test_externcall_with_multiple_results() {
+    let input = r#"
+_import ("m"."divmod": (int, int) -> (int, int))
+val (q, r) = _externcall ("m"."divmod") (7, 2)
+val x = q + r
+val y = _externcall ("m"."split": (real) -> (int, real)) 1.5
+"#;
+    with_compile_str_result(input, |res| {
+        let functions = Functions::read(&res.expect("failed to compile"));
+        assert_eq!(
+            functions.imported("m", "divmod"),
+            Some((vec![I32, I32], vec![I32, I32]))
+        );
+        assert_eq!(
+            functions.imported("m", "split"),
+            Some((vec![F64], vec![I32, F64]))
+        );
+        // the other types are kept
+        assert_eq!(
+            functions.imported("wasi", "fd_write"),
+            Some((vec![I32, I32, I32], vec![I32]))
+        );
+    });
+}
+
+#[test]
+fn test_compile_to_hir_errors() {
+    let config = Config::default();
+    match compile_to_hir("val x = ", &config) {
+        Err(TypeError::ParseError(_)) => (),
+        res => panic!("expected parse error, got {:?}", res),
+    }
+    let input = r#"_import ("m"."f": (int) -> int) val x = _externcall ("m"."f") (1.0)"#;
+    match compile_to_hir(input, &config) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+    let config = Config {
+        pipeline: vec![PassName::FlatExpr],
+        ..Config::default()
+    };
+    match compile_to_hir("val x = 1", &config) {
+        Err(TypeError::InvalidPipeline(_)) => (),
+        res => panic!("expected invalid pipeline error, got {:?}", res),
+    }
 }
@@ -0,0 +1,135 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use webml::ast::{CaseSimplify, Desugar, Monomorphize, Rename, Typer, VarToConstructor};
+use webml::compile_pass;
+use webml::hir::{Pipeline, AST2HIR};
+use webml::id::Id;
+use webml::pass::{Chain, ConvError, Pass, PrintablePass};
+use webml::util::{PpConfig, PP};
+use webml::{parse, Config, PassName};
+
+// set to rewrite the golden files with the current outputs
+const UPDATE: &str = "WEBML_UPDATE_GOLDEN";
+
+// the HIR of `input` printed after running the required passes up to `pass`, inclusive
+fn hir_after(input: &str, pass: PassName) -> String {
+    let id = Id::new();
+    let mut front = compile_pass![
+        parse: ConvError::new(parse),
+        desugar: Desugar::new(id.clone()),
+        rename: Rename::new(id.clone()),
+        var_to_constructor: VarToConstructor::new(id.clone()),
+        typing: Typer::new(),
+        monomorphize: Monomorphize::new(id.clone()),
+        case_simplify: CaseSimplify::new(id.clone()),
+        ast_to_hir: AST2HIR::new(id.clone()),
+    ];
+    let hir = front
+        .trans(input, &Config::default())
+        .unwrap_or_else(|e| panic!("failed to compile: {}", e));
+
+    let pipeline = PassName::default_pipeline();
+    let end = pipeline.iter().position(|p| *p == pass).unwrap() + 1;
+    // only the passes the translation to MIR needs, to keep the outputs small
+    let config = Config {
+        pipeline: pipeline[..end].to_vec(),
+        opt_level: 0,
+        ..Config::default()
+    };
+    let result: Result<_, ()> = Pipeline::new(id).trans(hir, &config);
+    let mut buf = Vec::new();
+    result
+        .unwrap()
+        .pp_with(&mut buf, &PpConfig::default())
+        .unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+// the lines only in `expected` prefixed by `-` and those only in `actual` by `+`,
+// aligned by the longest common subsequence
+fn diff(expected: &str, actual: &str) -> String {
+    let old = expected.lines().collect::<Vec<_>>();
+    let new = actual.lines().collect::<Vec<_>>();
+    // lcs[i][j] is the length of the LCS of old[i..] and new[j..]
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            out.push_str(&format!(" {}\n", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push_str(&format!("-{}\n", old[i]));
+            i += 1;
+        } else {
+            out.push_str(&format!("+{}\n", new[j]));
+            j += 1;
+        }
+    }
+    out
+}
+
+/// compares the HIR of `tests/tests/golden/<name>.sml` after `pass` with
+/// `tests/tests/golden/<name>.<pass>.hir`. a missing golden file is recorded from the output
+fn assert_golden(name: &str, pass: PassName) {
+    let dir = Path::new("tests/tests/golden");
+    let input = fs::read_to_string(dir.join(format!("{}.sml", name))).expect("failed to load file");
+    let actual = hir_after(&input, pass);
+    let golden = dir.join(format!("{}.{}.hir", name, pass));
+    match fs::read_to_string(&golden) {
+        Ok(ref expected) if env::var_os(UPDATE).is_none() => {
+            if *expected != actual {
+                panic!(
+                    "the HIR after {} differs from {} (set {} to update it):\n{}",
+                    pass,
+                    golden.display(),
+                    UPDATE,
+                    diff(expected, &actual)
+                )
+            }
+        }
+        _ => fs::write(&golden, actual).expect("failed to write the golden file"),
+    }
+}
+
+#[test]
+fn golden_flat_expr() {
+    assert_golden("nested_let", PassName::FlatExpr);
+    assert_golden("case", PassName::FlatExpr);
+}
+
+#[test]
+fn golden_flat_let() {
+    assert_golden("nested_let", PassName::FlatLet);
+}
+
+#[test]
+fn golden_unnest_func() {
+    assert_golden("closure", PassName::UnnestFunc);
+}
+
+#[test]
+fn golden_force_closure() {
+    assert_golden("closure", PassName::ForceClosure);
+}
+
+#[test]
+fn golden_decision_tree() {
+    assert_golden("case", PassName::DecisionTree);
+}
+
+#[test]
+fn diff_marks_changed_lines() {
+    assert_eq!(diff("a\nb\nc\n", "a\nx\nc\n"), " a\n-b\n+x\n c\n");
+}
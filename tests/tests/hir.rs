@@ -0,0 +1,2395 @@
+use webml::ast::{CaseSimplify, Desugar, Monomorphize, Rename, Typer, VarToConstructor};
+use webml::compile_pass;
+use webml::hir::util::Traverse;
+use webml::hir::{
+    verify, BetaReduce, ConstFold, CopyProp, DeadBindElim, DecisionTree, DumpHir, Expr, FlatExpr,
+    FlatLet, FlatTuple, ForceClosure, HTy, HirError, Inline, MarkTail, MergeBinds, Pattern,
+    Pipeline, PruneCaptures, Simplify, StrengthReduce, SymbolTable, UnboxDatatype, UnnestFunc, Val,
+    Verify, AST2HIR, CSE, HIR,
+};
+use webml::id::Id;
+use webml::pass::{Chain, ConvError, Pass, PrintablePass};
+use webml::prim::{Literal, Symbol, BIF};
+use webml::util::{ColorMode, PpConfig, PP};
+use webml::{parse, Config, PassName, TypeError};
+
+use std::collections::HashMap;
+
+fn to_hir<'a>(input: &'a str) -> Result<(SymbolTable, HIR), TypeError<'a>> {
+    let id = Id::new();
+    let mut passes = compile_pass![
+        parse: ConvError::new(parse),
+        desugar: Desugar::new(id.clone()),
+        rename: Rename::new(id.clone()),
+        var_to_constructor: VarToConstructor::new(id.clone()),
+        typing: Typer::new(),
+        monomorphize: Monomorphize::new(id.clone()),
+        case_simplify: CaseSimplify::new(id.clone()),
+        ast_to_hir: AST2HIR::new(id),
+    ];
+    passes.trans(input, &Config::default())
+}
+
+fn apply(
+    pass: impl Pass<(SymbolTable, HIR), (), Target = (SymbolTable, HIR)>,
+    hir: (SymbolTable, HIR),
+) -> HIR {
+    apply_with(pass, hir, &Config::default())
+}
+
+fn apply_with(
+    mut pass: impl Pass<(SymbolTable, HIR), (), Target = (SymbolTable, HIR)>,
+    hir: (SymbolTable, HIR),
+    config: &Config,
+) -> HIR {
+    pass.trans(hir, config).unwrap().1
+}
+
+fn val_expr<'h>(hir: &'h HIR, name: &str) -> &'h Expr {
+    &hir.0
+        .iter()
+        .filter(|val| val.name.0 == name)
+        .last()
+        .expect("no such val")
+        .expr
+}
+
+#[test]
+fn string_literal_is_lowered() {
+    let input = r#"val s = "a\tb""#;
+    let (_, hir) = to_hir(input).unwrap();
+    match val_expr(&hir, "s") {
+        Expr::Lit {
+            ty: HTy::String,
+            value: Literal::String(s),
+        } => assert_eq!(s, "a\tb"),
+        expr => panic!("expected string literal, got {:?}", expr),
+    }
+}
+
+#[test]
+fn const_fold_add() {
+    let input = r#"val x = _builtincall "add"(2, 3)"#;
+    let hir = apply(ConstFold::new(), to_hir(input).unwrap());
+    match val_expr(&hir, "x") {
+        Expr::Lit {
+            value: Literal::Int(5),
+            ..
+        } => (),
+        expr => panic!("expected 5, got {:?}", expr),
+    }
+}
+
+#[test]
+fn const_fold_keeps_division_by_zero() {
+    let input = r#"val x = _builtincall "div"(1, 0)"#;
+    let hir = apply(ConstFold::new(), to_hir(input).unwrap());
+    match val_expr(&hir, "x") {
+        Expr::BuiltinCall { fun: BIF::Div, .. } => (),
+        expr => panic!("expected division, got {:?}", expr),
+    }
+}
+
+#[test]
+fn const_fold_leaves_nan_comparison() {
+    let input = r#"
+val nan = _builtincall "divf"(0.0, 0.0)
+val b = _builtincall "lt"(_builtincall "divf"(0.0, 0.0), 1.0)
+"#;
+    let hir = apply(ConstFold::new(), to_hir(input).unwrap());
+    match val_expr(&hir, "nan") {
+        Expr::Lit {
+            value: Literal::Real(v),
+            ..
+        } if v.is_nan() => (),
+        expr => panic!("expected NaN, got {:?}", expr),
+    }
+    // the comparison is evaluated by the IEEE instruction at runtime, which gives false
+    match val_expr(&hir, "b") {
+        Expr::BuiltinCall { fun: BIF::Lt, .. } => (),
+        expr => panic!("expected comparison to be left, got {:?}", expr),
+    }
+}
+
+#[test]
+fn const_fold_neg() {
+    let input = r#"
+val x = _builtincall "neg"(5)
+val y = _builtincall "neg"(1.5)
+"#;
+    let hir = apply(ConstFold::new(), to_hir(input).unwrap());
+    match val_expr(&hir, "x") {
+        Expr::Lit {
+            value: Literal::Int(-5),
+            ..
+        } => (),
+        expr => panic!("expected -5, got {:?}", expr),
+    }
+    match val_expr(&hir, "y") {
+        Expr::Lit {
+            value: Literal::Real(v),
+            ..
+        } if *v == -1.5 => (),
+        expr => panic!("expected -1.5, got {:?}", expr),
+    }
+}
+
+#[test]
+fn const_fold_abs() {
+    let input = r#"
+val x = _builtincall "abs"(_builtincall "neg"(5))
+val y = _builtincall "abs"(_builtincall "neg"(1.5))
+"#;
+    let hir = apply(ConstFold::new(), to_hir(input).unwrap());
+    match val_expr(&hir, "x") {
+        Expr::Lit {
+            value: Literal::Int(5),
+            ..
+        } => (),
+        expr => panic!("expected 5, got {:?}", expr),
+    }
+    match val_expr(&hir, "y") {
+        Expr::Lit {
+            value: Literal::Real(v),
+            ..
+        } if *v == 1.5 => (),
+        expr => panic!("expected 1.5, got {:?}", expr),
+    }
+}
+
+#[test]
+fn const_fold_abs_of_min_int_is_left() {
+    let input = r#"val x = _builtincall "abs"(~2147483648)"#;
+    let hir = apply(ConstFold::new(), to_hir(input).unwrap());
+    match val_expr(&hir, "x") {
+        Expr::BuiltinCall { fun: BIF::Abs, .. } => (),
+        expr => panic!("expected abs to be left unfolded, got {:?}", expr),
+    }
+}
+
+#[test]
+fn const_fold_shift() {
+    let input = r#"
+val a = _builtincall "shl"(1, 4)
+val b = _builtincall "shr"(_builtincall "neg"(16), 2)
+val c = _builtincall "shl"(1, 33)
+"#;
+    let hir = apply(ConstFold::new(), to_hir(input).unwrap());
+    // shr keeps the sign and the shift amount is masked to 5 bits
+    for &(name, expected) in &[("a", 16), ("b", -4), ("c", 2)] {
+        match val_expr(&hir, name) {
+            Expr::Lit {
+                value: Literal::Int(v),
+                ..
+            } if *v == expected => (),
+            expr => panic!("expected {} for {}, got {:?}", expected, name, expr),
+        }
+    }
+}
+
+#[test]
+fn const_fold_word() {
+    let input = r#"
+val a = _builtincall "div"(0wxfffffffe, 0w2)
+val b = _builtincall "div"(_builtincall "neg"(2), 2)
+val c = _builtincall "shr"(0wx80000000, 0w31)
+val d = _builtincall "sub"(0w0, 0w1)
+"#;
+    let hir = apply(ConstFold::new(), to_hir(input).unwrap());
+    // the same bits divide differently when unsigned
+    for &(name, expected) in &[("a", 0x7fff_ffff), ("c", 1), ("d", 0xffff_ffff)] {
+        match val_expr(&hir, name) {
+            Expr::Lit {
+                ty: HTy::Word,
+                value: Literal::Word(v),
+            } if *v == expected => (),
+            expr => panic!("expected {} for {}, got {:?}", expected, name, expr),
+        }
+    }
+    match val_expr(&hir, "b") {
+        Expr::Lit {
+            value: Literal::Int(-1),
+            ..
+        } => (),
+        expr => panic!("expected -1, got {:?}", expr),
+    }
+}
+
+#[test]
+fn const_fold_real_of_int() {
+    let input = r#"val x = _builtincall "real"(_builtincall "neg"(3))"#;
+    let hir = apply(ConstFold::new(), to_hir(input).unwrap());
+    match val_expr(&hir, "x") {
+        Expr::Lit {
+            value: Literal::Real(v),
+            ..
+        } if *v == -3.0 => (),
+        expr => panic!("expected -3.0, got {:?}", expr),
+    }
+}
+
+#[test]
+fn const_fold_real_to_int() {
+    let input = r#"
+val f = _builtincall "floor"(_builtincall "neg"(1.5))
+val c = _builtincall "ceil"(_builtincall "neg"(1.5))
+val t = _builtincall "trunc"(_builtincall "neg"(1.7))
+val r1 = _builtincall "round"(2.5)
+val r2 = _builtincall "round"(3.5)
+val r3 = _builtincall "round"(_builtincall "neg"(2.5))
+val r4 = _builtincall "round"(2.6)
+val m = _builtincall "floor"(_builtincall "neg"(2147483648.0))
+"#;
+    let hir = apply(ConstFold::new(), to_hir(input).unwrap());
+    let expected = [
+        ("f", -2),
+        ("c", -1),
+        ("t", -1),
+        ("r1", 2),
+        ("r2", 4),
+        ("r3", -2),
+        ("r4", 3),
+        ("m", -2147483648),
+    ];
+    for &(name, expected) in &expected {
+        match val_expr(&hir, name) {
+            Expr::Lit {
+                value: Literal::Int(v),
+                ..
+            } if *v == expected => (),
+            expr => panic!("expected {} for {}, got {:?}", expected, name, expr),
+        }
+    }
+}
+
+#[test]
+fn const_fold_leaves_out_of_range_conversion() {
+    let input = r#"
+val x = _builtincall "floor"(2147483648.0)
+val y = _builtincall "trunc"(_builtincall "divf"(0.0, 0.0))
+"#;
+    let hir = apply(ConstFold::new(), to_hir(input).unwrap());
+    match val_expr(&hir, "x") {
+        Expr::BuiltinCall {
+            fun: BIF::Floor, ..
+        } => (),
+        expr => panic!("expected floor to be left, got {:?}", expr),
+    }
+    match val_expr(&hir, "y") {
+        Expr::BuiltinCall {
+            fun: BIF::Trunc, ..
+        } => (),
+        expr => panic!("expected trunc to be left, got {:?}", expr),
+    }
+}
+
+#[test]
+fn const_fold_sqrt_and_pow() {
+    let input = r#"
+val s = _builtincall "sqrt"(4.0)
+val p = _builtincall "pow"(2.0, 10.0)
+"#;
+    let hir = apply(ConstFold::new(), to_hir(input).unwrap());
+    for &(name, expected) in &[("s", 2.0), ("p", 1024.0)] {
+        match val_expr(&hir, name) {
+            Expr::Lit {
+                value: Literal::Real(v),
+                ..
+            } if *v == expected => (),
+            expr => panic!("expected {} for {}, got {:?}", expected, name, expr),
+        }
+    }
+}
+
+#[test]
+fn const_fold_leaves_inexact_pow() {
+    let input = r#"val p = _builtincall "pow"(2.0, 0.5)"#;
+    let hir = apply(ConstFold::new(), to_hir(input).unwrap());
+    match val_expr(&hir, "p") {
+        Expr::BuiltinCall { fun: BIF::Pow, .. } => (),
+        expr => panic!("expected pow to be left, got {:?}", expr),
+    }
+}
+
+#[test]
+fn const_fold_modf() {
+    let input = r#"
+val x = _builtincall "modf"(5.5, 2.0)
+val y = _builtincall "modf"(_builtincall "neg"(5.5), 2.0)
+val z = _builtincall "modf"(5.5, 0.0)
+val a = _builtincall "modf"(5.0, 2.0)
+val b = _builtincall "modf"(7.0, 2.0)
+val c = _builtincall "modf"(1.0e300, 7.0)
+"#;
+    let hir = apply(ConstFold::new(), to_hir(input).unwrap());
+    // the quotient is rounded to nearest with the ties to even, as IEEE `remainder` does
+    for &(name, expected) in &[("x", -0.5), ("y", 0.5), ("a", 1.0), ("b", -1.0), ("c", 1.0)] {
+        match val_expr(&hir, name) {
+            Expr::Lit {
+                value: Literal::Real(v),
+                ..
+            } if *v == expected => (),
+            expr => panic!("expected {} for {}, got {:?}", expected, name, expr),
+        }
+    }
+    // unlike `mod`, a zero divisor gives NaN instead of trapping
+    match val_expr(&hir, "z") {
+        Expr::Lit {
+            value: Literal::Real(v),
+            ..
+        } if v.is_nan() => (),
+        expr => panic!("expected NaN, got {:?}", expr),
+    }
+}
+
+#[test]
+fn const_fold_ord_chr() {
+    let input = r#"
+val o = _builtincall "ord"(#"A")
+val c = _builtincall "chr"(65)
+val n = _builtincall "chr"(256)
+"#;
+    let hir = apply(ConstFold::new(), to_hir(input).unwrap());
+    match val_expr(&hir, "o") {
+        Expr::Lit {
+            value: Literal::Int(65),
+            ..
+        } => (),
+        expr => panic!("expected 65, got {:?}", expr),
+    }
+    match val_expr(&hir, "c") {
+        Expr::Lit {
+            value: Literal::Char(65),
+            ..
+        } => (),
+        expr => panic!("expected #\"A\", got {:?}", expr),
+    }
+    match val_expr(&hir, "n") {
+        Expr::BuiltinCall { fun: BIF::Chr, .. } => (),
+        expr => panic!("expected chr to be left, got {:?}", expr),
+    }
+}
+
+#[test]
+fn const_fold_bitwise() {
+    let input = r#"
+val a = _builtincall "andb"(12, 10)
+val o = _builtincall "orb"(12, 10)
+val x = _builtincall "xorb"(12, 10)
+val n = _builtincall "notb"(0)
+"#;
+    let hir = apply(ConstFold::new(), to_hir(input).unwrap());
+    for &(name, expected) in &[("a", 8), ("o", 14), ("x", 6), ("n", -1)] {
+        match val_expr(&hir, name) {
+            Expr::Lit {
+                value: Literal::Int(v),
+                ..
+            } if *v == expected => (),
+            expr => panic!("expected {} for {}, got {:?}", expected, name, expr),
+        }
+    }
+}
+
+#[test]
+fn unused_binds_are_removed() {
+    let input = r#"
+val x = let
+  val y = 1
+  val z = (y, y)
+in
+  2
+end
+"#;
+    let hir = apply(DeadBindElim::new(), to_hir(input).unwrap());
+    match val_expr(&hir, "x") {
+        Expr::Lit {
+            value: Literal::Int(2),
+            ..
+        } => (),
+        expr => panic!("expected 2, got {:?}", expr),
+    }
+}
+
+#[test]
+fn unused_extern_call_is_kept() {
+    let input = r#"
+val x = let
+  val y = _externcall("js-ffi"."print": (int) -> unit)(1)
+in
+  2
+end
+"#;
+    let hir = apply(DeadBindElim::new(), to_hir(input).unwrap());
+    match val_expr(&hir, "x") {
+        Expr::Binds { binds, .. } => {
+            assert_eq!(binds.len(), 1);
+            assert_eq!(binds[0].name.0, "y");
+        }
+        expr => panic!("expected binds, got {:?}", expr),
+    }
+}
+
+#[test]
+fn unused_chr_is_kept() {
+    // `chr` traps when the code is out of range
+    let input = r#"
+val x = let
+  val c = _builtincall "chr"(256)
+in
+  2
+end
+"#;
+    let hir = apply(DeadBindElim::new(), to_hir(input).unwrap());
+    match val_expr(&hir, "x") {
+        Expr::Binds { binds, .. } => assert_eq!(binds[0].name.0, "c"),
+        expr => panic!("expected binds, got {:?}", expr),
+    }
+}
+
+#[test]
+fn unused_real_to_int_is_kept() {
+    // the conversions trap on the values out of the int range
+    for fun in &["floor", "ceil", "round", "trunc"] {
+        let input = format!(
+            r#"
+val x = let
+  val n = _builtincall "{}"(1.0e10)
+in
+  2
+end
+"#,
+            fun
+        );
+        let hir = apply(DeadBindElim::new(), to_hir(&input).unwrap());
+        match val_expr(&hir, "x") {
+            Expr::Binds { binds, .. } => assert_eq!(binds[0].name.0, "n"),
+            expr => panic!("expected binds for {}, got {:?}", fun, expr),
+        }
+    }
+}
+
+#[test]
+fn extern_call_with_multiple_results_is_a_tuple() {
+    let input = r#"
+val p = _externcall("m"."divmod": (int, int) -> (int, int))(7, 2)
+val (q, r) = p
+"#;
+    let hir = to_hir(input).unwrap().1;
+    let expr = val_expr(&hir, "p");
+    match expr {
+        Expr::ExternCall { retty, .. } => assert_eq!(*retty, vec![HTy::Int, HTy::Int]),
+        expr => panic!("expected extern call, got {:?}", expr),
+    }
+    assert_eq!(expr.ty(), HTy::Tuple(vec![HTy::Int, HTy::Int]));
+}
+
+fn binds_ret(expr: &Expr) -> &Expr {
+    match expr {
+        Expr::Binds { ret, .. } => ret,
+        expr => panic!("expected binds, got {:?}", expr),
+    }
+}
+
+#[test]
+fn inline_substitutes_argument() {
+    let input = r#"
+val x = let
+  val f = fn y => _builtincall "add"(y, 1)
+in
+  f 2
+end
+"#;
+    let hir = apply(Inline::new(Id::new()), to_hir(input).unwrap());
+    match binds_ret(val_expr(&hir, "x")) {
+        Expr::BuiltinCall {
+            fun: BIF::Add,
+            args,
+            ..
+        } => match args.as_slice() {
+            [Expr::Lit {
+                value: Literal::Int(2),
+                ..
+            }, Expr::Lit {
+                value: Literal::Int(1),
+                ..
+            }] => (),
+            args => panic!("expected 2 and 1, got {:?}", args),
+        },
+        expr => panic!("expected inlined body, got {:?}", expr),
+    }
+}
+
+#[test]
+fn inline_threshold() {
+    let input = r#"
+val x = let
+  val f = fn y => (y, y)
+in
+  f 2
+end
+"#;
+    let hir = apply(Inline::new(Id::new()), to_hir(input).unwrap());
+    match binds_ret(val_expr(&hir, "x")) {
+        Expr::App { .. } => (),
+        expr => panic!("expected application, got {:?}", expr),
+    }
+
+    let config = Config {
+        inline_threshold: 10,
+        ..Config::default()
+    };
+    let hir = apply_with(Inline::new(Id::new()), to_hir(input).unwrap(), &config);
+    match binds_ret(val_expr(&hir, "x")) {
+        Expr::Tuple { .. } => (),
+        expr => panic!("expected inlined body, got {:?}", expr),
+    }
+}
+
+#[test]
+fn recursive_function_is_not_inlined() {
+    let input = r#"
+val x = let
+  fun f n = case n of 0 => 0 | _ => f (_builtincall "sub"(n, 1))
+in
+  f 10
+end
+"#;
+    let hir = apply(Inline::new(Id::new()), to_hir(input).unwrap());
+    match binds_ret(val_expr(&hir, "x")) {
+        Expr::App { .. } => (),
+        expr => panic!("expected application, got {:?}", expr),
+    }
+}
+
+// the names of the called functions with their tail-ness
+struct Calls(Vec<(String, bool)>);
+
+impl Traverse for Calls {
+    fn traverse_app(
+        &mut self,
+        _ty: &mut HTy,
+        fun: &mut Box<Expr>,
+        arg: &mut Box<Expr>,
+        is_tail: &mut bool,
+    ) {
+        if let Expr::Sym { name, .. } = &**fun {
+            self.0.push((name.0.to_string(), *is_tail))
+        }
+        self.traverse_expr(fun);
+        self.traverse_expr(arg);
+    }
+}
+
+#[test]
+fn tail_calls_are_marked() {
+    let input = r#"
+fun loop n = case n of 0 => 0 | _ => loop (_builtincall "sub"(n, 1))
+fun g n = _builtincall "add"(loop n, 1)
+"#;
+    let mut hir = apply(MarkTail::new(), to_hir(input).unwrap());
+    let mut calls = |name: &str| {
+        let val = hir.0.iter_mut().find(|val| val.name.0 == name).unwrap();
+        let mut calls = Calls(Vec::new());
+        calls.traverse_val(val);
+        calls.0
+    };
+    assert_eq!(calls("loop"), vec![("loop".to_string(), true)]);
+    assert_eq!(calls("g"), vec![("loop".to_string(), false)]);
+}
+
+#[test]
+fn beta_reduce_substitutes_simple_argument() {
+    let input = r#"val x = (fn y => _builtincall "add"(y, 1)) 2"#;
+    let hir = apply(BetaReduce::new(), to_hir(input).unwrap());
+    let expr = val_expr(&hir, "x");
+    assert_eq!(expr.ty(), HTy::Int);
+    match expr {
+        Expr::BuiltinCall { args, .. } => match args.as_slice() {
+            [Expr::Lit {
+                value: Literal::Int(2),
+                ..
+            }, Expr::Lit {
+                value: Literal::Int(1),
+                ..
+            }] => (),
+            args => panic!("expected 2 and 1, got {:?}", args),
+        },
+        expr => panic!("expected reduced body, got {:?}", expr),
+    }
+}
+
+#[test]
+fn beta_reduce_binds_argument() {
+    let input = r#"val x = (fn y => (y, y)) (_builtincall "add"(1, 2))"#;
+    let hir = apply(BetaReduce::new(), to_hir(input).unwrap());
+    let expr = val_expr(&hir, "x");
+    assert_eq!(expr.ty(), HTy::Tuple(vec![HTy::Int, HTy::Int]));
+    match expr {
+        Expr::Binds { binds, ret, .. } => {
+            assert_eq!(binds.len(), 1);
+            assert_eq!(binds[0].name.0, "y");
+            match &**ret {
+                Expr::Tuple { .. } => (),
+                ret => panic!("expected tuple, got {:?}", ret),
+            }
+        }
+        expr => panic!("expected binds, got {:?}", expr),
+    }
+}
+
+fn flatten(hir: (SymbolTable, HIR)) -> (SymbolTable, HIR) {
+    let symbol_table = hir.0.clone();
+    let hir = apply(FlatExpr::new(Id::new()), hir);
+    let hir = apply(FlatLet::new(), (symbol_table.clone(), hir));
+    (symbol_table, hir)
+}
+
+// the number of the calls to `fun` and to external functions
+struct CountCalls {
+    fun: BIF,
+    builtin: usize,
+    extern_: usize,
+}
+
+impl CountCalls {
+    fn count(fun: BIF, expr: &Expr) -> (usize, usize) {
+        let mut count = CountCalls {
+            fun,
+            builtin: 0,
+            extern_: 0,
+        };
+        count.traverse_expr(&mut expr.clone());
+        (count.builtin, count.extern_)
+    }
+}
+
+impl Traverse for CountCalls {
+    fn traverse_builtin_call(&mut self, _ty: &mut HTy, fun: &mut BIF, args: &mut Vec<Expr>) {
+        if *fun == self.fun {
+            self.builtin += 1
+        }
+        for arg in args {
+            self.traverse_expr(arg)
+        }
+    }
+
+    fn traverse_extern_call(
+        &mut self,
+        _retty: &mut Vec<HTy>,
+        _module: &mut String,
+        _fun: &mut String,
+        args: &mut Vec<Expr>,
+    ) {
+        self.extern_ += 1;
+        for arg in args {
+            self.traverse_expr(arg)
+        }
+    }
+}
+
+#[test]
+fn cse_shares_repeated_computation() {
+    let input = r#"
+val x = let
+  val a = 2
+  val b = 3
+in
+  (_builtincall "mul"(a, b), _builtincall "mul"(a, b))
+end
+"#;
+    let hir = apply(CSE::new(), flatten(to_hir(input).unwrap()));
+    assert_eq!(CountCalls::count(BIF::Mul, val_expr(&hir, "x")), (1, 0));
+}
+
+#[test]
+fn cse_keeps_extern_calls() {
+    let input = r#"
+val x = (
+  _externcall("js-ffi"."print": (int) -> unit)(1),
+  _externcall("js-ffi"."print": (int) -> unit)(1)
+)
+"#;
+    let hir = apply(CSE::new(), flatten(to_hir(input).unwrap()));
+    assert_eq!(CountCalls::count(BIF::Mul, val_expr(&hir, "x")), (0, 2));
+}
+
+// the value bound to the symbol `expr` refers to
+fn bound_value<'h>(binds: &'h [Val], expr: &Expr) -> &'h Expr {
+    match expr {
+        Expr::Sym { name, .. } => {
+            &binds
+                .iter()
+                .find(|val| &val.name == name)
+                .expect("unbound symbol")
+                .expr
+        }
+        expr => panic!("expected symbol, got {:?}", expr),
+    }
+}
+
+#[test]
+fn copy_prop_collapses_chain() {
+    let input = r#"
+val x = let
+  val b = 1
+  val a = b
+  val c = a
+in
+  c
+end
+"#;
+    let hir = apply(CopyProp::new(), to_hir(input).unwrap());
+    match val_expr(&hir, "x") {
+        Expr::Binds { binds, ret, .. } => {
+            assert_eq!(binds.len(), 1);
+            match &**ret {
+                Expr::Sym { name, .. } => assert_eq!(name.0, "b"),
+                ret => panic!("expected b, got {:?}", ret),
+            }
+        }
+        expr => panic!("expected binds, got {:?}", expr),
+    }
+}
+
+#[test]
+fn copy_prop_respects_shadowing() {
+    let input = r#"
+val x = let
+  val a = 1
+  val b = a
+  val a = 2
+in
+  (a, b)
+end
+"#;
+    let hir = apply(CopyProp::new(), to_hir(input).unwrap());
+    match val_expr(&hir, "x") {
+        Expr::Binds { binds, ret, .. } => match &**ret {
+            Expr::Tuple { tuple, .. } => {
+                let values = tuple
+                    .iter()
+                    .map(|t| bound_value(binds, t).clone())
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    values,
+                    vec![
+                        Expr::Lit {
+                            ty: HTy::Int,
+                            value: Literal::Int(2)
+                        },
+                        Expr::Lit {
+                            ty: HTy::Int,
+                            value: Literal::Int(1)
+                        },
+                    ]
+                );
+            }
+            ret => panic!("expected tuple, got {:?}", ret),
+        },
+        expr => panic!("expected binds, got {:?}", expr),
+    }
+}
+
+// the captures of the lifted anonymous functions
+fn anonfun_captures(hir: &HIR) -> Vec<Vec<String>> {
+    hir.0
+        .iter()
+        .filter_map(|val| match &val.expr {
+            Expr::Fun { captures, .. } if val.name.0 == "<anonfun>" => Some(
+                captures
+                    .iter()
+                    .map(|(_, name)| name.0.to_string())
+                    .collect(),
+            ),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn unused_captures_are_pruned() {
+    let input = r#"
+val x = let
+  val a = 1
+  val b = 2
+in
+  fn y => let val unused = a in (b, y) end
+end
+"#;
+    let hir = flatten(to_hir(input).unwrap());
+    let symbol_table = hir.0.clone();
+    let hir = apply(UnnestFunc::new(Id::new()), hir);
+    let hir = apply(ForceClosure::new(), (symbol_table.clone(), hir));
+    let hir = apply(DeadBindElim::new(), (symbol_table.clone(), hir));
+    assert_eq!(
+        anonfun_captures(&hir),
+        vec![vec!["a".to_string(), "b".to_string()]]
+    );
+
+    let mut hir = apply(PruneCaptures::new(), (symbol_table, hir));
+    assert_eq!(anonfun_captures(&hir), vec![vec!["b".to_string()]]);
+    let mut envs = ClosureEnvs(Vec::new());
+    envs.traverse_hir(&mut hir);
+    assert_eq!(envs.0, vec![vec!["b".to_string()]]);
+}
+
+// the environments of the closures
+struct ClosureEnvs(Vec<Vec<String>>);
+
+impl Traverse for ClosureEnvs {
+    fn traverse_closure(
+        &mut self,
+        envs: &mut Vec<(HTy, Symbol)>,
+        _param_ty: &mut HTy,
+        _body_ty: &mut HTy,
+        _fname: &mut Symbol,
+    ) {
+        self.0
+            .push(envs.iter().map(|(_, name)| name.0.to_string()).collect())
+    }
+}
+
+// `val x = expr`
+fn hir_of(expr: Expr) -> (SymbolTable, HIR) {
+    let symbol_table = SymbolTable {
+        types: Default::default(),
+        exports: Default::default(),
+    };
+    let val = Val {
+        ty: expr.ty(),
+        rec: false,
+        name: Symbol::new("x"),
+        expr,
+    };
+    (symbol_table, HIR(vec![val]))
+}
+
+fn int(value: i64) -> Expr {
+    Expr::Lit {
+        ty: HTy::Int,
+        value: Literal::Int(value),
+    }
+}
+
+fn print(arg: Expr) -> Expr {
+    Expr::ExternCall {
+        retty: vec![HTy::Unit],
+        module: "js-ffi".into(),
+        fun: "print".into(),
+        args: vec![arg],
+    }
+}
+
+#[test]
+fn if_of_bool_literal_is_simplified() {
+    let input = r#"
+val x = if true then 1 else 2
+val y = if false then 1 else 2
+"#;
+    let hir = apply(Simplify::new(Id::new()), to_hir(input).unwrap());
+    assert_eq!(val_expr(&hir, "x"), &int(1));
+    assert_eq!(val_expr(&hir, "y"), &int(2));
+}
+
+#[test]
+fn proj_of_tuple_is_simplified() {
+    let expr = Expr::Proj {
+        ty: HTy::Int,
+        index: 1,
+        tuple: Box::new(Expr::Tuple {
+            tys: vec![HTy::Int, HTy::Int],
+            tuple: vec![int(1), int(2)],
+        }),
+    };
+    let hir = apply(Simplify::new(Id::new()), hir_of(expr));
+    assert_eq!(val_expr(&hir, "x"), &int(2));
+}
+
+#[test]
+fn proj_of_tuple_keeps_effects() {
+    let expr = Expr::Proj {
+        ty: HTy::Int,
+        index: 0,
+        tuple: Box::new(Expr::Tuple {
+            tys: vec![HTy::Int, HTy::Unit],
+            tuple: vec![int(1), print(int(2))],
+        }),
+    };
+    let hir = apply(Simplify::new(Id::new()), hir_of(expr));
+    match val_expr(&hir, "x") {
+        Expr::Binds { binds, ret, .. } => {
+            assert_eq!(
+                binds.iter().map(|val| &val.expr).collect::<Vec<_>>(),
+                vec![&int(1), &print(int(2))]
+            );
+            assert_eq!(bound_value(binds, ret), &int(1));
+            assert_eq!(ret.ty(), HTy::Int);
+        }
+        expr => panic!("expected binds, got {:?}", expr),
+    }
+}
+
+#[test]
+fn case_of_known_constructor() {
+    let input = r#"
+datatype option = NONE | SOME of int
+val x = case SOME 3 of SOME x => x | NONE => 0
+"#;
+    let hir = to_hir(input).unwrap();
+    let symbol_table = hir.0.clone();
+    let hir = apply(Simplify::new(Id::new()), hir);
+    let hir = apply(DeadBindElim::new(), (symbol_table, hir));
+    assert_eq!(val_expr(&hir, "x"), &int(3));
+}
+
+#[test]
+fn case_of_known_literal_respects_arm_order() {
+    let input = r#"
+val x = case 1 of 0 => 10 | y => y | 1 => 20
+"#;
+    let hir = to_hir(input).unwrap();
+    let symbol_table = hir.0.clone();
+    let hir = apply(Simplify::new(Id::new()), hir);
+    let hir = apply(DeadBindElim::new(), (symbol_table, hir));
+    assert_eq!(val_expr(&hir, "x"), &int(1));
+}
+
+#[test]
+fn case_of_tuple_keeps_effects_of_dropped_components() {
+    // `case (print 2, NONE, 1) of (_, NONE, y) => y`
+    let ty = HTy::Datatype(Symbol("t".into(), 0), vec![]);
+    let tys = vec![HTy::Unit, ty.clone(), HTy::Int];
+    let expr = case(
+        HTy::Int,
+        Expr::Tuple {
+            tys: tys.clone(),
+            tuple: vec![
+                print(int(2)),
+                Expr::Constructor {
+                    ty: ty.clone(),
+                    arg: None,
+                    descriminant: 0,
+                },
+                int(1),
+            ],
+        },
+        vec![(
+            Pattern::Tuple {
+                tys,
+                tuple: vec![
+                    Pattern::Wildcard { ty: HTy::Unit },
+                    Pattern::Constructor {
+                        descriminant: 0,
+                        arg: None,
+                        ty,
+                    },
+                    Pattern::Var {
+                        name: Symbol::new("y"),
+                        ty: HTy::Int,
+                    },
+                ],
+            },
+            Expr::Sym {
+                ty: HTy::Int,
+                name: Symbol::new("y"),
+            },
+        )],
+    );
+    let hir = apply(Simplify::new(Id::new()), hir_of(expr));
+    match val_expr(&hir, "x") {
+        Expr::Binds { binds, ret, .. } => {
+            assert_eq!(
+                binds.iter().map(|val| &val.expr).collect::<Vec<_>>(),
+                vec![&print(int(2))]
+            );
+            assert_eq!(**ret, int(1));
+        }
+        expr => panic!("expected binds, got {:?}", expr),
+    }
+}
+
+fn proj(index: u32, tuple: Expr) -> Expr {
+    let ty = match tuple.ty() {
+        HTy::Tuple(tys) => tys[index as usize].clone(),
+        ty => panic!("projection from {:?}", ty),
+    };
+    Expr::Proj {
+        ty,
+        index,
+        tuple: Box::new(tuple),
+    }
+}
+
+fn tuple(tuple: Vec<Expr>) -> Expr {
+    Expr::Tuple {
+        tys: tuple.iter().map(Expr::ty).collect(),
+        tuple,
+    }
+}
+
+// `let val t = (1, (2, 3), 4) in ret t end`
+fn nested_tuple(ret: impl FnOnce(Expr) -> Expr) -> Expr {
+    let t = tuple(vec![int(1), tuple(vec![int(2), int(3)]), int(4)]);
+    let sym = Expr::Sym {
+        ty: t.ty(),
+        name: Symbol("t".into(), 1),
+    };
+    let ret = ret(sym);
+    Expr::Binds {
+        ty: ret.ty(),
+        binds: vec![Val {
+            ty: t.ty(),
+            rec: false,
+            name: Symbol("t".into(), 1),
+            expr: t,
+        }],
+        ret: Box::new(ret),
+    }
+}
+
+#[test]
+fn nested_tuple_is_flattened() {
+    let expr = nested_tuple(|t| tuple(vec![proj(1, proj(1, t.clone())), proj(2, t)]));
+    let hir = apply(FlatTuple::new(), hir_of(expr));
+    match val_expr(&hir, "x") {
+        Expr::Binds { binds, ret, .. } => {
+            let flat = tuple(vec![int(1), int(2), int(3), int(4)]);
+            assert_eq!(binds[0].ty, flat.ty());
+            assert_eq!(binds[0].expr, flat);
+            let t = Expr::Sym {
+                ty: flat.ty(),
+                name: Symbol("t".into(), 1),
+            };
+            assert_eq!(**ret, tuple(vec![proj(2, t.clone()), proj(3, t)]));
+        }
+        expr => panic!("expected binds, got {:?}", expr),
+    }
+}
+
+#[test]
+fn tuple_used_as_whole_is_not_flattened() {
+    let expr = nested_tuple(|t| tuple(vec![proj(1, t.clone()), proj(2, t)]));
+    let hir = apply(FlatTuple::new(), hir_of(expr.clone()));
+    assert_eq!(val_expr(&hir, "x"), &expr);
+
+    let expr = nested_tuple(|t| t);
+    let hir = apply(FlatTuple::new(), hir_of(expr.clone()));
+    assert_eq!(val_expr(&hir, "x"), &expr);
+}
+
+#[test]
+fn nested_binds_are_merged() {
+    let input = r#"
+val x = let val a = 1 in let val b = 2 in _builtincall "add"(a, b) end end
+"#;
+    let hir = apply(MergeBinds::new(Id::new()), to_hir(input).unwrap());
+    match val_expr(&hir, "x") {
+        Expr::Binds { binds, ret, .. } => {
+            assert_eq!(
+                binds.iter().map(|val| &val.expr).collect::<Vec<_>>(),
+                vec![&int(1), &int(2)]
+            );
+            match &**ret {
+                Expr::BuiltinCall { .. } => (),
+                expr => panic!("expected builtin call, got {:?}", expr),
+            }
+        }
+        expr => panic!("expected binds, got {:?}", expr),
+    }
+}
+
+#[test]
+fn shadowing_binds_are_renamed() {
+    let sym = |name: &str| Expr::Sym {
+        ty: HTy::Int,
+        name: Symbol(name.into(), 0),
+    };
+    let val = |name: &str, expr| Val {
+        ty: HTy::Int,
+        rec: false,
+        name: Symbol(name.into(), 0),
+        expr,
+    };
+    // let val y = 1 in let val z = y val y = 2 in (y, z) end end
+    let expr = Expr::Binds {
+        ty: HTy::Tuple(vec![HTy::Int, HTy::Int]),
+        binds: vec![val("y", int(1))],
+        ret: Box::new(Expr::Binds {
+            ty: HTy::Tuple(vec![HTy::Int, HTy::Int]),
+            binds: vec![val("z", sym("y")), val("y", int(2))],
+            ret: Box::new(tuple(vec![sym("y"), sym("z")])),
+        }),
+    };
+    let hir = apply(MergeBinds::new(Id::new()), hir_of(expr));
+    match val_expr(&hir, "x") {
+        Expr::Binds { binds, ret, .. } => {
+            assert_eq!(binds.len(), 3);
+            assert_eq!(binds[1].expr, sym("y"));
+            let renamed = binds[2].name.clone();
+            assert_ne!(renamed, Symbol("y".into(), 0));
+            assert_eq!(binds[2].expr, int(2));
+            let y = Expr::Sym {
+                ty: HTy::Int,
+                name: renamed,
+            };
+            assert_eq!(**ret, tuple(vec![y, sym("z")]));
+        }
+        expr => panic!("expected binds, got {:?}", expr),
+    }
+}
+
+#[test]
+fn eta_reducible_wrapper() {
+    let input = r#"
+val g = fn y => _builtincall "add"(y, 1)
+val f = fn z => g z
+"#;
+    let hir = apply(Simplify::new(Id::new()), to_hir(input).unwrap());
+    match val_expr(&hir, "f") {
+        Expr::Sym { name, .. } => assert_eq!(name.0, "g"),
+        expr => panic!("expected sym, got {:?}", expr),
+    }
+}
+
+#[test]
+fn eta_keeps_escaping_parameter() {
+    let input = r#"
+val h = fn y => fn w => _builtincall "add"(y, w)
+val f = fn z => h z z
+"#;
+    let hir = apply(Simplify::new(Id::new()), to_hir(input).unwrap());
+    match val_expr(&hir, "f") {
+        Expr::Fun { .. } => (),
+        expr => panic!("expected fun, got {:?}", expr),
+    }
+}
+
+fn fun_body(expr: &Expr) -> &Expr {
+    match expr {
+        Expr::Fun { body, .. } => body,
+        expr => panic!("expected fun, got {:?}", expr),
+    }
+}
+
+#[test]
+fn mul_by_power_of_two_is_shift() {
+    let input = r#"val x = fn y => _builtincall "mul"(y, 8)"#;
+    let hir = apply(StrengthReduce::new(), to_hir(input).unwrap());
+    match fun_body(val_expr(&hir, "x")) {
+        Expr::BuiltinCall { fun, args, .. } => {
+            assert_eq!(*fun, BIF::Shl);
+            assert_eq!(args[1], int(3));
+        }
+        expr => panic!("expected builtin call, got {:?}", expr),
+    }
+}
+
+#[test]
+fn mul_by_non_power_of_two_is_kept() {
+    let input = r#"val x = fn y => _builtincall "mul"(y, 3)"#;
+    let hir = to_hir(input).unwrap();
+    let expected = hir.1.clone();
+    let hir = apply(StrengthReduce::new(), hir);
+    assert_eq!(hir.0, expected.0);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Int(i64),
+    Str(String),
+    Con(u32, Option<Box<Value>>),
+    Tuple(Vec<Value>),
+    Fun(Symbol, Expr),
+}
+
+// `false` and `true` are the constructors 0 and 1 of bool
+fn bool_value(value: bool) -> Value {
+    Value::Con(value as u32, None)
+}
+
+// binds the names in `pat` to the parts of `value` if it matches
+fn matches(pat: &Pattern, value: &Value, env: &mut HashMap<Symbol, Value>) -> bool {
+    match (pat, value) {
+        (Pattern::Var { name, .. }, value) => {
+            env.insert(name.clone(), value.clone());
+            true
+        }
+        (Pattern::Wildcard { .. }, _) => true,
+        (Pattern::Tuple { tuple, .. }, Value::Tuple(values)) => tuple
+            .iter()
+            .zip(values)
+            .all(|(pat, value)| matches(pat, value, env)),
+        (Pattern::Constant { value: c, .. }, Value::Int(i)) => c == i,
+        (
+            Pattern::Constructor {
+                descriminant, arg, ..
+            },
+            Value::Con(d, v),
+        ) if descriminant == d => match (arg, v) {
+            (Some(arg), Some(v)) => matches(arg, v, env),
+            _ => true,
+        },
+        _ => false,
+    }
+}
+
+// evaluates a subset of HIR with the arms of `case` tried in order.
+// the functions see the names in scope when they are called, which is enough for the closed ones
+fn eval(expr: &Expr, env: &mut HashMap<Symbol, Value>) -> Value {
+    match expr {
+        Expr::Lit {
+            value: Literal::Int(i),
+            ..
+        } => Value::Int(*i),
+        Expr::Lit {
+            value: Literal::String(s),
+            ..
+        } => Value::Str(s.clone()),
+        Expr::Fun {
+            param: (_, param),
+            body,
+            ..
+        } => Value::Fun(param.clone(), (**body).clone()),
+        Expr::App { fun, arg, .. } => match eval(fun, env) {
+            Value::Fun(param, body) => {
+                let arg = eval(arg, env);
+                let mut env = env.clone();
+                env.insert(param, arg);
+                eval(&body, &mut env)
+            }
+            value => panic!("applying non-function {:?}", value),
+        },
+        Expr::Proj { index, tuple, .. } => match eval(tuple, env) {
+            Value::Tuple(mut values) => values.swap_remove(*index as usize),
+            value => panic!("projecting non-tuple {:?}", value),
+        },
+        Expr::BuiltinCall {
+            fun: fun @ BIF::Eq,
+            args,
+            ..
+        }
+        | Expr::BuiltinCall {
+            fun: fun @ BIF::Neq,
+            args,
+            ..
+        } => {
+            let (l, r) = (eval(&args[0], env), eval(&args[1], env));
+            bool_value((l == r) == (*fun == BIF::Eq))
+        }
+        Expr::ExternCall { fun, args, .. } if fun == "string_eq" => {
+            bool_value(eval(&args[0], env) == eval(&args[1], env))
+        }
+        Expr::Sym { name, .. } => env[name].clone(),
+        Expr::Binds { binds, ret, .. } => {
+            for val in binds {
+                let value = eval(&val.expr, env);
+                env.insert(val.name.clone(), value);
+            }
+            eval(ret, env)
+        }
+        Expr::Constructor {
+            descriminant, arg, ..
+        } => Value::Con(
+            *descriminant,
+            arg.as_ref().map(|arg| Box::new(eval(arg, env))),
+        ),
+        Expr::Tuple { tuple, .. } => {
+            Value::Tuple(tuple.iter().map(|expr| eval(expr, env)).collect())
+        }
+        Expr::Case { expr, arms, .. } => {
+            let value = eval(expr, env);
+            for (pat, arm) in arms {
+                if matches(pat, &value, env) {
+                    return eval(arm, env);
+                }
+            }
+            panic!("match failure")
+        }
+        expr => panic!("cannot evaluate {:?}", expr),
+    }
+}
+
+fn eval_x(hir: &HIR) -> Value {
+    eval(val_expr(hir, "x"), &mut HashMap::new())
+}
+
+// the last value named `name`, after evaluating the top level values before it in order
+fn eval_val(hir: &HIR, name: &str) -> Value {
+    let mut env = HashMap::new();
+    let mut ret = None;
+    for val in &hir.0 {
+        let value = eval(&val.expr, &mut env);
+        if val.name.0 == name {
+            ret = Some(value.clone());
+        }
+        env.insert(val.name.clone(), value);
+    }
+    ret.expect("no such val")
+}
+
+#[test]
+fn structural_equality_is_compared_by_components() {
+    let input = r#"
+infix 4 = <>
+datatype tree = Leaf | Node of tree * int * tree
+val a = (1, "ab") = (1, "ab")
+val b = (1, "ab") = (1, "ba")
+val c = Node (Leaf, 1, Node (Leaf, 2, Leaf)) = Node (Leaf, 1, Node (Leaf, 2, Leaf))
+val d = Node (Leaf, 1, Leaf) = Node (Leaf, 1, Node (Leaf, 2, Leaf))
+val e = Node (Leaf, 1, Leaf) <> Node (Leaf, 2, Leaf)
+val f = (true, ()) = (false, ())
+val g = [1, 2] = [1, 2]
+val h = [1] = [1, 2]
+"#;
+    let (_, hir) = to_hir(input).unwrap();
+    for (name, expected) in &[
+        ("a", true),
+        ("b", false),
+        ("c", true),
+        ("d", false),
+        ("e", true),
+        ("f", false),
+        ("g", true),
+        ("h", false),
+    ] {
+        assert_eq!(eval_val(&hir, name), bool_value(*expected), "{}", name);
+    }
+}
+
+fn case(ty: HTy, expr: Expr, arms: Vec<(Pattern, Expr)>) -> Expr {
+    Expr::Case {
+        ty,
+        expr: Box::new(expr),
+        arms,
+    }
+}
+
+fn arm_keys(hir: &HIR) -> Vec<Option<i64>> {
+    let mut expr = val_expr(hir, "x");
+    while let Expr::Binds { ret, .. } = expr {
+        expr = &**ret;
+    }
+    match expr {
+        Expr::Case { arms, .. } => arms
+            .iter()
+            .map(|(pat, _)| {
+                if pat.is_irrefutable() {
+                    None
+                } else {
+                    Some(pat.match_key())
+                }
+            })
+            .collect(),
+        expr => panic!("expected case, got {:?}", expr),
+    }
+}
+
+#[test]
+fn int_match_to_decision_tree() {
+    let constant = |value| Pattern::Constant {
+        value,
+        ty: HTy::Int,
+    };
+    let y = Symbol("y".into(), 0);
+    for i in -1..5 {
+        let expr = case(
+            HTy::Int,
+            int(i),
+            vec![
+                (constant(3), int(30)),
+                (constant(1), int(10)),
+                (constant(3), int(300)),
+                (
+                    Pattern::Var {
+                        name: y.clone(),
+                        ty: HTy::Int,
+                    },
+                    Expr::Sym {
+                        ty: HTy::Int,
+                        name: y.clone(),
+                    },
+                ),
+                (constant(2), int(20)),
+            ],
+        );
+        let before = eval_x(&hir_of(expr.clone()).1);
+        let hir = apply(DecisionTree::new(Id::new()), hir_of(expr));
+        assert_eq!(eval_x(&hir), before);
+        assert_eq!(arm_keys(&hir), vec![Some(1), Some(3), None]);
+    }
+}
+
+#[test]
+fn int_match_keys_above_32_bits() {
+    let constant = |value| Pattern::Constant {
+        value,
+        ty: HTy::Int,
+    };
+    let large = 1 << 32;
+    for &i in &[1, large + 1] {
+        let expr = case(
+            HTy::Int,
+            int(i),
+            vec![
+                (constant(large + 1), int(20)),
+                (constant(1), int(10)),
+                (Pattern::Wildcard { ty: HTy::Int }, int(0)),
+            ],
+        );
+        let before = eval_x(&hir_of(expr.clone()).1);
+        let hir = apply(DecisionTree::new(Id::new()), hir_of(expr));
+        assert_eq!(eval_x(&hir), before);
+        assert_eq!(arm_keys(&hir), vec![Some(1), Some(large + 1), None]);
+    }
+}
+
+#[test]
+fn constructor_match_to_decision_tree() {
+    let ty = HTy::Datatype(Symbol("t".into(), 0), vec![]);
+    let z = Symbol("z".into(), 0);
+    let w = Symbol("w".into(), 0);
+    let sym = |name: &Symbol| Expr::Sym {
+        ty: HTy::Int,
+        name: name.clone(),
+    };
+    let values = vec![(0, None), (1, Some(int(5))), (2, Some(int(6)))];
+    for (descriminant, arg) in values {
+        let value = Expr::Constructor {
+            ty: ty.clone(),
+            descriminant,
+            arg: arg.map(Box::new),
+        };
+        let expr = case(
+            HTy::Int,
+            value,
+            vec![
+                (
+                    Pattern::Constructor {
+                        descriminant: 1,
+                        arg: Some(Box::new(Pattern::Var {
+                            name: z.clone(),
+                            ty: HTy::Int,
+                        })),
+                        ty: ty.clone(),
+                    },
+                    sym(&z),
+                ),
+                (
+                    Pattern::Constructor {
+                        descriminant: 0,
+                        arg: None,
+                        ty: ty.clone(),
+                    },
+                    int(0),
+                ),
+                (
+                    Pattern::Constructor {
+                        descriminant: 1,
+                        arg: Some(Box::new(Pattern::Var {
+                            name: w.clone(),
+                            ty: HTy::Int,
+                        })),
+                        ty: ty.clone(),
+                    },
+                    int(100),
+                ),
+                (
+                    Pattern::Var {
+                        name: w.clone(),
+                        ty: ty.clone(),
+                    },
+                    int(7),
+                ),
+            ],
+        );
+        let before = eval_x(&hir_of(expr.clone()).1);
+        let hir = apply(DecisionTree::new(Id::new()), hir_of(expr));
+        assert_eq!(eval_x(&hir), before);
+        assert_eq!(arm_keys(&hir), vec![Some(0), Some(1), None]);
+    }
+}
+
+#[test]
+fn nested_constructor_match_to_decision_tree() {
+    // datatype t = NONE | SOME of int * int | CONS of int * t
+    let ty = HTy::Datatype(Symbol("t".into(), 0), vec![]);
+    let pair = HTy::Tuple(vec![HTy::Int, HTy::Int]);
+    let cons = HTy::Tuple(vec![HTy::Int, ty.clone()]);
+    let sym = |name: &str, ty: &HTy| Expr::Sym {
+        ty: ty.clone(),
+        name: Symbol(name.into(), 0),
+    };
+    let con = |descriminant, arg: Option<Expr>| Expr::Constructor {
+        ty: ty.clone(),
+        descriminant,
+        arg: arg.map(Box::new),
+    };
+    let tuple = |tys: &HTy, tuple| match tys {
+        HTy::Tuple(tys) => Expr::Tuple {
+            tys: tys.clone(),
+            tuple,
+        },
+        _ => unreachable!(),
+    };
+    let pat = |descriminant, arg: Option<Pattern>| Pattern::Constructor {
+        descriminant,
+        arg: arg.map(Box::new),
+        ty: ty.clone(),
+    };
+    let names = |tys: &HTy, names: &[&str]| match tys {
+        HTy::Tuple(tys) => Pattern::Tuple {
+            tys: tys.clone(),
+            tuple: names
+                .iter()
+                .zip(tys)
+                .map(|(name, ty)| Pattern::Var {
+                    name: Symbol((*name).into(), 0),
+                    ty: ty.clone(),
+                })
+                .collect(),
+        },
+        _ => unreachable!(),
+    };
+    let values = vec![
+        con(0, None),
+        con(1, Some(tuple(&pair, vec![int(1), int(2)]))),
+        con(2, Some(tuple(&cons, vec![int(3), con(0, None)]))),
+        con(
+            2,
+            Some(tuple(
+                &cons,
+                vec![
+                    int(4),
+                    con(2, Some(tuple(&cons, vec![int(5), con(0, None)]))),
+                ],
+            )),
+        ),
+    ];
+    for value in values {
+        // case v of SOME (a, b) => b | CONS (h, t) => h | y => 0
+        let expr = case(
+            HTy::Int,
+            value,
+            vec![
+                (pat(1, Some(names(&pair, &["a", "b"]))), sym("b", &HTy::Int)),
+                (pat(2, Some(names(&cons, &["h", "t"]))), sym("h", &HTy::Int)),
+                (
+                    Pattern::Var {
+                        name: Symbol("y".into(), 0),
+                        ty: ty.clone(),
+                    },
+                    int(0),
+                ),
+            ],
+        );
+        let before = eval_x(&hir_of(expr.clone()).1);
+        let hir = apply(DecisionTree::new(Id::new()), hir_of(expr));
+        assert_eq!(eval_x(&hir), before);
+        assert_eq!(arm_keys(&hir), vec![Some(1), Some(2), None]);
+    }
+}
+
+#[test]
+fn nested_tuple_match_to_decision_tree() {
+    // datatype t = NONE | SOME of int
+    let ty = HTy::Datatype(Symbol("t".into(), 0), vec![]);
+    let tys = vec![HTy::Int, ty.clone()];
+    let var = |name: &str, ty: &HTy| Pattern::Var {
+        name: Symbol(name.into(), 0),
+        ty: ty.clone(),
+    };
+    let sym = |name: &str| Expr::Sym {
+        ty: HTy::Int,
+        name: Symbol(name.into(), 0),
+    };
+    let pat = |descriminant, arg: Option<Pattern>| Pattern::Constructor {
+        descriminant,
+        arg: arg.map(Box::new),
+        ty: ty.clone(),
+    };
+    let tuple = |tuple| Pattern::Tuple {
+        tys: tys.clone(),
+        tuple,
+    };
+    let values = vec![
+        (
+            0,
+            Expr::Constructor {
+                ty: ty.clone(),
+                descriminant: 1,
+                arg: Some(Box::new(int(5))),
+            },
+        ),
+        (
+            1,
+            Expr::Constructor {
+                ty: ty.clone(),
+                descriminant: 1,
+                arg: Some(Box::new(int(5))),
+            },
+        ),
+        (
+            2,
+            Expr::Constructor {
+                ty: ty.clone(),
+                descriminant: 0,
+                arg: None,
+            },
+        ),
+    ];
+    for (i, value) in values {
+        // case (i, v) of (0, _) => 100 | (x, SOME y) => y | (x, NONE) => x
+        let expr = case(
+            HTy::Int,
+            Expr::Tuple {
+                tys: tys.clone(),
+                tuple: vec![int(i), value],
+            },
+            vec![
+                (
+                    tuple(vec![
+                        Pattern::Constant {
+                            value: 0,
+                            ty: HTy::Int,
+                        },
+                        Pattern::Wildcard { ty: ty.clone() },
+                    ]),
+                    int(100),
+                ),
+                (
+                    tuple(vec![var("x", &HTy::Int), pat(1, Some(var("y", &HTy::Int)))]),
+                    sym("y"),
+                ),
+                (tuple(vec![var("x", &HTy::Int), pat(0, None)]), sym("x")),
+            ],
+        );
+        let before = eval_x(&hir_of(expr.clone()).1);
+        let hir = apply(DecisionTree::new(Id::new()), hir_of(expr));
+        assert_eq!(eval_x(&hir), before);
+        // the tuple is taken apart once, by a flat pattern
+        assert_eq!(arm_keys(&hir), vec![None]);
+    }
+}
+
+#[test]
+fn string_match_to_equality_tests() {
+    let input = r#"val x = fn s => case s of "yes" => 1 | "no" => 0 | _ => 2"#;
+    let hir = apply(DecisionTree::new(Id::new()), to_hir(input).unwrap());
+    // a comparison for each string, in the order of the arms
+    assert_eq!(CountCalls::count(BIF::Eq, val_expr(&hir, "x")), (0, 2));
+    let mut expr = fun_body(val_expr(&hir, "x"));
+    while let Expr::Binds { ret, .. } = expr {
+        expr = &**ret;
+    }
+    match expr {
+        Expr::Case { expr, arms, .. } => {
+            match &**expr {
+                Expr::ExternCall { fun, args, .. } => {
+                    assert_eq!(fun, "string_eq");
+                    assert_eq!(
+                        args[1],
+                        Expr::Lit {
+                            ty: HTy::String,
+                            value: Literal::String("yes".into()),
+                        }
+                    );
+                }
+                expr => panic!("expected string comparison, got {:?}", expr),
+            }
+            assert_eq!(arms.len(), 2);
+        }
+        expr => panic!("expected case, got {:?}", expr),
+    }
+}
+
+#[test]
+fn string_match_of_literal_is_simplified() {
+    let input = r#"val x = case "no" of "yes" => 1 | "no" => 0 | _ => 2"#;
+    let hir = apply(Simplify::new(Id::new()), to_hir(input).unwrap());
+    let mut expr = val_expr(&hir, "x");
+    while let Expr::Binds { ret, .. } = expr {
+        expr = &**ret;
+    }
+    assert_eq!(expr, &int(0));
+}
+
+#[test]
+fn wildcard_arm_binds_nothing() {
+    let input = r#"val x = case 1 of 1 => 2 | _ => 3"#;
+    let hir = to_hir(input).unwrap().1;
+    let mut expr = val_expr(&hir, "x");
+    while let Expr::Binds { ret, .. } = expr {
+        expr = &**ret;
+    }
+    let default = match expr {
+        Expr::Case { arms, .. } => &arms.last().expect("no arms").0,
+        expr => panic!("expected case, got {:?}", expr),
+    };
+    match default {
+        Pattern::Wildcard { ty } => assert_eq!(*ty, HTy::Int),
+        pat => panic!("expected wildcard, got {:?}", pat),
+    }
+    assert!(default.binds().is_empty());
+    assert!(default.is_irrefutable());
+}
+
+fn pp_hir(hir: &HIR) -> String {
+    let mut buf = Vec::new();
+    hir.pp(&mut buf, 0).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+fn var(ty: HTy, name: &str, id: u64) -> Expr {
+    Expr::Sym {
+        ty,
+        name: Symbol(name.into(), id),
+    }
+}
+
+fn int_val(name: &str, id: u64, expr: Expr) -> Val {
+    Val {
+        ty: HTy::Int,
+        rec: false,
+        name: Symbol(name.into(), id),
+        expr,
+    }
+}
+
+#[test]
+fn pp_after_flat_let() {
+    let pair = HTy::Tuple(vec![HTy::Int, HTy::Int]);
+    let ret = Expr::Fun {
+        param: (HTy::Int, Symbol("y".into(), 4)),
+        body_ty: pair.clone(),
+        body: Box::new(Expr::Tuple {
+            tys: vec![HTy::Int, HTy::Int],
+            tuple: vec![var(HTy::Int, "b", 2), var(HTy::Int, "y", 4)],
+        }),
+        captures: vec![(HTy::Int, Symbol("b".into(), 2))],
+    };
+    let add = Expr::BuiltinCall {
+        ty: HTy::Int,
+        fun: BIF::Add,
+        args: vec![
+            Expr::Binds {
+                ty: HTy::Int,
+                binds: vec![int_val("c", 3, int(2))],
+                ret: Box::new(var(HTy::Int, "c", 3)),
+            },
+            var(HTy::Int, "a", 1),
+        ],
+    };
+    let expr = Expr::Binds {
+        ty: HTy::Fun(Box::new(HTy::Int), Box::new(pair)),
+        binds: vec![int_val("a", 1, int(1)), int_val("b", 2, add)],
+        ret: Box::new(ret),
+    };
+    let hir = apply(FlatLet::new(), hir_of(expr));
+    assert_eq!(
+        pp_hir(&hir),
+        r#"val x@0: int -> int * int = let
+  val a@1: int = 1
+  val c@3: int = 2
+  val b@2: int = add(c@3, a@1)
+in
+  fn (y@4: int) (* captures b@2 *) => (b@2, y@4)
+end
+"#
+    );
+}
+
+#[test]
+fn pp_after_force_closure() {
+    let int_to_int = HTy::Fun(Box::new(HTy::Int), Box::new(HTy::Int));
+    let f = Val {
+        ty: int_to_int.clone(),
+        rec: true,
+        name: Symbol("f".into(), 1),
+        expr: Expr::Fun {
+            param: (HTy::Int, Symbol("n".into(), 2)),
+            body_ty: HTy::Int,
+            body: Box::new(var(HTy::Int, "n", 2)),
+            captures: vec![],
+        },
+    };
+    let app = |fun: Expr, arg: Expr| Expr::App {
+        ty: HTy::Int,
+        fun: Box::new(fun),
+        arg: Box::new(arg),
+        is_tail: false,
+    };
+    let x = int_val(
+        "x",
+        3,
+        Expr::Binds {
+            ty: HTy::Int,
+            binds: vec![Val {
+                ty: int_to_int.clone(),
+                rec: false,
+                name: Symbol("g".into(), 4),
+                expr: var(int_to_int.clone(), "f", 1),
+            }],
+            ret: Box::new(app(
+                var(int_to_int.clone(), "g", 4),
+                app(var(int_to_int, "f", 1), int(1)),
+            )),
+        },
+    );
+    let symbol_table = SymbolTable {
+        types: Default::default(),
+        exports: Default::default(),
+    };
+    let hir = apply(ForceClosure::new(), (symbol_table, HIR(vec![f, x])));
+    // only the function bound to a name becomes a closure
+    assert_eq!(
+        pp_hir(&hir),
+        r#"val rec f@1: int -> int = fn (n@2: int) => n@2
+val x@3: int = let
+  val g@4: int -> int = <closure f@1>
+in
+  g@4 (f@1 1)
+end
+"#
+    );
+}
+
+#[test]
+fn pp_hty_parenthesizes_by_precedence() {
+    let fun = |t1: HTy, t2: HTy| HTy::Fun(Box::new(t1), Box::new(t2));
+    let pp_hty = |ty: HTy| {
+        let mut buf = Vec::new();
+        ty.pp(&mut buf, 0).unwrap();
+        String::from_utf8(buf).unwrap()
+    };
+    let int_to_int = fun(HTy::Int, HTy::Int);
+    assert_eq!(
+        pp_hty(fun(HTy::Int, int_to_int.clone())),
+        "int -> int -> int"
+    );
+    assert_eq!(
+        pp_hty(fun(int_to_int.clone(), HTy::Int)),
+        "(int -> int) -> int"
+    );
+    assert_eq!(
+        pp_hty(HTy::Tuple(vec![int_to_int.clone(), HTy::Int])),
+        "(int -> int) * int"
+    );
+    assert_eq!(pp_hty(HTy::List(Box::new(int_to_int))), "(int -> int) list");
+}
+
+fn pp_hir_with(hir: &HIR, config: &PpConfig) -> String {
+    let mut buf = Vec::new();
+    hir.pp_with(&mut buf, config).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+#[test]
+fn pp_indent_width() {
+    let expr = Expr::Binds {
+        ty: HTy::Int,
+        binds: vec![int_val("a", 1, int(1))],
+        ret: Box::new(Expr::Case {
+            ty: HTy::Int,
+            expr: Box::new(var(HTy::Int, "a", 1)),
+            arms: vec![
+                (
+                    Pattern::Constant {
+                        value: 0,
+                        ty: HTy::Int,
+                    },
+                    int(2),
+                ),
+                (Pattern::Wildcard { ty: HTy::Int }, var(HTy::Int, "a", 1)),
+            ],
+        }),
+    };
+    let (_, hir) = hir_of(expr);
+    let two = PpConfig {
+        indent: 2,
+        ..PpConfig::default()
+    };
+    assert_eq!(
+        pp_hir_with(&hir, &two),
+        r#"val x@0: int = let
+  val a@1: int = 1
+in
+  case a@1 of
+    0 => 2
+  | _ => a@1
+end
+"#
+    );
+    let four = PpConfig {
+        indent: 4,
+        ..PpConfig::default()
+    };
+    assert_eq!(
+        pp_hir_with(&hir, &four),
+        r#"val x@0: int = let
+    val a@1: int = 1
+in
+    case a@1 of
+        0 => 2
+      | _ => a@1
+end
+"#
+    );
+    // two spaces by default
+    assert_eq!(pp_hir(&hir), pp_hir_with(&hir, &two));
+}
+
+#[test]
+fn pp_breaks_long_tuples() {
+    let expr = Expr::Tuple {
+        tys: vec![HTy::Int, HTy::Int, HTy::Int],
+        tuple: vec![
+            var(HTy::Int, "aaaa", 1),
+            var(HTy::Int, "bbbb", 2),
+            var(HTy::Int, "cccc", 3),
+        ],
+    };
+    let (_, hir) = hir_of(expr);
+    let narrow = PpConfig {
+        indent: 2,
+        width: 20,
+        ..PpConfig::default()
+    };
+    assert_eq!(
+        pp_hir_with(&hir, &narrow),
+        r#"val x@0: int * int * int = (
+  aaaa@1,
+  bbbb@2,
+  cccc@3
+)
+"#
+    );
+    assert_eq!(
+        pp_hir(&hir),
+        "val x@0: int * int * int = (aaaa@1, bbbb@2, cccc@3)\n"
+    );
+}
+
+#[test]
+fn pp_color() {
+    let expr = Expr::Case {
+        ty: HTy::Int,
+        expr: Box::new(var(HTy::Int, "a", 1)),
+        arms: vec![
+            (
+                Pattern::Constant {
+                    value: 0,
+                    ty: HTy::Int,
+                },
+                int(2),
+            ),
+            (Pattern::Wildcard { ty: HTy::Int }, var(HTy::Int, "a", 1)),
+        ],
+    };
+    let (_, hir) = hir_of(expr);
+    let with_color = |color| PpConfig {
+        color,
+        ..PpConfig::default()
+    };
+    assert_eq!(
+        pp_hir_with(&hir, &with_color(ColorMode::Never)),
+        pp_hir(&hir)
+    );
+    // `auto` is colored only after resolved by the output
+    assert_eq!(
+        pp_hir_with(&hir, &with_color(ColorMode::Auto)),
+        pp_hir(&hir)
+    );
+    assert_eq!(ColorMode::Auto.resolve(true), ColorMode::Always);
+    assert_eq!(ColorMode::Auto.resolve(false), ColorMode::Never);
+    assert_eq!(ColorMode::Never.resolve(true), ColorMode::Never);
+    assert_eq!(
+        pp_hir_with(&hir, &with_color(ColorMode::Always)),
+        "\x1b[1;35mval\x1b[0m x@0: int = \x1b[1;35mcase\x1b[0m a@1 \x1b[1;35mof\x1b[0m\n  \
+         \x1b[32m0\x1b[0m => \x1b[32m2\x1b[0m\n| _ => a@1\n"
+    );
+
+    // the escape codes take no columns
+    let tuple = Expr::Tuple {
+        tys: vec![HTy::Int; 3],
+        tuple: vec![int(1), int(2), int(3)],
+    };
+    let (_, hir) = hir_of(tuple);
+    let narrow = PpConfig {
+        width: 9,
+        color: ColorMode::Always,
+        ..PpConfig::default()
+    };
+    assert!(!pp_hir_with(&hir, &narrow).contains("(\n"));
+}
+
+#[test]
+fn dump_hir_after_named_pass() {
+    let config = Config {
+        dump_hir_after: vec![PassName::FlatLet],
+        ..Config::default()
+    };
+    let mut passes = Chain::new(
+        DumpHir(FlatLet::new(), PassName::FlatLet, Vec::new()),
+        DumpHir(CSE::new(), PassName::Cse, Vec::new()),
+    );
+    let result: Result<_, ()> = passes.trans(hir_of(int(1)), &config);
+    result.unwrap();
+    assert_eq!(
+        String::from_utf8(passes.fst.2).unwrap(),
+        "(* HIR after flat_let *)\nval x@0: int = 1\n"
+    );
+    assert!(passes.snd.2.is_empty());
+}
+
+#[test]
+fn custom_pipeline_agrees_with_default() {
+    let run = |config: &Config| {
+        let hir = to_hir("val x = 1").unwrap();
+        apply_with(Pipeline::new(Id::new()), hir, config)
+    };
+    let default = run(&Config::default());
+    let custom = run(&Config {
+        pipeline: vec![
+            PassName::FlatExpr,
+            PassName::FlatLet,
+            PassName::UnnestFunc,
+            PassName::ForceClosure,
+            PassName::DecisionTree,
+        ],
+        ..Config::default()
+    });
+    assert_eq!(pp_hir(&default), pp_hir(&custom));
+}
+
+#[test]
+fn check_pipeline_rejects_broken_orders() {
+    let check = |pipeline: Vec<PassName>| {
+        Config {
+            pipeline,
+            ..Config::default()
+        }
+        .check_pipeline()
+    };
+    assert_eq!(Config::default().check_pipeline(), Ok(()));
+    assert_eq!(
+        check(vec![
+            PassName::FlatExpr,
+            PassName::FlatLet,
+            PassName::UnnestFunc,
+            PassName::ForceClosure,
+        ]),
+        Err("the pipeline lacks decision_tree".to_string())
+    );
+    assert_eq!(
+        check(vec![
+            PassName::FlatExpr,
+            PassName::FlatLet,
+            PassName::ForceClosure,
+            PassName::UnnestFunc,
+            PassName::DecisionTree,
+        ]),
+        Err("force_closure needs unnest_func to run before it".to_string())
+    );
+    assert_eq!(
+        check(vec![
+            PassName::FlatExpr,
+            PassName::FlatLet,
+            PassName::UnnestFunc,
+            PassName::ForceClosure,
+            PassName::CopyProp,
+            PassName::DecisionTree,
+        ]),
+        Err("copy_prop must run before unnest_func".to_string())
+    );
+    assert!(check(vec![PassName::Ast2Hir]).is_err());
+}
+
+#[test]
+fn opt_level_selects_passes() {
+    let run = |opt_level| {
+        let hir = to_hir("val x = let val y = 1 in 2 end").unwrap();
+        let config = Config {
+            opt_level,
+            ..Config::default()
+        };
+        pp_hir(&apply_with(Pipeline::new(Id::new()), hir, &config))
+    };
+    assert!(run(0).contains("val y@"));
+    assert!(!run(2).contains("val y@"));
+}
+
+#[test]
+fn verify_accepts_optimized_hir() {
+    let (symbol_table, hir) =
+        to_hir("fun f x = case x of 0 => (1, 2) | _ => (x, x) val y = #2 (f 3)").unwrap();
+    assert_eq!(verify(&symbol_table, &hir), Ok(()));
+    let hir = apply(Pipeline::new(Id::new()), (symbol_table.clone(), hir));
+    assert_eq!(verify(&symbol_table, &hir), Ok(()));
+}
+
+#[test]
+fn verify_rejects_malformed_hir() {
+    let check = |expr| {
+        let (symbol_table, hir) = hir_of(expr);
+        verify(&symbol_table, &hir)
+    };
+    let app = Expr::App {
+        ty: HTy::Int,
+        fun: Box::new(int(1)),
+        arg: Box::new(int(2)),
+        is_tail: false,
+    };
+    assert_eq!(check(app), Err(HirError::NotFunction { ty: HTy::Int }));
+
+    let proj = Expr::Proj {
+        ty: HTy::Int,
+        index: 2,
+        tuple: Box::new(Expr::Tuple {
+            tys: vec![HTy::Int, HTy::Int],
+            tuple: vec![int(1), int(2)],
+        }),
+    };
+    assert_eq!(
+        check(proj),
+        Err(HirError::ProjOutOfRange {
+            index: 2,
+            ty: HTy::Tuple(vec![HTy::Int, HTy::Int]),
+        })
+    );
+
+    let datatype = HTy::Datatype(Symbol::new("t"), vec![]);
+    let constructor = Expr::Constructor {
+        ty: datatype.clone(),
+        arg: None,
+        descriminant: 0,
+    };
+    assert_eq!(
+        check(constructor),
+        Err(HirError::UnknownConstructor {
+            descriminant: 0,
+            ty: datatype,
+        })
+    );
+
+    let arm = (
+        Pattern::Char {
+            value: 97,
+            ty: HTy::Char,
+        },
+        int(2),
+    );
+    assert_eq!(
+        check(case(HTy::Int, int(1), vec![arm])),
+        Err(HirError::PatternMismatch {
+            expected: HTy::Int,
+            actual: HTy::Char,
+        })
+    );
+}
+
+#[test]
+fn verify_pass_reports_error_when_enabled() {
+    let app = Expr::App {
+        ty: HTy::Int,
+        fun: Box::new(int(1)),
+        arg: Box::new(int(2)),
+        is_tail: false,
+    };
+    let config = Config {
+        verify_hir: true,
+        ..Config::default()
+    };
+    let result: Result<_, HirError> = Verify::new().trans(hir_of(app.clone()), &config);
+    assert!(result.is_err());
+    let result: Result<_, HirError> = Verify::new().trans(hir_of(app), &Config::default());
+    assert!(result.is_ok());
+}
+
+#[test]
+fn if_is_lowered_to_case_on_bool() {
+    let (_, hir) = to_hir("val c = true val x = if c then 1 else 2").unwrap();
+    let bool_pat = |descriminant| Pattern::Constructor {
+        descriminant,
+        arg: None,
+        ty: HTy::Bool,
+    };
+    match val_expr(&hir, "x") {
+        Expr::Case { ty, expr, arms } => {
+            assert_eq!(*ty, HTy::Int);
+            assert_eq!(expr.ty(), HTy::Bool);
+            assert_eq!(arms, &vec![(bool_pat(1), int(1)), (bool_pat(0), int(2))]);
+        }
+        expr => panic!("expected case, got {:?}", expr),
+    }
+}
+
+#[test]
+fn andalso_orelse_short_circuit() {
+    let input = r#"
+val x = _externcall("js-ffi"."check": (int) -> bool)(1)
+  andalso _externcall("js-ffi"."check": (int) -> bool)(2)
+val y = _externcall("js-ffi"."check": (int) -> bool)(3)
+  orelse _externcall("js-ffi"."check": (int) -> bool)(4)
+"#;
+    let (_, hir) = to_hir(input).unwrap();
+    let bool_pat = |descriminant| Pattern::Constructor {
+        descriminant,
+        arg: None,
+        ty: HTy::Bool,
+    };
+    let bool_lit = |descriminant| Expr::Constructor {
+        descriminant,
+        arg: None,
+        ty: HTy::Bool,
+    };
+    // the right operand is called only in the arm where the left one does not decide the result
+    match val_expr(&hir, "x") {
+        Expr::Case { expr, arms, .. } => {
+            assert_eq!(CountCalls::count(BIF::Add, expr), (0, 1));
+            assert_eq!(arms[0].0, bool_pat(1));
+            assert_eq!(CountCalls::count(BIF::Add, &arms[0].1), (0, 1));
+            assert_eq!(arms[1], (bool_pat(0), bool_lit(0)));
+        }
+        expr => panic!("expected case, got {:?}", expr),
+    }
+    match val_expr(&hir, "y") {
+        Expr::Case { expr, arms, .. } => {
+            assert_eq!(CountCalls::count(BIF::Add, expr), (0, 1));
+            assert_eq!(arms[0], (bool_pat(1), bool_lit(1)));
+            assert_eq!(arms[1].0, bool_pat(0));
+            assert_eq!(CountCalls::count(BIF::Add, &arms[1].1), (0, 1));
+        }
+        expr => panic!("expected case, got {:?}", expr),
+    }
+}
+
+#[test]
+fn decided_andalso_orelse_drop_right_operand() {
+    let input = r#"
+val x = false andalso _externcall("js-ffi"."check": (int) -> bool)(1)
+val y = true orelse _externcall("js-ffi"."check": (int) -> bool)(2)
+val z = true andalso _externcall("js-ffi"."check": (int) -> bool)(3)
+"#;
+    let hir = apply(Simplify::new(Id::new()), to_hir(input).unwrap());
+    assert_eq!(CountCalls::count(BIF::Add, val_expr(&hir, "x")), (0, 0));
+    assert_eq!(CountCalls::count(BIF::Add, val_expr(&hir, "y")), (0, 0));
+    assert_eq!(CountCalls::count(BIF::Add, val_expr(&hir, "z")), (0, 1));
+}
+
+#[test]
+fn mutually_recursive_functions_are_hoisted_together() {
+    let input = r#"
+fun even [] = true | even (_ :: xs) = odd xs
+and odd [] = false | odd (_ :: xs) = even xs
+val x = let
+  fun ev [] = true | ev (_ :: xs) = od xs
+  and od [] = false | od (_ :: xs) = ev xs
+in
+  ev [1, 2]
+end
+"#;
+    let hir = apply(UnnestFunc::new(Id::new()), flatten(to_hir(input).unwrap()));
+    let captures = |name: &str| match &hir.0.iter().find(|val| val.name.0 == name) {
+        Some(Val {
+            expr: Expr::Fun { captures, .. },
+            ..
+        }) => captures.len(),
+        val => panic!("expected function {}, got {:?}", name, val),
+    };
+    // the functions call each other directly without closures
+    for name in &["even", "odd", "<ev>", "<od>"] {
+        assert_eq!(captures(name), 0, "{}", name);
+    }
+    let mut envs = ClosureEnvs(Vec::new());
+    envs.traverse_hir(&mut hir.clone());
+    assert!(envs.0.iter().all(|env| env.is_empty()), "{:?}", envs.0);
+}
+
+// the numbers of the constructors made and matched
+#[derive(Default)]
+struct Constructors {
+    made: usize,
+    matched: usize,
+}
+
+impl Traverse for Constructors {
+    fn traverse_case(
+        &mut self,
+        _ty: &mut HTy,
+        expr: &mut Box<Expr>,
+        arms: &mut Vec<(Pattern, Expr)>,
+    ) {
+        self.traverse_expr(expr);
+        for (pat, arm) in arms {
+            if let Pattern::Constructor { .. } = pat {
+                self.matched += 1;
+            }
+            self.traverse_expr(arm);
+        }
+    }
+
+    fn traverse_constructor(
+        &mut self,
+        _ty: &mut HTy,
+        arg: &mut Option<Box<Expr>>,
+        _descriminant: &mut u32,
+    ) {
+        self.made += 1;
+        if let Some(arg) = arg {
+            self.traverse_expr(arg)
+        }
+    }
+}
+
+#[test]
+fn single_constructor_datatype_is_unboxed() {
+    let input = r#"
+datatype box = Box of int
+val b = Box 1
+fun unbox (Box x) = x
+val y = unbox b
+"#;
+    let result: Result<_, ()> =
+        UnboxDatatype::new().trans(to_hir(input).unwrap(), &Config::default());
+    let (symbol_table, mut hir) = result.unwrap();
+    assert!(symbol_table.types.keys().all(|name| name.0 != "box"));
+    assert_eq!(val_expr(&hir, "b"), &int(1));
+    let unbox = hir.0.iter().find(|val| val.name.0 == "unbox").unwrap();
+    assert_eq!(unbox.ty, HTy::fun(HTy::Int, HTy::Int));
+
+    let mut constructors = Constructors::default();
+    constructors.traverse_hir(&mut hir);
+    assert_eq!((constructors.made, constructors.matched), (0, 0));
+}
+
+#[test]
+fn recursive_single_constructor_datatype_is_boxed() {
+    let input = r#"
+datatype stream = Cons of int * (unit -> stream)
+fun head (Cons (x, _)) = x
+"#;
+    let (symbol_table, hir) = to_hir(input).unwrap();
+    let expected = symbol_table.clone();
+    let result: Result<_, ()> = UnboxDatatype::new().trans((symbol_table, hir), &Config::default());
+    assert_eq!(result.unwrap().0, expected);
+}
+
+#[test]
+fn parametric_datatype_instances_have_own_layouts() {
+    let input = r#"
+datatype 'a option = NONE | SOME of 'a
+val a = SOME 1
+val b = SOME 1.5
+"#;
+    let (symbol_table, hir) = to_hir(input).unwrap();
+    let arg = |name| {
+        let val = hir.0.iter().find(|val| val.name.0 == name).unwrap();
+        let type_name = match &val.ty {
+            HTy::Datatype(type_name, args) if args.is_empty() => type_name,
+            ty => panic!("expected an instance of option, got {:?}", ty),
+        };
+        symbol_table.types[type_name].constructors[1].1.clone()
+    };
+    assert_eq!(arg("a"), Some(HTy::Int));
+    assert_eq!(arg("b"), Some(HTy::Real));
+}
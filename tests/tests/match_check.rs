@@ -0,0 +1,168 @@
+use webml::ast::{Desugar, MatchCheck, Rename, Typer, VarToConstructor};
+use webml::compile_pass;
+use webml::id::Id;
+use webml::pass::{Chain, ConvError, Pass, PrintablePass};
+use webml::prim::Span;
+use webml::{parse, Config, TypeError, Warning};
+
+fn match_warnings<'a>(input: &'a str) -> Result<Vec<Warning>, TypeError<'a>> {
+    let id = Id::new();
+    let mut passes = compile_pass![
+        parse: ConvError::new(parse),
+        desugar: Desugar::new(id.clone()),
+        rename: Rename::new(id.clone()),
+        var_to_constructor: VarToConstructor::new(id.clone()),
+        typing: Typer::new(),
+    ];
+    let typed = passes.trans(input, &Config::default())?;
+    let mut check = MatchCheck::new();
+    check.trans(typed, &Config::default())?;
+    Ok(check.warnings().to_vec())
+}
+
+#[test]
+fn missing_constructor() {
+    let input = r#"
+datatype t = A | B of int | C
+val f = fn x => case x of A => 1 | B _ => 2
+"#;
+    assert_eq!(
+        match_warnings(input).unwrap(),
+        vec![Warning::NonExhaustive {
+            span: Span::default(),
+            missing: vec!["C".to_string()],
+        }]
+    );
+}
+
+#[test]
+fn exhaustive_match() {
+    let input = r#"
+datatype t = A | B of int | C
+val f = fn x => case x of A => 1 | B 0 => 2 | B _ => 3 | C => 4
+val g = fn x => if x then A else C
+"#;
+    assert_eq!(match_warnings(input).unwrap(), vec![]);
+}
+
+#[test]
+fn arm_after_wildcard() {
+    let input = r#"
+datatype t = A | B of int | C
+val f = fn x => case x of A => 1 | _ => 2 | C => 3
+"#;
+    assert_eq!(
+        match_warnings(input).unwrap(),
+        vec![Warning::RedundantArm {
+            span: Span::default(),
+        }]
+    );
+}
+
+#[test]
+fn duplicated_constructor_arm() {
+    let input = r#"
+datatype t = A | B of int | C
+val f = fn x => case x of A => 1 | B _ => 2 | A => 3 | C => 4
+"#;
+    let arm_start = input.find("| A").unwrap() + 2;
+    let warnings = match_warnings(input).unwrap();
+    assert_eq!(
+        warnings,
+        vec![Warning::RedundantArm {
+            span: Span::default(),
+        }]
+    );
+    assert_eq!(warnings[0].span().start, arm_start);
+}
+
+#[test]
+fn or_pattern_arms() {
+    let input = r#"
+datatype t = A | B of int | C
+val f = fn x => case x of A | C => 1 | B _ => 2
+val g = fn x => case x of A | B _ => 1
+"#;
+    assert_eq!(
+        match_warnings(input).unwrap(),
+        vec![Warning::NonExhaustive {
+            span: Span::default(),
+            missing: vec!["C".to_string()],
+        }]
+    );
+}
+
+#[test]
+fn multi_clause_fn_missing_constructor() {
+    let input = r#"
+datatype t = A | B of int | C
+val f = fn A => 1 | B _ => 2
+val g = fn A => 1 | B _ => 2 | C => 3
+"#;
+    assert_eq!(
+        match_warnings(input).unwrap(),
+        vec![Warning::NonExhaustive {
+            span: Span::default(),
+            missing: vec!["C".to_string()],
+        }]
+    );
+}
+
+#[test]
+fn string_arms() {
+    let input = r#"
+val f = fn s => case s of "yes" => 1 | "no" => 0
+val g = fn s => case s of "yes" => 1 | "no" => 0 | "yes" => 2 | _ => ~1
+"#;
+    assert_eq!(
+        match_warnings(input).unwrap(),
+        vec![
+            Warning::NonExhaustive {
+                span: Span::default(),
+                missing: vec!["_".to_string()],
+            },
+            Warning::RedundantArm {
+                span: Span::default(),
+            }
+        ]
+    );
+}
+
+#[test]
+fn warnings_are_left_to_the_caller() {
+    let input = r#"
+datatype t = A | B
+val f = fn x => case x of A => 1 | B => 2 | A => 3
+"#;
+    let typed = webml::typecheck(input, &Config::default()).unwrap();
+    assert_eq!(
+        typed.warnings,
+        vec![Warning::RedundantArm {
+            span: Span::default(),
+        }]
+    );
+    let output = webml::compile(input, &Config::default());
+    assert!(output.code.is_ok());
+    assert_eq!(output.warnings, typed.warnings);
+}
+
+#[test]
+fn non_exhaustive_match_is_not_compiled() {
+    let input = r#"
+datatype t = A | B
+val f = fn x => case x of A => 1
+"#;
+    let output = webml::compile(input, &Config::default());
+    match output.code {
+        Err(TypeError::Inexhaustive) => (),
+        res => panic!("expected non-exhaustive match error, got {:?}", res),
+    }
+    // the warning tells the missing constructors
+    assert_eq!(
+        output.warnings,
+        vec![Warning::NonExhaustive {
+            span: Span::default(),
+            missing: vec!["B".to_string()],
+        }]
+    );
+}
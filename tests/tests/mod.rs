@@ -1,2 +1,9 @@
 pub mod compile;
+pub mod golden;
+pub mod hir;
+pub mod match_check;
 pub mod parser;
+pub mod pass;
+pub mod prim;
+pub mod typing;
+pub mod unification_pool;
@@ -1,9 +1,9 @@
 use webml::ast::{
-    Declaration, DerivedDeclaration, DerivedExprKind, Expr, ExprKind, Pattern, PatternKind, Type,
-    AST,
+    Declaration, DerivedDeclaration, DerivedExpr, Expr, ExprKind, Pattern, PatternKind, Type, AST,
 };
 use webml::parse;
 use webml::prim::*;
+use webml::util::PP;
 
 #[test]
 fn parse_char() {
@@ -15,12 +15,14 @@ fn parse_char() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("x"),
                 }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::Literal {
                     value: Literal::Char('a' as u32),
                 }
@@ -29,6 +31,114 @@ fn parse_char() {
     )
 }
 
+fn char_of(input: &str) -> u32 {
+    match parse(&format!("val x = #\"{}\"", input))
+        .unwrap()
+        .0
+        .remove(0)
+    {
+        Declaration::Val {
+            expr:
+                Expr {
+                    inner:
+                        ExprKind::Literal {
+                            value: Literal::Char(c),
+                        },
+                    ..
+                },
+            ..
+        } => c,
+        decl => panic!("expected char literal, got {:?}", decl),
+    }
+}
+
+#[test]
+fn parse_char_escape() {
+    let escapes = vec![
+        (r"\a", 7),
+        (r"\b", 8),
+        (r"\t", 9),
+        (r"\n", 10),
+        (r"\v", 11),
+        (r"\f", 12),
+        (r"\r", 13),
+        (r#"\""#, '"' as u32),
+        (r"\\", '\\' as u32),
+        (r"\000", 0),
+        (r"\065", 65),
+        (r"\255", 255),
+        (r"\u0041", 65),
+        (r"\u03bb", 0x3bb),
+    ];
+    for (input, c) in escapes {
+        assert_eq!(char_of(input), c, "{}", input);
+    }
+}
+
+#[test]
+fn parse_char_invalid_escape() {
+    for input in &[r"\q", r"\256", r"\12", r"\u12", r"\ud800"] {
+        let input = format!("val x = #\"{}\"", input);
+        assert!(parse(&input).is_err(), "{}", input);
+    }
+}
+
+#[test]
+fn pp_char_escape() {
+    let escapes = vec![
+        (7, r"\a"),
+        (9, r"\t"),
+        (10, r"\n"),
+        ('"' as u32, r#"\""#),
+        ('\\' as u32, r"\\"),
+        ('a' as u32, "a"),
+        (0, r"\000"),
+        (127, r"\127"),
+        (0x3bb, r"\u03bb"),
+    ];
+    for (c, escaped) in escapes {
+        let mut buf = Vec::new();
+        Literal::Char(c).pp(&mut buf, 0).unwrap();
+        let printed = String::from_utf8(buf).unwrap();
+        assert_eq!(printed, format!("#\"{}\"", escaped));
+        // the printed literal is parsed back to the same char
+        assert_eq!(char_of(escaped), c);
+    }
+}
+
+fn string_of(input: &str) -> String {
+    match parse(&format!("val x = \"{}\"", input))
+        .unwrap()
+        .0
+        .remove(0)
+    {
+        Declaration::Val {
+            expr:
+                Expr {
+                    inner:
+                        ExprKind::Literal {
+                            value: Literal::String(s),
+                        },
+                    ..
+                },
+            ..
+        } => s,
+        decl => panic!("expected string literal, got {:?}", decl),
+    }
+}
+
+#[test]
+fn pp_string_escape() {
+    let s = "a\n\"\\\u{7}\u{3bb}";
+    let escaped = r#"a\n\"\\\a\u03bb"#;
+    let mut buf = Vec::new();
+    Literal::String(s.to_string()).pp(&mut buf, 0).unwrap();
+    let printed = String::from_utf8(buf).unwrap();
+    assert_eq!(printed, format!("\"{}\"", escaped));
+    // the printed literal is parsed back to the same string
+    assert_eq!(string_of(escaped), s);
+}
+
 #[test]
 fn parse_int() {
     let input = r#"val x = 1"#;
@@ -39,12 +149,14 @@ fn parse_int() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("x"),
                 }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::Literal {
                     value: Literal::Int(1),
                 }
@@ -53,6 +165,186 @@ fn parse_int() {
     )
 }
 
+fn int_of(input: &str) -> i64 {
+    match parse(&format!("val x = {}", input)).unwrap().0.remove(0) {
+        Declaration::Val {
+            expr:
+                Expr {
+                    inner:
+                        ExprKind::Literal {
+                            value: Literal::Int(v),
+                        },
+                    ..
+                },
+            ..
+        } => v,
+        decl => panic!("expected int literal, got {:?}", decl),
+    }
+}
+
+#[test]
+fn parse_hex_and_bin_int() {
+    let literals = vec![
+        ("0xFF", 255),
+        ("0x1f", 31),
+        ("0b1010", 10),
+        ("0", 0),
+        ("0x7fffffff", 2147483647),
+        ("2147483647", 2147483647),
+    ];
+    for (input, v) in literals {
+        assert_eq!(int_of(input), v, "{}", input);
+    }
+}
+
+#[test]
+fn parse_word() {
+    for &(input, v) in &[
+        ("0w0", 0),
+        ("0w42", 42),
+        ("0wxff", 255),
+        ("0w4294967295", 4294967295),
+        ("0wxffffffff", 4294967295),
+    ] {
+        match parse(&format!("val x = {}", input)).unwrap().0.remove(0) {
+            Declaration::Val {
+                expr:
+                    Expr {
+                        inner:
+                            ExprKind::Literal {
+                                value: Literal::Word(w),
+                            },
+                        ..
+                    },
+                ..
+            } => assert_eq!(w, v, "{}", input),
+            decl => panic!("expected word literal, got {:?}", decl),
+        }
+    }
+}
+
+#[test]
+fn parse_int_out_of_range() {
+    for input in &[
+        "0x80000000",
+        "0b100000000000000000000000000000000",
+        "2147483648",
+        "~2147483649",
+        "9223372036854775808",
+        "0xg",
+        "0b2",
+        "0w4294967296",
+        "0wx100000000",
+        "0w18446744073709551616",
+        "0wx10000000000000000",
+    ] {
+        let input = format!("val x = {}", input);
+        assert!(parse(&input).is_err(), "{}", input);
+    }
+}
+
+#[test]
+fn parse_int_pattern_out_of_range() {
+    assert!(parse("val x = case 1 of 2147483647 => 0 | _ => 1").is_ok());
+    assert!(parse("val x = case 1 of 4294967297 => 0 | _ => 1").is_err());
+    assert!(parse("fun f ~2147483649 = 0 | f _ = 1").is_err());
+}
+
+#[test]
+fn parse_digits_out_of_range() {
+    assert!(parse("infix 256 +").is_err());
+    assert!(parse("val x = (1 : 'a99999999999999999999)").is_err());
+    assert!(parse("val x = (1 : 'z709490156681136601)").is_err());
+    assert!(parse("val x = (1 : 'z709490156681136600)").is_ok());
+}
+
+#[test]
+fn parse_negative_literal() {
+    let literals = vec![
+        ("~3", -3),
+        ("~0x10", -16),
+        ("~0b11", -3),
+        ("~2147483648", -2147483648),
+    ];
+    for (input, v) in literals {
+        assert_eq!(int_of(input), v, "{}", input);
+    }
+}
+
+// the expression of the last `val`
+fn last_val_expr(input: &str) -> Expr<()> {
+    match parse(input).unwrap().0.pop() {
+        Some(Declaration::Val { expr, .. }) => expr,
+        decl => panic!("expected val, got {:?}", decl),
+    }
+}
+
+fn expr(inner: ExprKind<()>) -> Expr<()> {
+    Expr {
+        ty: (),
+        span: Span::default(),
+        inner,
+    }
+}
+
+fn sym(name: &str) -> Expr<()> {
+    expr(ExprKind::Symbol {
+        name: Symbol::new(name),
+    })
+}
+
+fn int(value: i64) -> Expr<()> {
+    expr(ExprKind::Literal {
+        value: Literal::Int(value),
+    })
+}
+
+fn app(fun: Expr<()>, arg: Expr<()>) -> Expr<()> {
+    expr(ExprKind::App {
+        fun: fun.boxed(),
+        arg: arg.boxed(),
+    })
+}
+
+fn binop(op: &str, l: Expr<()>, r: Expr<()>) -> Expr<()> {
+    app(sym(op), expr(ExprKind::Tuple { tuple: vec![l, r] }))
+}
+
+#[test]
+fn parse_negation_and_subtraction() {
+    assert_eq!(
+        last_val_expr("infix 6 - val x = a-~3"),
+        binop("-", sym("a"), int(-3))
+    );
+    assert_eq!(
+        last_val_expr("infix 6 - val x = 3 - 4"),
+        binop("-", int(3), int(4))
+    );
+    assert_eq!(last_val_expr("val x = ~a"), app(sym("~"), sym("a")));
+    // `~` applied to an expression binds tighter than the infixes
+    assert_eq!(
+        last_val_expr("infix 6 - val x = ~ a - 1"),
+        binop("-", app(sym("~"), sym("a")), int(1))
+    );
+    // `-` is not a sign
+    assert_eq!(
+        last_val_expr("infix 6 - val x = a -1.5"),
+        binop(
+            "-",
+            sym("a"),
+            expr(ExprKind::Literal {
+                value: Literal::Real(1.5)
+            })
+        )
+    );
+    assert_eq!(
+        last_val_expr("val x = ~1.5"),
+        expr(ExprKind::Literal {
+            value: Literal::Real(-1.5)
+        })
+    );
+}
+
 #[test]
 fn parse_float() {
     let input = r#"val x = 1.0"#;
@@ -63,12 +355,14 @@ fn parse_float() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("x"),
                 }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::Literal {
                     value: Literal::Real(1.0),
                 }
@@ -87,12 +381,14 @@ fn parse_bool_true() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("x"),
                 }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::Constructor {
                     arg: None,
                     name: Symbol::new("true")
@@ -112,12 +408,14 @@ fn parse_bool_false() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("x"),
                 }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::Constructor {
                     arg: None,
                     name: Symbol::new("false")
@@ -137,12 +435,14 @@ fn parse_unit() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("x"),
                 }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::Tuple { tuple: vec![] }
             }
         }])
@@ -159,15 +459,18 @@ fn parse_apply() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("x"),
                 }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::App {
                     fun: Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Symbol {
                             name: Symbol::new("f")
                         }
@@ -175,6 +478,7 @@ fn parse_apply() {
                     .boxed(),
                     arg: Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Symbol {
                             name: Symbol::new("x")
                         }
@@ -196,15 +500,18 @@ fn parse_apply_tuple() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("x"),
                 }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::App {
                     fun: Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Symbol {
                             name: Symbol::new("f")
                         }
@@ -212,16 +519,19 @@ fn parse_apply_tuple() {
                     .boxed(),
                     arg: Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Tuple {
                             tuple: vec![
                                 Expr {
                                     ty: (),
+                                    span: Span::default(),
                                     inner: ExprKind::Symbol {
                                         name: Symbol::new("x")
                                     }
                                 },
                                 Expr {
                                     ty: (),
+                                    span: Span::default(),
                                     inner: ExprKind::Symbol {
                                         name: Symbol::new("y")
                                     }
@@ -250,15 +560,18 @@ fn parse_binop() {
                 rec: false,
                 pattern: Pattern {
                     ty: (),
+                    span: Span::default(),
                     inner: PatternKind::Variable {
                         name: Symbol::new("x"),
                     }
                 },
                 expr: Expr {
                     ty: (),
+                    span: Span::default(),
                     inner: ExprKind::App {
                         fun: Expr {
                             ty: (),
+                            span: Span::default(),
                             inner: ExprKind::Symbol {
                                 name: Symbol::new("+")
                             }
@@ -266,16 +579,19 @@ fn parse_binop() {
                         .boxed(),
                         arg: Expr {
                             ty: (),
+                            span: Span::default(),
                             inner: ExprKind::Tuple {
                                 tuple: vec![
                                     Expr {
                                         ty: (),
+                                        span: Span::default(),
                                         inner: ExprKind::Literal {
                                             value: Literal::Int(1),
                                         }
                                     },
                                     Expr {
                                         ty: (),
+                                        span: Span::default(),
                                         inner: ExprKind::Literal {
                                             value: Literal::Int(2),
                                         }
@@ -306,15 +622,18 @@ fn parse_binop_space() {
                 rec: false,
                 pattern: Pattern {
                     ty: (),
+                    span: Span::default(),
                     inner: PatternKind::Variable {
                         name: Symbol::new("x"),
                     }
                 },
                 expr: Expr {
                     ty: (),
+                    span: Span::default(),
                     inner: ExprKind::App {
                         fun: Expr {
                             ty: (),
+                            span: Span::default(),
                             inner: ExprKind::Symbol {
                                 name: Symbol::new("+")
                             }
@@ -322,16 +641,19 @@ fn parse_binop_space() {
                         .boxed(),
                         arg: Expr {
                             ty: (),
+                            span: Span::default(),
                             inner: ExprKind::Tuple {
                                 tuple: vec![
                                     Expr {
                                         ty: (),
+                                        span: Span::default(),
                                         inner: ExprKind::Literal {
                                             value: Literal::Int(1),
                                         }
                                     },
                                     Expr {
                                         ty: (),
+                                        span: Span::default(),
                                         inner: ExprKind::Literal {
                                             value: Literal::Int(2),
                                         }
@@ -362,15 +684,18 @@ fn parse_binop_assoc() {
                 rec: false,
                 pattern: Pattern {
                     ty: (),
+                    span: Span::default(),
                     inner: PatternKind::Variable {
                         name: Symbol::new("x"),
                     }
                 },
                 expr: Expr {
                     ty: (),
+                    span: Span::default(),
                     inner: ExprKind::App {
                         fun: Expr {
                             ty: (),
+                            span: Span::default(),
                             inner: ExprKind::Symbol {
                                 name: Symbol::new("+"),
                             }
@@ -378,13 +703,16 @@ fn parse_binop_assoc() {
                         .boxed(),
                         arg: Expr {
                             ty: (),
+                            span: Span::default(),
                             inner: ExprKind::Tuple {
                                 tuple: vec![
                                     Expr {
                                         ty: (),
+                                        span: Span::default(),
                                         inner: ExprKind::App {
                                             fun: Expr {
                                                 ty: (),
+                                                span: Span::default(),
                                                 inner: ExprKind::Symbol {
                                                     name: Symbol::new("+"),
                                                 }
@@ -392,16 +720,19 @@ fn parse_binop_assoc() {
                                             .boxed(),
                                             arg: Expr {
                                                 ty: (),
+                                                span: Span::default(),
                                                 inner: ExprKind::Tuple {
                                                     tuple: vec![
                                                         Expr {
                                                             ty: (),
+                                                            span: Span::default(),
                                                             inner: ExprKind::Literal {
                                                                 value: Literal::Int(1),
                                                             }
                                                         },
                                                         Expr {
                                                             ty: (),
+                                                            span: Span::default(),
                                                             inner: ExprKind::Literal {
                                                                 value: Literal::Int(2),
                                                             }
@@ -414,6 +745,7 @@ fn parse_binop_assoc() {
                                     },
                                     Expr {
                                         ty: (),
+                                        span: Span::default(),
                                         inner: ExprKind::Literal {
                                             value: Literal::Int(3),
                                         }
@@ -439,23 +771,27 @@ fn parse_builtincall() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("ret"),
                 }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::BuiltinCall {
                     fun: BIF::Add,
                     args: vec![
                         Expr {
                             ty: (),
+                            span: Span::default(),
                             inner: ExprKind::Symbol {
                                 name: Symbol::new("x")
                             }
                         },
                         Expr {
                             ty: (),
+                            span: Span::default(),
                             inner: ExprKind::Symbol {
                                 name: Symbol::new("y")
                             }
@@ -477,37 +813,90 @@ fn parse_externcall() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("ret"),
                 }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::ExternCall {
                     module: "module".into(),
                     fun: "add".into(),
                     args: vec![
                         Expr {
                             ty: (),
+                            span: Span::default(),
                             inner: ExprKind::Symbol {
                                 name: Symbol::new("x")
                             }
                         },
                         Expr {
                             ty: (),
+                            span: Span::default(),
                             inner: ExprKind::Symbol {
                                 name: Symbol::new("y")
                             }
                         }
                     ],
                     argty: vec![Type::Int, Type::Int],
-                    retty: Type::Int
+                    retty: vec![Type::Int]
                 }
             }
         }])
     )
 }
 
+#[test]
+fn parse_externcall_multiple_results() {
+    let input = r#"val ret = _externcall ("module" . "divmod" : (int, int) -> (int, int)) (x, y)"#;
+    match last_val_expr(input).inner {
+        ExprKind::ExternCall { argty, retty, .. } => {
+            assert_eq!(argty, vec![Type::Int, Type::Int]);
+            assert_eq!(retty, vec![Type::Int, Type::Int]);
+        }
+        inner => panic!("expected externcall, got {:?}", inner),
+    }
+}
+
+#[test]
+fn parse_import() {
+    let input = r#"_import ("module" . "add" : (int, int) -> int) val ret = _externcall ("module"."add") (x, y)"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(
+        ast.0[0],
+        Declaration::Import {
+            module: "module".into(),
+            fun: "add".into(),
+            argty: vec![Type::Int, Type::Int],
+            retty: vec![Type::Int],
+        }
+    );
+    match last_val_expr(input).inner {
+        ExprKind::ExternCall { argty, retty, .. } => {
+            assert!(argty.is_empty());
+            assert!(retty.is_empty());
+        }
+        inner => panic!("expected externcall, got {:?}", inner),
+    }
+}
+
+#[test]
+fn parse_export() {
+    let input = r#"export "inc" fun inc x = x export "one" val one = fn () => 1"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(ast.0.len(), 2);
+    for (decl, expected) in ast.0.iter().zip(&["inc", "one"]) {
+        match decl {
+            Declaration::D(DerivedDeclaration::Export { name, .. }) => assert_eq!(name, expected),
+            decl => panic!("expected export, got {:?}", decl),
+        }
+    }
+
+    assert!(parse(r#"export "pair" val (a, b) = (1, 2)"#).is_err());
+}
+
 #[test]
 fn parse_binop_pref() {
     let input = r#"infix 6 + infix 7 * val x = 1 + 2 * 3"#;
@@ -527,15 +916,18 @@ fn parse_binop_pref() {
                 rec: false,
                 pattern: Pattern {
                     ty: (),
+                    span: Span::default(),
                     inner: PatternKind::Variable {
                         name: Symbol::new("x"),
                     }
                 },
                 expr: Expr {
                     ty: (),
+                    span: Span::default(),
                     inner: ExprKind::App {
                         fun: Expr {
                             ty: (),
+                            span: Span::default(),
                             inner: ExprKind::Symbol {
                                 name: Symbol::new("+")
                             }
@@ -543,19 +935,23 @@ fn parse_binop_pref() {
                         .boxed(),
                         arg: Expr {
                             ty: (),
+                            span: Span::default(),
                             inner: ExprKind::Tuple {
                                 tuple: vec![
                                     Expr {
                                         ty: (),
+                                        span: Span::default(),
                                         inner: ExprKind::Literal {
                                             value: Literal::Int(1),
                                         }
                                     },
                                     Expr {
                                         ty: (),
+                                        span: Span::default(),
                                         inner: ExprKind::App {
                                             fun: Expr {
                                                 ty: (),
+                                                span: Span::default(),
                                                 inner: ExprKind::Symbol {
                                                     name: Symbol::new("*"),
                                                 }
@@ -563,16 +959,19 @@ fn parse_binop_pref() {
                                             .boxed(),
                                             arg: Expr {
                                                 ty: (),
+                                                span: Span::default(),
                                                 inner: ExprKind::Tuple {
                                                     tuple: vec![
                                                         Expr {
                                                             ty: (),
+                                                            span: Span::default(),
                                                             inner: ExprKind::Literal {
                                                                 value: Literal::Int(2),
                                                             }
                                                         },
                                                         Expr {
                                                             ty: (),
+                                                            span: Span::default(),
                                                             inner: ExprKind::Literal {
                                                                 value: Literal::Int(3),
                                                             }
@@ -604,16 +1003,19 @@ fn parse_fn_unary() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("f"),
                 }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::Fn {
                     param: Symbol::new("x"),
                     body: Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Symbol {
                             name: Symbol::new("x"),
                         }
@@ -633,6 +1035,7 @@ fn parse_datatype_single() {
         ast,
         AST(vec![Declaration::Datatype {
             name: Symbol::new("hoge"),
+            params: vec![],
             constructors: vec![(Symbol::new("Hoge"), None)]
         },])
     )
@@ -646,6 +1049,7 @@ fn parse_datatype_multi() {
         ast,
         AST(vec![Declaration::Datatype {
             name: Symbol::new("hoge"),
+            params: vec![],
             constructors: vec![
                 (Symbol::new("Hoge"), None),
                 (Symbol::new("Fuga"), None),
@@ -663,6 +1067,7 @@ fn parse_datatype_arg1() {
         ast,
         AST(vec![Declaration::Datatype {
             name: Symbol::new("hoge"),
+            params: vec![],
             constructors: vec![
                 (Symbol::new("Hoge"), Some(Type::Int)),
                 (Symbol::new("Fuga"), Some(Type::Real))
@@ -679,17 +1084,15 @@ fn parse_datatype_arg2() {
         ast,
         AST(vec![Declaration::Datatype {
             name: Symbol::new("hoge"),
+            params: vec![],
             constructors: vec![
                 (Symbol::new("Hoge"), Some(Type::Int)),
                 (Symbol::new("Fuga"), Some(Type::Real)),
                 (
                     Symbol::new("Piyo"),
                     Some(Type::Fun(
-                        Box::new(Type::Datatype(Symbol::new("bool"))),
-                        Box::new(Type::Fun(
-                            Box::new(Type::Tuple(vec![])),
-                            Box::new(Type::Int)
-                        ))
+                        Box::new(Type::Datatype(Symbol::new("bool"), vec![])),
+                        Box::new(Type::Fun(Box::new(Type::Unit), Box::new(Type::Int)))
                     ))
                 )
             ]
@@ -705,6 +1108,7 @@ fn parse_datatype_tuple() {
         ast,
         AST(vec![Declaration::Datatype {
             name: Symbol::new("hoge"),
+            params: vec![],
             constructors: vec![(
                 Symbol::new("Hoge"),
                 Some(Type::Tuple(vec![Type::Int, Type::Real]))
@@ -722,13 +1126,14 @@ fn parse_datatype_arg3() {
         ast,
         AST(vec![Declaration::Datatype {
             name: Symbol::new("hoge"),
+            params: vec![],
             constructors: vec![
                 (Symbol::new("Hoge"), Some(Type::Int)),
                 (Symbol::new("Fuga"), Some(Type::Real)),
                 (
                     Symbol::new("Piyo"),
                     Some(Type::Fun(
-                        Box::new(Type::Datatype(Symbol::new("bool"))),
+                        Box::new(Type::Datatype(Symbol::new("bool"), vec![])),
                         Box::new(Type::Tuple(vec![
                             Type::Fun(Box::new(Type::Real), Box::new(Type::Int)),
                             Type::Real
@@ -748,12 +1153,13 @@ fn parse_datatype_primlike() {
         ast,
         AST(vec![Declaration::Datatype {
             name: Symbol::new("intlist"),
+            params: vec![],
             constructors: vec![
                 (
                     Symbol::new("Cons"),
                     Some(Type::Tuple(vec![
                         Type::Int,
-                        Type::Datatype(Symbol::new("intlist"))
+                        Type::Datatype(Symbol::new("intlist"), vec![])
                     ]))
                 ),
                 (Symbol::new("Nil"), None)
@@ -762,6 +1168,29 @@ fn parse_datatype_primlike() {
     )
 }
 
+#[test]
+fn parse_datatype_params() {
+    let input = r#"datatype ('a, 'b) either = Left of 'a | Right of 'b option"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::Datatype {
+            name: Symbol::new("either"),
+            params: vec![0, 1],
+            constructors: vec![
+                (Symbol::new("Left"), Some(Type::Variable(0))),
+                (
+                    Symbol::new("Right"),
+                    Some(Type::Datatype(
+                        Symbol::new("option"),
+                        vec![Type::Variable(1)]
+                    ))
+                ),
+            ]
+        },])
+    )
+}
+
 #[test]
 fn parse_fun_unary() {
     let input = r#"fun f x = x"#;
@@ -773,12 +1202,14 @@ fn parse_fun_unary() {
             clauses: vec![(
                 vec![Pattern {
                     ty: (),
+                    span: Span::default(),
                     inner: PatternKind::Variable {
                         name: Symbol::new("x"),
                     }
                 }],
                 Expr {
                     ty: (),
+                    span: Span::default(),
                     inner: ExprKind::Symbol {
                         name: Symbol::new("x"),
                     }
@@ -788,6 +1219,84 @@ fn parse_fun_unary() {
     )
 }
 
+#[test]
+fn parse_fun_group() {
+    let input = r#"fun f x = g x and g x = f x"#;
+    let ast = parse(input).unwrap();
+    let clause = |f: &str, x: &str| {
+        (
+            vec![Pattern {
+                ty: (),
+                span: Span::default(),
+                inner: PatternKind::Variable {
+                    name: Symbol::new(x),
+                },
+            }],
+            Expr {
+                ty: (),
+                span: Span::default(),
+                inner: ExprKind::App {
+                    fun: Expr {
+                        ty: (),
+                        span: Span::default(),
+                        inner: ExprKind::Symbol {
+                            name: Symbol::new(f),
+                        },
+                    }
+                    .boxed(),
+                    arg: Expr {
+                        ty: (),
+                        span: Span::default(),
+                        inner: ExprKind::Symbol {
+                            name: Symbol::new(x),
+                        },
+                    }
+                    .boxed(),
+                },
+            },
+        )
+    };
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::D(DerivedDeclaration::FunGroup {
+            funs: vec![
+                (Symbol::new("f"), vec![clause("g", "x")]),
+                (Symbol::new("g"), vec![clause("f", "x")]),
+            ]
+        }),])
+    )
+}
+
+#[test]
+fn parse_val_rec_group() {
+    let input = r#"val rec f = g and g = f"#;
+    let ast = parse(input).unwrap();
+    let bind = |name: &str, value: &str| {
+        (
+            Pattern {
+                ty: (),
+                span: Span::default(),
+                inner: PatternKind::Variable {
+                    name: Symbol::new(name),
+                },
+            },
+            Expr {
+                ty: (),
+                span: Span::default(),
+                inner: ExprKind::Symbol {
+                    name: Symbol::new(value),
+                },
+            },
+        )
+    };
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::ValRecGroup {
+            binds: vec![bind("f", "g"), bind("g", "f")]
+        }])
+    )
+}
+
 #[test]
 fn parse_fun_binary() {
     let input = r#"fun f x y = x"#;
@@ -800,12 +1309,14 @@ fn parse_fun_binary() {
                 vec![
                     Pattern {
                         ty: (),
+                        span: Span::default(),
                         inner: PatternKind::Variable {
                             name: Symbol::new("x"),
                         }
                     },
                     Pattern {
                         ty: (),
+                        span: Span::default(),
                         inner: PatternKind::Variable {
                             name: Symbol::new("y"),
                         }
@@ -813,6 +1324,7 @@ fn parse_fun_binary() {
                 ],
                 Expr {
                     ty: (),
+                    span: Span::default(),
                     inner: ExprKind::Symbol {
                         name: Symbol::new("x"),
                     }
@@ -833,16 +1345,19 @@ fn parse_fun_pattern() {
             clauses: vec![(
                 vec![Pattern {
                     ty: (),
+                    span: Span::default(),
                     inner: PatternKind::Tuple {
                         tuple: vec![
                             Pattern {
                                 ty: (),
+                                span: Span::default(),
                                 inner: PatternKind::Variable {
                                     name: Symbol::new("x"),
                                 }
                             },
                             Pattern {
                                 ty: (),
+                                span: Span::default(),
                                 inner: PatternKind::Variable {
                                     name: Symbol::new("y"),
                                 }
@@ -852,6 +1367,7 @@ fn parse_fun_pattern() {
                 }],
                 Expr {
                     ty: (),
+                    span: Span::default(),
                     inner: ExprKind::Symbol {
                         name: Symbol::new("x"),
                     }
@@ -872,16 +1388,19 @@ fn parse_fun_op() {
             clauses: vec![(
                 vec![Pattern {
                     ty: (),
+                    span: Span::default(),
                     inner: PatternKind::Tuple {
                         tuple: vec![
                             Pattern {
                                 ty: (),
+                                span: Span::default(),
                                 inner: PatternKind::Variable {
                                     name: Symbol::new("x"),
                                 }
                             },
                             Pattern {
                                 ty: (),
+                                span: Span::default(),
                                 inner: PatternKind::Variable {
                                     name: Symbol::new("y"),
                                 }
@@ -891,6 +1410,7 @@ fn parse_fun_op() {
                 }],
                 Expr {
                     ty: (),
+                    span: Span::default(),
                     inner: ExprKind::Symbol {
                         name: Symbol::new("x"),
                     }
@@ -913,17 +1433,20 @@ fn parse_fun_multiclause() {
                     vec![
                         Pattern {
                             ty: (),
+                            span: Span::default(),
                             inner: PatternKind::Variable {
                                 name: Symbol::new("Nil"),
                             }
                         },
                         Pattern {
                             ty: (),
+                            span: Span::default(),
                             inner: PatternKind::Wildcard {}
                         }
                     ],
                     Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Symbol {
                             name: Symbol::new("Nil"),
                         }
@@ -933,10 +1456,12 @@ fn parse_fun_multiclause() {
                     vec![
                         Pattern {
                             ty: (),
+                            span: Span::default(),
                             inner: PatternKind::Wildcard {}
                         },
                         Pattern {
                             ty: (),
+                            span: Span::default(),
                             inner: PatternKind::Variable {
                                 name: Symbol::new("Nil"),
                             }
@@ -944,6 +1469,7 @@ fn parse_fun_multiclause() {
                     ],
                     Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Symbol {
                             name: Symbol::new("Nil"),
                         }
@@ -971,15 +1497,18 @@ fn parse_if() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("x"),
                 }
             },
             expr: Expr {
                 ty: (),
-                inner: ExprKind::D(DerivedExprKind::If {
+                span: Span::default(),
+                inner: ExprKind::If {
                     cond: Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Constructor {
                             arg: None,
                             name: Symbol::new("true")
@@ -988,6 +1517,7 @@ fn parse_if() {
                     .boxed(),
                     then: Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Constructor {
                             arg: None,
                             name: Symbol::new("false")
@@ -996,18 +1526,145 @@ fn parse_if() {
                     .boxed(),
                     else_: Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Constructor {
                             arg: None,
                             name: Symbol::new("true")
                         }
                     }
                     .boxed(),
-                })
+                }
             },
         },])
     )
 }
 
+fn if_(cond: Expr<()>, then: Expr<()>, else_: Expr<()>) -> Expr<()> {
+    expr(ExprKind::If {
+        cond: cond.boxed(),
+        then: then.boxed(),
+        else_: else_.boxed(),
+    })
+}
+
+#[test]
+fn parse_nested_if() {
+    assert_eq!(
+        last_val_expr("val x = if a then if b then 1 else 2 else 3"),
+        if_(sym("a"), if_(sym("b"), int(1), int(2)), int(3))
+    );
+    assert_eq!(
+        last_val_expr("val x = if a then 1 else if b then 2 else 3"),
+        if_(sym("a"), int(1), if_(sym("b"), int(2), int(3)))
+    );
+    // the branches extend as far to the right as possible, over applications and infixes
+    assert_eq!(
+        last_val_expr("infix 6 + val x = if a then f 1 else g 2 + 3"),
+        if_(
+            sym("a"),
+            app(sym("f"), int(1)),
+            binop("+", app(sym("g"), int(2)), int(3))
+        )
+    );
+    // an `if` in an operand is parenthesized
+    assert_eq!(
+        last_val_expr("infix 6 + val x = 1 + (if a then 2 else 3)"),
+        binop("+", int(1), if_(sym("a"), int(2), int(3)))
+    );
+}
+
+fn andalso(l: Expr<()>, r: Expr<()>) -> Expr<()> {
+    expr(ExprKind::AndAlso {
+        l: l.boxed(),
+        r: r.boxed(),
+    })
+}
+
+fn orelse(l: Expr<()>, r: Expr<()>) -> Expr<()> {
+    expr(ExprKind::OrElse {
+        l: l.boxed(),
+        r: r.boxed(),
+    })
+}
+
+#[test]
+fn parse_andalso_orelse() {
+    // `andalso` binds tighter than `orelse`, and both associate to the left
+    assert_eq!(
+        last_val_expr("val x = a orelse b andalso c orelse d"),
+        orelse(orelse(sym("a"), andalso(sym("b"), sym("c"))), sym("d"))
+    );
+    assert_eq!(
+        last_val_expr("val x = a andalso b andalso c"),
+        andalso(andalso(sym("a"), sym("b")), sym("c"))
+    );
+    // looser than any infix and application
+    assert_eq!(
+        last_val_expr("infix 4 = val x = f a andalso b = 1"),
+        andalso(app(sym("f"), sym("a")), binop("=", sym("b"), int(1)))
+    );
+    assert_eq!(
+        last_val_expr("val x = if a orelse b then c else d andalso e"),
+        if_(
+            orelse(sym("a"), sym("b")),
+            sym("c"),
+            andalso(sym("d"), sym("e"))
+        )
+    );
+    // not identifiers
+    assert!(parse("val andalso = 1").is_err());
+}
+
+#[test]
+fn parse_let() {
+    let binds = |input| match last_val_expr(input).inner {
+        ExprKind::Binds { binds, ret } => (binds.len(), *ret),
+        e => panic!("expected let, got {:?}", e),
+    };
+    assert_eq!(
+        binds("val x = let val a = 1 fun f y = a val b = f a in b end"),
+        (3, sym("b"))
+    );
+    assert_eq!(binds("val x = let in 1 end"), (0, int(1)));
+    assert_eq!(binds("val x = let val a = 1 in\n  a\nend"), (1, sym("a")));
+}
+
+#[test]
+fn let_infix_does_not_leak() {
+    assert_eq!(
+        last_val_expr("val x = let infix 6 + in a + b end val y = + (a, b)"),
+        app(
+            sym("+"),
+            expr(ExprKind::Tuple {
+                tuple: vec![sym("a"), sym("b")]
+            })
+        )
+    );
+}
+
+#[test]
+fn parse_multi_clause_fn() {
+    match last_val_expr("val f = fn A => 1 | B x => x | _ => 0").inner {
+        ExprKind::D(DerivedExpr::Fn { clauses }) => {
+            let bodies = clauses.into_iter().map(|(_, e)| e).collect::<Vec<_>>();
+            assert_eq!(bodies, vec![int(1), sym("x"), int(0)]);
+        }
+        e => panic!("expected multi-clause fn, got {:?}", e),
+    }
+    match last_val_expr("val f = fn (a, b) => a").inner {
+        ExprKind::D(DerivedExpr::Fn { clauses }) => assert_eq!(clauses.len(), 1),
+        e => panic!("expected fn with a pattern, got {:?}", e),
+    }
+    // `fn x => e` is kept as is
+    assert_eq!(
+        last_val_expr("val f = fn x => x"),
+        expr(ExprKind::Fn {
+            param: Symbol::new("x"),
+            body: sym("x").boxed(),
+        })
+    );
+}
+
 #[test]
 fn parse_case_bool() {
     let input = r#"val x = case true of true => false | false => true"#;
@@ -1018,15 +1675,18 @@ fn parse_case_bool() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("x"),
                 }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::Case {
                     cond: Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Constructor {
                             arg: None,
                             name: Symbol::new("true")
@@ -1037,6 +1697,7 @@ fn parse_case_bool() {
                         (
                             Pattern {
                                 ty: (),
+                                span: Span::default(),
                                 inner: PatternKind::Constructor {
                                     arg: None,
                                     name: Symbol::new("true")
@@ -1044,6 +1705,7 @@ fn parse_case_bool() {
                             },
                             Expr {
                                 ty: (),
+                                span: Span::default(),
                                 inner: ExprKind::Constructor {
                                     arg: None,
                                     name: Symbol::new("false")
@@ -1053,6 +1715,7 @@ fn parse_case_bool() {
                         (
                             Pattern {
                                 ty: (),
+                                span: Span::default(),
                                 inner: PatternKind::Constructor {
                                     arg: None,
                                     name: Symbol::new("false"),
@@ -1060,6 +1723,7 @@ fn parse_case_bool() {
                             },
                             Expr {
                                 ty: (),
+                                span: Span::default(),
                                 inner: ExprKind::Constructor {
                                     name: Symbol::new("true"),
                                     arg: None,
@@ -1083,15 +1747,18 @@ fn parse_case_constructor() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("x"),
                 }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::Case {
                     cond: Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Symbol {
                             name: Symbol::new("NONE")
                         }
@@ -1101,10 +1768,12 @@ fn parse_case_constructor() {
                         (
                             Pattern {
                                 ty: (),
+                                span: Span::default(),
                                 inner: PatternKind::Constructor {
                                     name: Symbol::new("SOME"),
                                     arg: Some(Box::new(Pattern {
                                         ty: (),
+                                        span: Span::default(),
                                         inner: PatternKind::Variable {
                                             name: Symbol::new("x"),
                                         }
@@ -1113,6 +1782,7 @@ fn parse_case_constructor() {
                             },
                             Expr {
                                 ty: (),
+                                span: Span::default(),
                                 inner: ExprKind::Constructor {
                                     arg: None,
                                     name: Symbol::new("false")
@@ -1122,12 +1792,14 @@ fn parse_case_constructor() {
                         (
                             Pattern {
                                 ty: (),
+                                span: Span::default(),
                                 inner: PatternKind::Variable {
                                     name: Symbol::new("NONE"),
                                 }
                             },
                             Expr {
                                 ty: (),
+                                span: Span::default(),
                                 inner: ExprKind::Constructor {
                                     name: Symbol::new("true"),
                                     arg: None,
@@ -1151,15 +1823,18 @@ fn parse_case_var() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("x"),
                 }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::Case {
                     cond: Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Constructor {
                             arg: None,
                             name: Symbol::new("true")
@@ -1170,6 +1845,7 @@ fn parse_case_var() {
                         (
                             Pattern {
                                 ty: (),
+                                span: Span::default(),
                                 inner: PatternKind::Constructor {
                                     arg: None,
                                     name: Symbol::new("true")
@@ -1177,6 +1853,7 @@ fn parse_case_var() {
                             },
                             Expr {
                                 ty: (),
+                                span: Span::default(),
                                 inner: ExprKind::Constructor {
                                     arg: None,
                                     name: Symbol::new("false")
@@ -1186,12 +1863,14 @@ fn parse_case_var() {
                         (
                             Pattern {
                                 ty: (),
+                                span: Span::default(),
                                 inner: PatternKind::Variable {
                                     name: Symbol::new("x"),
                                 }
                             },
                             Expr {
                                 ty: (),
+                                span: Span::default(),
                                 inner: ExprKind::Constructor {
                                     arg: None,
                                     name: Symbol::new("true")
@@ -1215,15 +1894,18 @@ fn parse_case_wildcard() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("x"),
                 }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::Case {
                     cond: Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Constructor {
                             arg: None,
                             name: Symbol::new("true")
@@ -1234,6 +1916,7 @@ fn parse_case_wildcard() {
                         (
                             Pattern {
                                 ty: (),
+                                span: Span::default(),
                                 inner: PatternKind::Constructor {
                                     arg: None,
                                     name: Symbol::new("true")
@@ -1241,6 +1924,7 @@ fn parse_case_wildcard() {
                             },
                             Expr {
                                 ty: (),
+                                span: Span::default(),
                                 inner: ExprKind::Constructor {
                                     arg: None,
                                     name: Symbol::new("false")
@@ -1250,10 +1934,12 @@ fn parse_case_wildcard() {
                         (
                             Pattern {
                                 ty: (),
+                                span: Span::default(),
                                 inner: PatternKind::Wildcard {}
                             },
                             Expr {
                                 ty: (),
+                                span: Span::default(),
                                 inner: ExprKind::Constructor {
                                     arg: None,
                                     name: Symbol::new("true")
@@ -1277,15 +1963,18 @@ fn parse_case_int() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("x"),
                 }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::Case {
                     cond: Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Literal {
                             value: Literal::Int(3),
                         }
@@ -1295,10 +1984,12 @@ fn parse_case_int() {
                         (
                             Pattern {
                                 ty: (),
+                                span: Span::default(),
                                 inner: PatternKind::Constant { value: 1 }
                             },
                             Expr {
                                 ty: (),
+                                span: Span::default(),
                                 inner: ExprKind::Literal {
                                     value: Literal::Int(1),
                                 }
@@ -1307,10 +1998,12 @@ fn parse_case_int() {
                         (
                             Pattern {
                                 ty: (),
+                                span: Span::default(),
                                 inner: PatternKind::Constant { value: 2 }
                             },
                             Expr {
                                 ty: (),
+                                span: Span::default(),
                                 inner: ExprKind::Literal {
                                     value: Literal::Int(2),
                                 }
@@ -1319,10 +2012,12 @@ fn parse_case_int() {
                         (
                             Pattern {
                                 ty: (),
+                                span: Span::default(),
                                 inner: PatternKind::Wildcard {}
                             },
                             Expr {
                                 ty: (),
+                                span: Span::default(),
                                 inner: ExprKind::Literal {
                                     value: Literal::Int(10),
                                 }
@@ -1335,6 +2030,30 @@ fn parse_case_int() {
     )
 }
 
+#[test]
+fn parse_case_str() {
+    let input = r#"val x = case s of "yes" => 1 | "a\"b" => 2 | _ => 3"#;
+    let pats = match last_val_expr(input).inner {
+        ExprKind::Case { clauses, .. } => clauses
+            .into_iter()
+            .map(|(pat, _)| pat.inner)
+            .collect::<Vec<_>>(),
+        expr => panic!("expected case, got {:?}", expr),
+    };
+    assert_eq!(
+        pats,
+        vec![
+            PatternKind::Str {
+                value: "yes".into()
+            },
+            PatternKind::Str {
+                value: "a\"b".into()
+            },
+            PatternKind::Wildcard {},
+        ]
+    );
+}
+
 #[test]
 fn parse_case_tuple() {
     let input = r#"val x = case (1, 2, 3) of (x, y, z) => z"#;
@@ -1345,31 +2064,37 @@ fn parse_case_tuple() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Variable {
                     name: Symbol::new("x"),
                 }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::Case {
                     cond: Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Tuple {
                             tuple: vec![
                                 Expr {
                                     ty: (),
+                                    span: Span::default(),
                                     inner: ExprKind::Literal {
                                         value: Literal::Int(1),
                                     }
                                 },
                                 Expr {
                                     ty: (),
+                                    span: Span::default(),
                                     inner: ExprKind::Literal {
                                         value: Literal::Int(2),
                                     }
                                 },
                                 Expr {
                                     ty: (),
+                                    span: Span::default(),
                                     inner: ExprKind::Literal {
                                         value: Literal::Int(3),
                                     }
@@ -1381,22 +2106,26 @@ fn parse_case_tuple() {
                     clauses: vec![(
                         Pattern {
                             ty: (),
+                            span: Span::default(),
                             inner: PatternKind::Tuple {
                                 tuple: vec![
                                     Pattern {
                                         ty: (),
+                                        span: Span::default(),
                                         inner: PatternKind::Variable {
                                             name: Symbol::new("x"),
                                         }
                                     },
                                     Pattern {
                                         ty: (),
+                                        span: Span::default(),
                                         inner: PatternKind::Variable {
                                             name: Symbol::new("y"),
                                         }
                                     },
                                     Pattern {
                                         ty: (),
+                                        span: Span::default(),
                                         inner: PatternKind::Variable {
                                             name: Symbol::new("z"),
                                         }
@@ -1406,6 +2135,7 @@ fn parse_case_tuple() {
                         },
                         Expr {
                             ty: (),
+                            span: Span::default(),
                             inner: ExprKind::Symbol {
                                 name: Symbol::new("z"),
                             }
@@ -1427,16 +2157,64 @@ fn parse_pattern_unit() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Tuple { tuple: vec![] }
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::Tuple { tuple: vec![] }
             }
         }])
     )
 }
 
+#[test]
+fn parse_pattern_as() {
+    let input = r#"val p as (a, _) = x"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::Val {
+            rec: false,
+            pattern: Pattern {
+                ty: (),
+                span: Span::default(),
+                inner: PatternKind::As {
+                    name: Symbol::new("p"),
+                    pattern: Box::new(Pattern {
+                        ty: (),
+                        span: Span::default(),
+                        inner: PatternKind::Tuple {
+                            tuple: vec![
+                                Pattern {
+                                    ty: (),
+                                    span: Span::default(),
+                                    inner: PatternKind::Variable {
+                                        name: Symbol::new("a")
+                                    }
+                                },
+                                Pattern {
+                                    ty: (),
+                                    span: Span::default(),
+                                    inner: PatternKind::Wildcard {}
+                                },
+                            ]
+                        }
+                    })
+                }
+            },
+            expr: Expr {
+                ty: (),
+                span: Span::default(),
+                inner: ExprKind::Symbol {
+                    name: Symbol::new("x")
+                }
+            }
+        }])
+    )
+}
+
 #[test]
 fn parse_case_val_pattern_wildcard() {
     let input = r#"val _ = 1"#;
@@ -1447,10 +2225,12 @@ fn parse_case_val_pattern_wildcard() {
             rec: false,
             pattern: Pattern {
                 ty: (),
+                span: Span::default(),
                 inner: PatternKind::Wildcard {}
             },
             expr: Expr {
                 ty: (),
+                span: Span::default(),
                 inner: ExprKind::Literal {
                     value: Literal::Int(1),
                 }
@@ -1472,20 +2252,24 @@ fn parse_funarg_pattern() {
                     vec![
                         Pattern {
                             ty: (),
+                            span: Span::default(),
                             inner: PatternKind::Constructor {
                                 name: Symbol::new("SOME"),
                                 arg: Some(Box::new(Pattern {
                                     ty: (),
+                                    span: Span::default(),
                                     inner: PatternKind::Wildcard {}
                                 }))
                             }
                         },
                         Pattern {
                             ty: (),
+                            span: Span::default(),
                             inner: PatternKind::Constructor {
                                 name: Symbol::new("SOME"),
                                 arg: Some(Box::new(Pattern {
                                     ty: (),
+                                    span: Span::default(),
                                     inner: PatternKind::Wildcard {}
                                 }))
                             }
@@ -1493,6 +2277,7 @@ fn parse_funarg_pattern() {
                     ],
                     Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Symbol {
                             name: Symbol::new("NONE"),
                         }
@@ -1502,16 +2287,19 @@ fn parse_funarg_pattern() {
                     vec![
                         Pattern {
                             ty: (),
+                            span: Span::default(),
                             inner: PatternKind::Variable {
                                 name: Symbol::new("NONE"),
                             }
                         },
                         Pattern {
                             ty: (),
+                            span: Span::default(),
                             inner: PatternKind::Constructor {
                                 name: Symbol::new("SOME"),
                                 arg: Some(Box::new(Pattern {
                                     ty: (),
+                                    span: Span::default(),
                                     inner: PatternKind::Variable {
                                         name: Symbol::new("x")
                                     }
@@ -1521,9 +2309,11 @@ fn parse_funarg_pattern() {
                     ],
                     Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::App {
                             fun: Expr {
                                 ty: (),
+                                span: Span::default(),
                                 inner: ExprKind::Symbol {
                                     name: Symbol::new("SOME")
                                 }
@@ -1531,6 +2321,7 @@ fn parse_funarg_pattern() {
                             .boxed(),
                             arg: Expr {
                                 ty: (),
+                                span: Span::default(),
                                 inner: ExprKind::Symbol {
                                     name: Symbol::new("x")
                                 }
@@ -1543,10 +2334,12 @@ fn parse_funarg_pattern() {
                     vec![
                         Pattern {
                             ty: (),
+                            span: Span::default(),
                             inner: PatternKind::Constructor {
                                 name: Symbol::new("SOME"),
                                 arg: Some(Box::new(Pattern {
                                     ty: (),
+                                    span: Span::default(),
                                     inner: PatternKind::Variable {
                                         name: Symbol::new("x")
                                     }
@@ -1555,6 +2348,7 @@ fn parse_funarg_pattern() {
                         },
                         Pattern {
                             ty: (),
+                            span: Span::default(),
                             inner: PatternKind::Variable {
                                 name: Symbol::new("NONE"),
                             }
@@ -1562,9 +2356,11 @@ fn parse_funarg_pattern() {
                     ],
                     Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::App {
                             fun: Expr {
                                 ty: (),
+                                span: Span::default(),
                                 inner: ExprKind::Symbol {
                                     name: Symbol::new("SOME")
                                 }
@@ -1572,6 +2368,7 @@ fn parse_funarg_pattern() {
                             .boxed(),
                             arg: Expr {
                                 ty: (),
+                                span: Span::default(),
                                 inner: ExprKind::Symbol {
                                     name: Symbol::new("x")
                                 }
@@ -1584,12 +2381,14 @@ fn parse_funarg_pattern() {
                     vec![
                         Pattern {
                             ty: (),
+                            span: Span::default(),
                             inner: PatternKind::Variable {
                                 name: Symbol::new("NONE"),
                             }
                         },
                         Pattern {
                             ty: (),
+                            span: Span::default(),
                             inner: PatternKind::Variable {
                                 name: Symbol::new("NONE"),
                             }
@@ -1597,6 +2396,7 @@ fn parse_funarg_pattern() {
                     ],
                     Expr {
                         ty: (),
+                        span: Span::default(),
                         inner: ExprKind::Symbol {
                             name: Symbol::new("NONE"),
                         }
@@ -1618,12 +2418,14 @@ fn parse_multistatement_val_datatype() {
                 rec: false,
                 pattern: Pattern {
                     ty: (),
+                    span: Span::default(),
                     inner: PatternKind::Variable {
                         name: Symbol::new("version")
                     }
                 },
                 expr: Expr {
                     ty: (),
+                    span: Span::default(),
                     inner: ExprKind::Literal {
                         value: Literal::Int(1)
                     }
@@ -1631,6 +2433,7 @@ fn parse_multistatement_val_datatype() {
             },
             Declaration::Datatype {
                 name: Symbol::new("order"),
+                params: vec![],
                 constructors: vec![
                     (Symbol::new("GREATER"), None),
                     (Symbol::new("EQUAL"), None),
@@ -1640,3 +2443,174 @@ fn parse_multistatement_val_datatype() {
         ])
     )
 }
+
+#[test]
+fn parse_cons_is_right_associative() {
+    assert_eq!(
+        parse("val x = 1 :: 2 :: nil").unwrap(),
+        parse("val x = 1 :: (2 :: nil)").unwrap()
+    );
+    assert_eq!(
+        parse("val a :: b :: c = x").unwrap(),
+        parse("val a :: (b :: c) = x").unwrap()
+    );
+}
+
+#[test]
+fn parse_list_literal() {
+    let int = |value| Expr {
+        ty: (),
+        span: Span::default(),
+        inner: ExprKind::Literal {
+            value: Literal::Int(value),
+        },
+    };
+    let cons = |head, tail| Expr {
+        ty: (),
+        span: Span::default(),
+        inner: ExprKind::Constructor {
+            name: Symbol::new("::"),
+            arg: Some(Box::new(Expr {
+                ty: (),
+                span: Span::default(),
+                inner: ExprKind::Tuple {
+                    tuple: vec![head, tail],
+                },
+            })),
+        },
+    };
+    let nil = Expr {
+        ty: (),
+        span: Span::default(),
+        inner: ExprKind::Constructor {
+            name: Symbol::new("nil"),
+            arg: None,
+        },
+    };
+    let ast = parse("val x = [1, 2]").unwrap();
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::Val {
+            rec: false,
+            pattern: Pattern {
+                ty: (),
+                span: Span::default(),
+                inner: PatternKind::Variable {
+                    name: Symbol::new("x"),
+                },
+            },
+            expr: cons(int(1), cons(int(2), nil)),
+        }])
+    )
+}
+
+#[test]
+fn parse_list_pattern() {
+    let var = |name: &str| Pattern {
+        ty: (),
+        span: Span::default(),
+        inner: PatternKind::Variable {
+            name: Symbol::new(name),
+        },
+    };
+    let cons = |head, tail| Pattern {
+        ty: (),
+        span: Span::default(),
+        inner: PatternKind::Constructor {
+            name: Symbol::new("::"),
+            arg: Some(Box::new(Pattern {
+                ty: (),
+                span: Span::default(),
+                inner: PatternKind::Tuple {
+                    tuple: vec![head, tail],
+                },
+            })),
+        },
+    };
+    let nil = Pattern {
+        ty: (),
+        span: Span::default(),
+        inner: PatternKind::Constructor {
+            name: Symbol::new("nil"),
+            arg: None,
+        },
+    };
+    let ast = parse("val [a, b] = x").unwrap();
+    let expected = cons(var("a"), cons(var("b"), nil));
+    match &ast.0[0] {
+        Declaration::Val { pattern, .. } => assert_eq!(*pattern, expected),
+        decl => panic!("expected val, got {:?}", decl),
+    }
+}
+
+#[test]
+fn parse_seq() {
+    let sym = |name: &str| Expr {
+        ty: (),
+        span: Span::default(),
+        inner: ExprKind::Symbol {
+            name: Symbol::new(name),
+        },
+    };
+    let ast = parse("val x = (a; b; c)").unwrap();
+    let expected = ExprKind::Seq {
+        exprs: vec![sym("a"), sym("b"), sym("c")],
+    };
+    match &ast.0[0] {
+        Declaration::Val { expr, .. } => assert_eq!(expr.inner, expected),
+        decl => panic!("expected val, got {:?}", decl),
+    }
+    assert_eq!(
+        parse("val x = let val y = 1 in a; b end").unwrap(),
+        parse("val x = let val y = 1 in (a; b) end").unwrap()
+    );
+}
+
+fn pp_string(ast: &AST<()>) -> String {
+    let mut buf = Vec::new();
+    ast.pp(&mut buf, 0).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+#[test]
+fn pp_round_trip() {
+    let corpus = [
+        "infix 6 + - infix 7 * div val x = 1 + 2 * 3 - 4 div 5",
+        "infix 6 + - infix 7 * val x = (1 + 2) * (3 - 4) val y = a - (b - c) + (d + e)",
+        "infix 6 - val x = ~ a - 1 val y = f ~1 - ~2",
+        "infix 5 @ val l = [1, 2, 3] @ 4 :: 5 :: [] val m = (1 :: [2]) :: [[3], []]",
+        "infix 6 + val x = f a + g (b + c) d val y = (f a) (g b)",
+        "val x = let infix 4 <> val y = a <> b in y <> c end val z = a <> b",
+        r#"val s = ("a\"b\\c\n", #"\t", ~3, 0w7, 0x10, 1.5, ~0.25, 1.0e20, 1.5e10)"#,
+        "datatype 'a tree = Leaf | Node of 'a tree * 'a * 'a tree \
+         infix 6 + fun size Leaf = 0 | size (Node (l, _, r)) = size l + 1 + size r",
+        "datatype ('a, 'b) either = Left of 'a | Right of 'b option \
+         datatype t = T of (int -> int) * int -> real ref | U of word",
+        "fun f 0 = (case x of 1 => 2 | _ => 3) \
+         | f n = fn y => if y then n else case n of ~1 => 0 | _ => n",
+        "val x = case l of [] => 0 | [a] => a | a :: b :: rest => a \
+         | (SOME x | NONE) :: _ => 1 | y as (z, w) => 2",
+        "fun g (SOME (x :: xs)) [] = (fn y => y) | g _ _ = f (fn y => y) (if a then b else c)",
+        "val f = fn x => (print x; (x : int)) val () = ()",
+        r#"val g = _builtincall "add"(1, 2) val h = _externcall ("js"."log": (string) -> unit) ("hi")"#,
+        r#"val q = _externcall ("m"."divmod": (int, int) -> (int, int)) (7, 2)"#,
+        r#"_import ("js"."log": (string) -> unit) val h = _externcall ("js"."log") ("hi")"#,
+        "infix 4 = infix 6 - val rec even = fn n => if n = 0 then true else odd (n - 1) \
+         and odd = fn n => if n = 0 then false else even (n - 1)",
+        "fun f x = g x and g x = f x fun op+ (x, y) = x",
+        "val x = let val y = 1 in a; b end",
+        "datatype t = A | B of int val f = fn A => 0 | B n => n \
+         val g = (fn (a, b) => a) (1, 2) val h = fn A => (fn B _ => 1 | _ => 2) | _ => 3",
+        "infix 4 = val x = a andalso b orelse c andalso (d orelse e) \
+         val y = (a orelse b) andalso f (c = d) val z = a andalso (if b then c else d)",
+        r#"fun f "yes" = 1 | f "\tno\n" = 0 | f _ = ~1 val g = fn ("", x) => x | (s, _) => s"#,
+        r#"export "inc" fun inc x = x export "one" val one = fn () => 1 val two = 2"#,
+    ];
+    for src in corpus.iter() {
+        let ast = parse(src).unwrap();
+        let printed = pp_string(&ast);
+        let reparsed =
+            parse(&printed).unwrap_or_else(|e| panic!("failed to parse {}: {:?}", printed, e));
+        assert_eq!(reparsed, ast, "printed as:\n{}", printed);
+    }
+}
@@ -0,0 +1,58 @@
+use std::thread;
+use std::time::Duration;
+use webml::pass::{Pass, TimedPass, Timings};
+use webml::{compile, Config};
+
+fn add_one(x: i32) -> Result<i32, ()> {
+    thread::sleep(Duration::from_millis(1));
+    Ok(x + 1)
+}
+
+#[test]
+fn timed_pass_records_duration() {
+    let config = Config {
+        time_passes: true,
+        ..Config::default()
+    };
+    let mut pass = TimedPass(add_one, "add_one");
+    let ((), timings) = Timings::collect(|| {
+        assert_eq!(pass.trans(1, &config), Ok(2));
+        assert_eq!(pass.trans(2, &config), Ok(3));
+    });
+    let entries = timings.entries();
+    assert_eq!(entries.len(), 2);
+    for (name, duration) in entries {
+        assert_eq!(*name, "add_one");
+        assert!(*duration > Duration::from_secs(0));
+    }
+}
+
+#[test]
+fn timed_pass_records_nothing_by_default() {
+    let config = Config::default();
+    let mut pass = TimedPass(add_one, "add_one");
+    let (ret, timings) = Timings::collect(|| pass.trans(1, &config));
+    assert_eq!(ret, Ok(2));
+    assert!(timings.entries().is_empty());
+}
+
+#[test]
+fn compile_returns_timings() {
+    let config = Config {
+        time_passes: true,
+        ..Config::default()
+    };
+    let output = compile("val x = 1", &config);
+    assert!(output.code.is_ok());
+    let names = output
+        .timings
+        .entries()
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+    assert_eq!(names.first(), Some(&"parse"));
+    assert_eq!(names.last(), Some(&"backend"));
+    // the records are not left for the next compilation
+    let output = compile("val x = 1", &Config::default());
+    assert!(output.timings.entries().is_empty());
+}
@@ -0,0 +1,25 @@
+use webml::prim::{Name, Symbol};
+
+#[test]
+fn equal_names_intern_to_the_same_id() {
+    let a = Name::new("interned");
+    let b = Name::from(String::from("interned"));
+    assert_eq!(a, b);
+    assert_eq!(a.id(), b.id());
+    assert_ne!(a.id(), Name::new("other").id());
+}
+
+#[test]
+fn interned_name_keeps_its_string() {
+    let name = Name::new("x'");
+    assert_eq!(name.as_str(), "x'");
+    assert_eq!(name.to_string(), "x'");
+    assert_eq!(format!("{:?}", name), "\"x'\"");
+    assert_eq!(name, "x'");
+}
+
+#[test]
+fn symbols_compare_names_and_ids() {
+    assert_eq!(Symbol::new("s"), Symbol::new(String::from("s")));
+    assert_ne!(Symbol::new("s"), Symbol("s".into(), 1));
+}
@@ -0,0 +1,1509 @@
+use webml::ast::{
+    Annot, Declaration, Desugar, ExprKind, PatternKind, Rename, SymbolTable, Type, TypedCore,
+    Typer, UntypedCore, VarToConstructor,
+};
+use webml::compile_pass;
+use webml::id::Id;
+use webml::pass::{Chain, ConvError, Pass, PrintablePass};
+use webml::prim::{Literal, Span, Symbol};
+use webml::util::PP;
+use webml::{parse, Config, DefaultNum, TypeError};
+
+fn typecheck<'a>(input: &'a str) -> Result<TypedCore, TypeError<'a>> {
+    typecheck_with(input, &Config::default())
+}
+
+fn typecheck_with<'a>(input: &'a str, config: &Config) -> Result<TypedCore, TypeError<'a>> {
+    let id = Id::new();
+    let mut passes = compile_pass![
+        parse: ConvError::new(parse),
+        desugar: Desugar::new(id.clone()),
+        rename: Rename::new(id.clone()),
+        var_to_constructor: VarToConstructor::new(id.clone()),
+        typing: Typer::new(),
+    ];
+    let (_, ast) = passes.trans(input, config)?;
+    Ok(ast)
+}
+
+fn typeof_val(ast: &TypedCore, name: &str) -> Type {
+    ast.0
+        .iter()
+        .filter_map(|decl| match decl {
+            Declaration::Val { pattern, .. } => match &pattern.inner {
+                PatternKind::Variable { name: sym } if sym.0 == name => Some(pattern.ty.clone()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .last()
+        .expect("no such val")
+}
+
+#[test]
+fn polymorphic_id() {
+    let input = r#"
+val id = fn x => x
+val a = id 1
+val b = id #"c"
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "a"), Type::Int);
+    assert_eq!(typeof_val(&ast, "b"), Type::Char);
+}
+
+#[test]
+fn expansive_binding_is_monomorphic() {
+    let input = r#"
+val id = (fn x => x) (fn y => y)
+val a = id 1
+val b = id #"c"
+"#;
+    match typecheck(input) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn mismatch_span() {
+    let input = r#"val x = 1
+val y = (1, x #"c")"#;
+    let err = typecheck(input).unwrap_err();
+    let span = err.span().expect("no span");
+    assert_eq!(&input[span.start..span.end], r#"x #"c""#);
+}
+
+#[test]
+fn bool_literals() {
+    let input = r#"
+val t = true
+val f = false
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "t"), Type::Bool);
+    assert_eq!(typeof_val(&ast, "f"), Type::Bool);
+}
+
+#[test]
+fn if_expression() {
+    let input = r#"
+val x = if true then 1 else 2
+val f = fn b => if b then #"a" else #"b"
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "x"), Type::Int);
+    assert_eq!(typeof_val(&ast, "f").to_string(), "bool -> char");
+}
+
+#[test]
+fn if_condition_mismatch_span() {
+    let input = r#"infix 6 +
+val n = 1
+val x = if n + 1 then 2 else 3"#;
+    match typecheck(input) {
+        Err(TypeError::MisMatch {
+            span: Some(span), ..
+        }) => assert_eq!(&input[span.start..span.end], "n + 1"),
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn seq_has_type_of_last() {
+    let input = r#"
+fun print x = _externcall("js-ffi"."print": (int) -> unit)(x)
+val x = (print 1; print 2; #"c")
+val y = let val z = 1 in print z; print z end
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "x"), Type::Char);
+    assert_eq!(typeof_val(&ast, "y"), Type::Unit);
+}
+
+#[test]
+fn seq_requires_unit() {
+    let input = r#"val x = (1; 2)"#;
+    match typecheck(input) {
+        Err(TypeError::MisMatch {
+            span: Some(span), ..
+        }) => assert_eq!(&input[span.start..span.end], "1"),
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn neg_int_and_real() {
+    let input = r#"
+val x = 1
+val a = ~x
+val y = 1.5
+val b = ~y
+val c = ~(~2)
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "a"), Type::Int);
+    assert_eq!(typeof_val(&ast, "b"), Type::Real);
+    assert_eq!(typeof_val(&ast, "c"), Type::Int);
+}
+
+#[test]
+fn abs_int_and_real() {
+    let input = r#"
+val a = abs (~1)
+val b = abs 1.5
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "a"), Type::Int);
+    assert_eq!(typeof_val(&ast, "b"), Type::Real);
+}
+
+#[test]
+fn real_comparison() {
+    let input = r#"
+infix 4 <
+val b = 1.0 < 2.0
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "b"), Type::Bool);
+}
+
+#[test]
+fn int_real_conversions() {
+    let input = r#"
+val r = real 1
+val f = floor 1.5
+val c = ceil 1.5
+val n = round 1.5
+val t = trunc 1.5
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "r"), Type::Real);
+    for name in &["f", "c", "n", "t"] {
+        assert_eq!(typeof_val(&ast, name), Type::Int);
+    }
+}
+
+#[test]
+fn conversions_fix_operand_types() {
+    for input in &["val r = real 1.0", "val f = floor 1", "val t = trunc 1"] {
+        match typecheck(input) {
+            Err(TypeError::MisMatch { .. }) => (),
+            res => panic!("expected type mismatch for {}, got {:?}", input, res),
+        }
+    }
+}
+
+#[test]
+fn sqrt_and_pow_are_real() {
+    let input = r#"
+val s = sqrt 4.0
+val p = pow (2.0, 10.0)
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "s"), Type::Real);
+    assert_eq!(typeof_val(&ast, "p"), Type::Real);
+}
+
+#[test]
+fn pow_rejects_int() {
+    let input = "val p = pow (2, 10)";
+    match typecheck(input) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn modf_is_real() {
+    let input = r#"val x = _builtincall "modf"(5.5, 2.0)"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "x"), Type::Real);
+    match typecheck(r#"val x = _builtincall "modf"(5, 2)"#) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn ord_and_chr() {
+    let input = r#"
+val o = ord #"A"
+val c = chr 65
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "o"), Type::Int);
+    assert_eq!(typeof_val(&ast, "c"), Type::Char);
+    match typecheck("val c = chr #\"A\"") {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn shift_is_int() {
+    let input = r#"
+val n = 4
+val x = _builtincall "shl"(1, n)
+val y = _builtincall "shr"(x, n)
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "x"), Type::Int);
+    assert_eq!(typeof_val(&ast, "y"), Type::Int);
+}
+
+#[test]
+fn shift_rejects_real_amount() {
+    let input = r#"val x = _builtincall "shl"(1, 4.0)"#;
+    match typecheck(input) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn bitwise_ops_are_int() {
+    let input = r#"
+val a = _builtincall "andb"(12, 10)
+val o = _builtincall "orb"(12, 10)
+val x = _builtincall "xorb"(12, 10)
+val n = _builtincall "notb"(0)
+"#;
+    let ast = typecheck(input).unwrap();
+    for name in &["a", "o", "x", "n"] {
+        assert_eq!(typeof_val(&ast, name), Type::Int);
+    }
+}
+
+#[test]
+fn bitwise_ops_reject_real() {
+    let inputs = [
+        r#"val a = _builtincall "andb"(1.0, 2)"#,
+        r#"val o = _builtincall "orb"(1, 2.0)"#,
+        r#"val x = _builtincall "xorb"(1.0, 2.0)"#,
+        r#"val n = _builtincall "notb"(1.0)"#,
+    ];
+    for input in &inputs {
+        match typecheck(input) {
+            Err(TypeError::MisMatch { .. }) => (),
+            res => panic!("expected type mismatch for {}, got {:?}", input, res),
+        }
+    }
+}
+
+#[test]
+fn neg_requires_number() {
+    let input = r#"val x = ~ #"a""#;
+    match typecheck(input) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn mismatch_names_type_variables() {
+    let input = "val x = (fn p => case p of (a, b) => a) 1";
+    match typecheck(input) {
+        Err(TypeError::MisMatch {
+            expected, actual, ..
+        }) => {
+            assert_eq!(
+                expected,
+                Type::Tuple(vec![Type::Variable(0), Type::Variable(1)])
+            );
+            assert_eq!(actual, Type::Int);
+            let mut buf = Vec::new();
+            expected.pp(&mut buf, 0).unwrap();
+            assert_eq!(String::from_utf8(buf).unwrap(), "'a * 'b");
+        }
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn unit_and_empty_tuple() {
+    let input = r#"
+val a = ()
+val b = _externcall("js-ffi"."print": (int) -> unit)(1)
+val c = if true then a else b
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "a"), Type::Unit);
+    assert_eq!(typeof_val(&ast, "b"), Type::Unit);
+    assert_eq!(typeof_val(&ast, "c"), Type::Unit);
+}
+
+#[test]
+fn externcall_with_multiple_results() {
+    let input = r#"
+val p = _externcall("m"."divmod": (int, int) -> (int, real))(7, 2)
+val (q, r) = p
+val x = q
+val y = r
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(
+        typeof_val(&ast, "p"),
+        Type::Tuple(vec![Type::Int, Type::Real])
+    );
+    assert_eq!(typeof_val(&ast, "x"), Type::Int);
+    assert_eq!(typeof_val(&ast, "y"), Type::Real);
+}
+
+#[test]
+fn string_literal() {
+    let input = r#"
+infix 4 <
+val s = "hello"
+val t = "a" < s
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "s"), Type::String);
+    assert_eq!(typeof_val(&ast, "t"), Type::Bool);
+}
+
+#[test]
+fn string_pattern() {
+    let input = r#"fun answer "yes" = true | answer "no" = false | answer _ = false"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "answer").to_string(), "string -> bool");
+
+    let input = r#"val x = case 1 of "one" => 1 | _ => 0"#;
+    match typecheck(input) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected mismatch, got {:?}", res),
+    }
+}
+
+fn datatype_args(ty: Type, name: &str) -> Vec<Type> {
+    match ty {
+        Type::Datatype(sym, args) if sym.0 == name => args,
+        ty => panic!("expected {}, got {:?}", name, ty),
+    }
+}
+
+#[test]
+fn parametric_datatype() {
+    let input = r#"
+datatype 'a option = NONE | SOME of 'a
+val a = SOME 3
+val b = SOME #"c"
+val c = case a of SOME x => x | NONE => 0
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(
+        datatype_args(typeof_val(&ast, "a"), "option"),
+        vec![Type::Int]
+    );
+    assert_eq!(
+        datatype_args(typeof_val(&ast, "b"), "option"),
+        vec![Type::Char]
+    );
+    assert_eq!(typeof_val(&ast, "c"), Type::Int);
+}
+
+#[test]
+fn parametric_datatype_mismatch() {
+    let input = r#"
+datatype 'a option = NONE | SOME of 'a
+val a = if true then SOME 1 else SOME #"c"
+"#;
+    match typecheck(input) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn value_restriction() {
+    let input = r#"
+datatype 'a box = Box of 'a
+val r = Box (fn x => x)
+val a = case r of Box f => f 1
+val b = case r of Box f => f #"c"
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "a"), Type::Int);
+    assert_eq!(typeof_val(&ast, "b"), Type::Char);
+
+    let input = r#"
+datatype 'a box = Box of 'a
+val r = Box ((fn x => x) (fn y => y))
+val a = case r of Box f => f 1
+val b = case r of Box f => f #"c"
+"#;
+    match typecheck(input) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn mutual_recursion() {
+    let input = r#"
+infix 6 -
+infix 4 =
+fun even n = if n = 0 then true else odd (n - 1)
+and odd n = if n = 0 then false else even (n - 1)
+val a = even 10
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "a"), Type::Bool);
+}
+
+#[test]
+fn mutual_recursion_generalized_together() {
+    let input = r#"
+val rec f = fn x => g x
+and g = fn y => f y
+val a = f 1
+val b = g #"c"
+"#;
+    assert!(typecheck(input).is_ok());
+}
+
+#[test]
+fn annotation_pins_overloaded_num() {
+    let input = r#"
+infix 6 +
+val f = fn x => (x + x : real)
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(
+        typeof_val(&ast, "f"),
+        Type::Fun(Box::new(Type::Real), Box::new(Type::Real))
+    );
+}
+
+#[test]
+fn wrong_annotation() {
+    let input = r#"val x = (1 : real)"#;
+    match typecheck(input) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn structural_equality() {
+    let input = r#"
+infix 4 =
+datatype t = A | B of int
+val a = (1, #"c") = (1, #"c")
+val b = B 1 = A
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "a"), Type::Bool);
+    assert_eq!(typeof_val(&ast, "b"), Type::Bool);
+}
+
+#[test]
+fn function_equality() {
+    let input = r#"
+infix 4 =
+val a = (fn x => x) = (fn y => y)
+"#;
+    match typecheck(input) {
+        Err(TypeError::NotEqualityType { .. }) => (),
+        res => panic!("expected equality type error, got {:?}", res),
+    }
+}
+
+#[test]
+fn datatype_of_function_equality() {
+    for input in &[
+        "datatype t = F of int -> int\nval a = fn (f, g) => F f = F g",
+        // through the recursion and a tuple
+        "datatype t = A | B of t * (int -> int)\nval a = fn (x, y) => B (A, x) = y",
+        // the type of the operands is known after the comparison
+        "datatype t = F of int -> int\nfun eq (x, y) = x = y\nval a = eq (F (fn x => x), F (fn y => y))",
+    ] {
+        let input = format!("infix 4 =\n{}", input);
+        match typecheck(&input) {
+            Err(TypeError::NotEqualityType { span, .. }) => assert!(span.is_some(), "{}", input),
+            res => panic!("expected equality type error, got {:?}", res),
+        }
+    }
+    let input = r#"
+infix 4 =
+datatype l = N | C of int * l
+val a = C (1, N) = N
+"#;
+    assert!(typecheck(input).is_ok());
+}
+
+#[test]
+fn unknown_type_in_datatype() {
+    let input = "datatype t = C of u";
+    match typecheck(input) {
+        Err(TypeError::UnknownType { name }) => assert_eq!(name.0, "u"),
+        res => panic!("expected unknown type error, got {:?}", res),
+    }
+}
+
+#[test]
+fn default_num() {
+    let input = r#"
+infix 6 +
+val f = fn x => x + x
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(
+        typeof_val(&ast, "f"),
+        Type::Fun(Box::new(Type::Int), Box::new(Type::Int))
+    );
+
+    let config = Config {
+        default_num: DefaultNum::Real,
+        ..Config::default()
+    };
+    let ast = typecheck_with(input, &config).unwrap();
+    assert_eq!(
+        typeof_val(&ast, "f"),
+        Type::Fun(Box::new(Type::Real), Box::new(Type::Real))
+    );
+}
+
+#[test]
+fn word_arithmetic() {
+    let input = r#"
+val a = _builtincall "add"(0w1, 0wx10)
+val d = _builtincall "div"(a, 0w2)
+val s = _builtincall "shr"(d, 0w1)
+val c = _builtincall "lt"(a, d)
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "a"), Type::Word);
+    assert_eq!(typeof_val(&ast, "d"), Type::Word);
+    assert_eq!(typeof_val(&ast, "s"), Type::Word);
+    assert_eq!(typeof_val(&ast, "c"), Type::Bool);
+}
+
+#[test]
+fn word_and_int_do_not_unify() {
+    for input in &[
+        r#"val x = _builtincall "add"(0w1, 1)"#,
+        r#"val x = _builtincall "div"(1, 0w1)"#,
+        r#"val x = (0w1 : int)"#,
+    ] {
+        match typecheck(input) {
+            Err(TypeError::MisMatch { .. }) => (),
+            res => panic!("expected type mismatch for {}, got {:?}", input, res),
+        }
+    }
+}
+
+#[test]
+fn default_num_word() {
+    let input = r#"
+infix 6 +
+val f = fn x => x + x
+val g = fn x => _builtincall "div"(x, x)
+"#;
+    let config = Config {
+        default_num: DefaultNum::Word,
+        ..Config::default()
+    };
+    let ast = typecheck_with(input, &config).unwrap();
+    let word_to_word = Type::Fun(Box::new(Type::Word), Box::new(Type::Word));
+    assert_eq!(typeof_val(&ast, "f"), word_to_word);
+    assert_eq!(typeof_val(&ast, "g"), word_to_word);
+
+    // division is on ints unless told otherwise
+    let ast = typecheck(input).unwrap();
+    assert_eq!(
+        typeof_val(&ast, "g"),
+        Type::Fun(Box::new(Type::Int), Box::new(Type::Int))
+    );
+}
+
+#[test]
+fn recursive_datatype() {
+    let input = "datatype t = C of t * int";
+    match typecheck(input) {
+        Err(TypeError::RecursiveType { name }) => assert_eq!(name.0, "t"),
+        res => panic!("expected recursive type error, got {:?}", res),
+    }
+
+    let input = "datatype list = Nil | Cons of int * list";
+    assert!(typecheck(input).is_ok());
+}
+
+#[test]
+fn display_type() {
+    let int_to_int = Type::fun(Type::Int, Type::Int);
+    assert_eq!(
+        Type::fun(Type::Int, int_to_int.clone()).to_string(),
+        "int -> int -> int"
+    );
+    assert_eq!(
+        Type::fun(int_to_int.clone(), Type::Int).to_string(),
+        "(int -> int) -> int"
+    );
+    assert_eq!(
+        Type::fun(Type::Tuple(vec![Type::Int, Type::Int]), Type::Int).to_string(),
+        "(int, int) -> int"
+    );
+    assert_eq!(
+        Type::fun(Type::Variable(0), Type::Variable(0)).to_string(),
+        "'a -> 'a"
+    );
+    assert_eq!(
+        Type::Datatype(Symbol::new("option"), vec![int_to_int]).to_string(),
+        "(int -> int) option"
+    );
+}
+
+fn pp_type(ty: &Type) -> String {
+    let mut buf = Vec::new();
+    ty.pp(&mut buf, 0).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+#[test]
+fn pp_type_parenthesizes_by_precedence() {
+    let int_to_int = Type::fun(Type::Int, Type::Int);
+    let pair = |t1: Type, t2: Type| Type::Tuple(vec![t1, t2]);
+    assert_eq!(
+        pp_type(&Type::fun(Type::Int, int_to_int.clone())),
+        "int -> int -> int"
+    );
+    assert_eq!(
+        pp_type(&Type::fun(int_to_int.clone(), Type::Int)),
+        "(int -> int) -> int"
+    );
+    assert_eq!(
+        pp_type(&pair(int_to_int.clone(), Type::Int)),
+        "(int -> int) * int"
+    );
+    assert_eq!(
+        pp_type(&Type::fun(pair(Type::Int, Type::Int), Type::Int)),
+        "int * int -> int"
+    );
+    assert_eq!(
+        pp_type(&pair(pair(Type::Int, Type::Int), Type::Int)),
+        "(int * int) * int"
+    );
+    assert_eq!(
+        pp_type(&Type::Datatype(
+            Symbol::new("option"),
+            vec![pair(Type::Int, Type::Int)]
+        )),
+        "(int * int) option"
+    );
+}
+
+#[test]
+fn let_binding_does_not_leak() {
+    let input = r#"
+val x = 1
+val y = let val x = #"c" in x end
+val z = x
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "y"), Type::Char);
+    assert_eq!(typeof_val(&ast, "z"), Type::Int);
+}
+
+#[test]
+fn shared_types_are_resolved() {
+    let input = r#"
+val pair = fn x => (x, x)
+val a = pair (pair (pair 1))
+"#;
+    let ast = typecheck(input).unwrap();
+    let int2 = Type::Tuple(vec![Type::Int, Type::Int]);
+    let int4 = Type::Tuple(vec![int2.clone(), int2]);
+    assert_eq!(typeof_val(&ast, "a"), Type::Tuple(vec![int4.clone(), int4]));
+}
+
+#[test]
+fn as_pattern_binds_whole_and_parts() {
+    let input = r#"
+val x = case (1, #"c") of p as (a, b) => (p, a, b)
+"#;
+    let ast = typecheck(input).unwrap();
+    let pair = Type::Tuple(vec![Type::Int, Type::Char]);
+    assert_eq!(
+        typeof_val(&ast, "x"),
+        Type::Tuple(vec![pair, Type::Int, Type::Char])
+    );
+}
+
+#[test]
+fn constructor_pattern_binds_nested_parts() {
+    let input = r#"
+datatype option = NONE | SOME of int * char
+datatype list = NIL | CONS of int * list
+val x = case SOME (1, #"c") of SOME (a, b) => b | NONE => #"d"
+val y = case CONS (1, NIL) of CONS (h, t) => t | NIL => NIL
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "x"), Type::Char);
+    assert_eq!(
+        typeof_val(&ast, "y").to_string(),
+        Type::Datatype(Symbol::new("list"), vec![]).to_string()
+    );
+}
+
+#[test]
+fn tuple_pattern_with_mixed_elements() {
+    let input = r#"
+datatype option = NONE | SOME of char
+fun f t = case t of (x, _, SOME y) => y | (0, true, NONE) => #"a" | (_, _, NONE) => #"b"
+val x = f (1, false, SOME #"c")
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "x"), Type::Char);
+    assert_eq!(
+        typeof_val(&ast, "f").to_string(),
+        "int * bool * option -> char"
+    );
+}
+
+#[test]
+fn list_literal() {
+    let input = r#"
+val x = [1, 2, 3]
+val y = 1 :: 2 :: nil
+val z = [[#"a"], []]
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "x").to_string(), "int list");
+    assert_eq!(typeof_val(&ast, "y").to_string(), "int list");
+    assert_eq!(typeof_val(&ast, "z").to_string(), "char list list");
+}
+
+#[test]
+fn list_pattern_head_and_tail() {
+    let input = r#"
+val l = [1, 2, 3]
+val h = case l of x :: _ => x | [] => 0
+val t = case l of _ :: xs => xs | [] => []
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "h"), Type::Int);
+    assert_eq!(typeof_val(&ast, "t").to_string(), "int list");
+}
+
+#[test]
+fn list_elements_must_agree() {
+    let input = r#"
+val x = [1, #"a"]
+"#;
+    match typecheck(input) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn ref_cell_create_deref_assign() {
+    let input = r#"
+infix 3 :=
+val r = ref 1
+val x = !r
+val u = r := 2
+val c = (ref #"a" : char ref)
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "r").to_string(), "int ref");
+    assert_eq!(typeof_val(&ast, "x"), Type::Int);
+    assert_eq!(typeof_val(&ast, "u"), Type::Unit);
+    assert_eq!(typeof_val(&ast, "c").to_string(), "char ref");
+}
+
+#[test]
+fn ref_cell_contents_must_agree() {
+    let input = r#"
+infix 3 :=
+val r = ref 1
+val u = r := #"c"
+"#;
+    match typecheck(input) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn ref_cell_is_not_generalized() {
+    let input = r#"
+infix 3 :=
+val r = ref (fn x => x)
+val u = r := (fn x => x + 1)
+val b = !r #"c"
+"#;
+    match typecheck(input) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn or_pattern_alternatives_bind_same_names() {
+    let input = r#"
+val x = case (1, 2) of (0, n) | (n, _) => n
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "x"), Type::Int);
+}
+
+#[test]
+fn or_pattern_alternatives_bind_different_names() {
+    let input = r#"
+val x = case (1, 2) of (0, n) | (m, _) => 1
+"#;
+    match typecheck(input) {
+        Err(TypeError::OrPatternBinds { name, .. }) => assert_eq!(name.0, "n"),
+        res => panic!("expected or-pattern error, got {:?}", res),
+    }
+}
+
+#[test]
+fn or_pattern_alternatives_bind_different_types() {
+    let input = r#"
+datatype t = A of int | B of char
+val f = fn x => case x of A v | B v => 1
+"#;
+    match typecheck(input) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn comparison_without_bool_datatype() {
+    let input = r#"val t = _builtincall "lt"(1, 2)"#;
+    let id = Id::new();
+    let mut passes = compile_pass![
+        parse: ConvError::new(parse),
+        desugar: Desugar::new(id.clone()),
+        rename: Rename::new(id.clone()),
+        var_to_constructor: VarToConstructor::new(id),
+    ];
+    let result: Result<(SymbolTable, UntypedCore), TypeError> =
+        passes.trans(input, &Config::default());
+    let (mut symbol_table, ast) = result.unwrap();
+    let bool = Symbol::new("bool");
+    symbol_table.types.remove(&bool);
+    symbol_table.constructors.retain(|_, ty| *ty != bool);
+    let (_, ast) = Typer::new()
+        .trans((symbol_table, ast), &Config::default())
+        .unwrap();
+    assert_eq!(typeof_val(&ast, "t"), Type::Bool);
+}
+
+#[test]
+fn type_error_messages() {
+    let var = |id| Type::Variable(id);
+    let fun = |param, ret| Type::Fun(Box::new(param), Box::new(ret));
+    let message = |e: TypeError| e.to_string();
+    assert_eq!(
+        message(TypeError::MisMatch {
+            expected: fun(var(42), var(42)),
+            actual: fun(var(7), Type::Int),
+            span: None,
+        }),
+        "expected 'a -> 'a but found 'b -> int"
+    );
+    assert_eq!(
+        message(TypeError::InfiniteType {
+            var: var(3),
+            ty: Type::Datatype(Symbol::new("list"), vec![var(3)]),
+            span: None,
+        }),
+        "type variable 'a occurs in 'a list"
+    );
+    assert_eq!(
+        message(TypeError::NotEqualityType {
+            ty: Type::Real,
+            span: None,
+        }),
+        "real does not admit equality"
+    );
+    assert_eq!(
+        message(TypeError::UnknownType {
+            name: Symbol::new("t"),
+        }),
+        "unknown type constructor t"
+    );
+    assert_eq!(
+        message(TypeError::RecursiveType {
+            name: Symbol::new("t"),
+        }),
+        "datatype t has no finite values"
+    );
+    assert_eq!(message(TypeError::CannotInfer), "cannot infer the type");
+    assert_eq!(
+        message(TypeError::FreeVar {
+            name: Symbol::new("lenght"),
+            suggestion: None,
+            span: None,
+        }),
+        "unbound identifier `lenght`"
+    );
+    assert_eq!(
+        message(TypeError::NotFunction(Annot {
+            ty: Type::Int,
+            span: Span::default(),
+            inner: ExprKind::Literal {
+                value: Literal::Int(1),
+            },
+        })),
+        "1 is not a function"
+    );
+    assert_eq!(
+        message(TypeError::OrPatternBinds {
+            name: Symbol::new("x"),
+            span: None,
+        }),
+        "x is not bound in all the alternatives of the or-pattern"
+    );
+    assert_eq!(
+        message(TypeError::ParseError(nom::Err::Error((
+            "val = 1\nval y = 2",
+            nom::error::ErrorKind::Tag,
+        )))),
+        "parse error (Tag) near `val = 1`"
+    );
+    assert_eq!(
+        message(TypeError::InvalidPipeline(
+            "the pipeline lacks flat_let".into()
+        )),
+        "invalid pipeline: the pipeline lacks flat_let"
+    );
+}
+
+#[test]
+fn type_error_message_from_typing() {
+    match typecheck("val x = 1 + true") {
+        Err(e @ TypeError::MisMatch { .. }) => {
+            let message = e.to_string();
+            assert!(message.starts_with("expected "), "{}", message);
+            assert!(message.contains("bool"), "{}", message);
+        }
+        res => panic!("expected mismatch error, got {:?}", res),
+    }
+}
+
+#[test]
+fn collect_type_errors_of_declarations() {
+    let input = "val x = 1 + true val y = x + 1 val z = if 1 then 2 else 3";
+    match typecheck(input) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected a single mismatch error, got {:?}", res),
+    }
+    let config = Config {
+        collect_type_errors: true,
+        ..Config::default()
+    };
+    match typecheck_with(input, &config) {
+        Err(TypeError::Multiple(errors)) => {
+            assert_eq!(errors.len(), 2, "{:?}", errors);
+            for e in errors {
+                match e {
+                    TypeError::MisMatch { .. } => (),
+                    e => panic!("expected mismatch error, got {:?}", e),
+                }
+            }
+        }
+        res => panic!("expected two errors, got {:?}", res),
+    }
+}
+
+#[test]
+fn free_var_suggests_similar_name() {
+    let message = |input| match typecheck(input) {
+        Err(e @ TypeError::FreeVar { .. }) => e.to_string(),
+        res => panic!("expected unbound identifier error, got {:?}", res),
+    };
+    assert_eq!(
+        message("val length = 1 val x = lenght"),
+        "unbound identifier `lenght`, did you mean `length`?"
+    );
+    assert_eq!(
+        message("datatype t = Foo | Bar val x = Fooo"),
+        "unbound identifier `Fooo`, did you mean `Foo`?"
+    );
+    assert_eq!(
+        message("val length = 1 val x = width"),
+        "unbound identifier `width`"
+    );
+}
+
+#[test]
+fn recovered_mismatch_does_not_cascade() {
+    // `y` is unified with bool before the arms of the `if` mismatch,
+    // which must not make `y + 1` mismatch again
+    let input = "val g = fn y => (if y then 1 else 2.0, y + 1)";
+    let config = Config {
+        collect_type_errors: true,
+        ..Config::default()
+    };
+    match typecheck_with(input, &config) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected a single mismatch error, got {:?}", res),
+    }
+}
+
+#[test]
+fn pattern_mismatch_keeps_later_declarations() {
+    // the failed unification of the pattern with `1` must leave the type of `1` usable
+    let input = "val (a, b) = 1 val c = 1 + 2 val d = a";
+    let config = Config {
+        collect_type_errors: true,
+        ..Config::default()
+    };
+    match typecheck_with(input, &config) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected a single mismatch error, got {:?}", res),
+    }
+}
+
+#[test]
+fn recover_from_mismatch_in_function_body() {
+    let input = r#"fun f x = (1 + true, "a" + 2)"#;
+    match typecheck(input) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected a single mismatch error, got {:?}", res),
+    }
+    let config = Config {
+        collect_type_errors: true,
+        ..Config::default()
+    };
+    match typecheck_with(input, &config) {
+        Err(TypeError::Multiple(errors)) => {
+            assert_eq!(errors.len(), 2, "{:?}", errors);
+            for e in errors {
+                match e {
+                    TypeError::MisMatch { .. } => (),
+                    e => panic!("expected mismatch error, got {:?}", e),
+                }
+            }
+        }
+        res => panic!("expected two errors, got {:?}", res),
+    }
+}
+
+#[test]
+fn andalso_orelse_are_bool() {
+    let input = r#"
+val f = fn x => fn y => x andalso y orelse x
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(
+        typeof_val(&ast, "f"),
+        Type::Fun(
+            Box::new(Type::Bool),
+            Box::new(Type::Fun(Box::new(Type::Bool), Box::new(Type::Bool)))
+        )
+    );
+    for input in &["val x = 1 andalso true", "val x = true orelse #\"c\""] {
+        match typecheck(input) {
+            Err(TypeError::MisMatch { .. }) => (),
+            res => panic!("expected type mismatch, got {:?}", res),
+        }
+    }
+}
+
+#[test]
+fn let_bindings_see_earlier_ones() {
+    let input = r#"
+val x = let
+  val a = 1
+  val b = (a, #"c")
+  fun f y = (y, b)
+  val a = f a
+in
+  a
+end
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(
+        typeof_val(&ast, "x"),
+        Type::Tuple(vec![Type::Int, Type::Tuple(vec![Type::Int, Type::Char])])
+    );
+}
+
+#[test]
+fn let_local_name_is_not_visible_outside() {
+    let input = r#"
+val x = let val hidden = 1 in hidden end
+val y = hidden
+"#;
+    match typecheck(input) {
+        Err(TypeError::FreeVar { name, .. }) => assert_eq!(name.0, "hidden"),
+        res => panic!("expected unbound identifier, got {:?}", res),
+    }
+}
+
+#[test]
+fn local_datatype() {
+    let input = r#"
+val x = let
+  datatype shape = Square of int | Rect of int * int
+  fun area (Square n) = n * n
+    | area (Rect (w, h)) = w * h
+in
+  area (Square 2) + area (Rect (2, 3))
+end
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "x"), Type::Int);
+
+    // the constructors go out of scope with the `let`
+    let input = r#"
+val x = let datatype t = Foo in 1 end
+val y = Foo
+"#;
+    match typecheck(input) {
+        Err(TypeError::FreeVar {
+            name, suggestion, ..
+        }) => {
+            assert_eq!(name.0, "Foo");
+            assert_eq!(suggestion, None);
+        }
+        res => panic!("expected unbound identifier, got {:?}", res),
+    }
+
+    // and so does the type
+    let input = r#"
+val x = let datatype t = Foo in 1 end
+val y = fn (z: t) => z
+"#;
+    match typecheck(input) {
+        Err(TypeError::UnknownType { name }) => assert_eq!(name.0, "t"),
+        res => panic!("expected unknown type, got {:?}", res),
+    }
+}
+
+#[test]
+fn local_constructor_is_unbound_outside_its_let() {
+    // before the `let`
+    let input = r#"
+val y = Foo
+val x = let datatype t = Foo in 1 end
+"#;
+    match typecheck(input) {
+        Err(TypeError::FreeVar { name, .. }) => assert_eq!(name.0, "Foo"),
+        res => panic!("expected unbound identifier, got {:?}", res),
+    }
+
+    // in a pattern
+    let input = r#"
+val x = let datatype t = Foo in 1 end
+val f = fn Foo => 1
+"#;
+    match typecheck(input) {
+        Err(TypeError::FreeVar { name, .. }) => assert_eq!(name.0, "Foo"),
+        res => panic!("expected unbound identifier, got {:?}", res),
+    }
+
+    // in a sibling `let`
+    let input = r#"
+val x = let datatype t = Foo in 1 end
+val y = let datatype u = Bar in Foo end
+"#;
+    match typecheck(input) {
+        Err(TypeError::FreeVar { name, .. }) => assert_eq!(name.0, "Foo"),
+        res => panic!("expected unbound identifier, got {:?}", res),
+    }
+}
+
+#[test]
+fn local_datatype_cannot_escape() {
+    let input = "val x = let datatype t = Foo in Foo end";
+    match typecheck(input) {
+        Err(TypeError::EscapingType { name, .. }) => assert_eq!(name.0, "t"),
+        res => panic!("expected escaping type, got {:?}", res),
+    }
+
+    let input = r#"
+infix 3 :=
+val r = ref []
+val x = let datatype t = Foo in r := [Foo] end
+"#;
+    match typecheck(input) {
+        Err(TypeError::EscapingType { name, .. }) => assert_eq!(name.0, "t"),
+        res => panic!("expected escaping type, got {:?}", res),
+    }
+}
+
+#[test]
+fn curried_fun_is_nested_fn() {
+    let input = r#"
+fun f x y = (x, y)
+val a = f 1 #"c"
+val g = f 1
+val b = g 2.0
+"#;
+    let ast = typecheck(input).unwrap();
+    let fun = ast
+        .0
+        .iter()
+        .find_map(|decl| match decl {
+            Declaration::Val {
+                rec: true, expr, ..
+            } => Some(expr),
+            _ => None,
+        })
+        .expect("no fun");
+    match &fun.inner {
+        ExprKind::Fn { body, .. } => match &body.inner {
+            ExprKind::Fn { .. } => (),
+            e => panic!("expected the second parameter, got {:?}", e),
+        },
+        e => panic!("expected fn, got {:?}", e),
+    }
+    let fun = |param, ret| Type::Fun(Box::new(param), Box::new(ret));
+    assert_eq!(
+        typeof_val(&ast, "a"),
+        Type::Tuple(vec![Type::Int, Type::Char])
+    );
+    // partially applied
+    assert_eq!(
+        typeof_val(&ast, "g"),
+        fun(Type::Real, Type::Tuple(vec![Type::Int, Type::Real]))
+    );
+    assert_eq!(
+        typeof_val(&ast, "b"),
+        Type::Tuple(vec![Type::Int, Type::Real])
+    );
+}
+
+#[test]
+fn multi_clause_fn() {
+    let input = r#"
+datatype t = A | B of int
+val f = fn A => 0 | B n => n
+val x = f (B 1)
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(typeof_val(&ast, "x"), Type::Int);
+
+    for input in &[
+        "datatype t = A | B of int val f = fn A => 0 | B n => #\"c\"",
+        "datatype t = A | B of int val f = fn A => 0 | (a, b) => 1",
+    ] {
+        match typecheck(input) {
+            Err(TypeError::MisMatch { .. }) => (),
+            res => panic!("expected type mismatch, got {:?}", res),
+        }
+    }
+}
+
+#[test]
+fn imported_signature() {
+    let input = r#"
+_import ("m"."divmod": (int, int) -> (int, int))
+val p = _externcall ("m"."divmod") (7, 2)
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(
+        typeof_val(&ast, "p"),
+        Type::Tuple(vec![Type::Int, Type::Int])
+    );
+
+    match typecheck(r#"_import ("m"."f": (int) -> int) val x = _externcall ("m"."f") (1.0)"#) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn imported_real_signature() {
+    let input = r#"
+_import ("m"."pow": (real, real) -> real)
+fun square x = _externcall ("m"."pow") (x, 2.0)
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(
+        typeof_val(&ast, "square"),
+        Type::Fun(Box::new(Type::Real), Box::new(Type::Real))
+    );
+
+    // integers are not converted to reals implicitly
+    match typecheck(
+        r#"_import ("m"."pow": (real, real) -> real) val x = _externcall ("m"."pow") (2, 2)"#,
+    ) {
+        Err(TypeError::MisMatch { .. }) => (),
+        res => panic!("expected type mismatch, got {:?}", res),
+    }
+}
+
+#[test]
+fn imported_signature_arity() {
+    let input = r#"
+_import ("m"."divmod": (int, int) -> (int, int))
+val p = _externcall ("m"."divmod") (7)
+"#;
+    match typecheck(input) {
+        Err(TypeError::ImportArity {
+            expected, actual, ..
+        }) => assert_eq!((expected, actual), (2, 1)),
+        res => panic!("expected arity error, got {:?}", res),
+    }
+
+    match typecheck(r#"val x = _externcall ("m"."f") (1)"#) {
+        Err(TypeError::UndeclaredImport { fun, .. }) => assert_eq!(fun, "f"),
+        res => panic!("expected undeclared import, got {:?}", res),
+    }
+}
+
+#[test]
+fn exports() {
+    let input = r#"
+infix 6 +
+export "inc" fun inc x = x + 1
+"#;
+    let ast = typecheck(input).unwrap();
+    assert_eq!(
+        typeof_val(&ast, "inc"),
+        Type::Fun(Box::new(Type::Int), Box::new(Type::Int))
+    );
+
+    for (input, name) in &[
+        // polymorphic
+        (r#"export "id" fun id x = x"#, "id"),
+        // not between scalars
+        (r#"export "pair" fun pair x = ((x: int), x)"#, "pair"),
+        // a closure
+        (
+            "infix 6 +\nexport \"f\" val f = let val k = 1 in fn x => x + k end",
+            "f",
+        ),
+    ] {
+        match typecheck(input) {
+            Err(TypeError::NotExportable { name: sym, .. }) => assert_eq!(sym.0, *name),
+            res => panic!("expected not exportable, got {:?}", res),
+        }
+    }
+}
+
+#[test]
+fn public_typecheck_exposes_inferred_types() {
+    let input = r#"
+infix 6 +
+datatype shape = Circle of real | Square of int
+fun area (Square a) = a + a | area (Circle _) = 0
+val (x, y) = (area (Square 1), 1.5)
+fun id z = z
+"#;
+    let typed = webml::typecheck(input, &Config::default()).unwrap();
+    let inferred = typecheck(input).unwrap();
+    for name in &["area", "id"] {
+        assert_eq!(typed.type_of(name), Some(&typeof_val(&inferred, name)));
+    }
+    let names = typed
+        .bindings()
+        .into_iter()
+        .map(|(name, ty)| (name.0.as_str(), ty.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(names[0].0, "area");
+    assert_eq!(&names[1..3], &[("x", Type::Int), ("y", Type::Real)]);
+    match typed.type_of("id") {
+        Some(Type::Fun(param, ret)) if param == ret => match **param {
+            Type::Variable(_) => (),
+            ref ty => panic!("expected type variable, got {:?}", ty),
+        },
+        ty => panic!("expected polymorphic function, got {:?}", ty),
+    }
+
+    let (shape, info) = typed
+        .symbol_table
+        .types
+        .iter()
+        .find(|(name, _)| name.0 == "shape")
+        .unwrap();
+    assert_eq!(info.constructors.len(), 2);
+    for (constructor, _) in &info.constructors {
+        assert_eq!(
+            typed.symbol_table.constructors.get(constructor),
+            Some(shape)
+        );
+    }
+}
+
+#[test]
+fn type_at_offset() {
+    let input = "infix 6 +\nval x = 1 + 2\nfun f y = (y, 2.5)\nval z = f true\n";
+    let typed = webml::typecheck(input, &Config::default()).unwrap();
+    let at = |pat: &str, skip: usize| typed.type_at(input, input.find(pat).unwrap() + skip);
+    let pair = Type::Tuple(vec![Type::Bool, Type::Real]);
+
+    assert_eq!(at("1 + 2", 0), Some(&Type::Int));
+    assert_eq!(at("2.5", 1), Some(&Type::Real));
+    assert_eq!(at("y, 2.5", 0), Some(&Type::Bool));
+    assert_eq!(at("(y, 2.5)", 0), Some(&pair));
+    assert_eq!(
+        at("f true", 0),
+        Some(&Type::Fun(Box::new(Type::Bool), Box::new(pair.clone())))
+    );
+    assert_eq!(at("true", 0), Some(&Type::Bool));
+    assert_eq!(at("z =", 0), Some(&pair));
+
+    // whitespaces and keywords
+    assert_eq!(at(" 2.5", 0), None);
+    assert_eq!(at("val x", 0), None);
+    assert_eq!(typed.type_at(input, input.len()), None);
+}
+
+#[test]
+fn incremental_retyping() {
+    use webml::incremental::Session;
+    let fun = |param: Type, ret: Type| Type::Fun(Box::new(param), Box::new(ret));
+    let pair = Type::Tuple(vec![Type::Int, Type::Int]);
+
+    let mut session = Session::new(Config::default());
+    for source in &["infix 6 +", "fun f x = x + 1", "fun g y = y", "val a = f 1"] {
+        session.push(*source).unwrap();
+    }
+    session.typecheck().unwrap();
+    assert_eq!(session.retyped(), &[0, 1, 2, 3]);
+    assert_eq!(session.type_of("a"), Some(&Type::Int));
+
+    // only the edited one and the one referring to it
+    session.edit(1, "fun f x = (x, x + 1)").unwrap();
+    session.typecheck().unwrap();
+    assert_eq!(session.retyped(), &[1, 3]);
+    assert_eq!(session.type_of("f"), Some(&fun(Type::Int, pair.clone())));
+    assert_eq!(session.type_of("a"), Some(&pair));
+    match session.type_of("g") {
+        Some(Type::Fun(param, ret)) if param == ret => (),
+        ty => panic!("expected polymorphic function, got {:?}", ty),
+    }
+
+    session.typecheck().unwrap();
+    assert_eq!(session.retyped(), &[] as &[usize]);
+
+    session.edit(2, "fun g y = y + 1.0").unwrap();
+    session.edit(3, "val a = g (f 1)").unwrap();
+    let error = session.typecheck().unwrap_err();
+    assert_eq!(error.index, 3);
+    assert!(session.push("val = ").is_err());
+}
+
+#[test]
+fn type_variables_numbered_deterministically() {
+    let input = r#"
+datatype 'a option = NONE | SOME of 'a
+datatype ('a, 'b) either = Left of 'a | Right of 'b
+datatype color = Red | Green | Blue
+datatype shape = Circle of real | Square of int
+fun map f (SOME x) = SOME (f x) | map f NONE = NONE
+fun swap (Left x) = Right x | swap (Right y) = Left y
+fun pair x y = (x, y, Red, Square 1)
+val p = pair (SOME Green) (swap (Left NONE))
+"#;
+    // with the types of all the nodes
+    let render = |input: &str| match typecheck(input) {
+        Ok(ast) => format!("{:?}", ast),
+        Err(e) => e.to_string(),
+    };
+    let error = "val f = fn ((a, b, c) | (c, d, e)) => c";
+    let first = (render(input), render(error));
+    for _ in 0..10 {
+        assert_eq!((render(input), render(error)), first);
+    }
+}
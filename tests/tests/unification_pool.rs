@@ -0,0 +1,113 @@
+use webml::unification_pool::{NodeId, UnificationPool};
+
+// `None` is a variable that unifies with anything
+fn unify(pool: &mut UnificationPool<Option<i32>>, id1: NodeId, id2: NodeId) -> Result<NodeId, ()> {
+    pool.try_unify_with(id1, id2, |_, t1, t2| match (t1, t2) {
+        (None, t) | (t, None) => Ok(t),
+        (Some(v1), Some(v2)) if v1 == v2 => Ok(Some(v1)),
+        _ => Err(()),
+    })
+}
+
+#[test]
+fn representative_keeps_value() {
+    for &flip in &[false, true] {
+        let mut pool = UnificationPool::new();
+        let large = pool.node_new(None);
+        let var1 = pool.node_new(None);
+        let var2 = pool.node_new(None);
+        unify(&mut pool, large, var1).unwrap();
+        unify(&mut pool, var1, var2).unwrap();
+        let small = pool.node_new(Some(1));
+
+        let root = if flip {
+            unify(&mut pool, small, large).unwrap()
+        } else {
+            unify(&mut pool, large, small).unwrap()
+        };
+        assert_eq!(pool.value_of(root), &Some(1));
+        for &id in &[large, var1, var2, small] {
+            assert_eq!(pool.value_of(id), &Some(1));
+        }
+    }
+}
+
+#[test]
+fn many_unions() {
+    let mut pool = UnificationPool::new();
+    let ids = (0..10000).map(|_| pool.node_new(None)).collect::<Vec<_>>();
+    for pair in ids.windows(2) {
+        unify(&mut pool, pair[0], pair[1]).unwrap();
+    }
+    let last = pool.node_new(Some(42));
+    unify(&mut pool, last, ids[0]).unwrap();
+    for &id in &ids {
+        assert_eq!(pool.value_of(id), &Some(42));
+    }
+}
+
+#[test]
+fn rollback_failed_unification() {
+    let mut pool = UnificationPool::new();
+    let one = pool.node_new(Some(1));
+    let var = pool.node_new(None);
+    let two = pool.node_new(Some(2));
+
+    let checkpoint = pool.snapshot();
+    unify(&mut pool, one, var).unwrap();
+    pool.node_new(Some(3));
+    assert!(unify(&mut pool, var, two).is_err());
+    pool.rollback(checkpoint);
+
+    assert_eq!(pool.value_of(one), &Some(1));
+    assert_eq!(pool.value_of(var), &None);
+    assert_eq!(pool.value_of(two), &Some(2));
+    // the nodes are not taken nor linked anymore
+    let root = unify(&mut pool, var, two).unwrap();
+    assert_eq!(pool.value_of(root), &Some(2));
+    assert_eq!(pool.value_of(one), &Some(1));
+}
+
+#[test]
+fn failed_unification_keeps_values() {
+    let mut pool = UnificationPool::new();
+    let one = pool.node_new(Some(1));
+    let two = pool.node_new(Some(2));
+
+    assert!(unify(&mut pool, one, two).is_err());
+    assert_eq!(pool.value_of(one), &Some(1));
+    assert_eq!(pool.value_of(two), &Some(2));
+    let var = pool.node_new(None);
+    let root = unify(&mut pool, var, one).unwrap();
+    assert_eq!(pool.value_of(root), &Some(1));
+}
+
+#[test]
+fn equivalence_classes() {
+    let mut pool = UnificationPool::new();
+    let a = pool.node_new(None);
+    let b = pool.node_new(None);
+    let c = pool.node_new(Some(1));
+    let d = pool.node_new(None);
+    unify(&mut pool, c, a).unwrap();
+    unify(&mut pool, b, d).unwrap();
+    assert_eq!(
+        pool.classes(),
+        vec![(&Some(1), vec![a, c]), (&None, vec![b, d])]
+    );
+}
+
+#[test]
+fn class_of_node() {
+    let mut pool = UnificationPool::new();
+    let a = pool.node_new(None);
+    let b = pool.node_new(Some(1));
+    let c = pool.node_new(None);
+    let d = pool.node_new(Some(2));
+    let e = pool.node_new(None);
+    unify(&mut pool, a, c).unwrap();
+    unify(&mut pool, c, b).unwrap();
+    unify(&mut pool, e, d).unwrap();
+    assert_eq!(pool.class_of(c).collect::<Vec<_>>(), vec![a, b, c]);
+    assert_eq!(pool.class_of(d).collect::<Vec<_>>(), vec![d, e]);
+}
@@ -3,6 +3,7 @@
 use core::arch::wasm32::{memory_grow, memory_size};
 use core::mem;
 use core::panic::PanicInfo;
+use core::slice;
 
 #[repr(C)]
 struct Page {
@@ -19,8 +20,10 @@ const GC_PAGE_SIZE: usize = 1 * WASM_PAGE_SIZE;
 static mut GC: *mut Page = 0 as *mut _;
 static mut HEAD: *mut Page = 0 as *mut _;
 
-unsafe fn new_page() -> *mut Page {
-    let ret = memory_grow(MEMORY, 1);
+// a page with room for at least `size` bytes
+unsafe fn new_page(size: usize) -> *mut Page {
+    let pages = (size + mem::size_of::<Page>() + GC_PAGE_SIZE - 1) / GC_PAGE_SIZE;
+    let ret = memory_grow(MEMORY, pages * GC_PAGE_SIZE / WASM_PAGE_SIZE);
     // if we failed to allocate a page then panic
     if ret == usize::max_value() {
         // TODO: collect garbage
@@ -28,40 +31,84 @@ unsafe fn new_page() -> *mut Page {
     } else {
         let page = (ret * WASM_PAGE_SIZE) as *mut u8 as *mut Page;
         // next, top: relying wasm's page is 0 initialized
-        (*page).size = GC_PAGE_SIZE - mem::size_of::<Page>();
+        (*page).size = pages * GC_PAGE_SIZE - mem::size_of::<Page>();
         (*page).data = (page as *mut u8).offset(mem::size_of::<Page>() as isize);
         page
     }
 }
 
-unsafe fn add_new_page() {
-    let page = new_page();
+unsafe fn add_new_page(size: usize) {
+    let next = (*HEAD).next;
+    // the pages after `HEAD` are the ones freed by `release`
+    if !next.is_null() && size <= (*next).size {
+        (*next).top = 0;
+        HEAD = next;
+        return;
+    }
+    let page = new_page(size);
+    (*page).next = next;
     (*HEAD).next = page;
     HEAD = page;
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn init() {
-    let page_ptr = new_page();
+    let page_ptr = new_page(0);
     GC = page_ptr;
     HEAD = GC;
 }
 
+// the boxed values, e.g. the tuples of the tag and the argument of a constructor
 #[no_mangle]
 pub unsafe extern "C" fn alloc(size: usize) -> *mut u8 {
-    if (*HEAD).size <= (*HEAD).top + size {
-        add_new_page();
+    if (*HEAD).size < (*HEAD).top + size {
+        add_new_page(size);
     }
     let ret = (*HEAD).data.offset((*HEAD).top as isize);
-    (*GC).top += size;
+    // bump the page allocated from, which is not the first one once it is full
+    (*HEAD).top += size;
     ret
 }
 
+// the end of the allocated values, for `release` to free the ones allocated after it
+#[no_mangle]
+pub unsafe extern "C" fn mark() -> *mut u8 {
+    (*HEAD).data.offset((*HEAD).top as isize)
+}
+
+// frees the values allocated after `mark` returned `point`, for `alloc` to reuse.
+// the caller must not use those values anymore, e.g. the results of an exported function
+// after reading them
+#[no_mangle]
+pub unsafe extern "C" fn release(point: *mut u8) {
+    let mut page = GC;
+    while !page.is_null() {
+        let data = (*page).data;
+        if data <= point && point <= data.offset((*page).size as isize) {
+            (*page).top = point as usize - data as usize;
+            HEAD = page;
+            return;
+        }
+        page = (*page).next;
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn memory_used() -> usize {
     WASM_PAGE_SIZE * memory_size(MEMORY)
 }
 
+// strings are passed to extern functions as the pointer to the bytes and the length
+#[no_mangle]
+pub unsafe extern "C" fn string_eq(
+    lptr: *const u8,
+    llen: usize,
+    rptr: *const u8,
+    rlen: usize,
+) -> i32 {
+    (slice::from_raw_parts(lptr, llen) == slice::from_raw_parts(rptr, rlen)) as i32
+}
+
 #[panic_handler]
 fn panic(_: &PanicInfo) -> ! {
     // currently no way to handle panic